@@ -9,6 +9,16 @@ use rustysd::control::jsonrpc2::Call;
 use serde_json::Value;
 use std::io::Write;
 
+/// Print everything rustysd sends. Usually that is one response, but following the logs streams one notification per
+/// new entry until rsdctl is killed.
+fn print_responses<R: std::io::Read>(stream: &mut R) {
+    for resp in serde_json::Deserializer::from_reader(stream).into_iter::<Value>() {
+        let resp = resp.unwrap();
+        println!("Got response");
+        println!("{}", serde_json::to_string_pretty(&resp).unwrap());
+    }
+}
+
 fn main() {
     let mut args: Vec<_> = std::env::args().collect();
     let _exec_name = args.remove(0);
@@ -61,17 +71,13 @@ fn main() {
         stream.write_all(str_call.as_bytes()).unwrap();
         stream.shutdown(std::net::Shutdown::Write).unwrap();
         println!("Wait for response");
-        let resp: Value = serde_json::from_reader(&mut stream).unwrap();
-        println!("Got response");
-        println!("{}", serde_json::to_string_pretty(&resp).unwrap());
+        print_responses(&mut stream);
     } else {
         let mut stream = std::net::TcpStream::connect(addr).unwrap();
         println!("Write cmd: {}", str_call);
         stream.write_all(str_call.as_bytes()).unwrap();
         stream.shutdown(std::net::Shutdown::Write).unwrap();
         println!("Wait for response");
-        let resp: Value = serde_json::from_reader(&mut stream).unwrap();
-        println!("Got response");
-        println!("{}", serde_json::to_string_pretty(&resp).unwrap());
+        print_responses(&mut stream);
     };
 }
@@ -2,13 +2,15 @@
 //!
 //! Currently configurable:
 //! ### Logging
-//! 1. Wether or not to log to disk (and the dir to put the logs in)
+//! 1. Wether or not to log to disk (and the dir to put the logs in). The logs on disk can be queried with the logs call of the control interface
+//! 1. The most verbose level of rustysd's own messages that is stored on disk (error, warn, info, debug, trace or off; info by default)
 //! 1. Wether or not to log to stdout
 //!
 //! ### General config
 //! 1. Where to find the units (one or more directories)
 //! 1. notification-socket directory (where the unix-domain sockets are placed on which services can notify rustysd)
 //! 1. Which unit is the target that should be started
+//! 1. state directory (where state is kept that should survive restarts of rustysd, like the last trigger of persistent timers)
 
 use std::{collections::HashMap, fs::File, io::Read, path::PathBuf};
 use toml;
@@ -17,6 +19,7 @@ use toml;
 pub struct LoggingConfig {
     pub log_to_stdout: bool,
     pub log_to_disk: bool,
+    pub log_to_disk_level: log::LevelFilter,
     pub log_dir: PathBuf,
 }
 
@@ -26,6 +29,7 @@ pub struct Config {
     pub target_unit: String,
     pub notification_sockets_dir: PathBuf,
     pub self_path: PathBuf,
+    pub state_dir: PathBuf,
 }
 
 #[derive(Debug)]
@@ -72,6 +76,12 @@ fn load_toml(
         if let Some(toml::Value::Boolean(val)) = map.get("log_to_disk") {
             settings.insert("logging.to.disk".to_owned(), SettingValue::Boolean(*val));
         }
+        if let Some(toml::Value::String(val)) = map.get("log_to_disk_level") {
+            settings.insert(
+                "logging.to.disk.level".to_owned(),
+                SettingValue::Str(val.clone()),
+            );
+        }
         if let Some(toml::Value::Boolean(val)) = map.get("log_to_stdout") {
             settings.insert("logging.to.stdout".to_owned(), SettingValue::Boolean(*val));
         }
@@ -87,6 +97,9 @@ fn load_toml(
                 SettingValue::Str(val.clone()),
             );
         }
+        if let Some(toml::Value::String(val)) = map.get("state_dir") {
+            settings.insert("state.dir".to_owned(), SettingValue::Str(val.clone()));
+        }
     }
     Ok(())
 }
@@ -123,10 +136,16 @@ fn load_json(
             settings.insert("logging.dir".to_owned(), SettingValue::Str(val.clone()));
         }
         if let Some(serde_json::Value::Bool(val)) = map.get("log_to_disk") {
-            settings.insert("logging.to_disk".to_owned(), SettingValue::Boolean(*val));
+            settings.insert("logging.to.disk".to_owned(), SettingValue::Boolean(*val));
+        }
+        if let Some(serde_json::Value::String(val)) = map.get("log_to_disk_level") {
+            settings.insert(
+                "logging.to.disk.level".to_owned(),
+                SettingValue::Str(val.clone()),
+            );
         }
         if let Some(serde_json::Value::Bool(val)) = map.get("log_to_stdout") {
-            settings.insert("logging.to_stdout".to_owned(), SettingValue::Boolean(*val));
+            settings.insert("logging.to.stdout".to_owned(), SettingValue::Boolean(*val));
         }
         if let Some(serde_json::Value::String(val)) = map.get("target_unit") {
             settings.insert("target.unit".to_owned(), SettingValue::Str(val.clone()));
@@ -140,6 +159,9 @@ fn load_json(
                 SettingValue::Str(val.clone()),
             );
         }
+        if let Some(serde_json::Value::String(val)) = map.get("state_dir") {
+            settings.insert("state.dir".to_owned(), SettingValue::Str(val.clone()));
+        }
     }
     Ok(())
 }
@@ -189,19 +211,32 @@ pub fn load_config(config_path: &Option<PathBuf>) -> (LoggingConfig, Result<Conf
         _ => None,
     });
 
-    let log_to_stdout = settings.get("logging.to_stdout").map(|val| match val {
+    // env vars like RUSTYSD_LOGGING_TO_DISK are strings
+    let log_to_stdout = settings.get("logging.to.stdout").map(|val| match val {
         SettingValue::Boolean(b) => *b,
+        SettingValue::Str(s) => crate::units::string_to_bool(s),
         _ => false,
     });
-    let log_to_disk = settings.get("logging.to_disk").map(|val| match val {
+    let log_to_disk = settings.get("logging.to.disk").map(|val| match val {
         SettingValue::Boolean(b) => *b,
+        SettingValue::Str(s) => crate::units::string_to_bool(s),
         _ => false,
     });
+    let log_to_disk_level = settings
+        .get("logging.to.disk.level")
+        .and_then(|val| match val {
+            SettingValue::Str(s) => s.parse::<log::LevelFilter>().ok(),
+            _ => None,
+        });
 
     let notification_sockets_dir = settings.get("notifications.dir").and_then(|dir| match dir {
         SettingValue::Str(s) => Some(PathBuf::from(s)),
         _ => None,
     });
+    let state_dir = settings.get("state.dir").and_then(|dir| match dir {
+        SettingValue::Str(s) => Some(PathBuf::from(s)),
+        _ => None,
+    });
     let target_unit = settings.get("target.unit").and_then(|name| match name {
         SettingValue::Str(s) => Some(s.clone()),
         _ => None,
@@ -237,6 +272,8 @@ pub fn load_config(config_path: &Option<PathBuf>) -> (LoggingConfig, Result<Conf
         notification_sockets_dir: notification_sockets_dir
             .unwrap_or_else(|| PathBuf::from("./notifications")),
 
+        state_dir: state_dir.unwrap_or_else(|| PathBuf::from("./state")),
+
         self_path: self_path.unwrap_or_else(|| {
             std::env::current_exe()
                 .expect("Could not get own executable name and it was not configured explicitly")
@@ -272,6 +309,7 @@ pub fn load_config(config_path: &Option<PathBuf>) -> (LoggingConfig, Result<Conf
                 .unwrap_or_else(|| Some(PathBuf::from("./logs")))
                 .unwrap_or_else(|| PathBuf::from("./logs")),
             log_to_disk: log_to_disk.unwrap_or(false),
+            log_to_disk_level: log_to_disk_level.unwrap_or(log::LevelFilter::Info),
             log_to_stdout: log_to_stdout.unwrap_or(true),
         },
         conf,
@@ -1,8 +1,13 @@
+use crate::log_store::{LogEntry, LogFilter};
 use crate::runtime_info::*;
 use crate::units::*;
 
 use log::trace;
 use serde_json::Value;
+use std::convert::TryInto;
+
+/// While following the log an empty line is sent after this long without new entries to notice closed connections
+const FOLLOW_HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5);
 
 pub fn open_all_sockets(run_info: ArcMutRuntimeInfo, conf: &crate::config::Config) {
     // TODO make configurable
@@ -38,10 +43,21 @@ pub enum Command {
     LoadAllNewDry,
     Remove(String),
     Restart(String),
+    Reload(String),
+    ReloadOrRestart(String),
     Start(String),
     StartAll(String),
     Stop(String),
     StopAll(String),
+    ListJobs,
+    CancelJob(JobId),
+    SetProperty(String, Vec<(String, String)>),
+    Top,
+    Logs {
+        filter: LogFilter,
+        lines: Option<usize>,
+        follow: bool,
+    },
     Shutdown,
 }
 
@@ -183,6 +199,9 @@ fn parse_command(call: &super::jsonrpc2::Call) -> Result<Command, ParseError> {
                             "target" => UnitIdKind::Target,
                             "socket" => UnitIdKind::Socket,
                             "service" => UnitIdKind::Service,
+                            "timer" => UnitIdKind::Timer,
+                            "path" => UnitIdKind::Path,
+                            "slice" => UnitIdKind::Slice,
                             _ => {
                                 return Err(ParseError::ParamsInvalid(format!(
                                     "Kind not recognized: {}",
@@ -203,8 +222,76 @@ fn parse_command(call: &super::jsonrpc2::Call) -> Result<Command, ParseError> {
             Command::ListUnits(kind)
         }
         "shutdown" => Command::Shutdown,
-        "reload" => Command::LoadAllNew,
+        "list-jobs" => Command::ListJobs,
+        "top" => Command::Top,
+        "cancel-job" => {
+            let job_id = match &call.params {
+                Some(Value::Number(n)) => n.as_u64(),
+                Some(Value::String(s)) => s.parse().ok(),
+                _ => None,
+            };
+            match job_id {
+                Some(job_id) => Command::CancelJob(job_id),
+                None => {
+                    return Err(ParseError::ParamsInvalid(
+                        "Params must be a single job id".into(),
+                    ))
+                }
+            }
+        }
+        "reload" => match &call.params {
+            // without a unit name all unit files are reloaded
+            None => Command::LoadAllNew,
+            Some(Value::String(s)) => Command::Reload(s.clone()),
+            Some(_) => {
+                return Err(ParseError::ParamsInvalid(
+                    "Params must be either none or a single string".into(),
+                ))
+            }
+        },
+        "reload-or-restart" => {
+            let name = match &call.params {
+                Some(Value::String(s)) => s.clone(),
+                _ => {
+                    return Err(ParseError::ParamsInvalid(
+                        "Params must be a single string".into(),
+                    ))
+                }
+            };
+            Command::ReloadOrRestart(name)
+        }
+        "set-property" => {
+            let params = match &call.params {
+                Some(Value::Array(params)) => params
+                    .iter()
+                    .map(|param| param.as_str().map(str::to_owned))
+                    .collect::<Option<Vec<_>>>(),
+                _ => None,
+            };
+            let params =
+                match params {
+                    Some(params) if params.len() > 1 => params,
+                    _ => return Err(ParseError::ParamsInvalid(
+                        "Params must be the unit name followed by at least one \"Setting=value\""
+                            .into(),
+                    )),
+                };
+            let mut properties = Vec::new();
+            for property in &params[1..] {
+                match property.split_once('=') {
+                    Some((name, value)) => properties.push((name.to_owned(), value.to_owned())),
+                    None => {
+                        return Err(ParseError::ParamsInvalid(format!(
+                            "Not a \"Setting=value\" pair: {}",
+                            property
+                        )))
+                    }
+                }
+            }
+            Command::SetProperty(params[0].clone(), properties)
+        }
         "reload-dry" => Command::LoadAllNewDry,
+        "logs" => parse_logs_params(&call.params).map_err(ParseError::ParamsInvalid)?,
         "enable" => {
             let names = match &call.params {
                 Some(params) => match params {
@@ -247,10 +334,214 @@ fn parse_command(call: &super::jsonrpc2::Call) -> Result<Command, ParseError> {
     Ok(command)
 }
 
+/// The params of logs are either an object or "key=value" strings. A string without a "=" is the name of the unit, or
+/// "follow".
+fn parse_logs_params(params: &Option<Value>) -> Result<Command, String> {
+    let mut options = Vec::new();
+    let mut add_string_option = |option: &str| match option.split_once('=') {
+        Some((key, value)) => options.push((key.to_owned(), Value::String(value.to_owned()))),
+        None if option == "follow" => options.push(("follow".to_owned(), Value::Bool(true))),
+        None => options.push(("unit".to_owned(), Value::String(option.to_owned()))),
+    };
+    match params {
+        None => {}
+        Some(Value::String(s)) => add_string_option(s),
+        Some(Value::Array(values)) => {
+            for value in values {
+                match value {
+                    Value::String(s) => add_string_option(s),
+                    _ => return Err("Params must be strings".into()),
+                }
+            }
+        }
+        Some(Value::Object(map)) => {
+            for (key, value) in map {
+                options.push((key.clone(), value.clone()));
+            }
+        }
+        Some(_) => {
+            return Err("Params must be either none, strings or an object".into());
+        }
+    }
+
+    let mut filter = LogFilter::default();
+    let mut lines = None;
+    let mut follow = false;
+    for (key, value) in options {
+        match key.as_str() {
+            "unit" => match value {
+                Value::String(unit) => filter.unit = Some(unit),
+                _ => return Err("unit must be a string".into()),
+            },
+            "priority" => filter.priority = Some(parse_log_priority(&value)?),
+            "since" => filter.since = Some(parse_log_time(&value)?),
+            "until" => filter.until = Some(parse_log_time(&value)?),
+            "lines" => {
+                let parsed = match &value {
+                    Value::Number(n) => n.as_u64().map(|n| n as usize),
+                    Value::String(s) => s.parse().ok(),
+                    _ => None,
+                };
+                lines =
+                    Some(parsed.ok_or_else(|| format!("Not a valid number of lines: {}", value))?);
+            }
+            "follow" => {
+                follow = match &value {
+                    Value::Bool(b) => *b,
+                    Value::String(s) => string_to_bool(s),
+                    _ => return Err("follow must be a boolean".into()),
+                }
+            }
+            _ => return Err(format!("Unknown param for logs: {}", key)),
+        }
+    }
+    // like journalctl -f only show the last few entries before following
+    if follow && lines.is_none() {
+        lines = Some(10);
+    }
+    Ok(Command::Logs {
+        filter,
+        lines,
+        follow,
+    })
+}
+
+/// Either a syslog priority from 0 to 7 or its name
+fn parse_log_priority(value: &Value) -> Result<u8, String> {
+    const NAMES: [&str; 8] = [
+        "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+    ];
+    let priority = match value {
+        Value::Number(n) => n.as_u64(),
+        Value::String(s) => s
+            .parse()
+            .ok()
+            .or_else(|| NAMES.iter().position(|name| name == s).map(|p| p as u64)),
+        _ => None,
+    };
+    match priority {
+        Some(priority) if priority < NAMES.len() as u64 => Ok(priority as u8),
+        _ => Err(format!("Not a valid priority: {}", value)),
+    }
+}
+
+/// Seconds since the epoch, "now", a time relative to now like "-5min" or a local time like "2020-01-01 12:00:00".
+/// Returns microseconds since the epoch like the log entries use them.
+fn parse_log_time(value: &Value) -> Result<u64, String> {
+    use chrono::TimeZone;
+    let err = || format!("Not a valid time: {}", value);
+    let now = || {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+    };
+    let descr = match value {
+        Value::Number(n) => return n.as_u64().map(|secs| secs * 1_000_000).ok_or_else(err),
+        Value::String(s) => s.trim(),
+        _ => return Err(err()),
+    };
+    if descr == "now" {
+        return Ok(now().as_micros() as u64);
+    }
+    if let Some(ago) = descr.strip_prefix('-') {
+        let ago = parse_timespan(ago).map_err(|_| err())?;
+        return Ok(now().saturating_sub(ago).as_micros() as u64);
+    }
+    if let Ok(secs) = descr.parse::<u64>() {
+        return Ok(secs * 1_000_000);
+    }
+    let naive = chrono::NaiveDateTime::parse_from_str(descr, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .or_else(|| {
+            chrono::NaiveDate::parse_from_str(descr, "%Y-%m-%d")
+                .ok()
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
+        .ok_or_else(err)?;
+    let local = chrono::Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(err)?;
+    Ok(local.timestamp_micros().max(0) as u64)
+}
+
+/// Instances that were loaded from a template file show which template they share
+/// Where the config of the unit came from: the template it was instantiated from and the drop-ins merged into it
+fn insert_unit_file_info(map: &mut serde_json::Map<String, Value>, unit: &Unit) {
+    if let Some(template) = &unit.common.unit.template {
+        map.insert("Template".into(), Value::String(template.name.clone()));
+    }
+    if !unit.common.unit.dropins.is_empty() {
+        map.insert(
+            "DropIns".into(),
+            Value::Array(
+                unit.common
+                    .unit
+                    .dropins
+                    .iter()
+                    .map(|path| Value::String(path.display().to_string()))
+                    .collect(),
+            ),
+        );
+    }
+}
+
+fn insert_resources(map: &mut serde_json::Map<String, Value>, resources: &ResourceControl) {
+    let properties = resources.properties();
+    if !properties.is_empty() {
+        map.insert(
+            "Resources".into(),
+            Value::Object(
+                properties
+                    .into_iter()
+                    .map(|(name, value)| (name.to_owned(), Value::String(value)))
+                    .collect(),
+            ),
+        );
+    }
+}
+
+/// Insert the resource usage read from the cgroup, the names are the ones systemd uses for these properties
+#[cfg(feature = "cgroups")]
+fn insert_resource_usage(
+    map: &mut serde_json::Map<String, Value>,
+    cgroup_path: &std::path::PathBuf,
+) {
+    let usage = match crate::platform::cgroups::read_resource_usage(cgroup_path) {
+        Some(usage) => usage,
+        None => return,
+    };
+    let values = [
+        ("MemoryCurrent", usage.memory_current),
+        ("MemoryPeak", usage.memory_peak),
+        ("CPUUsageUSec", usage.cpu_usage_usec),
+        ("TasksCurrent", usage.tasks_current),
+        ("IOReadBytes", usage.io.map(|io| io.read_bytes)),
+        ("IOWriteBytes", usage.io.map(|io| io.write_bytes)),
+        ("IOReadOperations", usage.io.map(|io| io.read_operations)),
+        ("IOWriteOperations", usage.io.map(|io| io.write_operations)),
+    ];
+    for (name, value) in values.iter() {
+        if let Some(value) = value {
+            map.insert(name.to_string(), Value::Number((*value).into()));
+        }
+    }
+}
+
+pub fn format_job(job: &Job) -> Value {
+    let mut map = serde_json::Map::new();
+    map.insert("Id".into(), Value::Number(job.id.into()));
+    map.insert("Unit".into(), Value::String(job.unit.name.clone()));
+    map.insert("Type".into(), Value::String(job.kind.as_str().into()));
+    map.insert("State".into(), Value::String(format!("{:?}", job.state)));
+    Value::Object(map)
+}
+
 pub fn format_socket(socket_unit: &Unit, status: UnitStatus) -> Value {
     let mut map = serde_json::Map::new();
     map.insert("Name".into(), Value::String(socket_unit.id.name.clone()));
     map.insert("Status".into(), Value::String(format!("{:?}", status)));
+    insert_unit_file_info(&mut map, socket_unit);
 
     if let Specific::Socket(sock) = &socket_unit.specific {
         map.insert(
@@ -267,6 +558,21 @@ pub fn format_socket(socket_unit: &Unit, status: UnitStatus) -> Value {
                     .collect(),
             ),
         );
+        if sock.conf.accept {
+            let state = &*sock.state.read().unwrap();
+            map.insert("Accepted".into(), Value::Number(state.sock.accepted.into()));
+            map.insert(
+                "Connections".into(),
+                Value::Array(
+                    state
+                        .sock
+                        .connections
+                        .iter()
+                        .map(|accepted| Value::String(accepted.instance.name.clone()))
+                        .collect(),
+                ),
+            );
+        }
     }
 
     Value::Object(map)
@@ -276,6 +582,23 @@ pub fn format_target(socket_unit: &Unit, status: UnitStatus) -> Value {
     let mut map = serde_json::Map::new();
     map.insert("Name".into(), Value::String(socket_unit.id.name.clone()));
     map.insert("Status".into(), Value::String(format!("{:?}", status)));
+    insert_unit_file_info(&mut map, socket_unit);
+    Value::Object(map)
+}
+
+pub fn format_slice(slice_unit: &Unit, status: UnitStatus) -> Value {
+    let mut map = serde_json::Map::new();
+    map.insert("Name".into(), Value::String(slice_unit.id.name.clone()));
+    map.insert("Status".into(), Value::String(format!("{:?}", status)));
+    insert_unit_file_info(&mut map, slice_unit);
+    if let Some(parent) = slice_unit.id.parent_slice() {
+        map.insert("Slice".into(), Value::String(parent.name));
+    }
+    if let Specific::Slice(slice) = &slice_unit.specific {
+        insert_resources(&mut map, &slice.conf.resources);
+        #[cfg(feature = "cgroups")]
+        insert_resource_usage(&mut map, &slice.conf.platform_specific.cgroup_path);
+    }
     Value::Object(map)
 }
 
@@ -283,6 +606,7 @@ pub fn format_service(srvc_unit: &Unit, status: UnitStatus) -> Value {
     let mut map = serde_json::Map::new();
     map.insert("Name".into(), Value::String(srvc_unit.id.name.clone()));
     map.insert("Status".into(), Value::String(format!("{:?}", status)));
+    insert_unit_file_info(&mut map, srvc_unit);
     if let Specific::Service(srvc) = &srvc_unit.specific {
         map.insert(
             "Sockets".into(),
@@ -294,18 +618,138 @@ pub fn format_service(srvc_unit: &Unit, status: UnitStatus) -> Value {
                     .collect(),
             ),
         );
-        if let Some(instant) = srvc.state.read().unwrap().common.up_since {
+        if let Some(slice) = &srvc.conf.slice {
+            map.insert("Slice".into(), Value::String(slice.name.clone()));
+        }
+        insert_resources(&mut map, &srvc.conf.resources);
+        #[cfg(feature = "cgroups")]
+        insert_resource_usage(&mut map, &srvc.conf.platform_specific.cgroup_path);
+        if status.is_started() {
+            if let Some(instant) = srvc.state.read().unwrap().common.up_since {
+                map.insert(
+                    "UpSince".into(),
+                    Value::String(format!("{:?}", instant.elapsed())),
+                );
+            }
+        }
+        map.insert(
+            "Restarted".into(),
+            Value::Number(srvc.state.read().unwrap().common.restart_count.into()),
+        );
+        if let Some(errno) = srvc.state.read().unwrap().srvc.errno {
             map.insert(
-                "UpSince".into(),
-                Value::String(format!("{:?}", instant.elapsed())),
+                "Errno".into(),
+                Value::String(format!(
+                    "{} ({})",
+                    errno,
+                    nix::errno::Errno::from_i32(errno)
+                )),
             );
         }
+    }
+    Value::Object(map)
+}
+
+pub fn format_log_entry(entry: &LogEntry) -> Value {
+    let mut map = serde_json::Map::new();
+    let local: chrono::DateTime<chrono::Local> = entry.time().into();
+    map.insert(
+        "Time".into(),
+        Value::String(local.format("%Y-%m-%d %H:%M:%S%.6f").to_string()),
+    );
+    if let Some(unit) = &entry.unit {
+        map.insert("Unit".into(), Value::String(unit.clone()));
+    }
+    if let Some(identifier) = &entry.identifier {
+        map.insert("Identifier".into(), Value::String(identifier.clone()));
+    }
+    if let Some(pid) = entry.pid {
+        map.insert("Pid".into(), Value::Number(pid.into()));
+    }
+    map.insert("Stream".into(), serde_json::to_value(entry.stream).unwrap());
+    map.insert("Priority".into(), Value::Number(entry.priority.into()));
+    map.insert("Message".into(), Value::String(entry.message.clone()));
+    Value::Object(map)
+}
+
+fn format_systemtime(time: std::time::SystemTime) -> Value {
+    let local: chrono::DateTime<chrono::Local> = time.into();
+    Value::String(local.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+pub fn format_timer(timer_unit: &Unit, status: UnitStatus) -> Value {
+    let mut map = serde_json::Map::new();
+    map.insert("Name".into(), Value::String(timer_unit.id.name.clone()));
+    map.insert("Status".into(), Value::String(format!("{:?}", status)));
+    insert_unit_file_info(&mut map, timer_unit);
+    if let Specific::Timer(timer) = &timer_unit.specific {
+        map.insert("Unit".into(), Value::String(timer.conf.unit.name.clone()));
         map.insert(
-            "Restarted".into(),
-            Value::String(format!(
-                "{:?}",
-                srvc.state.read().unwrap().common.restart_count
-            )),
+            "OnCalendar".into(),
+            Value::Array(
+                timer
+                    .conf
+                    .on_calendar
+                    .iter()
+                    .map(|spec| Value::String(spec.to_string()))
+                    .collect(),
+            ),
+        );
+        let state = timer.state.read().unwrap();
+        map.insert(
+            "NextElapse".into(),
+            state
+                .timer
+                .next_elapse
+                .map(format_systemtime)
+                .unwrap_or(Value::Null),
+        );
+        map.insert(
+            "LastTrigger".into(),
+            state
+                .timer
+                .last_trigger
+                .map(format_systemtime)
+                .unwrap_or(Value::Null),
+        );
+    }
+    Value::Object(map)
+}
+
+pub fn format_path(path_unit: &Unit, status: UnitStatus) -> Value {
+    let mut map = serde_json::Map::new();
+    map.insert("Name".into(), Value::String(path_unit.id.name.clone()));
+    map.insert("Status".into(), Value::String(format!("{:?}", status)));
+    insert_unit_file_info(&mut map, path_unit);
+    if let Specific::Path(path) = &path_unit.specific {
+        map.insert("Unit".into(), Value::String(path.conf.unit.name.clone()));
+        map.insert(
+            "Conditions".into(),
+            Value::Array(
+                path.conf
+                    .conditions
+                    .iter()
+                    .map(|cond| Value::String(cond.to_string()))
+                    .collect(),
+            ),
+        );
+        let state = path.state.read().unwrap();
+        map.insert(
+            "LastTrigger".into(),
+            state
+                .path
+                .last_trigger
+                .map(format_systemtime)
+                .unwrap_or(Value::Null),
+        );
+        map.insert(
+            "TriggeredBy".into(),
+            state
+                .path
+                .triggered_by
+                .as_ref()
+                .map(|cond| Value::String(cond.to_string()))
+                .unwrap_or(Value::Null),
         );
     }
     Value::Object(map)
@@ -322,6 +766,22 @@ fn find_units_with_name<'a>(unit_name: &str, unit_table: &'a UnitTable) -> Vec<&
         .collect()
 }
 
+/// Like find_units_with_name but the name must match exactly one unit
+fn find_single_unit_id(unit_name: &str, unit_table: &UnitTable) -> Result<UnitId, String> {
+    let units = find_units_with_name(unit_name, unit_table);
+    if units.len() > 1 {
+        let names: Vec<_> = units.iter().map(|unit| unit.id.name.clone()).collect();
+        return Err(format!(
+            "More than one unit found with name: {}: {:?}",
+            unit_name, names
+        ));
+    }
+    match units.first() {
+        Some(unit) => Ok(unit.id.clone()),
+        None => Err(format!("No unit found with name: {}", unit_name)),
+    }
+}
+
 // TODO make this some kind of regex pattern matching
 fn find_units_with_pattern<'a>(
     name_pattern: &str,
@@ -338,6 +798,50 @@ fn find_units_with_pattern<'a>(
     units
 }
 
+/// Instances of templates are loaded when they are started for the first time
+fn load_instance_if_missing(unit_name: &str, run_info: &ArcMutRuntimeInfo) -> Result<(), String> {
+    let id: UnitId = match unit_name.try_into() {
+        Ok(id) => id,
+        Err(_) => return Ok(()),
+    };
+    if id.template_name().is_none() {
+        return Ok(());
+    }
+    let run_info = &mut *run_info.write().unwrap();
+    if run_info.unit_table.contains_key(&id) {
+        return Ok(());
+    }
+    let unit = load_new_unit(&run_info.config.unit_dirs, unit_name)?;
+    let mut map = std::collections::HashMap::new();
+    map.insert(unit.id.clone(), unit);
+    insert_new_units(map, run_info)
+}
+
+/// Run the jobs for a request and report the errors of the jobs that failed
+fn run_jobs(
+    id: UnitId,
+    kind: JobKind,
+    pull_in_dependencies: bool,
+    run_info: ArcMutRuntimeInfo,
+) -> Result<(), String> {
+    let transaction = {
+        let run_info = run_info.read().unwrap();
+        Transaction::new(id, kind, pull_in_dependencies, &run_info.unit_table)?
+    };
+    let errs = run_transaction(transaction, run_info);
+    match errs.len() {
+        0 => Ok(()),
+        1 => Err(format!("{}", errs[0])),
+        _ => {
+            let mut errstr = String::from("Errors while running the jobs:");
+            for err in errs {
+                errstr.push_str(&format!("\n{}", err));
+            }
+            Err(errstr)
+        }
+    }
+}
+
 pub fn execute_command(
     cmd: Command,
     run_info: ArcMutRuntimeInfo,
@@ -347,91 +851,88 @@ pub fn execute_command(
         Command::Shutdown => {
             crate::shutdown::shutdown_sequence(run_info);
         }
-        Command::Restart(unit_name) => {
-            let run_info = &*run_info.read().unwrap();
-            let id = {
-                let unit_table = &run_info.unit_table;
-                let units = find_units_with_name(&unit_name, unit_table);
-                if units.len() > 1 {
-                    let names: Vec<_> = units.iter().map(|unit| unit.id.name.clone()).collect();
-                    return Err(format!(
-                        "More than one unit found with name: {}: {:?}",
-                        unit_name, names
-                    ));
-                }
-                if units.len() == 0 {
-                    return Err(format!("No unit found with name: {}", unit_name));
-                }
-                let x = units[0].id.clone();
-                x
+        Command::SetProperty(unit_name, properties) => {
+            let run_info = &mut *run_info.write().unwrap();
+            let id = find_single_unit_id(&unit_name, &run_info.unit_table)?;
+            let unit = run_info.unit_table.get_mut(&id).unwrap();
+            let (current, cgroup_path) = match &mut unit.specific {
+                Specific::Service(srvc) => (
+                    &mut srvc.conf.resources,
+                    &srvc.conf.platform_specific.cgroup_path,
+                ),
+                Specific::Slice(slice) => (
+                    &mut slice.conf.resources,
+                    &slice.conf.platform_specific.cgroup_path,
+                ),
+                _ => return Err(format!("{} has no resource control settings", unit_name)),
             };
+            // only change anything if all properties are valid
+            let mut resources = current.clone();
+            for (name, value) in &properties {
+                set_resource_property(&mut resources, name, value).map_err(|e| match e {
+                    ParsingErrorReason::UnknownSetting(name, value) => {
+                        format!("Not a valid value for {}: {}", name, value)
+                    }
+                    ParsingErrorReason::UnusedSetting(name) => {
+                        format!("{} is not a resource control setting", name)
+                    }
+                    e => format!("{:?}", e),
+                })?;
+            }
+            *current = resources;
 
-            match crate::units::reactivate_unit(id, run_info).map_err(|e| format!("{}", e)) {
-                Err(e) => {
-                    return Err(e);
-                }
-                Ok(_) => {
-                    // Happy
+            // an active unit gets the new settings right away, otherwise they are applied when it is started
+            #[cfg(feature = "cgroups")]
+            {
+                if cgroup_path.exists() {
+                    crate::platform::cgroups::apply_resource_control(cgroup_path, current)
+                        .map_err(|e| format!("Could not apply the settings: {}", e))?;
                 }
-            };
+            }
+            let _ = cgroup_path;
         }
-        Command::Start(unit_name) => {
-            let run_info = &*run_info.read().unwrap();
-            let id = {
-                let unit_table = &run_info.unit_table;
-                let units = find_units_with_name(&unit_name, unit_table);
-                if units.len() > 1 {
-                    let names: Vec<_> = units.iter().map(|unit| unit.id.name.clone()).collect();
-                    return Err(format!(
-                        "More than one unit found with name: {}: {:?}",
-                        unit_name, names
-                    ));
-                }
-                if units.len() == 0 {
-                    return Err(format!("No unit found with name: {}", unit_name));
-                }
-                let x = units[0].id.clone();
-                x
+        Command::Logs { filter, lines, .. } => {
+            let log_store = run_info.read().unwrap().log_store.clone();
+            let log_store = log_store.ok_or_else(|| "Logging to disk is disabled".to_owned())?;
+            let entries = result_vec.as_array_mut().unwrap();
+            for entry in log_store.query(&filter, lines) {
+                entries.push(format_log_entry(&entry));
+            }
+        }
+        Command::Restart(unit_name) => {
+            let id = find_single_unit_id(&unit_name, &run_info.read().unwrap().unit_table)?;
+            run_jobs(id, JobKind::Restart, false, run_info)?;
+        }
+        Command::Reload(unit_name) => {
+            let id = find_single_unit_id(&unit_name, &run_info.read().unwrap().unit_table)?;
+            run_jobs(id, JobKind::Reload, false, run_info)?;
+        }
+        Command::ReloadOrRestart(unit_name) => {
+            let (id, reload) = {
+                let run_info = &*run_info.read().unwrap();
+                let id = find_single_unit_id(&unit_name, &run_info.unit_table)?;
+                let unit = run_info.unit_table.get(&id).unwrap();
+                let reload = unit.can_reload()
+                    && *unit.common.status.read().unwrap()
+                        == UnitStatus::Started(StatusStarted::Running);
+                (id, reload)
             };
-
-            match crate::units::activate_unit(id, run_info, ActivationSource::Regular)
-                .map_err(|e| format!("{}", e))
-            {
-                Err(e) => {
-                    return Err(e);
-                }
-                Ok(_) => {
-                    // Happy
-                }
+            let kind = if reload {
+                JobKind::Reload
+            } else {
+                JobKind::Restart
             };
+            run_jobs(id, kind, false, run_info)?;
+        }
+        Command::Start(unit_name) => {
+            load_instance_if_missing(&unit_name, &run_info)?;
+            let id = find_single_unit_id(&unit_name, &run_info.read().unwrap().unit_table)?;
+            run_jobs(id, JobKind::Start, false, run_info)?;
         }
         Command::StartAll(unit_name) => {
-            let id = {
-                let run_info_locked = &*run_info.read().unwrap();
-                let unit_table = &run_info_locked.unit_table;
-                let units = find_units_with_name(&unit_name, unit_table);
-                if units.len() > 1 {
-                    let names: Vec<_> = units.iter().map(|unit| unit.id.name.clone()).collect();
-                    return Err(format!(
-                        "More than one unit found with name: {}: {:?}",
-                        unit_name, names
-                    ));
-                }
-                if units.len() == 0 {
-                    return Err(format!("No unit found with name: {}", unit_name));
-                }
-                let x = units[0].id.clone();
-                x
-            };
-
-            let errs = crate::units::activate_needed_units(id, run_info);
-            if errs.len() > 0 {
-                let mut errstr = String::from("Errors while starting the units:");
-                for err in errs {
-                    errstr.push_str(&format!("\n{:?}", err));
-                }
-                return Err(errstr);
-            }
+            load_instance_if_missing(&unit_name, &run_info)?;
+            let id = find_single_unit_id(&unit_name, &run_info.read().unwrap().unit_table)?;
+            run_jobs(id, JobKind::Start, true, run_info)?;
         }
         Command::Remove(unit_name) => {
             let run_info = &mut *run_info.write().unwrap();
@@ -455,60 +956,55 @@ pub fn execute_command(
                 .map_err(|e| format!("{}", e))?;
         }
         Command::Stop(unit_name) => {
-            let run_info = &*run_info.read().unwrap();
-            let id = {
-                let units = find_units_with_name(&unit_name, &run_info.unit_table);
-                if units.len() > 1 {
-                    let names: Vec<_> = units.iter().map(|unit| unit.id.name.clone()).collect();
-                    return Err(format!(
-                        "More than one unit found with name: {}: {:?}",
-                        unit_name, names
-                    ));
-                }
-                if units.len() == 0 {
-                    return Err(format!("No unit found with name: {}", unit_name));
-                }
-                let x = units[0].id.clone();
-                x
-            };
-
-            match crate::units::deactivate_unit(&id, run_info).map_err(|e| format!("{}", e)) {
-                Err(e) => {
-                    return Err(e);
-                }
-                Ok(_) => {
-                    // Happy
-                }
-            };
+            let id = find_single_unit_id(&unit_name, &run_info.read().unwrap().unit_table)?;
+            run_jobs(id, JobKind::Stop, false, run_info)?;
         }
         Command::StopAll(unit_name) => {
-            let run_info = &*run_info.read().unwrap();
-            let id = {
-                let units = find_units_with_name(&unit_name, &run_info.unit_table);
-                if units.len() > 1 {
-                    let names: Vec<_> = units.iter().map(|unit| unit.id.name.clone()).collect();
-                    return Err(format!(
-                        "More than one unit found with name: {}: {:?}",
-                        unit_name, names
-                    ));
-                }
-                if units.len() == 0 {
-                    return Err(format!("No unit found with name: {}", unit_name));
-                }
-                let x = units[0].id.clone();
-                x
-            };
-
-            match crate::units::deactivate_unit_recursive(&id, run_info)
-                .map_err(|e| format!("{}", e))
+            let id = find_single_unit_id(&unit_name, &run_info.read().unwrap().unit_table)?;
+            run_jobs(id, JobKind::Stop, true, run_info)?;
+        }
+        Command::Top => {
+            #[cfg(feature = "cgroups")]
             {
-                Err(e) => {
-                    return Err(e);
-                }
-                Ok(_) => {
-                    // Happy
+                let run_info = &*run_info.read().unwrap();
+                let mut entries: Vec<(u64, Value)> = Vec::new();
+                for unit in run_info.unit_table.values() {
+                    let cgroup_path = match unit.cgroup_path() {
+                        Some(cgroup_path) if cgroup_path.exists() => cgroup_path,
+                        _ => continue,
+                    };
+                    let mut map = serde_json::Map::new();
+                    map.insert("Name".into(), Value::String(unit.id.name.clone()));
+                    map.insert(
+                        "Status".into(),
+                        Value::String(format!("{:?}", unit.common.status.read().unwrap())),
+                    );
+                    insert_resource_usage(&mut map, cgroup_path);
+                    let cpu_usage = map.get("CPUUsageUSec").and_then(Value::as_u64).unwrap_or(0);
+                    entries.push((cpu_usage, Value::Object(map)));
                 }
-            };
+                // like top the units that used the most cpu time come first
+                entries.sort_by(|(a, _), (b, _)| b.cmp(a));
+                let result = result_vec.as_array_mut().unwrap();
+                result.extend(entries.into_iter().map(|(_, entry)| entry));
+            }
+            #[cfg(not(feature = "cgroups"))]
+            {
+                return Err(
+                    "Resource accounting needs rustysd to be built with the cgroups feature".into(),
+                );
+            }
+        }
+        Command::ListJobs => {
+            let jobs = run_info.read().unwrap().jobs.clone();
+            for job in jobs.list() {
+                result_vec.as_array_mut().unwrap().push(format_job(&job));
+            }
+        }
+        Command::CancelJob(job_id) => {
+            let jobs = run_info.read().unwrap().jobs.clone();
+            let job = jobs.cancel(job_id)?;
+            result_vec.as_array_mut().unwrap().push(format_job(&job));
         }
         Command::Status(unit_name) => {
             let run_info = &*run_info.read().unwrap();
@@ -534,6 +1030,21 @@ pub fn execute_command(
                                 .as_array_mut()
                                 .unwrap()
                                 .push(format_target(&unit, status));
+                        } else if name.ends_with(".timer") {
+                            result_vec
+                                .as_array_mut()
+                                .unwrap()
+                                .push(format_timer(unit, status));
+                        } else if name.ends_with(".path") {
+                            result_vec
+                                .as_array_mut()
+                                .unwrap()
+                                .push(format_path(unit, status));
+                        } else if name.ends_with(".slice") {
+                            result_vec
+                                .as_array_mut()
+                                .unwrap()
+                                .push(format_slice(unit, status));
                         } else {
                             return Err("Name suffix not recognized".into());
                         }
@@ -549,6 +1060,9 @@ pub fn execute_command(
                                 Specific::Socket(_) => format_socket(&unit, status),
                                 Specific::Service(_) => format_service(&unit, status),
                                 Specific::Target(_) => format_target(&unit, status),
+                                Specific::Timer(_) => format_timer(unit, status),
+                                Specific::Path(_) => format_path(unit, status),
+                                Specific::Slice(_) => format_slice(unit, status),
                             }
                         })
                         .collect();
@@ -669,6 +1183,57 @@ pub fn execute_command(
 
 use std::io::Read;
 use std::io::Write;
+
+/// Answer with the stored entries and then send each new entry as a "log" notification until the connection is closed
+fn follow_logs<T: Write + ?Sized>(
+    source: &mut T,
+    id: Option<Value>,
+    filter: &LogFilter,
+    lines: Option<usize>,
+    run_info: &ArcMutRuntimeInfo,
+) {
+    let log_store = run_info.read().unwrap().log_store.clone();
+    let log_store = match log_store {
+        Some(log_store) => log_store,
+        None => {
+            let err = super::jsonrpc2::make_error(
+                super::jsonrpc2::SERVER_ERROR,
+                "Logging to disk is disabled".into(),
+                None,
+            );
+            let msg = super::jsonrpc2::make_error_response(id, err);
+            let response_string = serde_json::to_string_pretty(&msg).unwrap();
+            let _ = source.write_all(response_string.as_bytes());
+            return;
+        }
+    };
+    let (entries, receiver) = log_store.follow(filter, lines);
+    let result = Value::Array(entries.iter().map(format_log_entry).collect());
+    let msg = super::jsonrpc2::make_result_response(id, result);
+    let response_string = serde_json::to_string_pretty(&msg).unwrap() + "\n";
+    if source.write_all(response_string.as_bytes()).is_err() {
+        return;
+    }
+    loop {
+        let notification = match receiver.recv_timeout(FOLLOW_HEARTBEAT_INTERVAL) {
+            Ok(entry) => {
+                let call = super::jsonrpc2::Call {
+                    method: "log".into(),
+                    params: Some(format_log_entry(&entry)),
+                    id: None,
+                };
+                serde_json::to_string(&call.to_json()).unwrap() + "\n"
+            }
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => "\n".into(),
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => return,
+        };
+        if source.write_all(notification.as_bytes()).is_err() {
+            // dropping the receiver removes this follower from the log store
+            return;
+        }
+    }
+}
+
 pub fn listen_on_commands<T: 'static + Read + Write + Send>(
     mut source: Box<T>,
     run_info: ArcMutRuntimeInfo,
@@ -719,6 +1284,14 @@ pub fn listen_on_commands<T: 'static + Read + Write + Send>(
                                 let response_string = serde_json::to_string_pretty(&msg).unwrap();
                                 source.write_all(response_string.as_bytes()).unwrap();
                             }
+                            Ok(Command::Logs {
+                                filter,
+                                lines,
+                                follow: true,
+                            }) => {
+                                follow_logs(source.as_mut(), call.id, &filter, lines, &run_info);
+                                return;
+                            }
                             Ok(cmd) => {
                                 trace!("Execute command: {:?}", cmd);
                                 let msg = match execute_command(cmd, run_info.clone()) {
@@ -14,6 +14,11 @@ pub struct ExecHelperConfig {
     pub group: libc::gid_t,
     pub supplementary_groups: Vec<libc::gid_t>,
     pub user: libc::uid_t,
+    /// Only set for services of type exec. Errors are written to this fd, a successful exec closes it.
+    pub exec_status_fd: Option<libc::c_int>,
+    /// Only set with StandardInput=socket. The connection is moved to stdin before the exec. stdout is connected by
+    /// rustysd according to StandardOutput=.
+    pub stdin_socket_fd: Option<libc::c_int>,
 
     pub platform_specific: PlatformSpecificServiceFields,
 }
@@ -39,17 +44,54 @@ fn prepare_exec_args(
     (cmd, args)
 }
 
+/// Tell rustysd why the service could not be started. This is only needed for services of type exec, for the other
+/// types the exit of the process is enough.
+fn report_exec_error(config: &ExecHelperConfig, msg: &str) {
+    if let Some(fd) = config.exec_status_fd {
+        let _ = nix::unistd::write(fd, msg.as_bytes());
+    }
+}
+
 pub fn run_exec_helper() {
-    println!("Exec helper trying to read config from stdin");
+    // Nothing but errors may be written to stderr here. It might be the connection of an instance started by a socket
+    // with Accept=yes and the config contains the whole environment of the service.
     let config: ExecHelperConfig = serde_json::from_reader(std::io::stdin()).unwrap();
-    println!("Apply config: {:?}", config);
 
     nix::unistd::close(libc::STDIN_FILENO).expect("I want to be able to close this fd!");
 
+    if let Some(fd) = config.stdin_socket_fd {
+        let dup_res = nix::unistd::dup2(fd, libc::STDIN_FILENO);
+        if let Err(e) = dup_res {
+            eprintln!(
+                "[EXEC_HELPER {}] could not connect stdin to the socket: {}",
+                config.name, e
+            );
+            report_exec_error(&config, &format!("could not dup the socket: {}", e));
+            std::process::exit(1);
+        }
+        let _ = nix::unistd::close(fd);
+    }
+
+    if let Some(fd) = config.exec_status_fd {
+        // make the exec of the service close the status pipe
+        if let Err(e) = nix::fcntl::fcntl(
+            fd,
+            nix::fcntl::FcntlArg::F_SETFD(nix::fcntl::FdFlag::FD_CLOEXEC),
+        ) {
+            eprintln!(
+                "[EXEC_HELPER {}] could not set CLOEXEC on the exec status fd: {}",
+                config.name, e
+            );
+            report_exec_error(&config, &format!("could not set CLOEXEC: {}", e));
+            std::process::exit(1);
+        }
+    }
+
     if let Err(e) =
         crate::services::fork_os_specific::post_fork_os_specific(&config.platform_specific)
     {
         eprintln!("[FORK_CHILD {}] postfork error: {}", config.name, e);
+        report_exec_error(&config, &format!("postfork error: {}", e));
         std::process::exit(1);
     }
 
@@ -69,6 +111,7 @@ pub fn run_exec_helper() {
                     "[EXEC_HELPER {}] could not drop privileges because: {}",
                     config.name, e
                 );
+                report_exec_error(&config, &format!("could not drop privileges: {}", e));
                 std::process::exit(1);
             }
         }
@@ -82,8 +125,13 @@ pub fn run_exec_helper() {
     }
 
     std::env::set_var("LISTEN_PID", format!("{}", nix::unistd::getpid()));
+    if config.env.iter().any(|(k, _)| k == "WATCHDOG_USEC") {
+        std::env::set_var("WATCHDOG_PID", format!("{}", nix::unistd::getpid()));
+    }
 
-    eprintln!("EXECV: {:?} {:?}", &cmd, &args);
-
-    nix::unistd::execv(&cmd, &args).unwrap();
+    // execv only returns on errors
+    let e = nix::unistd::execv(&cmd, &args).unwrap_err();
+    eprintln!("[EXEC_HELPER {}] execv failed: {}", config.name, e);
+    report_exec_error(&config, &format!("execv failed: {}", e));
+    std::process::exit(1);
 }
@@ -4,13 +4,18 @@ use std::sync::{Arc, Mutex, RwLock};
 
 use crate::config;
 use crate::control;
+use crate::hook_activation;
 use crate::logging;
 use crate::notification_handler;
+use crate::path_activation;
 use crate::platform;
+use crate::reactor;
 use crate::runtime_info;
 use crate::signal_handler;
 use crate::socket_activation;
+use crate::timer_activation;
 use crate::units;
+use crate::watchdog;
 
 pub fn run_service_manager() {
     pid1_specific_setup();
@@ -31,7 +36,7 @@ pub fn run_service_manager() {
 
     let (log_conf, conf) = config::load_config(&cli_args.conf);
 
-    logging::setup_logging(&log_conf).unwrap();
+    let log_store = logging::setup_logging(&log_conf).unwrap();
     let conf = match conf {
         Ok(conf) => conf,
         Err(e) => {
@@ -60,7 +65,7 @@ pub fn run_service_manager() {
 
     crate::platform::become_subreaper(true);
 
-    let run_info = prepare_runtimeinfo(&conf, cli_args.dry_run);
+    let run_info = prepare_runtimeinfo(&conf, log_store, cli_args.dry_run);
 
     let signals = match Signals::new(&[
         signal_hook::consts::SIGCHLD,
@@ -84,8 +89,15 @@ pub fn run_service_manager() {
     start_notification_handler_thread(run_info.clone());
     start_stdout_handler_thread(run_info.clone());
     start_stderr_handler_thread(run_info.clone());
+    #[cfg(feature = "cgroups")]
+    start_cgroup_events_handler_thread(run_info.clone());
 
     socket_activation::start_socketactivation_thread(run_info.clone());
+    reactor::start_reactor_thread(run_info.read().unwrap().reactor.clone());
+    timer_activation::start_timeractivation_thread(run_info.clone());
+    path_activation::start_pathactivation_thread(run_info.clone());
+    watchdog::start_watchdog_thread(run_info.clone());
+    hook_activation::start_hookactivation_thread(run_info.clone());
 
     trace!("Started all helper threads. Start activating units");
 
@@ -196,7 +208,11 @@ fn pid1_specific_setup() {
 #[cfg(not(target_os = "linux"))]
 fn pid1_specific_setup() {}
 
-fn prepare_runtimeinfo(conf: &config::Config, dry_run: bool) -> runtime_info::ArcMutRuntimeInfo {
+fn prepare_runtimeinfo(
+    conf: &config::Config,
+    log_store: Option<Arc<crate::log_store::LogStore>>,
+    dry_run: bool,
+) -> runtime_info::ArcMutRuntimeInfo {
     // initial loading of the units and matching of the various before/after settings
     // also opening all fildescriptors in the socket files
     let unit_table =
@@ -235,30 +251,48 @@ fn prepare_runtimeinfo(conf: &config::Config, dry_run: bool) -> runtime_info::Ar
         pid_table: pid_table,
         fd_store: std::sync::RwLock::new(crate::fd_store::FDStore::default()),
         config: conf.clone(),
-        stdout_eventfd: platform::make_event_fd().unwrap(),
-        stderr_eventfd: platform::make_event_fd().unwrap(),
-        notification_eventfd: platform::make_event_fd().unwrap(),
-        socket_activation_eventfd: platform::make_event_fd().unwrap(),
+        reactor: Arc::new(reactor::Reactor::new().unwrap()),
+        jobs: Arc::new(units::JobQueue::default()),
+        hooks: Arc::new(hook_activation::HookQueue::default()),
+        log_store,
+        timer_activation_eventfd: platform::make_event_fd().unwrap(),
+        path_activation_eventfd: platform::make_event_fd().unwrap(),
     }));
 
     run_info
 }
 
 fn start_notification_handler_thread(run_info: runtime_info::ArcMutRuntimeInfo) {
+    let events = subscribe(&run_info, reactor::StreamKind::Notifications);
     std::thread::spawn(move || {
-        notification_handler::handle_all_streams(run_info.clone());
+        notification_handler::handle_all_streams(run_info.clone(), events);
     });
 }
 fn start_stdout_handler_thread(run_info: runtime_info::ArcMutRuntimeInfo) {
+    let events = subscribe(&run_info, reactor::StreamKind::Stdout);
     std::thread::spawn(move || {
-        notification_handler::handle_all_std_out(run_info.clone());
+        notification_handler::handle_all_std_out(run_info.clone(), events);
     });
 }
 fn start_stderr_handler_thread(run_info: runtime_info::ArcMutRuntimeInfo) {
+    let events = subscribe(&run_info, reactor::StreamKind::Stderr);
+    std::thread::spawn(move || {
+        notification_handler::handle_all_std_err(run_info.clone(), events);
+    });
+}
+#[cfg(feature = "cgroups")]
+fn start_cgroup_events_handler_thread(run_info: runtime_info::ArcMutRuntimeInfo) {
+    let events = subscribe(&run_info, reactor::StreamKind::CgroupEvents);
     std::thread::spawn(move || {
-        notification_handler::handle_all_std_err(run_info.clone());
+        notification_handler::handle_all_cgroup_events(run_info.clone(), events);
     });
 }
+fn subscribe(
+    run_info: &runtime_info::ArcMutRuntimeInfo,
+    kind: reactor::StreamKind,
+) -> std::sync::mpsc::Receiver<reactor::ReactorEvent> {
+    run_info.read().unwrap().reactor.subscribe(kind)
+}
 fn start_signal_handler_thread(
     signals: Signals,
     run_info: runtime_info::ArcMutRuntimeInfo,
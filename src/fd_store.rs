@@ -51,6 +51,15 @@ impl FDStore {
         self.global_sockets.get(name)
     }
 
+    /// Find the first fd of all socket units with this name (FileDescriptorName=)
+    pub fn find_global_by_fd_name(&self, fd_name: &str) -> Option<RawFd> {
+        self.global_sockets
+            .values()
+            .flatten()
+            .find(|(_, name, _)| name == fd_name)
+            .map(|(_, _, fd)| fd.as_raw_fd())
+    }
+
     /// Insert FDs from the sd_notify API. Indexed by service unit name and the given name for the fds
     pub fn insert_service_stored(
         &mut self,
@@ -78,6 +87,27 @@ impl FDStore {
             None
         }
     }
+    /// Remove all fds a service stored. They need to be closed by the caller.
+    pub fn remove_all_service_stored(&mut self, srvc_name: &str) -> Vec<Box<RawFd>> {
+        self.service_stored_sockets
+            .remove(srvc_name)
+            .map(|fds| fds.into_values().flatten().collect())
+            .unwrap_or_default()
+    }
+
+    /// All fds a service stored together with their names, in the order they should be passed to the service
+    pub fn get_all_service_stored(&self, srvc_name: &str) -> Vec<(String, RawFd)> {
+        let mut all = Vec::new();
+        if let Some(fds) = self.service_stored_sockets.get(srvc_name) {
+            for (fd_name, fds) in fds {
+                for fd in fds {
+                    all.push((fd_name.clone(), **fd));
+                }
+            }
+        }
+        all
+    }
+
     /// normal get semantics on a hashmap
     pub fn get_service_stored(
         &self,
@@ -0,0 +1,183 @@
+//! Start the OnFailure= and OnSuccess= units of units that failed or finished. The units that ended are queued by the
+//! code that notices it and picked up by the thread started here, which queues the jobs for the hooks.
+use log::{error, trace};
+
+use crate::runtime_info::*;
+use crate::signal_handler::ChildTermination;
+use crate::units::*;
+
+use std::sync::{Condvar, Mutex};
+
+/// A unit that failed or finished successfully
+#[derive(Clone, Debug)]
+pub struct UnitEnded {
+    pub id: UnitId,
+    /// The result as systemd names it in $MONITOR_SERVICE_RESULT, e.g. "success", "exit-code" or "timeout"
+    pub result: &'static str,
+    /// How the main process ended if that is what made the unit stop
+    pub exit: Option<ChildTermination>,
+}
+
+impl UnitEnded {
+    pub fn succeeded(&self) -> bool {
+        self.result == "success"
+    }
+
+    /// The environment the hook services get, like the $MONITOR_* variables systemd passes
+    pub fn monitor_env(&self) -> Vec<(String, String)> {
+        let mut env = vec![
+            ("MONITOR_UNIT".to_owned(), self.id.name.clone()),
+            ("MONITOR_SERVICE_RESULT".to_owned(), self.result.to_owned()),
+        ];
+        match self.exit {
+            Some(ChildTermination::Exit(code)) => {
+                env.push(("MONITOR_EXIT_CODE".to_owned(), "exited".to_owned()));
+                env.push(("MONITOR_EXIT_STATUS".to_owned(), code.to_string()));
+            }
+            Some(ChildTermination::Signal(signal)) => {
+                env.push(("MONITOR_EXIT_CODE".to_owned(), "killed".to_owned()));
+                env.push((
+                    "MONITOR_EXIT_STATUS".to_owned(),
+                    signal.as_str().trim_start_matches("SIG").to_owned(),
+                ));
+            }
+            None => {}
+        }
+        env
+    }
+}
+
+/// The result of a start that failed, named like systemd does
+pub fn start_failure_result(reason: &UnitOperationErrorReason) -> &'static str {
+    use crate::services::{RunCmdError, ServiceErrorReason};
+    let cmd_error = match reason {
+        UnitOperationErrorReason::StartLimitHit => return "start-limit-hit",
+        UnitOperationErrorReason::ServiceStartError(
+            ServiceErrorReason::PrestartFailed(e)
+            | ServiceErrorReason::StartFailed(e)
+            | ServiceErrorReason::PoststartFailed(e)
+            | ServiceErrorReason::PrestartAndPoststopFailed(e, _)
+            | ServiceErrorReason::StartAndPoststopFailed(e, _)
+            | ServiceErrorReason::PoststartAndPoststopFailed(e, _),
+        ) => e,
+        _ => return "resources",
+    };
+    match cmd_error {
+        RunCmdError::Timeout(_, _) => "timeout",
+        RunCmdError::BadExitCode(_, ChildTermination::Exit(_))
+        | RunCmdError::ExitBeforeNotify(_, ChildTermination::Exit(_)) => "exit-code",
+        RunCmdError::BadExitCode(_, ChildTermination::Signal(_))
+        | RunCmdError::ExitBeforeNotify(_, ChildTermination::Signal(_)) => "signal",
+        _ => "resources",
+    }
+}
+
+/// The units that ended and whose hooks were not started yet
+#[derive(Default)]
+pub struct HookQueue {
+    ended: Mutex<Vec<UnitEnded>>,
+    added: Condvar,
+}
+
+impl HookQueue {
+    pub fn push(&self, ended: UnitEnded) {
+        trace!("Unit {} ended with result: {}", ended.id.name, ended.result);
+        self.ended.lock().unwrap().push(ended);
+        self.added.notify_all();
+    }
+
+    fn wait(&self) -> Vec<UnitEnded> {
+        let mut ended = self.ended.lock().unwrap();
+        while ended.is_empty() {
+            ended = self.added.wait(ended).unwrap();
+        }
+        std::mem::take(&mut *ended)
+    }
+}
+
+pub fn start_hookactivation_thread(run_info: ArcMutRuntimeInfo) {
+    let queue = run_info.read().unwrap().hooks.clone();
+    std::thread::spawn(move || loop {
+        for ended in queue.wait() {
+            start_hooks(ended, run_info.clone());
+        }
+    });
+}
+
+fn start_hooks(ended: UnitEnded, run_info: ArcMutRuntimeInfo) {
+    let (hooks, mode) = {
+        let run_info = &*run_info.read().unwrap();
+        let unit = match run_info.unit_table.get(&ended.id) {
+            Some(unit) => unit,
+            None => return,
+        };
+        if ended.succeeded() {
+            (
+                unit.common.unit.on_success.clone(),
+                unit.common.unit.on_success_job_mode,
+            )
+        } else {
+            (
+                unit.common.unit.on_failure.clone(),
+                unit.common.unit.on_failure_job_mode,
+            )
+        }
+    };
+    for hook_id in hooks {
+        let ended = ended.clone();
+        let run_info = run_info.clone();
+        std::thread::spawn(move || {
+            trace!(
+                "Start unit {} because {} ended with result: {}",
+                hook_id.name,
+                ended.id.name,
+                ended.result
+            );
+            let transaction = {
+                let run_info = &*run_info.read().unwrap();
+                prepare_hook(&hook_id, &ended, mode, run_info)
+            };
+            let errs = match transaction {
+                Ok(transaction) => run_transaction(transaction, run_info),
+                Err(e) => vec![UnitOperationError {
+                    reason: UnitOperationErrorReason::TransactionRejected(e),
+                    unit_name: hook_id.name.clone(),
+                    unit_id: hook_id.clone(),
+                }],
+            };
+            for err in errs {
+                error!(
+                    "Error while starting {} for {}: {}",
+                    hook_id.name, ended.id.name, err
+                );
+            }
+        });
+    }
+}
+
+/// Pass the result to the hook service and plan its start. Oneshot services that already ran are started again.
+fn prepare_hook(
+    hook_id: &UnitId,
+    ended: &UnitEnded,
+    mode: JobMode,
+    run_info: &RuntimeInfo,
+) -> Result<Transaction, String> {
+    let unit = run_info
+        .unit_table
+        .get(hook_id)
+        .ok_or_else(|| format!("No unit found with id: {}", hook_id))?;
+    let finished_oneshot = unit.is_finished_oneshot();
+    if let Specific::Service(srvc) = &unit.specific {
+        let state = &mut *srvc.state.write().unwrap();
+        let running = unit.common.status.read().unwrap().is_started();
+        if !running || finished_oneshot {
+            state.srvc.monitor_env = ended.monitor_env();
+        }
+    }
+    let kind = if finished_oneshot {
+        JobKind::Restart
+    } else {
+        JobKind::Start
+    };
+    Transaction::with_mode(hook_id.clone(), kind, mode, &run_info.unit_table)
+}
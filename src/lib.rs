@@ -13,12 +13,12 @@
 //! 1. Startup sorted by dependencies (parallel if possible for unrelated services)
 //! 1. Socket activation of services
 //! 1. Kill services that have dependencies on failed services
+//! 1. Timer activation of services
+//! 1. Path activation of services
 //!
 //! What is explicitly out of scope (for now, this project is still very young):
-//! 1. Timers
 //! 1. Mounts
 //! 1. Device
-//! 1. Path activation
 //! 1. Scopes
 //! 1. Slices (this might be added as it is fairly important if you are not running inside of a container)
 pub mod config;
@@ -26,16 +26,24 @@ pub mod control;
 pub mod dbus_wait;
 pub mod entrypoints;
 pub mod fd_store;
+pub mod hook_activation;
+pub mod log_store;
 pub mod logging;
 pub mod notification_handler;
+pub mod path_activation;
+pub mod paths;
 pub mod platform;
+pub mod reactor;
 pub mod runtime_info;
 pub mod services;
 pub mod shutdown;
 pub mod signal_handler;
 pub mod socket_activation;
 pub mod sockets;
+pub mod timer_activation;
+pub mod timers;
 pub mod units;
+pub mod watchdog;
 
 #[cfg(test)]
 mod tests;
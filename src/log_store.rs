@@ -0,0 +1,285 @@
+//! The log store keeps the messages of rustysd and every line the services write to stdout/stderr in the log dir. The
+//! entries are stored as one json object per line. When the current file gets too big it is rotated and only the newest
+//! files are kept.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Take, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const LOG_FILE_NAME: &str = "rustysd.log";
+/// The current file is rotated once it gets bigger than this
+const MAX_FILE_SIZE: u64 = 8 * 1024 * 1024;
+/// The current file and the rotated files rustysd.log.1 to rustysd.log.3
+const MAX_FILES: usize = 4;
+
+/// Priorities like syslog uses them
+pub const PRIORITY_ERR: u8 = 3;
+pub const PRIORITY_WARNING: u8 = 4;
+pub const PRIORITY_INFO: u8 = 6;
+pub const PRIORITY_DEBUG: u8 = 7;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogStream {
+    /// Messages of rustysd itself
+    Manager,
+    Stdout,
+    Stderr,
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct LogEntry {
+    /// Microseconds since the unix epoch
+    pub timestamp: u64,
+    pub unit: Option<String>,
+    /// The SyslogIdentifier= of the service if it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub identifier: Option<String>,
+    pub pid: Option<i32>,
+    pub stream: LogStream,
+    /// Syslog priority from 0 (emerg) to 7 (debug)
+    pub priority: u8,
+    pub message: String,
+}
+
+impl LogEntry {
+    pub fn new(
+        unit: Option<String>,
+        pid: Option<i32>,
+        stream: LogStream,
+        priority: u8,
+        message: String,
+    ) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_micros() as u64)
+            .unwrap_or(0);
+        LogEntry {
+            timestamp,
+            unit,
+            identifier: None,
+            pid,
+            stream,
+            priority,
+            message,
+        }
+    }
+
+    /// A line of a service. Like systemd does it a "<N>" prefix sets the priority of the line.
+    pub fn from_service_line(
+        unit: &str,
+        identifier: Option<&str>,
+        pid: Option<i32>,
+        stream: LogStream,
+        line: &str,
+    ) -> Self {
+        let bytes = line.as_bytes();
+        let (priority, message) = match bytes {
+            [b'<', prio @ b'0'..=b'7', b'>', ..] => (prio - b'0', &line[3..]),
+            _ => (PRIORITY_INFO, line),
+        };
+        let mut entry = Self::new(
+            Some(unit.to_owned()),
+            pid,
+            stream,
+            priority,
+            message.to_owned(),
+        );
+        entry.identifier = identifier.map(str::to_owned);
+        entry
+    }
+
+    pub fn time(&self) -> SystemTime {
+        UNIX_EPOCH + std::time::Duration::from_micros(self.timestamp)
+    }
+}
+
+pub fn priority_of_level(level: log::Level) -> u8 {
+    match level {
+        log::Level::Error => PRIORITY_ERR,
+        log::Level::Warn => PRIORITY_WARNING,
+        log::Level::Info => PRIORITY_INFO,
+        log::Level::Debug | log::Level::Trace => PRIORITY_DEBUG,
+    }
+}
+
+/// Which entries a query returns. Unset fields do not filter.
+#[derive(Clone, Default, Debug)]
+pub struct LogFilter {
+    pub unit: Option<String>,
+    /// Microseconds since the unix epoch
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    /// Only entries with this priority or a more important one
+    pub priority: Option<u8>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        if let Some(unit) = &self.unit {
+            if entry.unit.as_ref() != Some(unit) {
+                return false;
+            }
+        }
+        if let Some(since) = self.since {
+            if entry.timestamp < since {
+                return false;
+            }
+        }
+        if let Some(until) = self.until {
+            if entry.timestamp > until {
+                return false;
+            }
+        }
+        match self.priority {
+            Some(priority) => entry.priority <= priority,
+            None => true,
+        }
+    }
+}
+
+struct CurrentFile {
+    file: File,
+    size: u64,
+    /// Everyone following the log gets the new entries that match their filter
+    followers: Vec<(LogFilter, Sender<LogEntry>)>,
+}
+
+pub struct LogStore {
+    dir: PathBuf,
+    current: Mutex<CurrentFile>,
+}
+
+fn rotated_name(dir: &Path, idx: usize) -> PathBuf {
+    if idx == 0 {
+        dir.join(LOG_FILE_NAME)
+    } else {
+        dir.join(format!("{}.{}", LOG_FILE_NAME, idx))
+    }
+}
+
+fn open_log_file(path: &Path) -> std::io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+impl LogStore {
+    pub fn open(dir: &Path) -> Result<Arc<LogStore>, String> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Could not create log dir {:?}: {}", dir, e))?;
+        let path = rotated_name(dir, 0);
+        let file =
+            open_log_file(&path).map_err(|e| format!("Could not open log {:?}: {}", path, e))?;
+        let size = file.metadata().map(|meta| meta.len()).unwrap_or(0);
+        Ok(Arc::new(LogStore {
+            dir: dir.to_owned(),
+            current: Mutex::new(CurrentFile {
+                file,
+                size,
+                followers: Vec::new(),
+            }),
+        }))
+    }
+
+    /// Errors can not be logged because that would end up here again, so they are printed on stderr
+    pub fn append(&self, entry: LogEntry) {
+        let mut line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                eprintln!("Could not serialize log entry: {}", e);
+                return;
+            }
+        };
+        line.push('\n');
+
+        let current = &mut *self.current.lock().unwrap();
+        if current.size + line.len() as u64 > MAX_FILE_SIZE && current.size > 0 {
+            if let Err(e) = self.rotate(current) {
+                eprintln!("Could not rotate the log in {:?}: {}", self.dir, e);
+            }
+        }
+        match current.file.write_all(line.as_bytes()) {
+            Ok(()) => current.size += line.len() as u64,
+            Err(e) => eprintln!("Could not write to the log in {:?}: {}", self.dir, e),
+        }
+        current.followers.retain(|(filter, follower)| {
+            !filter.matches(&entry) || follower.send(entry.clone()).is_ok()
+        });
+    }
+
+    fn rotate(&self, current: &mut CurrentFile) -> std::io::Result<()> {
+        let oldest = rotated_name(&self.dir, MAX_FILES - 1);
+        if oldest.exists() {
+            std::fs::remove_file(oldest)?;
+        }
+        for idx in (0..MAX_FILES - 1).rev() {
+            let path = rotated_name(&self.dir, idx);
+            if path.exists() {
+                std::fs::rename(path, rotated_name(&self.dir, idx + 1))?;
+            }
+        }
+        current.file = open_log_file(&rotated_name(&self.dir, 0))?;
+        current.size = 0;
+        Ok(())
+    }
+
+    /// All stored entries that match the filter, oldest first. With lines set only the newest entries are returned.
+    pub fn query(&self, filter: &LogFilter, lines: Option<usize>) -> Vec<LogEntry> {
+        let files = self.open_files(&self.current.lock().unwrap());
+        read_entries(files, filter, lines)
+    }
+
+    /// Like query, but also return a receiver that gets all matching entries that are stored from now on. No entry
+    /// is missed or received twice, new entries wait until the stored ones are read.
+    pub fn follow(
+        &self,
+        filter: &LogFilter,
+        lines: Option<usize>,
+    ) -> (Vec<LogEntry>, Receiver<LogEntry>) {
+        let (sender, receiver) = channel();
+        let files = {
+            let current = &mut *self.current.lock().unwrap();
+            current.followers.push((filter.clone(), sender));
+            self.open_files(current)
+        };
+        (read_entries(files, filter, lines), receiver)
+    }
+
+    /// Opens the stored files, oldest first. The current file is cut off at its size at the time of the call, so the
+    /// files can be read without holding the lock while new entries are appended or the files are rotated.
+    fn open_files(&self, current: &CurrentFile) -> Vec<Take<File>> {
+        (0..MAX_FILES)
+            .rev()
+            .filter_map(|idx| {
+                let file = File::open(rotated_name(&self.dir, idx)).ok()?;
+                let size = if idx == 0 { current.size } else { u64::MAX };
+                Some(file.take(size))
+            })
+            .collect()
+    }
+}
+
+fn read_entries(files: Vec<Take<File>>, filter: &LogFilter, lines: Option<usize>) -> Vec<LogEntry> {
+    let mut entries = Vec::new();
+    for file in files {
+        for line in BufReader::new(file).lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+            // a line that was cut off by a crash is skipped
+            if let Ok(entry) = serde_json::from_str::<LogEntry>(&line) {
+                if filter.matches(&entry) {
+                    entries.push(entry);
+                }
+            }
+        }
+    }
+    if let Some(lines) = lines {
+        let skip = entries.len().saturating_sub(lines);
+        entries.drain(..skip);
+    }
+    entries
+}
@@ -1,25 +1,48 @@
-pub fn setup_logging(conf: &crate::config::LoggingConfig) -> Result<(), String> {
-    let mut logger = fern::Dispatch::new()
-        .format(|out, message, record| {
-            out.finish(format_args!(
-                "{}[{}][{}] {}",
-                chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
-                record.target(),
-                record.level(),
-                message
-            ))
-        })
-        .level(log::LevelFilter::Trace);
+use crate::log_store::{priority_of_level, LogEntry, LogStore, LogStream};
+use std::sync::Arc;
+
+/// Returns the log store if logging to disk is enabled
+pub fn setup_logging(conf: &crate::config::LoggingConfig) -> Result<Option<Arc<LogStore>>, String> {
+    let mut logger = fern::Dispatch::new().level(log::LevelFilter::Trace);
 
     if conf.log_to_stdout {
-        logger = logger.chain(std::io::stdout());
+        logger = logger.chain(
+            fern::Dispatch::new()
+                .format(|out, message, record| {
+                    out.finish(format_args!(
+                        "{}[{}][{}] {}",
+                        chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
+                        record.target(),
+                        record.level(),
+                        message
+                    ))
+                })
+                .chain(std::io::stdout()),
+        );
     }
 
-    if conf.log_to_disk {
-        unimplemented!("Logging to disk is currently not supported. Pipe the stdout logs to your preferred logging solution");
-    }
+    let log_store = if conf.log_to_disk {
+        let log_store = LogStore::open(&conf.log_dir)?;
+        let store = log_store.clone();
+        // the trace messages of rustysd would push the lines of the services out of the rotated files
+        logger = logger.chain(fern::Dispatch::new().level(conf.log_to_disk_level).chain(
+            fern::Output::call(move |record| {
+                store.append(LogEntry::new(
+                    None,
+                    None,
+                    LogStream::Manager,
+                    priority_of_level(record.level()),
+                    record.args().to_string(),
+                ))
+            }),
+        ));
+        Some(log_store)
+    } else {
+        None
+    };
 
     logger
         .apply()
-        .map_err(|e| format!("Error while stting up logger: {}", e))
+        .map_err(|e| format!("Error while stting up logger: {}", e))?;
+    Ok(log_store)
 }
@@ -1,257 +1,192 @@
 //! collect the different streams from the services
 //! Stdout and stderr get redirected to the normal stdout/err but are prefixed with a unique string to identify their output
 //! streams from the notification sockets get parsed and applied to the respective service
+//! changes of the cgroup.events of the services tell when all processes of a service are gone
 
 use log::trace;
 use log::warn;
 
-use crate::platform::reset_event_fd;
+use crate::reactor::ReactorEvent;
 use crate::runtime_info::*;
 use crate::services::Service;
-use crate::services::StdIo;
 use crate::units::*;
-use std::{collections::HashMap, os::unix::io::AsRawFd};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::Duration;
 
-fn collect_from_srvc<F>(run_info: ArcMutRuntimeInfo, f: F) -> HashMap<i32, UnitId>
-where
-    F: Fn(&mut HashMap<i32, UnitId>, &Service, UnitId),
-{
-    let run_info_locked = run_info.read().unwrap();
-    let unit_table = &run_info_locked.unit_table;
-    unit_table
-        .iter()
-        .fold(HashMap::new(), |mut map, (id, srvc_unit)| {
-            if let Specific::Service(srvc) = &srvc_unit.specific {
-                let state = &*srvc.state.read().unwrap();
-                f(&mut map, &state.srvc, id.clone());
-            }
-            map
-        })
-}
+/// How long to wait before looking at the fds of a locked service again
+const LOCKED_RETRY_INTERVAL: Duration = Duration::from_millis(100);
 
-pub fn handle_all_streams(run_info: ArcMutRuntimeInfo) {
-    let eventfd = { run_info.read().unwrap().notification_eventfd };
+/// Handle the readable fds of services the reactor reports. Services that are locked right now are retried a bit later,
+/// whoever holds the lock is busy starting/stopping/reloading the service and might read from the fd itself.
+fn handle_service_events<F>(
+    run_info: ArcMutRuntimeInfo,
+    events: Receiver<ReactorEvent>,
+    handle_fd: F,
+) where
+    F: Fn(RawFd, &Unit, &ServiceSpecific, &mut ServiceState, &RuntimeInfo),
+{
+    let mut deferred: Vec<(RawFd, UnitId)> = Vec::new();
     loop {
-        // need to collect all again. There might be a newly started service
-        let fd_to_srvc_id = collect_from_srvc(run_info.clone(), |map, srvc, id| {
-            if let Some(socket) = &srvc.notifications {
-                map.insert(socket.as_raw_fd(), id);
+        let event = if deferred.is_empty() {
+            events.recv().map_err(|_| RecvTimeoutError::Disconnected)
+        } else {
+            events.recv_timeout(LOCKED_RETRY_INTERVAL)
+        };
+        let mut ready = std::mem::take(&mut deferred);
+        match event {
+            Ok(ReactorEvent::Readable(fd, id)) => ready.push((fd, id)),
+            Ok(ReactorEvent::Wakeup) | Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                warn!("The reactor stopped sending events");
+                return;
             }
-        });
-
-        let mut fdset = nix::sys::select::FdSet::new();
-        for fd in fd_to_srvc_id.keys() {
-            fdset.insert(*fd);
         }
-        fdset.insert(eventfd.read_end());
-
-        let result = nix::sys::select::select(None, Some(&mut fdset), None, None, None);
 
         let run_info_locked = run_info.read().unwrap();
-        let unit_table = &run_info_locked.unit_table;
-        match result {
-            Ok(_) => {
-                if fdset.contains(eventfd.read_end()) {
-                    trace!("Interrupted notification select because the eventfd fired");
-                    reset_event_fd(eventfd);
-                    trace!("Reset eventfd value");
-                }
-                let mut buf = [0u8; 512];
-                for (fd, id) in &fd_to_srvc_id {
-                    if fdset.contains(*fd) {
-                        if let Some(srvc_unit) = unit_table.get(id) {
-                            if let Specific::Service(srvc) = &srvc_unit.specific {
-                                let mut_state = &mut *srvc.state.write().unwrap();
-                                if let Some(socket) = &mut_state.srvc.notifications {
-                                    let old_flags =
-                                        nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_GETFL)
-                                            .unwrap();
-
-                                    let old_flags =
-                                        nix::fcntl::OFlag::from_bits(old_flags).unwrap();
-                                    let mut new_flags = old_flags.clone();
-                                    new_flags.insert(nix::fcntl::OFlag::O_NONBLOCK);
-                                    nix::fcntl::fcntl(
-                                        *fd,
-                                        nix::fcntl::FcntlArg::F_SETFL(new_flags),
-                                    )
-                                    .unwrap();
-                                    let bytes = {
-                                        match socket.recv(&mut buf[..]) {
-                                            Ok(b) => b,
-                                            Err(e) => match e.kind() {
-                                                std::io::ErrorKind::WouldBlock => 0,
-                                                _ => panic!("{}", e),
-                                            },
-                                        }
-                                    };
-                                    nix::fcntl::fcntl(
-                                        *fd,
-                                        nix::fcntl::FcntlArg::F_SETFL(old_flags),
-                                    )
-                                    .unwrap();
-                                    let note_str =
-                                        String::from_utf8(buf[..bytes].to_vec()).unwrap();
-                                    mut_state.srvc.notifications_buffer.push_str(&note_str);
-                                    crate::notification_handler::handle_notifications_from_buffer(
-                                        &mut mut_state.srvc,
-                                        &srvc_unit.id.name,
-                                    );
-                                }
-                            }
-                        }
+        for (fd, id) in ready {
+            let srvc_unit = match run_info_locked.unit_table.get(&id) {
+                Some(unit) => unit,
+                // removed in the meantime, this also deregistered the fd
+                None => continue,
+            };
+            if let Specific::Service(srvc) = &srvc_unit.specific {
+                match srvc.state.try_write() {
+                    Ok(mut state) => {
+                        handle_fd(fd, srvc_unit, srvc, &mut state, &run_info_locked);
+                        run_info_locked.reactor.rearm(fd);
                     }
+                    Err(_) => deferred.push((fd, id)),
                 }
             }
-            Err(e) => {
-                warn!("Error while selecting: {}", e);
-            }
         }
     }
 }
 
-pub fn handle_all_std_out(run_info: ArcMutRuntimeInfo) {
-    let eventfd = { run_info.read().unwrap().stdout_eventfd };
-    loop {
-        // need to collect all again. There might be a newly started service
-        let fd_to_srvc_id = collect_from_srvc(run_info.clone(), |map, srvc, id| {
-            if let Some(StdIo::Piped(r, _w)) = &srvc.stdout {
-                map.insert(*r, id);
-            }
-        });
+/// Read what is available right now. Someone else might have read it in the meantime, so dont block.
+fn read_nonblocking(fd: RawFd, buf: &mut [u8]) -> usize {
+    let old_flags = nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_GETFL).unwrap();
+    let old_flags = nix::fcntl::OFlag::from_bits(old_flags).unwrap();
+    let mut new_flags = old_flags.clone();
+    new_flags.insert(nix::fcntl::OFlag::O_NONBLOCK);
+    nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_SETFL(new_flags)).unwrap();
 
-        let mut fdset = nix::sys::select::FdSet::new();
-        for fd in fd_to_srvc_id.keys() {
-            fdset.insert(*fd);
-        }
-        fdset.insert(eventfd.read_end());
-
-        let result = nix::sys::select::select(None, Some(&mut fdset), None, None, None);
-
-        let run_info_locked = run_info.read().unwrap();
-        let unit_table = &run_info_locked.unit_table;
-        match result {
-            Ok(_) => {
-                if fdset.contains(eventfd.read_end()) {
-                    trace!("Interrupted stdout select because the eventfd fired");
-                    reset_event_fd(eventfd);
-                    trace!("Reset eventfd value");
-                }
-                let mut buf = [0u8; 512];
-                for (fd, id) in &fd_to_srvc_id {
-                    if fdset.contains(*fd) {
-                        if let Some(srvc_unit) = unit_table.get(id) {
-                            let name = srvc_unit.id.name.clone();
-                            if let Specific::Service(srvc) = &srvc_unit.specific {
-                                let mut_state = &mut *srvc.state.write().unwrap();
-                                let status = srvc_unit.common.status.read().unwrap();
-
-                                let old_flags =
-                                    nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_GETFL).unwrap();
-                                let old_flags = nix::fcntl::OFlag::from_bits(old_flags).unwrap();
-                                let mut new_flags = old_flags.clone();
-                                new_flags.insert(nix::fcntl::OFlag::O_NONBLOCK);
-                                nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_SETFL(new_flags))
-                                    .unwrap();
+    let bytes = match nix::unistd::read(fd, buf) {
+        Ok(b) => b,
+        Err(nix::Error::EWOULDBLOCK) => 0,
+        Err(e) => panic!("{}", e),
+    };
 
-                                ////
-                                let bytes = match nix::unistd::read(*fd, &mut buf[..]) {
-                                    Ok(b) => b,
-                                    Err(nix::Error::EWOULDBLOCK) => 0,
-                                    Err(e) => panic!("{}", e),
-                                };
-                                ////
-
-                                nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_SETFL(old_flags))
-                                    .unwrap();
-
-                                mut_state.srvc.stdout_buffer.extend(&buf[..bytes]);
-                                mut_state.srvc.log_stdout_lines(&name, &status).unwrap();
-                            }
-                        }
-                    }
-                }
-            }
-            Err(e) => {
-                warn!("Error while selecting: {}", e);
-            }
-        }
-    }
+    nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_SETFL(old_flags)).unwrap();
+    bytes
 }
 
-pub fn handle_all_std_err(run_info: ArcMutRuntimeInfo) {
-    let eventfd = { run_info.read().unwrap().stderr_eventfd };
-    loop {
-        // need to collect all again. There might be a newly started service
-        let fd_to_srvc_id = collect_from_srvc(run_info.clone(), |map, srvc, id| {
-            if let Some(StdIo::Piped(r, _w)) = &srvc.stderr {
-                map.insert(*r, id);
+pub fn handle_all_streams(run_info: ArcMutRuntimeInfo, events: Receiver<ReactorEvent>) {
+    handle_service_events(run_info, events, |_fd, srvc_unit, srvc, state, run_info| {
+        match receive_notifications(&mut state.srvc, &srvc.conf, &srvc_unit.id, run_info, false) {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
+            Err(e) => warn!(
+                "Error while reading notifications of service {}: {}",
+                srvc_unit.id.name, e
+            ),
+        }
+        if state.srvc.signaled_stopping {
+            let mut status = srvc_unit.common.status.write().unwrap();
+            if status.is_started() {
+                trace!("Service {} is stopping on its own", srvc_unit.id.name);
+                *status = UnitStatus::Stopping;
             }
-        });
-
-        let mut fdset = nix::sys::select::FdSet::new();
-        for fd in fd_to_srvc_id.keys() {
-            fdset.insert(*fd);
         }
-        fdset.insert(eventfd.read_end());
-
-        let result = nix::sys::select::select(None, Some(&mut fdset), None, None, None);
-        let run_info_locked = run_info.read().unwrap();
-        let unit_table = &run_info_locked.unit_table;
-
-        match result {
-            Ok(_) => {
-                if fdset.contains(eventfd.read_end()) {
-                    trace!("Interrupted stderr select because the eventfd fired");
-                    reset_event_fd(eventfd);
-                    trace!("Reset eventfd value");
-                }
-                let mut buf = [0u8; 512];
-                for (fd, id) in &fd_to_srvc_id {
-                    if fdset.contains(*fd) {
-                        if let Some(srvc_unit) = unit_table.get(id) {
-                            let name = srvc_unit.id.name.clone();
-                            if let Specific::Service(srvc) = &srvc_unit.specific {
-                                let mut_state = &mut *srvc.state.write().unwrap();
-                                let status = srvc_unit.common.status.read().unwrap();
+    });
+}
 
-                                let old_flags =
-                                    nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_GETFL).unwrap();
-                                let old_flags = nix::fcntl::OFlag::from_bits(old_flags).unwrap();
-                                let mut new_flags = old_flags.clone();
-                                new_flags.insert(nix::fcntl::OFlag::O_NONBLOCK);
-                                nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_SETFL(new_flags))
-                                    .unwrap();
+pub fn handle_all_std_out(run_info: ArcMutRuntimeInfo, events: Receiver<ReactorEvent>) {
+    handle_service_events(run_info, events, |fd, srvc_unit, _srvc, state, run_info| {
+        let mut buf = [0u8; 512];
+        let bytes = read_nonblocking(fd, &mut buf[..]);
+        let status = srvc_unit.common.status.read().unwrap();
+        state.srvc.stdout_buffer.extend(&buf[..bytes]);
+        let pid = state.srvc.pid;
+        state
+            .srvc
+            .log_stdout_lines(srvc_unit, pid, &status, run_info)
+            .unwrap();
+    });
+}
 
-                                ////
-                                let bytes = match nix::unistd::read(*fd, &mut buf[..]) {
-                                    Ok(b) => b,
-                                    Err(nix::Error::EWOULDBLOCK) => 0,
-                                    Err(e) => panic!("{}", e),
-                                };
-                                ////
-                                nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_SETFL(old_flags))
-                                    .unwrap();
+pub fn handle_all_std_err(run_info: ArcMutRuntimeInfo, events: Receiver<ReactorEvent>) {
+    handle_service_events(run_info, events, |fd, srvc_unit, _srvc, state, run_info| {
+        let mut buf = [0u8; 512];
+        let bytes = read_nonblocking(fd, &mut buf[..]);
+        let status = srvc_unit.common.status.read().unwrap();
+        state.srvc.stderr_buffer.extend(&buf[..bytes]);
+        let pid = state.srvc.pid;
+        state
+            .srvc
+            .log_stderr_lines(srvc_unit, pid, &status, run_info)
+            .unwrap();
+    });
+}
 
-                                mut_state.srvc.stderr_buffer.extend(&buf[..bytes]);
-                                mut_state.srvc.log_stderr_lines(&name, &status).unwrap();
-                            }
-                        }
-                    }
-                }
+/// Services whose main process rustysd knows are handled by the exit handler when that process exits. For the others
+/// the service is gone when its cgroup is empty.
+#[cfg(feature = "cgroups")]
+pub fn handle_all_cgroup_events(run_info: ArcMutRuntimeInfo, events: Receiver<ReactorEvent>) {
+    let exit_run_info = run_info.clone();
+    handle_service_events(
+        run_info,
+        events,
+        move |fd, srvc_unit, srvc, state, _run_info| {
+            match &state.srvc.cgroup_events {
+                // an event for an older watch of this service
+                Some(cgroup_events) if cgroup_events.as_raw_fd() == fd => cgroup_events.drain(),
+                _ => return,
             }
-            Err(e) => {
-                warn!("Error while selecting: {}", e);
+            if srvc.conf.srcv_type == ServiceType::OneShot
+                || state.srvc.pid.is_some()
+                || !srvc_unit.common.status.read().unwrap().is_started()
+            {
+                return;
             }
-        }
-    }
+            let cgroup_path = &srvc.conf.platform_specific.cgroup_path;
+            match crate::platform::cgroups::is_populated(cgroup_path) {
+                Ok(true) => {}
+                Ok(false) => crate::services::service_cgroup_empty_new_thread(
+                    srvc_unit.id.clone(),
+                    exit_run_info.clone(),
+                ),
+                Err(e) => warn!(
+                    "Could not read the cgroup.events of service {}: {}",
+                    srvc_unit.id.name, e
+                ),
+            }
+        },
+    );
 }
 
-pub fn handle_notification_message(msg: &str, srvc: &mut Service, name: &str) {
-    let split: Vec<_> = msg.split('=').collect();
-    match split[0] {
+pub fn handle_notification_message(
+    msg: &str,
+    srvc: &mut Service,
+    conf: &ServiceConfig,
+    id: &UnitId,
+    run_info: &RuntimeInfo,
+) {
+    let name = &id.name;
+    // values may contain '=' themselves
+    let (key, value) = match msg.split_once('=') {
+        Some(pair) => pair,
+        None => {
+            warn!(
+                "Ignore malformed notification from service {}: {}",
+                name, msg
+            );
+            return;
+        }
+    };
+    match key {
         "STATUS" => {
-            srvc.status_msgs.push(split[1].to_owned());
+            srvc.status_msgs.push(value.to_owned());
             trace!(
                 "New status message pushed from service {}: {}",
                 name,
@@ -260,14 +195,105 @@ pub fn handle_notification_message(msg: &str, srvc: &mut Service, name: &str) {
         }
         "READY" => {
             srvc.signaled_ready = true;
+            srvc.signaled_reloading = false;
+        }
+        "WATCHDOG" => match value {
+            "1" => srvc.watchdog.ping(),
+            "trigger" => {
+                trace!("Service {} triggered its watchdog", name);
+                srvc.watchdog.trigger();
+            }
+            value => warn!("Unknown WATCHDOG value from service {}: {}", name, value),
+        },
+        "WATCHDOG_USEC" => match value.parse::<u64>() {
+            Ok(usec) => srvc.watchdog.set_timeout(usec),
+            Err(_) => warn!("Invalid WATCHDOG_USEC from service {}: {}", name, value),
+        },
+        "RELOADING" => {
+            srvc.signaled_reloading = value == "1";
+            trace!("Service {} is reloading", name);
         }
+        "STOPPING" => {
+            srvc.signaled_stopping = value == "1";
+        }
+        "MAINPID" => change_main_pid(srvc, conf, id, value, run_info),
+        "EXTEND_TIMEOUT_USEC" => match value.parse::<u64>() {
+            Ok(usec) => {
+                trace!("Service {} wants {}us more time", name, usec);
+                srvc.extend_timeout =
+                    Some(std::time::Instant::now() + std::time::Duration::from_micros(usec));
+            }
+            Err(_) => warn!(
+                "Invalid EXTEND_TIMEOUT_USEC from service {}: {}",
+                name, value
+            ),
+        },
+        // these are handled together with the fds they were sent with, see store_fds
+        "FDSTORE" | "FDSTOREREMOVE" | "FDNAME" => {}
+        "ERRNO" => match value.parse::<i32>() {
+            Ok(errno) => {
+                trace!("Service {} reported errno {}", name, errno);
+                srvc.errno = Some(errno);
+            }
+            Err(_) => warn!("Invalid ERRNO from service {}: {}", name, value),
+        },
         _ => {
-            warn!("Unknown notification name{}", split[0]);
+            warn!("Unknown notification name{}", key);
         }
     }
 }
 
-pub fn handle_notifications_from_buffer(srvc: &mut Service, name: &str) {
+/// MAINPID=. The exit handler follows the new main process from now on, the old one is not supervised anymore.
+fn change_main_pid(
+    srvc: &mut Service,
+    conf: &ServiceConfig,
+    id: &UnitId,
+    value: &str,
+    run_info: &RuntimeInfo,
+) {
+    let new_pid = match value.parse::<i32>() {
+        Ok(pid) if pid > 0 => nix::unistd::Pid::from_raw(pid),
+        _ => {
+            warn!("Invalid MAINPID from service {}: {}", id.name, value);
+            return;
+        }
+    };
+    if srvc.pid == Some(new_pid) {
+        return;
+    }
+    let mut pid_table_locked = run_info.pid_table.lock().unwrap();
+    if nix::sys::signal::kill(new_pid, None).is_err() {
+        warn!(
+            "Service {} sent MAINPID={} but there is no such process",
+            id.name, new_pid
+        );
+        return;
+    }
+    if let Some(old_pid) = srvc.pid {
+        // the initial process of a forking service is still waited for by wait_for_service
+        if let Some(PidEntry::Service(_, srvc_type)) = pid_table_locked.get(&old_pid) {
+            if *srvc_type != ServiceType::Forking {
+                pid_table_locked.remove(&old_pid);
+            }
+        }
+    }
+    // forking entries are taken for the initial process by the exit handler
+    let srvc_type = if conf.srcv_type == ServiceType::Forking {
+        ServiceType::Simple
+    } else {
+        conf.srcv_type
+    };
+    pid_table_locked.insert(new_pid, PidEntry::Service(id.clone(), srvc_type));
+    trace!("Main pid of service {} is now {}", id.name, new_pid);
+    srvc.pid = Some(new_pid);
+}
+
+pub fn handle_notifications_from_buffer(
+    srvc: &mut Service,
+    conf: &ServiceConfig,
+    id: &UnitId,
+    run_info: &RuntimeInfo,
+) {
     while srvc.notifications_buffer.contains('\n') {
         let (line, rest) = srvc
             .notifications_buffer
@@ -275,6 +301,202 @@ pub fn handle_notifications_from_buffer(srvc: &mut Service, name: &str) {
         let line = line.to_owned();
         srvc.notifications_buffer = rest[1..].to_owned();
 
-        handle_notification_message(&line, srvc, name);
+        if !line.is_empty() {
+            handle_notification_message(&line, srvc, conf, id, run_info);
+        }
+    }
+}
+
+/// Receive one datagram from the notification socket of a service and handle the notifications in it. File
+/// descriptors that are sent along are put into the fd store if the datagram contains FDSTORE=1. Returns the
+/// number of bytes received.
+pub fn receive_notifications(
+    srvc: &mut Service,
+    conf: &ServiceConfig,
+    id: &UnitId,
+    run_info: &RuntimeInfo,
+    blocking: bool,
+) -> std::io::Result<usize> {
+    use nix::sys::socket::{recvmsg, ControlMessageOwned, MsgFlags};
+
+    let fd = match &srvc.notifications {
+        Some(socket) => socket.as_raw_fd(),
+        None => return Ok(0),
+    };
+    let mut buf = [0u8; 4096];
+    // the kernel allows at most 253 fds per message
+    #[cfg(target_os = "linux")]
+    let mut cmsg_buf = nix::cmsg_space!(
+        [std::os::unix::io::RawFd; 253],
+        nix::sys::socket::UnixCredentials
+    );
+    #[cfg(not(target_os = "linux"))]
+    let mut cmsg_buf = nix::cmsg_space!([std::os::unix::io::RawFd; 253]);
+    let mut flags = MsgFlags::MSG_CMSG_CLOEXEC;
+    if !blocking {
+        flags.insert(MsgFlags::MSG_DONTWAIT);
+    }
+    let (bytes, fds, sender) = {
+        let mut iov = [std::io::IoSliceMut::new(&mut buf[..])];
+        let msg = recvmsg::<()>(fd, &mut iov, Some(&mut cmsg_buf), flags)
+            .map_err(|e| std::io::Error::from_raw_os_error(e as i32))?;
+        let mut fds = Vec::new();
+        let mut sender = None;
+        for cmsg in msg.cmsgs() {
+            match cmsg {
+                ControlMessageOwned::ScmRights(received) => fds.extend(received),
+                #[cfg(target_os = "linux")]
+                ControlMessageOwned::ScmCredentials(creds) => {
+                    sender = Some(nix::unistd::Pid::from_raw(creds.pid()))
+                }
+                _ => {}
+            }
+        }
+        (msg.bytes, fds, sender)
+    };
+
+    if !sender_allowed(srvc, conf, id, sender, run_info) {
+        warn!(
+            "Ignore notification from process {} for service {} because of NotifyAccess={:?}",
+            sender
+                .map(|pid| pid.to_string())
+                .unwrap_or_else(|| "?".into()),
+            id.name,
+            format!("{:?}", conf.notifyaccess).to_lowercase()
+        );
+        close_fds(&fds);
+        return Ok(bytes);
+    }
+
+    let mut msg = String::from_utf8_lossy(&buf[..bytes]).into_owned();
+    // every datagram is a complete message, even if it does not end with a newline
+    if !msg.is_empty() && !msg.ends_with('\n') {
+        msg.push('\n');
+    }
+    store_fds(&msg, fds, conf, id, run_info);
+    srvc.notifications_buffer.push_str(&msg);
+    handle_notifications_from_buffer(srvc, conf, id, run_info);
+    Ok(bytes)
+}
+
+/// Check NotifyAccess= for the sender of a notification
+pub fn sender_allowed(
+    srvc: &Service,
+    conf: &ServiceConfig,
+    id: &UnitId,
+    sender: Option<nix::unistd::Pid>,
+    run_info: &RuntimeInfo,
+) -> bool {
+    let sender = match sender {
+        Some(sender) => sender,
+        // Without SCM_CREDENTIALS the sender is not known, only NotifyAccess=none can be enforced
+        None => return conf.notifyaccess != NotifyKind::None,
+    };
+    match conf.notifyaccess {
+        NotifyKind::None => false,
+        NotifyKind::All => true,
+        NotifyKind::Main => srvc.pid == Some(sender),
+        // the main process or one of the ExecStartPre=/ExecStartPost=/ExecReload=/ExecStop=/ExecStopPost= commands
+        NotifyKind::Exec => {
+            srvc.pid == Some(sender)
+                || match run_info.pid_table.lock().unwrap().get(&sender) {
+                    Some(PidEntry::Helper(helper_id, _)) => helper_id == id,
+                    _ => false,
+                }
+        }
+    }
+}
+
+fn close_fds(fds: &[std::os::unix::io::RawFd]) {
+    for fd in fds {
+        let _ = nix::unistd::close(*fd);
+    }
+}
+
+/// FDSTORE=1 and FDSTOREREMOVE=1 (with FDNAME=) apply to the fds that were sent in the same datagram
+fn store_fds(
+    msg: &str,
+    fds: Vec<std::os::unix::io::RawFd>,
+    conf: &ServiceConfig,
+    id: &UnitId,
+    run_info: &RuntimeInfo,
+) {
+    let mut store = false;
+    let mut remove = false;
+    let mut fd_name = "stored";
+    for line in msg.lines() {
+        match line.split_once('=') {
+            Some(("FDSTORE", value)) => store = value == "1",
+            Some(("FDSTOREREMOVE", value)) => remove = value == "1",
+            Some(("FDNAME", value)) => fd_name = value,
+            _ => {}
+        }
+    }
+    if !store && !remove {
+        if !fds.is_empty() {
+            warn!(
+                "Service {} sent file descriptors without FDSTORE=1. Closing them.",
+                id.name
+            );
+            close_fds(&fds);
+        }
+        return;
+    }
+
+    let fd_store = &mut *run_info.fd_store.write().unwrap();
+    if remove {
+        if let Some(removed) = fd_store.remove_service_stored(&id.name, &fd_name.to_owned()) {
+            trace!(
+                "Remove {} stored fds with name {} of service {}",
+                removed.len(),
+                fd_name,
+                id.name
+            );
+            for fd in removed {
+                let _ = nix::unistd::close(*fd);
+            }
+        }
+    }
+    if !store {
+        close_fds(&fds);
+        return;
+    }
+    let already_stored = fd_store.get_all_service_stored(&id.name).len();
+    if already_stored + fds.len() > conf.fd_store_max {
+        warn!(
+            "Service {} tried to store {} more fds but FileDescriptorStoreMax={} ({} stored already). Closing them.",
+            id.name,
+            fds.len(),
+            conf.fd_store_max,
+            already_stored
+        );
+        close_fds(&fds);
+        return;
+    }
+    trace!(
+        "Store {} fds with name {} for service {}",
+        fds.len(),
+        fd_name,
+        id.name
+    );
+    fd_store.insert_service_stored(
+        id.name.clone(),
+        fd_name.to_owned(),
+        fds.into_iter().map(Box::new).collect(),
+    );
+}
+
+/// Handle the notifications a service sent while it was locked by someone else (e.g. while waiting for a helper
+/// process). The notification thread can not read them in the meantime.
+pub fn read_pending_notifications(
+    srvc: &mut Service,
+    conf: &ServiceConfig,
+    id: &UnitId,
+    run_info: &RuntimeInfo,
+) {
+    while let Ok(bytes) = receive_notifications(srvc, conf, id, run_info, false) {
+        if bytes == 0 {
+            break;
+        }
     }
 }
@@ -0,0 +1,374 @@
+//! Watch the paths of path units and activate their respective units
+use log::error;
+use log::trace;
+use log::warn;
+
+use crate::paths::PathEvent;
+use crate::runtime_info::*;
+use crate::units::*;
+
+use std::collections::HashSet;
+use std::os::unix::io::RawFd;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// If a condition holds while the unit it activates is still active or busy, check again after this time
+const BUSY_UNIT_RECHECK: Duration = Duration::from_secs(1);
+
+pub fn start_pathactivation_thread(run_info: ArcMutRuntimeInfo) {
+    std::thread::spawn(move || {
+        let mut watcher = match PathWatcher::new() {
+            Ok(watcher) => watcher,
+            Err(e) => {
+                error!("Could not start watching paths: {}", e);
+                return;
+            }
+        };
+        let eventfd = { run_info.read().unwrap().path_activation_eventfd };
+        let poller = match crate::platform::Poller::new().and_then(|poller| {
+            poller.add(eventfd.read_end())?;
+            if let Some(fd) = watcher.fd() {
+                poller.add(fd)?;
+            }
+            Ok(poller)
+        }) {
+            Ok(poller) => poller,
+            Err(e) => {
+                error!("Could not start waiting for path events: {}", e);
+                return;
+            }
+        };
+        let mut events = Vec::new();
+        loop {
+            // Update the watches before checking the conditions, so nothing that happens in between is missed
+            watcher.update_watches(collect_watch_paths(&run_info));
+            let (triggered, wait_time) = collect_triggered_paths(&run_info, &events);
+            for (path_id, unit_id) in triggered {
+                trigger_unit_new_thread(path_id, unit_id, run_info.clone());
+            }
+            match wait_for_path_event(&poller, eventfd, &mut watcher, wait_time) {
+                Ok(new_events) => events = new_events,
+                Err(e) => {
+                    error!("Error in path activation loop: {}", e);
+                    break;
+                }
+            }
+        }
+    });
+}
+
+/// All paths that need to be watched for the currently started path units
+fn collect_watch_paths(run_info: &ArcMutRuntimeInfo) -> HashSet<PathBuf> {
+    let run_info = &*run_info.read().unwrap();
+    let mut paths = HashSet::new();
+    for unit in run_info.unit_table.values() {
+        if let Specific::Path(specific) = &unit.specific {
+            if unit.common.status.read().unwrap().is_started() {
+                for condition in &specific.conf.conditions {
+                    paths.extend(condition.watch_paths());
+                }
+            }
+        }
+    }
+    paths
+}
+
+/// Whether the unit is inactive and may be activated again by a level triggered condition. Returns None if
+/// the unit is busy and needs to be looked at again later.
+fn triggered_unit_inactive(unit: &Unit, last_trigger: Option<SystemTime>) -> Option<bool> {
+    let status = unit.common.status.read().unwrap().clone();
+    match status {
+        UnitStatus::NeverStarted
+        | UnitStatus::Stopped(_, _)
+        | UnitStatus::Started(StatusStarted::WaitingForSocket) => Some(true),
+        UnitStatus::Started(StatusStarted::Running) => {
+            if !unit.is_finished_oneshot() {
+                return Some(false);
+            }
+            // oneshot services stay started, check if it ran since the last trigger
+            let (_, down_since) = unit.try_activity_times()?;
+            match (down_since, last_trigger) {
+                (Some(down_since), Some(last_trigger)) => {
+                    let since_trigger = SystemTime::now()
+                        .duration_since(last_trigger)
+                        .unwrap_or_default();
+                    Some(down_since.elapsed() < since_trigger)
+                }
+                _ => Some(true),
+            }
+        }
+        UnitStatus::Starting
+        | UnitStatus::Stopping
+        | UnitStatus::Restarting
+        | UnitStatus::Reloading => None,
+    }
+}
+
+/// Find all started path units whose conditions triggered and mark them as triggered. Also returns how long the
+/// watcher may sleep at most before the conditions need to be looked at again.
+fn collect_triggered_paths(
+    run_info: &ArcMutRuntimeInfo,
+    events: &[PathEvent],
+) -> (Vec<(UnitId, UnitId)>, Option<Duration>) {
+    let run_info = &*run_info.read().unwrap();
+    let now = SystemTime::now();
+    let mut triggered = Vec::new();
+    let mut wait_time: Option<Duration> = None;
+
+    for unit in run_info.unit_table.values() {
+        let specific = if let Specific::Path(specific) = &unit.specific {
+            specific
+        } else {
+            continue;
+        };
+        if !unit.common.status.read().unwrap().is_started() {
+            continue;
+        }
+
+        let triggered_unit = match run_info.unit_table.get(&specific.conf.unit) {
+            Some(triggered_unit) => triggered_unit,
+            None => {
+                error!(
+                    "Unit {:?} of path unit {} can not be found",
+                    specific.conf.unit, unit.id.name
+                );
+                continue;
+            }
+        };
+
+        let last_trigger = { specific.state.read().unwrap().path.last_trigger };
+        let edge_triggered = specific
+            .conf
+            .conditions
+            .iter()
+            .find(|cond| events.iter().any(|event| cond.is_triggered_by(event)));
+        let level_triggered = specific
+            .conf
+            .conditions
+            .iter()
+            .find(|cond| cond.is_level_triggered() && cond.is_fulfilled());
+
+        let condition = match (edge_triggered, level_triggered) {
+            (Some(cond), _) => cond,
+            (None, Some(cond)) => match triggered_unit_inactive(triggered_unit, last_trigger) {
+                Some(true) => cond,
+                _ => {
+                    wait_time = Some(BUSY_UNIT_RECHECK);
+                    continue;
+                }
+            },
+            (None, None) => continue,
+        };
+
+        let state = &mut *specific.state.write().unwrap();
+        if state.path.triggered(condition, now) {
+            triggered.push((unit.id.clone(), specific.conf.unit.clone()));
+        } else {
+            error!(
+                "Path unit {} triggered too often in a short time. Stopping it.",
+                unit.id.name
+            );
+            state.path.deactivate();
+            let mut status = unit.common.status.write().unwrap();
+            *status = UnitStatus::Stopped(
+                StatusStopped::StoppedUnexpected,
+                vec![UnitOperationErrorReason::GenericStartError(
+                    "Trigger limit hit".into(),
+                )],
+            );
+            run_info.hooks.push(crate::hook_activation::UnitEnded {
+                id: unit.id.clone(),
+                result: "trigger-limit-hit",
+                exit: None,
+            });
+        }
+    }
+
+    (triggered, wait_time)
+}
+
+fn trigger_unit_new_thread(path_id: UnitId, unit_id: UnitId, run_info: ArcMutRuntimeInfo) {
+    std::thread::spawn(move || {
+        trace!("Start unit {} by path unit {}", unit_id.name, path_id.name);
+        let run_info = &*run_info.read().unwrap();
+        match crate::units::activate_unit(
+            unit_id.clone(),
+            run_info,
+            ActivationSource::PathActivation,
+        ) {
+            Ok(_) => {
+                trace!(
+                    "New status after path activation: {:?}",
+                    *run_info
+                        .unit_table
+                        .get(&unit_id)
+                        .unwrap()
+                        .common
+                        .status
+                        .read()
+                        .unwrap()
+                );
+            }
+            Err(e) => {
+                error!("Error while starting unit from path activation: {}", e);
+            }
+        }
+        // the status of the unit changed, which might change whether the conditions trigger again
+        crate::platform::notify_event_fd(run_info.path_activation_eventfd);
+    });
+}
+
+/// Sleep until either a watched path changed, the wait_time passed or the eventfd fired because the
+/// path units need to be looked at again
+fn wait_for_path_event(
+    poller: &crate::platform::Poller,
+    eventfd: crate::platform::EventFd,
+    watcher: &mut PathWatcher,
+    wait_time: Option<Duration>,
+) -> Result<Vec<PathEvent>, String> {
+    let mut events = Vec::new();
+    for fd in poller.wait(watcher.max_wait_time(wait_time))? {
+        if fd == eventfd.read_end() {
+            trace!("Interrupted path wait because the eventfd fired");
+            crate::platform::reset_event_fd(eventfd);
+            trace!("Reset eventfd value");
+        } else if Some(fd) == watcher.fd() {
+            events = watcher.read_events();
+        }
+        poller.rearm(fd)?;
+    }
+    Ok(events)
+}
+
+#[cfg(target_os = "linux")]
+struct PathWatcher {
+    inotify: nix::sys::inotify::Inotify,
+    watches: std::collections::HashMap<PathBuf, nix::sys::inotify::WatchDescriptor>,
+}
+
+#[cfg(target_os = "linux")]
+impl PathWatcher {
+    fn new() -> Result<Self, String> {
+        use nix::sys::inotify::{InitFlags, Inotify};
+        let inotify = Inotify::init(InitFlags::IN_CLOEXEC | InitFlags::IN_NONBLOCK)
+            .map_err(|e| format!("Could not init inotify: {}", e))?;
+        Ok(PathWatcher {
+            inotify,
+            watches: std::collections::HashMap::new(),
+        })
+    }
+
+    fn fd(&self) -> Option<RawFd> {
+        use std::os::unix::io::AsRawFd;
+        Some(self.inotify.as_raw_fd())
+    }
+
+    fn max_wait_time(&self, wait_time: Option<Duration>) -> Option<Duration> {
+        wait_time
+    }
+
+    fn update_watches(&mut self, wanted: HashSet<PathBuf>) {
+        use nix::sys::inotify::AddWatchFlags;
+
+        let unwanted: Vec<PathBuf> = self
+            .watches
+            .keys()
+            .filter(|path| !wanted.contains(*path))
+            .cloned()
+            .collect();
+        for path in unwanted {
+            let wd = self.watches.remove(&path).unwrap();
+            // the watch might already be gone if the path was deleted
+            let _ = self.inotify.rm_watch(wd);
+            trace!("Stopped watching path {:?}", path);
+        }
+
+        let flags = AddWatchFlags::IN_CREATE
+            | AddWatchFlags::IN_DELETE
+            | AddWatchFlags::IN_MOVED_FROM
+            | AddWatchFlags::IN_MOVED_TO
+            | AddWatchFlags::IN_CLOSE_WRITE
+            | AddWatchFlags::IN_MODIFY
+            | AddWatchFlags::IN_ATTRIB
+            | AddWatchFlags::IN_DELETE_SELF
+            | AddWatchFlags::IN_MOVE_SELF;
+        for path in wanted {
+            if self.watches.contains_key(&path) {
+                continue;
+            }
+            match self.inotify.add_watch(&path, flags) {
+                Ok(wd) => {
+                    trace!("Started watching path {:?}", path);
+                    self.watches.insert(path, wd);
+                }
+                Err(e) => {
+                    // the path might have been removed in the meantime. It is picked up again
+                    // with the next event on its parent
+                    warn!("Could not watch path {:?}: {}", path, e);
+                }
+            }
+        }
+    }
+
+    fn read_events(&mut self) -> Vec<PathEvent> {
+        use nix::sys::inotify::AddWatchFlags;
+
+        let mut events = Vec::new();
+        loop {
+            let raw_events = match self.inotify.read_events() {
+                Ok(raw_events) => raw_events,
+                Err(nix::Error::EAGAIN) => break,
+                Err(e) => {
+                    error!("Error while reading inotify events: {}", e);
+                    break;
+                }
+            };
+            for raw in raw_events {
+                let watched: Vec<PathBuf> = self
+                    .watches
+                    .iter()
+                    .filter(|(_, wd)| **wd == raw.wd)
+                    .map(|(path, _)| path.clone())
+                    .collect();
+                if raw.mask.contains(AddWatchFlags::IN_IGNORED) {
+                    // the watched path is gone, forget the watch so it gets added again if the path reappears
+                    self.watches.retain(|_, wd| *wd != raw.wd);
+                }
+                for watched in watched {
+                    events.push(PathEvent {
+                        watched,
+                        name: raw.name.clone(),
+                        modify_only: raw.mask == AddWatchFlags::IN_MODIFY,
+                    });
+                }
+            }
+        }
+        events
+    }
+}
+
+/// Without inotify only the level triggered conditions (PathExists=, PathExistsGlob=, DirectoryNotEmpty=) work.
+/// They are checked periodically.
+#[cfg(not(target_os = "linux"))]
+struct PathWatcher;
+
+#[cfg(not(target_os = "linux"))]
+impl PathWatcher {
+    fn new() -> Result<Self, String> {
+        Ok(PathWatcher)
+    }
+
+    fn fd(&self) -> Option<RawFd> {
+        None
+    }
+
+    fn max_wait_time(&self, wait_time: Option<Duration>) -> Option<Duration> {
+        Some(wait_time.map_or(BUSY_UNIT_RECHECK, |dur| dur.min(BUSY_UNIT_RECHECK)))
+    }
+
+    fn update_watches(&mut self, _wanted: HashSet<PathBuf>) {}
+
+    fn read_events(&mut self) -> Vec<PathEvent> {
+        Vec::new()
+    }
+}
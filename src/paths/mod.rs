@@ -0,0 +1,196 @@
+//! Everything needed to check the conditions of path units. The watching itself happens in path_activation.rs
+
+use log::{trace, warn};
+
+use std::ffi::OsString;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Same defaults as systemd uses for TriggerLimitIntervalSec= and TriggerLimitBurst=
+const TRIGGER_LIMIT_INTERVAL: Duration = Duration::from_secs(2);
+const TRIGGER_LIMIT_BURST: u32 = 200;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum PathCondition {
+    Exists(PathBuf),
+    ExistsGlob(PathBuf),
+    Changed(PathBuf),
+    Modified(PathBuf),
+    DirectoryNotEmpty(PathBuf),
+}
+
+/// Something happened to a watched path. If the watched path is a directory, name is the entry in that directory
+/// that changed.
+#[derive(Clone, Debug)]
+pub struct PathEvent {
+    pub watched: PathBuf,
+    pub name: Option<OsString>,
+    /// The content was written to but the file was not closed yet
+    pub modify_only: bool,
+}
+
+impl std::fmt::Display for PathCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}={}", self.setting_name(), self.path().display())
+    }
+}
+
+fn nearest_existing_dir(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .find(|ancestor| ancestor.is_dir())
+        .map(Path::to_path_buf)
+}
+
+/// The part of the glob pattern before the first component with wildcards
+fn glob_prefix(pattern: &Path) -> PathBuf {
+    pattern
+        .components()
+        .take_while(|comp| !comp.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+        .collect()
+}
+
+impl PathCondition {
+    pub fn path(&self) -> &Path {
+        match self {
+            PathCondition::Exists(path)
+            | PathCondition::ExistsGlob(path)
+            | PathCondition::Changed(path)
+            | PathCondition::Modified(path)
+            | PathCondition::DirectoryNotEmpty(path) => path,
+        }
+    }
+
+    pub fn setting_name(&self) -> &'static str {
+        match self {
+            PathCondition::Exists(_) => "PathExists",
+            PathCondition::ExistsGlob(_) => "PathExistsGlob",
+            PathCondition::Changed(_) => "PathChanged",
+            PathCondition::Modified(_) => "PathModified",
+            PathCondition::DirectoryNotEmpty(_) => "DirectoryNotEmpty",
+        }
+    }
+
+    /// Level triggered conditions activate the unit as long as they hold. The others activate the unit
+    /// each time the path changes.
+    pub fn is_level_triggered(&self) -> bool {
+        match self {
+            PathCondition::Exists(_)
+            | PathCondition::ExistsGlob(_)
+            | PathCondition::DirectoryNotEmpty(_) => true,
+            PathCondition::Changed(_) | PathCondition::Modified(_) => false,
+        }
+    }
+
+    /// Check a level triggered condition. This is always false for the other conditions.
+    pub fn is_fulfilled(&self) -> bool {
+        match self {
+            PathCondition::Exists(path) => path.exists(),
+            PathCondition::ExistsGlob(pattern) => match crate::platform::glob::glob(pattern) {
+                Ok(matches) => !matches.is_empty(),
+                Err(e) => {
+                    warn!("{}", e);
+                    false
+                }
+            },
+            PathCondition::DirectoryNotEmpty(path) => std::fs::read_dir(path)
+                .map(|mut entries| entries.next().is_some())
+                .unwrap_or(false),
+            PathCondition::Changed(_) | PathCondition::Modified(_) => false,
+        }
+    }
+
+    /// The paths that need to be watched to notice all changes relevant for this condition. Paths that do not
+    /// exist yet are covered by watching the closest directory that does exist.
+    pub fn watch_paths(&self) -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        let path = match self {
+            PathCondition::ExistsGlob(pattern) => glob_prefix(pattern),
+            _ => self.path().to_path_buf(),
+        };
+        if path.is_dir() {
+            paths.push(path.clone());
+        }
+        if let Some(parent) = path.parent().and_then(nearest_existing_dir) {
+            paths.push(parent);
+        }
+        paths
+    }
+
+    /// Check if the event should trigger the unit. This is always false for level triggered conditions, they
+    /// are checked with is_fulfilled() instead.
+    pub fn is_triggered_by(&self, event: &PathEvent) -> bool {
+        let path = match self {
+            PathCondition::Changed(path) if !event.modify_only => path,
+            PathCondition::Modified(path) => path,
+            _ => return false,
+        };
+        if event.watched == *path {
+            return true;
+        }
+        match &event.name {
+            Some(name) => event.watched.join(name) == *path,
+            None => false,
+        }
+    }
+
+    /// Create the watched directory for MakeDirectory=. Like systemd this is only done for conditions that
+    /// are not about the existence of the path.
+    pub fn make_directory(&self) -> Result<(), String> {
+        match self {
+            PathCondition::Exists(_) | PathCondition::ExistsGlob(_) => Ok(()),
+            _ => std::fs::DirBuilder::new()
+                .recursive(true)
+                .mode(0o755)
+                .create(self.path())
+                .map_err(|e| format!("Could not create dir {:?}: {}", self.path(), e)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PathWatch {
+    pub last_trigger: Option<SystemTime>,
+    /// The condition that caused the last trigger
+    pub triggered_by: Option<PathCondition>,
+    trigger_window_start: Option<Instant>,
+    triggers_in_window: u32,
+}
+
+impl PathWatch {
+    pub fn activate(&mut self, conditions: &[PathCondition], make_directory: bool) {
+        if make_directory {
+            for condition in conditions {
+                if let Err(e) = condition.make_directory() {
+                    warn!("{}", e);
+                }
+            }
+        }
+        self.trigger_window_start = None;
+        self.triggers_in_window = 0;
+    }
+
+    pub fn deactivate(&mut self) {
+        self.trigger_window_start = None;
+        self.triggers_in_window = 0;
+    }
+
+    /// Record that the condition triggered the unit at 'now'. Returns false if the unit was triggered too often in
+    /// a short time, which means the path unit should be stopped.
+    pub fn triggered(&mut self, condition: &PathCondition, now: SystemTime) -> bool {
+        trace!("Path condition {} triggered", condition);
+        self.last_trigger = Some(now);
+        self.triggered_by = Some(condition.clone());
+
+        let window_passed = self
+            .trigger_window_start
+            .map(|start| start.elapsed() > TRIGGER_LIMIT_INTERVAL)
+            .unwrap_or(true);
+        if window_passed {
+            self.trigger_window_start = Some(Instant::now());
+            self.triggers_in_window = 0;
+        }
+        self.triggers_in_window += 1;
+        self.triggers_in_window <= TRIGGER_LIMIT_BURST
+    }
+}
@@ -29,7 +29,6 @@ pub fn move_self_to_cgroup(cgroup_path: &std::path::PathBuf) -> Result<(), Cgrou
 }
 
 /// retrieve all controllers that are currently in this cgroup
-#[allow(dead_code)]
 pub fn get_available_controllers(
     cgroup_path: &std::path::PathBuf,
 ) -> Result<Vec<String>, CgroupError> {
@@ -40,11 +39,10 @@ pub fn get_available_controllers(
     f.read_to_string(&mut buf)
         .map_err(|e| CgroupError::IOErr(e, format!("{:?}", cgroup_ctrls)))?;
 
-    Ok(buf.split('\n').map(|s| s.to_string()).collect())
+    Ok(buf.split_whitespace().map(|s| s.to_string()).collect())
 }
 
 /// enable controllers for child-cgroups
-#[allow(dead_code)]
 pub fn enable_controllers(
     cgroup_path: &std::path::PathBuf,
     controllers: &Vec<String>,
@@ -89,6 +87,22 @@ pub fn disable_controllers(
     Ok(())
 }
 
+/// write a file of a controller like memory.max
+pub fn write_setting(
+    cgroup_path: &std::path::Path,
+    file_name: &str,
+    value: &str,
+) -> Result<(), CgroupError> {
+    let setting_file = cgroup_path.join(file_name);
+    let mut f = fs::OpenOptions::new()
+        .write(true)
+        .open(&setting_file)
+        .map_err(|e| CgroupError::IOErr(e, format!("{:?}", setting_file)))?;
+    f.write_all(value.as_bytes())
+        .map_err(|e| CgroupError::IOErr(e, format!("{:?}", setting_file)))?;
+    Ok(())
+}
+
 fn write_freeze_state(
     cgroup_path: &std::path::PathBuf,
     desired_state: &str,
@@ -138,6 +152,24 @@ pub fn freeze(cgroup_path: &std::path::PathBuf) -> Result<(), CgroupError> {
 }
 
 pub fn thaw(cgroup_path: &std::path::PathBuf) -> Result<(), CgroupError> {
-    let desired_state = "1";
+    let desired_state = "0";
     write_freeze_state(cgroup_path, desired_state)
 }
+
+/// kill all processes in the cgroup and its child cgroups at once, including the ones that are forked while killing.
+/// Returns false if the kernel does not support cgroup.kill (it was added in linux 5.14).
+pub fn kill(cgroup_path: &std::path::Path) -> Result<bool, CgroupError> {
+    if !cgroup_path.join("cgroup.kill").exists() {
+        return Ok(false);
+    }
+    write_setting(cgroup_path, "cgroup.kill", "1")?;
+    Ok(true)
+}
+
+/// whether there are processes in the cgroup or its child cgroups, read from cgroup.events
+pub fn is_populated(cgroup_path: &std::path::Path) -> Result<bool, CgroupError> {
+    let cgroup_events = cgroup_path.join("cgroup.events");
+    let content = fs::read_to_string(&cgroup_events)
+        .map_err(|e| CgroupError::IOErr(e, format!("{:?}", cgroup_events)))?;
+    Ok(super::parse_flat_keyed(&content, "populated") != Some(0))
+}
@@ -1,5 +1,9 @@
-//! This module provides methods to manage processes with cgroups. Not resource management but reliable tracking of services.
-//! It dynamically decides wether cgroups v1 or v2 should be used.
+//! This module provides methods to manage processes with cgroups. Mainly reliable tracking of services, with cgroups v2
+//! the resource control settings of units are also written and their resource usage is read here. It dynamically decides wether cgroups v1 or v2 should
+//! be used.
+//!
+//! With cgroups v2 units are killed with cgroup.kill and the cgroup.events file tells when a cgroup is empty. With cgroups v1
+//! the cgroups are frozen and signaled instead and their cgroup.procs are polled.
 //!
 //! The cgroup paths created by get_own_freezer return a path that is inside the cgroup that contains rustysd itself. With the naming scheme of the freezer
 //! cgroups we should mostly comply to the guidelines here https://www.freedesktop.org/wiki/Software/systemd/PaxControlGroups/
@@ -8,6 +12,9 @@ use std::fs;
 use std::io::Read;
 
 use log::trace;
+use log::warn;
+
+use crate::units::{ResourceControl, ResourceLimit};
 
 mod cgroup1;
 mod cgroup2;
@@ -254,6 +261,157 @@ pub fn remove_cgroup(cgroup_path: &std::path::PathBuf) -> Result<(), CgroupError
     fs::remove_dir(&cgroup_path).map_err(|e| CgroupError::IOErr(e, format!("{:?}", cgroup_path)))
 }
 
+/// kill all processes in the cgroup. With cgroups v2 SIGKILL is sent through cgroup.kill, which kills all processes
+/// atomically without freezing the cgroup. Other signals, cgroups v1 and kernels without cgroup.kill fall back to
+/// freeze_kill_thaw_cgroup.
+pub fn terminate_cgroup(
+    cgroup_path: &std::path::PathBuf,
+    sig: nix::sys::signal::Signal,
+) -> Result<(), CgroupError> {
+    if sig == nix::sys::signal::Signal::SIGKILL
+        && use_v2(cgroup_path)
+        && cgroup2::kill(cgroup_path)?
+    {
+        trace!("Killed cgroup with cgroup.kill: {:?}", cgroup_path);
+        return Ok(());
+    }
+    freeze_kill_thaw_cgroup(cgroup_path, sig)
+}
+
+/// whether there are processes left in the cgroup or its child cgroups. Killed processes leave the cgroup when they
+/// exit, even if they were not reaped yet.
+pub fn is_populated(cgroup_path: &std::path::PathBuf) -> Result<bool, CgroupError> {
+    if use_v2(cgroup_path) {
+        cgroup2::is_populated(cgroup_path)
+    } else {
+        has_procs_recursive(cgroup_path)
+    }
+}
+
+/// cgroups v1 have no cgroup.events, so look at the cgroup.procs of all cgroups in the tree
+fn has_procs_recursive(cgroup_path: &std::path::PathBuf) -> Result<bool, CgroupError> {
+    if !get_all_procs(cgroup_path)?.is_empty() {
+        return Ok(true);
+    }
+    let entries = fs::read_dir(cgroup_path)
+        .map_err(|e| CgroupError::IOErr(e, format!("{:?}", cgroup_path)))?;
+    for entry in entries.flatten() {
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false)
+            && has_procs_recursive(&entry.path())?
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// How often cgroups without cgroup.events are checked while waiting for them to become empty
+const EMPTY_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// Wait until all processes left the cgroup, so it can be removed. Returns false if there are still processes in it
+/// after the timeout.
+pub fn wait_empty(
+    cgroup_path: &std::path::PathBuf,
+    timeout: std::time::Duration,
+) -> Result<bool, CgroupError> {
+    let start = std::time::Instant::now();
+    // without cgroup.events this falls back to polling
+    let events = CgroupEvents::watch(cgroup_path).ok();
+    loop {
+        if !is_populated(cgroup_path)? {
+            return Ok(true);
+        }
+        let elapsed = start.elapsed();
+        if elapsed >= timeout {
+            return Ok(false);
+        }
+        match &events {
+            Some(events) => events.wait(timeout - elapsed),
+            None => std::thread::sleep(EMPTY_POLL_INTERVAL.min(timeout - elapsed)),
+        }
+    }
+}
+
+/// Watches the cgroup.events file of a cgroup v2 with inotify. The fd becomes readable when the cgroup gets populated
+/// or empty and when the cgroup is removed.
+#[cfg(target_os = "linux")]
+#[derive(Debug)]
+pub struct CgroupEvents {
+    inotify: nix::sys::inotify::Inotify,
+}
+
+#[cfg(target_os = "linux")]
+impl CgroupEvents {
+    pub fn watch(cgroup_path: &std::path::Path) -> Result<CgroupEvents, CgroupError> {
+        use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify};
+        let cgroup_events = cgroup_path.join("cgroup.events");
+        let inotify = Inotify::init(InitFlags::IN_CLOEXEC | InitFlags::IN_NONBLOCK)
+            .map_err(CgroupError::NixErr)?;
+        // the inotify fd is only closed on drop after the watch was added
+        let events = CgroupEvents { inotify };
+        events
+            .inotify
+            .add_watch(&cgroup_events, AddWatchFlags::IN_MODIFY)
+            .map_err(|e| CgroupError::IOErr(e.into(), format!("{:?}", cgroup_events)))?;
+        Ok(events)
+    }
+
+    pub fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        use std::os::unix::io::AsRawFd;
+        self.inotify.as_raw_fd()
+    }
+
+    /// Read all pending events, so the fd is only readable again after the next change
+    pub fn drain(&self) {
+        while let Ok(events) = self.inotify.read_events() {
+            if events.is_empty() {
+                break;
+            }
+        }
+    }
+
+    /// Wait until cgroup.events changes or the timeout passed
+    pub fn wait(&self, timeout: std::time::Duration) {
+        use nix::poll::{poll, PollFd, PollFlags};
+        let mut poll_fds = [PollFd::new(self.as_raw_fd(), PollFlags::POLLIN)];
+        let timeout = timeout.as_millis().min(libc::c_int::MAX as u128) as libc::c_int;
+        if let Err(e) = poll(&mut poll_fds, timeout) {
+            trace!("Error while waiting for cgroup events: {}", e);
+        }
+        self.drain();
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for CgroupEvents {
+    fn drop(&mut self) {
+        let _ = nix::unistd::close(self.as_raw_fd());
+    }
+}
+
+/// Without inotify the cgroup.events can not be watched
+#[cfg(not(target_os = "linux"))]
+#[derive(Debug)]
+pub struct CgroupEvents;
+
+#[cfg(not(target_os = "linux"))]
+impl CgroupEvents {
+    pub fn watch(cgroup_path: &std::path::Path) -> Result<CgroupEvents, CgroupError> {
+        let _ = cgroup_path;
+        Err(CgroupError::NotMounted)
+    }
+
+    pub fn as_raw_fd(&self) -> std::os::unix::io::RawFd {
+        -1
+    }
+
+    pub fn drain(&self) {}
+
+    pub fn wait(&self, timeout: std::time::Duration) {
+        std::thread::sleep(EMPTY_POLL_INTERVAL.min(timeout));
+    }
+}
+
 /// kill all processes that are currently in this cgroup.
 /// You should use wait_frozen before or make in another way sure
 /// there are no more processes spawned while killing
@@ -292,3 +450,241 @@ pub fn thaw(cgroup_path: &std::path::PathBuf) -> Result<(), CgroupError> {
         cgroup1::thaw(cgroup_path)
     }
 }
+
+/// Parse a line like "MemTotal:       16318412 kB" from /proc/meminfo
+fn total_memory() -> Option<u64> {
+    let meminfo = fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|line| line.starts_with("MemTotal:"))?;
+    let kb = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+    Some(kb * 1024)
+}
+
+/// Like systemd the maximum number of tasks is the smaller one of pid_max and threads-max
+fn max_tasks() -> Option<u64> {
+    let read = |path: &str| {
+        fs::read_to_string(path)
+            .ok()
+            .and_then(|content| content.trim().parse::<u64>().ok())
+    };
+    match (
+        read("/proc/sys/kernel/pid_max"),
+        read("/proc/sys/kernel/threads-max"),
+    ) {
+        (Some(pid_max), Some(threads_max)) => Some(pid_max.min(threads_max)),
+        (pid_max, threads_max) => pid_max.or(threads_max),
+    }
+}
+
+fn limit_value(limit: ResourceLimit, total: Option<u64>) -> String {
+    match limit {
+        ResourceLimit::Absolute(value) => value.to_string(),
+        ResourceLimit::Infinity => "max".to_owned(),
+        ResourceLimit::Percent(percent) => match total {
+            Some(total) => (total / 100 * percent).to_string(),
+            None => "max".to_owned(),
+        },
+    }
+}
+
+/// The controller, the file and the value for each setting that is set
+fn resource_settings(resources: &ResourceControl) -> Vec<(&'static str, &'static str, String)> {
+    let mut settings = Vec::new();
+    let memory_limits = [
+        ("memory.max", resources.memory_max),
+        ("memory.high", resources.memory_high),
+        ("memory.low", resources.memory_low),
+    ];
+    for (file, limit) in memory_limits.iter() {
+        if let Some(limit) = limit {
+            settings.push(("memory", *file, limit_value(*limit, total_memory())));
+        }
+    }
+    if let Some(weight) = resources.cpu_weight {
+        settings.push(("cpu", "cpu.weight", weight.to_string()));
+    }
+    if let Some(quota) = resources.cpu_quota {
+        // the quota is given per period of 100ms
+        settings.push(("cpu", "cpu.max", format!("{} 100000", quota * 1000)));
+    }
+    if let Some(limit) = resources.tasks_max {
+        settings.push(("pids", "pids.max", limit_value(limit, max_tasks())));
+    }
+    if let Some(weight) = resources.io_weight {
+        settings.push(("io", "io.weight", format!("default {}", weight)));
+    }
+    if let Some(cpus) = &resources.allowed_cpus {
+        settings.push(("cpuset", "cpuset.cpus", cpus.clone()));
+    }
+    settings
+}
+
+/// Enable the controllers for the cgroup in its parent. If the parent is the cgroup of a slice the controllers are made
+/// available there first, up to the cgroup of rustysd. Returns the controllers that could be enabled.
+fn enable_controllers_for(
+    cgroup_path: &std::path::Path,
+    controllers: &[String],
+) -> Result<Vec<String>, CgroupError> {
+    let parent = match cgroup_path.parent() {
+        Some(parent) => parent.to_path_buf(),
+        None => return Ok(Vec::new()),
+    };
+    let mut available = cgroup2::get_available_controllers(&parent)?;
+    let missing: Vec<String> = controllers
+        .iter()
+        .filter(|controller| !available.contains(controller))
+        .cloned()
+        .collect();
+    let parent_is_slice = parent
+        .file_name()
+        .map(|name| name.to_string_lossy().ends_with(".slice"))
+        .unwrap_or(false);
+    if !missing.is_empty() && parent_is_slice {
+        available.extend(enable_controllers_for(&parent, &missing)?);
+    }
+
+    let enabled: Vec<String> = controllers
+        .iter()
+        .filter(|controller| available.contains(controller))
+        .cloned()
+        .collect();
+    if !enabled.is_empty() {
+        cgroup2::enable_controllers(&parent, &enabled)?;
+    }
+    Ok(enabled)
+}
+
+/// Write the resource control settings into the cgroup. The controllers they need are enabled in the parent cgroups
+/// first. Controllers that are not available are skipped with a warning. Only cgroups v2 supports this, with v1 the
+/// settings are ignored.
+pub fn apply_resource_control(
+    cgroup_path: &std::path::PathBuf,
+    resources: &ResourceControl,
+) -> Result<(), CgroupError> {
+    let settings = resource_settings(resources);
+    if settings.is_empty() {
+        return Ok(());
+    }
+    if !use_v2(cgroup_path) {
+        warn!(
+            "Resource control needs cgroups v2, ignoring the settings for {:?}",
+            cgroup_path
+        );
+        return Ok(());
+    }
+
+    let mut controllers = Vec::new();
+    for (controller, _, _) in &settings {
+        if !controllers.contains(&controller.to_string()) {
+            controllers.push(controller.to_string());
+        }
+    }
+    let enabled = enable_controllers_for(cgroup_path, &controllers)?;
+    let missing: Vec<_> = controllers
+        .iter()
+        .filter(|controller| !enabled.contains(controller))
+        .collect();
+    if !missing.is_empty() {
+        warn!(
+            "The controllers {:?} are not available for {:?}, ignoring their settings",
+            missing, cgroup_path
+        );
+    }
+
+    for (controller, file_name, value) in settings {
+        if enabled.iter().any(|enabled| enabled == controller) {
+            trace!("Write {} to {:?}", value, cgroup_path.join(file_name));
+            cgroup2::write_setting(cgroup_path, file_name, &value)?;
+        }
+    }
+    Ok(())
+}
+
+/// The controllers that are enabled for the cgroups of units, so their resource usage can be read
+const ACCOUNTING_CONTROLLERS: [&str; 3] = ["memory", "pids", "io"];
+
+/// Enable the controllers that account the resource usage for the cgroup, as far as they are available. Only cgroups v2
+/// supports this. Accounting is not essential for the unit, so errors are only logged.
+pub fn enable_accounting(cgroup_path: &std::path::PathBuf) {
+    if !use_v2(cgroup_path) {
+        return;
+    }
+    let controllers: Vec<String> = ACCOUNTING_CONTROLLERS
+        .iter()
+        .map(|controller| controller.to_string())
+        .collect();
+    match enable_controllers_for(cgroup_path, &controllers) {
+        Ok(enabled) => trace!("Accounting for {:?} with {:?}", cgroup_path, enabled),
+        Err(e) => trace!("Could not enable accounting for {:?}: {}", cgroup_path, e),
+    }
+}
+
+/// The summed up io.stat of all devices
+#[derive(Clone, Copy, Default, Debug, Eq, PartialEq)]
+pub struct IoUsage {
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub read_operations: u64,
+    pub write_operations: u64,
+}
+
+/// The resource usage of a cgroup. Values whose controller is not enabled for the cgroup are missing.
+#[derive(Clone, Default, Debug, Eq, PartialEq)]
+pub struct ResourceUsage {
+    pub memory_current: Option<u64>,
+    pub memory_peak: Option<u64>,
+    pub cpu_usage_usec: Option<u64>,
+    pub tasks_current: Option<u64>,
+    pub io: Option<IoUsage>,
+}
+
+/// Get the value of a key from a file like cpu.stat with lines like "usage_usec 1234"
+pub fn parse_flat_keyed(content: &str, key: &str) -> Option<u64> {
+    content.lines().find_map(|line| {
+        let mut split = line.split_whitespace();
+        if split.next() == Some(key) {
+            split.next().and_then(|value| value.parse().ok())
+        } else {
+            None
+        }
+    })
+}
+
+/// Sum up the lines of io.stat like "8:0 rbytes=1 wbytes=2 rios=3 wios=4 dbytes=0 dios=0"
+pub fn parse_io_stat(content: &str) -> IoUsage {
+    let mut usage = IoUsage::default();
+    for line in content.lines() {
+        // the first field is the device
+        for field in line.split_whitespace().skip(1) {
+            let (key, value) = match field.find('=') {
+                Some(pos) => (&field[..pos], &field[pos + 1..]),
+                None => continue,
+            };
+            let value = value.parse::<u64>().unwrap_or(0);
+            match key {
+                "rbytes" => usage.read_bytes += value,
+                "wbytes" => usage.write_bytes += value,
+                "rios" => usage.read_operations += value,
+                "wios" => usage.write_operations += value,
+                _ => {}
+            }
+        }
+    }
+    usage
+}
+
+/// Read the resource usage of the cgroup. Returns None if the cgroup does not exist (the unit is not active) or if it
+/// is not a cgroup v2.
+pub fn read_resource_usage(cgroup_path: &std::path::PathBuf) -> Option<ResourceUsage> {
+    if !cgroup_path.exists() || !use_v2(cgroup_path) {
+        return None;
+    }
+    let read = |file_name: &str| fs::read_to_string(cgroup_path.join(file_name)).ok();
+    let read_number = |file_name: &str| read(file_name).and_then(|c| c.trim().parse::<u64>().ok());
+    Some(ResourceUsage {
+        memory_current: read_number("memory.current"),
+        memory_peak: read_number("memory.peak"),
+        cpu_usage_usec: read("cpu.stat").and_then(|c| parse_flat_keyed(&c, "usage_usec")),
+        tasks_current: read_number("pids.current"),
+        io: read("io.stat").map(|c| parse_io_stat(&c)),
+    })
+}
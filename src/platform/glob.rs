@@ -0,0 +1,28 @@
+//! Wrapper around the libc glob() function
+
+use std::ffi::{CStr, CString};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// Returns all existing paths that match the pattern
+pub fn glob(pattern: &Path) -> Result<Vec<PathBuf>, String> {
+    let c_pattern = CString::new(pattern.as_os_str().as_bytes())
+        .map_err(|e| format!("Invalid glob pattern {:?}: {}", pattern, e))?;
+
+    let mut glob_buf: libc::glob_t = unsafe { std::mem::zeroed() };
+    let res = unsafe { libc::glob(c_pattern.as_ptr(), 0, None, &mut glob_buf) };
+    let result = match res {
+        0 => {
+            let mut paths = Vec::new();
+            for idx in 0..glob_buf.gl_pathc {
+                let entry = unsafe { CStr::from_ptr(*glob_buf.gl_pathv.add(idx)) };
+                paths.push(PathBuf::from(std::ffi::OsStr::from_bytes(entry.to_bytes())));
+            }
+            Ok(paths)
+        }
+        libc::GLOB_NOMATCH => Ok(Vec::new()),
+        _ => Err(format!("glob() failed for pattern {:?}: {}", pattern, res)),
+    };
+    unsafe { libc::globfree(&mut glob_buf) };
+    result
+}
@@ -10,6 +10,9 @@
 //! eventfd should contain an implementation that creates an eventfd (or a similarly working) tuple of filedescriptors
 //! The pipe() implementation should work (in some variation) on many platforms
 //!
+//! poller should contain an implementation that waits on many filedescriptors at once without handing the whole set
+//! to the kernel for every wait (epoll on linux, poll() as the fallback)
+//!
 //! ## Redox support
 //! To implement all this stuff in redox we probably need these crates:
 //! 1. relibc (for the select, which is not yet in the syscalls crate?)
@@ -23,14 +26,19 @@
 
 mod drop_privileges;
 mod eventfd;
+mod poller;
 mod subreaper;
 mod unix_common;
 
 pub use drop_privileges::*;
 pub use eventfd::*;
+pub use poller::*;
 pub use subreaper::*;
+pub mod glob;
 pub mod grnam;
+pub mod procfs;
 pub mod pwnam;
+pub mod virtualization;
 
 //#[cfg(feature = "cgroups")]
 pub mod cgroups;
@@ -0,0 +1,165 @@
+//! Wait on many fds at once without rebuilding the set of fds for every wait. Every fd is reported once when it
+//! becomes readable and is not reported again until it is rearmed.
+
+pub use poller_impl::*;
+
+use std::time::Duration;
+
+/// Timeouts are rounded up to whole milliseconds, so a waiter never wakes up before its timeout passed. None waits
+/// forever.
+fn timeout_millis(timeout: Option<Duration>) -> i32 {
+    match timeout {
+        Some(timeout) => timeout.as_micros().div_ceil(1000).min(i32::MAX as u128) as i32,
+        None => -1,
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod poller_impl {
+    use nix::sys::epoll::{
+        epoll_create1, epoll_ctl, epoll_wait, EpollCreateFlags, EpollEvent, EpollFlags, EpollOp,
+    };
+    use std::os::unix::io::RawFd;
+    use std::time::Duration;
+
+    pub struct Poller {
+        epoll_fd: RawFd,
+    }
+
+    impl Poller {
+        pub fn new() -> Result<Poller, String> {
+            let epoll_fd = epoll_create1(EpollCreateFlags::EPOLL_CLOEXEC)
+                .map_err(|e| format!("Could not create epoll instance: {}", e))?;
+            Ok(Poller { epoll_fd })
+        }
+
+        fn armed_event(fd: RawFd) -> EpollEvent {
+            EpollEvent::new(EpollFlags::EPOLLIN | EpollFlags::EPOLLONESHOT, fd as u64)
+        }
+
+        pub fn add(&self, fd: RawFd) -> Result<(), String> {
+            match epoll_ctl(
+                self.epoll_fd,
+                EpollOp::EpollCtlAdd,
+                fd,
+                &mut Self::armed_event(fd),
+            ) {
+                // the fd number was reused without removing the old fd first
+                Err(nix::Error::EEXIST) => self.rearm(fd),
+                res => res.map_err(|e| format!("Could not add fd {} to epoll: {}", fd, e)),
+            }
+        }
+
+        pub fn rearm(&self, fd: RawFd) -> Result<(), String> {
+            epoll_ctl(
+                self.epoll_fd,
+                EpollOp::EpollCtlMod,
+                fd,
+                &mut Self::armed_event(fd),
+            )
+            .map_err(|e| format!("Could not rearm fd {} in epoll: {}", fd, e))
+        }
+
+        pub fn remove(&self, fd: RawFd) -> Result<(), String> {
+            match epoll_ctl(self.epoll_fd, EpollOp::EpollCtlDel, fd, None) {
+                // closed fds are removed by the kernel
+                Ok(()) | Err(nix::Error::ENOENT) | Err(nix::Error::EBADF) => Ok(()),
+                Err(e) => Err(format!("Could not remove fd {} from epoll: {}", fd, e)),
+            }
+        }
+
+        /// Blocks until at least one fd is readable or the timeout passed. Returns an empty list if the wait timed out
+        /// or got interrupted.
+        pub fn wait(&self, timeout: Option<Duration>) -> Result<Vec<RawFd>, String> {
+            let mut events = [EpollEvent::empty(); 64];
+            match epoll_wait(
+                self.epoll_fd,
+                &mut events,
+                super::timeout_millis(timeout) as isize,
+            ) {
+                Ok(count) => Ok(events[..count]
+                    .iter()
+                    .map(|event| event.data() as RawFd)
+                    .collect()),
+                Err(nix::Error::EINTR) => Ok(Vec::new()),
+                Err(e) => Err(format!("Error while waiting on epoll: {}", e)),
+            }
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod poller_impl {
+    use crate::platform::{make_event_fd, notify_event_fd, reset_event_fd, EventFd};
+    use nix::poll::{poll, PollFd, PollFlags};
+    use std::collections::HashMap;
+    use std::os::unix::io::{AsRawFd, RawFd};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    /// Without epoll the fds are polled with poll(). The eventfd interrupts the poll when the set of armed fds changes.
+    pub struct Poller {
+        armed: Mutex<HashMap<RawFd, bool>>,
+        eventfd: EventFd,
+    }
+
+    impl Poller {
+        pub fn new() -> Result<Poller, String> {
+            Ok(Poller {
+                armed: Mutex::new(HashMap::new()),
+                eventfd: make_event_fd()?,
+            })
+        }
+
+        pub fn add(&self, fd: RawFd) -> Result<(), String> {
+            self.rearm(fd)
+        }
+
+        pub fn rearm(&self, fd: RawFd) -> Result<(), String> {
+            self.armed.lock().unwrap().insert(fd, true);
+            notify_event_fd(self.eventfd);
+            Ok(())
+        }
+
+        pub fn remove(&self, fd: RawFd) -> Result<(), String> {
+            self.armed.lock().unwrap().remove(&fd);
+            notify_event_fd(self.eventfd);
+            Ok(())
+        }
+
+        /// Blocks until at least one fd is readable or the timeout passed. Returns an empty list if the wait timed out
+        /// or got interrupted.
+        pub fn wait(&self, timeout: Option<Duration>) -> Result<Vec<RawFd>, String> {
+            let mut pollfds: Vec<PollFd> = self
+                .armed
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(_, armed)| **armed)
+                .map(|(fd, _)| PollFd::new(*fd, PollFlags::POLLIN))
+                .collect();
+            pollfds.push(PollFd::new(self.eventfd.read_end(), PollFlags::POLLIN));
+
+            match poll(&mut pollfds, super::timeout_millis(timeout)) {
+                Ok(_) => {}
+                Err(nix::Error::EINTR) => return Ok(Vec::new()),
+                Err(e) => return Err(format!("Error while polling: {}", e)),
+            }
+
+            let mut ready = Vec::new();
+            let armed = &mut *self.armed.lock().unwrap();
+            for pollfd in &pollfds {
+                if !pollfd.any().unwrap_or(false) {
+                    continue;
+                }
+                if pollfd.as_raw_fd() == self.eventfd.read_end() {
+                    reset_event_fd(self.eventfd);
+                } else if let Some(fd_armed) = armed.get_mut(&pollfd.as_raw_fd()) {
+                    *fd_armed = false;
+                    ready.push(pollfd.as_raw_fd());
+                }
+            }
+            Ok(ready)
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Find processes by reading /proc. Only available on linux, other platforms do not find any processes and can not tell
+//! zombies from running processes.
+
+use nix::unistd::Pid;
+
+/// All processes that are currently in the process group
+#[cfg(target_os = "linux")]
+pub fn pids_in_process_group(pgid: Pid) -> Vec<Pid> {
+    let entries = match std::fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut pids = Vec::new();
+    for entry in entries.flatten() {
+        let pid = match entry
+            .file_name()
+            .to_str()
+            .and_then(|name| name.parse().ok())
+        {
+            Some(pid) => Pid::from_raw(pid),
+            None => continue,
+        };
+        // the process might have exited in the meantime
+        let stat = match std::fs::read_to_string(entry.path().join("stat")) {
+            Ok(stat) => stat,
+            Err(_) => continue,
+        };
+        // the name of the executable is in parens and may contain spaces. The fields after it are:
+        // state, ppid, pgrp, ...
+        let fields: Vec<&str> = match stat.rfind(')') {
+            Some(pos) => stat[pos + 1..].split_whitespace().collect(),
+            None => continue,
+        };
+        // zombies are just waiting to be reaped
+        if fields.first() == Some(&"Z") {
+            continue;
+        }
+        if fields.get(2).and_then(|pgrp| pgrp.parse().ok()) == Some(pgid.as_raw()) {
+            pids.push(pid);
+        }
+    }
+    pids
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pids_in_process_group(_pgid: Pid) -> Vec<Pid> {
+    Vec::new()
+}
+
+/// Whether the process exists and is not a zombie that is waiting to be reaped
+#[cfg(target_os = "linux")]
+pub fn is_running(pid: Pid) -> bool {
+    let stat = match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        Ok(stat) => stat,
+        Err(_) => return false,
+    };
+    // the state is the first field after the name of the executable
+    match stat.rfind(')') {
+        Some(pos) => stat[pos + 1..].split_whitespace().next() != Some("Z"),
+        None => false,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_running(pid: Pid) -> bool {
+    nix::sys::signal::kill(pid, None).is_ok()
+}
+
+/// Whether the process is a child of the ancestor or a child of one of its children and so on
+#[cfg(target_os = "linux")]
+pub fn is_descendant_of(pid: Pid, ancestor: Pid) -> bool {
+    let mut current = pid;
+    // the chain ends at init or at the kernel threads, which have the parent 0
+    while current.as_raw() > 1 {
+        current = match parent_pid(current) {
+            Some(parent) => parent,
+            None => return false,
+        };
+        if current == ancestor {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(target_os = "linux")]
+fn parent_pid(pid: Pid) -> Option<Pid> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // the ppid is the second field after the name of the executable
+    let pos = stat.rfind(')')?;
+    stat[pos + 1..]
+        .split_whitespace()
+        .nth(1)
+        .and_then(|ppid| ppid.parse().ok())
+        .map(Pid::from_raw)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn is_descendant_of(_pid: Pid, _ancestor: Pid) -> bool {
+    false
+}
@@ -0,0 +1,80 @@
+//! Detect whether rustysd runs in a virtual machine or a container, for ConditionVirtualization=. The names are the
+//! ones systemd-detect-virt uses.
+
+pub struct Virtualization {
+    pub is_vm: bool,
+    pub name: String,
+}
+
+/// Returns None if rustysd runs on bare metal. Containers are checked first, a container might run inside of a VM.
+pub fn detect_virtualization() -> Option<Virtualization> {
+    if let Some(name) = detect_container() {
+        return Some(Virtualization { is_vm: false, name });
+    }
+    detect_vm().map(|name| Virtualization { is_vm: true, name })
+}
+
+fn detect_container() -> Option<String> {
+    // set by container managers that follow the systemd container interface
+    if let Ok(name) = std::fs::read_to_string("/run/systemd/container") {
+        let name = name.trim();
+        if !name.is_empty() {
+            return Some(name.to_owned());
+        }
+    }
+    if let Ok(environ) = std::fs::read("/proc/1/environ") {
+        let container = environ
+            .split(|byte| *byte == b'\0')
+            .find_map(|var| var.strip_prefix(b"container="));
+        if let Some(name) = container {
+            if !name.is_empty() {
+                return Some(String::from_utf8_lossy(name).into_owned());
+            }
+        }
+    }
+    if std::path::Path::new("/.dockerenv").exists() {
+        return Some("docker".to_owned());
+    }
+    if std::path::Path::new("/run/.containerenv").exists() {
+        return Some("podman".to_owned());
+    }
+    None
+}
+
+fn detect_vm() -> Option<String> {
+    let dmi = |file: &str| {
+        std::fs::read_to_string(format!("/sys/class/dmi/id/{}", file))
+            .map(|content| content.trim().to_owned())
+            .unwrap_or_default()
+    };
+    for vendor in &[dmi("sys_vendor"), dmi("product_name"), dmi("bios_vendor")] {
+        let name = match vendor.as_str() {
+            "QEMU" => "qemu",
+            "KVM" => "kvm",
+            "VMware, Inc." | "VMware" => "vmware",
+            "innotek GmbH" | "Oracle Corporation" | "VirtualBox" => "oracle",
+            "Xen" => "xen",
+            "Microsoft Corporation" | "Virtual Machine" => "microsoft",
+            "Amazon EC2" => "amazon",
+            "Google" | "Google Compute Engine" => "google",
+            "Parallels Software International Inc." => "parallels",
+            "Bochs" => "bochs",
+            _ => continue,
+        };
+        return Some(name.to_owned());
+    }
+    if std::path::Path::new("/proc/xen").exists() {
+        return Some("xen".to_owned());
+    }
+    // the cpu tells us that there is a hypervisor, but not which one
+    let cpuinfo = std::fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+    let has_hypervisor = cpuinfo
+        .lines()
+        .filter(|line| line.starts_with("flags"))
+        .any(|line| line.split_whitespace().any(|flag| flag == "hypervisor"));
+    if has_hypervisor {
+        Some("vm-other".to_owned())
+    } else {
+        None
+    }
+}
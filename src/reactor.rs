@@ -0,0 +1,130 @@
+//! One reactor waits on the stdout/stderr pipes, notification sockets and cgroup.events watches of all services and on
+//! the listening fds of all sockets. The fds are registered when they are opened and deregistered when they are closed, so the handlers
+//! never have to scan the unit table to find out what to wait on.
+//!
+//! Readable fds are sent to the thread that handles their kind. An fd is not reported again until that thread rearms
+//! it, so a handler that is busy with one fd does not get flooded with events for it.
+use log::{error, trace};
+
+use crate::units::UnitId;
+
+use std::collections::HashMap;
+use std::os::unix::io::RawFd;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug)]
+pub enum StreamKind {
+    Stdout,
+    Stderr,
+    Notifications,
+    /// The inotify fd watching the cgroup.events of a service
+    CgroupEvents,
+    Socket,
+}
+
+#[derive(Debug)]
+pub enum ReactorEvent {
+    /// This fd of the unit is readable
+    Readable(RawFd, UnitId),
+    /// Sent by wake() so the handler can look at its units without a readable fd
+    Wakeup,
+}
+
+pub struct Reactor {
+    poller: crate::platform::Poller,
+    sources: Mutex<HashMap<RawFd, (StreamKind, UnitId)>>,
+    handlers: Mutex<HashMap<StreamKind, Sender<ReactorEvent>>>,
+}
+
+impl Reactor {
+    pub fn new() -> Result<Reactor, String> {
+        Ok(Reactor {
+            poller: crate::platform::Poller::new()?,
+            sources: Mutex::new(HashMap::new()),
+            handlers: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The events for all fds of this kind are sent to the returned receiver. Events that happen before anyone
+    /// subscribed are dropped.
+    pub fn subscribe(&self, kind: StreamKind) -> Receiver<ReactorEvent> {
+        let (sender, receiver) = channel();
+        self.handlers.lock().unwrap().insert(kind, sender);
+        receiver
+    }
+
+    pub fn register(&self, fd: RawFd, kind: StreamKind, id: UnitId) {
+        trace!("Register fd {} ({:?}) of unit {}", fd, kind, id.name);
+        self.sources.lock().unwrap().insert(fd, (kind, id));
+        if let Err(e) = self.poller.add(fd) {
+            error!("{}", e);
+        }
+    }
+
+    pub fn deregister(&self, fd: RawFd) {
+        if self.sources.lock().unwrap().remove(&fd).is_some() {
+            if let Err(e) = self.poller.remove(fd) {
+                error!("{}", e);
+            }
+        }
+    }
+
+    /// Deregister all fds of a unit that is being removed
+    pub fn deregister_unit(&self, id: &UnitId) {
+        let fds: Vec<RawFd> = self
+            .sources
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, (_, unit_id))| unit_id == id)
+            .map(|(fd, _)| *fd)
+            .collect();
+        for fd in fds {
+            self.deregister(fd);
+        }
+    }
+
+    /// Report the fd again when it is readable. Fds that have been deregistered in the meantime are ignored.
+    pub fn rearm(&self, fd: RawFd) {
+        if self.sources.lock().unwrap().contains_key(&fd) {
+            if let Err(e) = self.poller.rearm(fd) {
+                error!("{}", e);
+            }
+        }
+    }
+
+    /// Let the handler of this kind run without a readable fd
+    pub fn wake(&self, kind: StreamKind) {
+        if let Some(handler) = self.handlers.lock().unwrap().get(&kind) {
+            let _ = handler.send(ReactorEvent::Wakeup);
+        }
+    }
+
+    fn dispatch(&self) -> Result<(), String> {
+        for fd in self.poller.wait(None)? {
+            let source = self.sources.lock().unwrap().get(&fd).cloned();
+            let (kind, id) = match source {
+                Some(source) => source,
+                // deregistered while we were waiting
+                None => continue,
+            };
+            match self.handlers.lock().unwrap().get(&kind) {
+                Some(handler) => {
+                    let _ = handler.send(ReactorEvent::Readable(fd, id));
+                }
+                None => trace!("Nobody handles {:?} events. Drop event for fd {}", kind, fd),
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn start_reactor_thread(reactor: Arc<Reactor>) {
+    std::thread::spawn(move || loop {
+        if let Err(e) = reactor.dispatch() {
+            error!("Error in the reactor: {}", e);
+            break;
+        }
+    });
+}
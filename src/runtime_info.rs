@@ -6,6 +6,7 @@
 
 use crate::fd_store::FDStore;
 use crate::platform::EventFd;
+use crate::reactor::Reactor;
 use crate::units::*;
 
 use nix::unistd::Pid;
@@ -21,18 +22,22 @@ pub struct RuntimeInfo {
     pub pid_table: Mutex<PidTable>,
     pub fd_store: MutFDStore,
     pub config: crate::config::Config,
-    pub stdout_eventfd: EventFd,
-    pub stderr_eventfd: EventFd,
-    pub notification_eventfd: EventFd,
-    pub socket_activation_eventfd: EventFd,
+    /// Waits on the stdout/stderr/notification/cgroup.events fds of the services and the fds of the sockets
+    pub reactor: Arc<Reactor>,
+    /// The jobs of all transactions that have not finished yet
+    pub jobs: Arc<JobQueue>,
+    /// Units that failed or finished and whose OnFailure=/OnSuccess= units still have to be started
+    pub hooks: Arc<crate::hook_activation::HookQueue>,
+    /// Only set if logging to disk is enabled
+    pub log_store: Option<Arc<crate::log_store::LogStore>>,
+    pub timer_activation_eventfd: EventFd,
+    pub path_activation_eventfd: EventFd,
 }
 
 impl RuntimeInfo {
     pub fn notify_eventfds(&self) {
-        crate::platform::notify_event_fd(self.stdout_eventfd);
-        crate::platform::notify_event_fd(self.stderr_eventfd);
-        crate::platform::notify_event_fd(self.notification_eventfd);
-        crate::platform::notify_event_fd(self.socket_activation_eventfd);
+        crate::platform::notify_event_fd(self.timer_activation_eventfd);
+        crate::platform::notify_event_fd(self.path_activation_eventfd);
     }
 }
 
@@ -11,6 +11,7 @@ pub fn after_fork_child(
     new_stdout: RawFd,
     new_stderr: RawFd,
     exec_helper_config: RawFd,
+    exec_status_fd: Option<RawFd>,
 ) {
     // DO NOT USE THE LOGGER HERE. It aquires a global lock which might be held at the time of forking
     // But since this is the only thread that is in the child process the lock will never be released!
@@ -21,10 +22,10 @@ pub fn after_fork_child(
     // Hopefully the close() means that no old logs will get written to that filedescriptor
 
     // Setup the new stdio so println! and eprintln! go to the expected fds
-    dup_stdio(new_stdout, new_stderr, exec_helper_config);
+    dup_stdio(new_stdout, new_stderr, exec_helper_config, socket_fds);
 
-    // Now we may at least write to stderr
-    write_to_stderr("Prepare fork child before execing!");
+    // From here on stderr is the one of the service. It might be a connection to a client, so only errors are written
+    // to it.
 
     // Lets move into a new process group before execing
     move_into_new_process_group();
@@ -32,13 +33,14 @@ pub fn after_fork_child(
     // Dup all the fds for the service here, because we use SO_CLOEXEC on all fds so doing it after exec isn't possible
     dup_fds(socket_fds);
 
-    // Just so we have a clearer picture on what is happening while debugging
-    write_to_stderr("Exec the exec helper");
-
     // Finally exec the exec_helper
     match unsafe { libc::execv(selfpath.as_ptr(), self_args.as_ptr().cast()) } {
         -1 => {
             write_to_stderr("execv errored");
+            if let Some(fd) = exec_status_fd {
+                let msg = "Could not exec the exec helper";
+                unsafe { libc::write(fd, (msg.as_bytes() as *const [u8]).cast(), msg.len() as _) };
+            }
             std::process::exit(1);
         }
         _ => {
@@ -59,7 +61,12 @@ fn write_to_stderr(msg: &str) {
     }
 }
 
-fn dup_stdio(new_stdout: RawFd, new_stderr: RawFd, exec_helper_config: RawFd) {
+fn dup_stdio(
+    new_stdout: RawFd,
+    new_stderr: RawFd,
+    exec_helper_config: RawFd,
+    socket_fds: &[RawFd],
+) {
     fn dup_one_stdio(
         old_stdio: RawFd,
         new_stdio: RawFd,
@@ -75,13 +82,22 @@ fn dup_stdio(new_stdout: RawFd, new_stderr: RawFd, exec_helper_config: RawFd) {
             }
             std::process::exit(1);
         }
-        unsafe { libc::close(old_stdio) };
     }
 
     // First dup stderr so we can potentially log other dup errors
     dup_one_stdio(new_stderr, libc::STDERR_FILENO, "stderr", false);
     dup_one_stdio(new_stdout, libc::STDOUT_FILENO, "stdout", true);
     dup_one_stdio(exec_helper_config, libc::STDIN_FILENO, "stdin", true);
+
+    // Close the old fds only now, stdout and stderr can be the same fd. Sockets that are also used as stdout/stderr
+    // are still needed for dup_fds.
+    let old_fds = [new_stderr, new_stdout, exec_helper_config];
+    for (idx, old_fd) in old_fds.iter().enumerate() {
+        let is_stdio = *old_fd <= libc::STDERR_FILENO;
+        if !is_stdio && !old_fds[..idx].contains(old_fd) && !socket_fds.contains(old_fd) {
+            unsafe { libc::close(*old_fd) };
+        }
+    }
 }
 
 fn move_into_new_process_group() {
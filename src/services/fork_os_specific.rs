@@ -15,6 +15,15 @@ pub fn pre_fork_os_specific(srvc: &ServiceConfig) -> Result<(), String> {
                 srvc.platform_specific.cgroup_path, e
             )
         })?;
+        cgroups::enable_accounting(&srvc.platform_specific.cgroup_path);
+        // the limits must be in place before the service is moved into the cgroup
+        cgroups::apply_resource_control(&srvc.platform_specific.cgroup_path, &srvc.resources)
+            .map_err(|e| {
+                format!(
+                    "Couldnt apply the resource control of the service ({:?}): {}",
+                    srvc.platform_specific.cgroup_path, e
+                )
+            })?;
     }
     let _ = srvc;
     Ok(())
@@ -11,6 +11,7 @@ use crate::units::*;
 pub fn wait_for_service(
     srvc: &mut Service,
     conf: &ServiceConfig,
+    id: &UnitId,
     name: &str,
     run_info: &RuntimeInfo,
 ) -> Result<(), RunCmdError> {
@@ -31,7 +32,6 @@ pub fn wait_for_service(
             );
 
             //let duration_timeout = Some(std::time::Duration::from_nanos(1_000_000_000_000));
-            let mut buf = [0u8; 512];
             loop {
                 let stream = if let Some(stream) = &srvc.notifications {
                     stream
@@ -58,31 +58,31 @@ pub fn wait_for_service(
                 }
 
                 if let Some(duration_timeout) = duration_timeout {
-                    let duration_elapsed = start_time.elapsed();
-                    if duration_elapsed > duration_timeout {
-                        trace!("[FORK_PARENT] Service {} notification timed out", name);
-                        return Err(RunCmdError::Timeout(
-                            conf.exec.to_string(),
-                            format!("{:?}", duration_timeout),
-                        ));
-                    } else {
-                        let duration_till_timeout = duration_timeout - duration_elapsed;
-                        stream
-                            .set_read_timeout(Some(duration_till_timeout))
-                            .unwrap();
+                    match srvc.remaining_timeout(start_time, duration_timeout) {
+                        Some(duration_till_timeout) => {
+                            stream
+                                .set_read_timeout(Some(duration_till_timeout))
+                                .unwrap();
+                        }
+                        None => {
+                            trace!("[FORK_PARENT] Service {} notification timed out", name);
+                            return Err(RunCmdError::Timeout(
+                                conf.exec.to_string(),
+                                format!("{:?}", duration_timeout),
+                            ));
+                        }
                     }
                 }
-                let bytes = match stream.recv(&mut buf[..]) {
-                    Ok(bytes) => bytes,
+                match crate::notification_handler::receive_notifications(
+                    srvc, conf, id, run_info, true,
+                ) {
+                    Ok(_) => {}
                     Err(e) => match e.kind() {
-                        std::io::ErrorKind::WouldBlock => 0,
-                        std::io::ErrorKind::Interrupted => 0,
+                        std::io::ErrorKind::WouldBlock => {}
+                        std::io::ErrorKind::Interrupted => {}
                         _ => panic!("{}", e),
                     },
                 };
-                srvc.notifications_buffer
-                    .push_str(&String::from_utf8(buf[..bytes].to_vec()).unwrap());
-                crate::notification_handler::handle_notifications_from_buffer(srvc, &name);
                 if srvc.signaled_ready {
                     srvc.signaled_ready = false;
                     trace!("[FORK_PARENT] Service {} sent READY=1 notification", name);
@@ -95,86 +95,31 @@ pub fn wait_for_service(
                 stream.set_read_timeout(None).unwrap();
             }
         }
-        ServiceType::Simple => {
+        ServiceType::Simple | ServiceType::Idle => {
             trace!("[FORK_PARENT] service {} doesnt notify", name);
         }
+        ServiceType::Exec => {
+            trace!("[FORK_PARENT] Waiting for the exec of service {}", name);
+            if let Some(exec_status) = srvc.exec_status.take() {
+                wait_for_exec(exec_status, conf, name, start_time, duration_timeout)?;
+            }
+            trace!("[FORK_PARENT] Service {} execed successfully", name);
+        }
         ServiceType::OneShot => {
             trace!(
                 "[FORK_PARENT] Waiting for oneshot service to exit: {}",
                 name
             );
-            let mut counter = 1u64;
-            let pid = srvc.pid.unwrap();
-            loop {
-                if let Some(time_out) = duration_timeout {
-                    if start_time.elapsed() >= time_out {
-                        error!("oneshot service {} reached timeout", name);
-                        return Err(RunCmdError::Timeout(
-                            conf.exec.to_string(),
-                            format!("{:?}", duration_timeout),
-                        ));
-                    }
-                }
-                {
-                    let mut pid_table_locked = pid_table.lock().unwrap();
-                    match pid_table_locked.get(&pid) {
-                        Some(entry) => {
-                            match entry {
-                                PidEntry::Service(_, _) => {
-                                    // Still running. Wait more
-                                }
-                                PidEntry::ServiceExited(_) => {
-                                    trace!("End wait for {}", name);
-                                    let entry_owned = pid_table_locked.remove(&pid).unwrap();
-                                    if let PidEntry::ServiceExited(code) = entry_owned {
-                                        if !code.success() {
-                                            if !conf
-                                                .exec
-                                                .prefixes
-                                                .contains(&CommandlinePrefix::Minus)
-                                            {
-                                                return Err(RunCmdError::BadExitCode(
-                                                    conf.exec.to_string(),
-                                                    code,
-                                                ));
-                                            }
-                                        }
-                                    }
-                                    break;
-                                }
-                                PidEntry::Helper(_, _) => {
-                                    // Should never happen
-                                    unreachable!(
-                                            "Was waiting on oneshot process but pid got saved as PidEntry::Helper"
-                                        );
-                                }
-                                PidEntry::HelperExited(_) => {
-                                    // Should never happen
-                                    unreachable!(
-                                            "Was waiting on oneshot process but pid got saved as PidEntry::HelperExited"
-                                        );
-                                }
-                            }
-                        }
-                        None => {
-                            // Should not happen. Either there is an Helper entry oder a Exited entry
-                            unreachable!("No entry for child found")
-                        }
-                    }
-                }
-                // exponential backoff to get low latencies for fast processes
-                // but not hog the cpu for too long
-                // start at 0.05 ms
-                // capped to 10 ms to not introduce too big latencies
-                // TODO review those numbers
-                let sleep_dur = std::time::Duration::from_micros(counter * 50);
-                let sleep_cap = std::time::Duration::from_millis(10);
-                let sleep_dur = sleep_dur.min(sleep_cap);
-                if sleep_dur < sleep_cap {
-                    counter = counter * 2;
-                }
-                std::thread::sleep(sleep_dur);
-            }
+            wait_for_exit(srvc, conf, id, run_info, start_time, duration_timeout)?;
+        }
+        ServiceType::Forking => {
+            trace!(
+                "[FORK_PARENT] Waiting for the initial process of forking service {} to exit",
+                name
+            );
+            wait_for_exit(srvc, conf, id, run_info, start_time, duration_timeout)?;
+            let main_pid = find_main_pid(srvc, conf, id, run_info, start_time, duration_timeout)?;
+            track_main_pid(srvc, id, name, main_pid, run_info)?;
         }
         ServiceType::Dbus => {
             if let Some(dbus_name) = &conf.dbus_name {
@@ -209,3 +154,362 @@ pub fn wait_for_service(
     }
     Ok(())
 }
+
+/// Whether the start ran out of time. The service is locked while it starts, so its notifications are read here. It
+/// might have asked for more time with EXTEND_TIMEOUT_USEC=.
+fn start_timed_out(
+    srvc: &mut Service,
+    conf: &ServiceConfig,
+    id: &UnitId,
+    run_info: &RuntimeInfo,
+    start_time: std::time::Instant,
+    duration_timeout: Option<std::time::Duration>,
+) -> bool {
+    crate::notification_handler::read_pending_notifications(srvc, conf, id, run_info);
+    match duration_timeout {
+        Some(time_out) => srvc.remaining_timeout(start_time, time_out).is_none(),
+        None => false,
+    }
+}
+
+/// Wait for the process to exit. It is an error if it exits with a bad exit code, unless the '-' prefix was used.
+fn wait_for_exit(
+    srvc: &mut Service,
+    conf: &ServiceConfig,
+    id: &UnitId,
+    run_info: &RuntimeInfo,
+    start_time: std::time::Instant,
+    duration_timeout: Option<std::time::Duration>,
+) -> Result<(), RunCmdError> {
+    let name = &id.name;
+    let pid = srvc.pid.unwrap();
+    let pid_table = &run_info.pid_table;
+    let mut counter = 1u64;
+    loop {
+        if start_timed_out(srvc, conf, id, run_info, start_time, duration_timeout) {
+            error!("service {} reached timeout", name);
+            return Err(RunCmdError::Timeout(
+                conf.exec.to_string(),
+                format!("{:?}", duration_timeout),
+            ));
+        }
+        {
+            let mut pid_table_locked = pid_table.lock().unwrap();
+            match pid_table_locked.get(&pid) {
+                Some(entry) => {
+                    match entry {
+                        PidEntry::Service(_, _) => {
+                            // Still running. Wait more
+                        }
+                        PidEntry::ServiceExited(_) => {
+                            trace!("End wait for {}", name);
+                            let entry_owned = pid_table_locked.remove(&pid).unwrap();
+                            if let PidEntry::ServiceExited(code) = entry_owned {
+                                if !code.success() {
+                                    if !conf.exec.prefixes.contains(&CommandlinePrefix::Minus) {
+                                        return Err(RunCmdError::BadExitCode(
+                                            conf.exec.to_string(),
+                                            code,
+                                        ));
+                                    }
+                                }
+                            }
+                            return Ok(());
+                        }
+                        PidEntry::Helper(_, _) => {
+                            // Should never happen
+                            unreachable!(
+                                "Was waiting on service process but pid got saved as PidEntry::Helper"
+                            );
+                        }
+                        PidEntry::HelperExited(_) => {
+                            // Should never happen
+                            unreachable!(
+                                "Was waiting on service process but pid got saved as PidEntry::HelperExited"
+                            );
+                        }
+                    }
+                }
+                None => {
+                    // Should not happen. Either there is an Helper entry oder a Exited entry
+                    unreachable!("No entry for child found")
+                }
+            }
+        }
+        // exponential backoff to get low latencies for fast processes
+        // but not hog the cpu for too long
+        // start at 0.05 ms
+        // capped to 10 ms to not introduce too big latencies
+        // TODO review those numbers
+        let sleep_dur = std::time::Duration::from_micros(counter * 50);
+        let sleep_cap = std::time::Duration::from_millis(10);
+        let sleep_dur = sleep_dur.min(sleep_cap);
+        if sleep_dur < sleep_cap {
+            counter = counter * 2;
+        }
+        std::thread::sleep(sleep_dur);
+    }
+}
+
+/// Find the main process of a forking service after the initial process exited. This is read from the PIDFile= if it is
+/// set. Otherwise it is guessed from the processes left in the cgroup or process group of the service, which only works
+/// if exactly one process is left.
+fn find_main_pid(
+    srvc: &mut Service,
+    conf: &ServiceConfig,
+    id: &UnitId,
+    run_info: &RuntimeInfo,
+    start_time: std::time::Instant,
+    duration_timeout: Option<std::time::Duration>,
+) -> Result<Option<nix::unistd::Pid>, RunCmdError> {
+    let name = &id.name;
+    if let Some(pid_file) = &conf.pid_file {
+        let mut rejected = None;
+        // the daemon might write the file a little after the initial process exited
+        loop {
+            if let Some(pid) = read_pid_file(pid_file) {
+                if belongs_to_service(srvc, conf, pid) {
+                    trace!(
+                        "[FORK_PARENT] Read main pid {} of service {} from {:?}",
+                        pid,
+                        name,
+                        pid_file
+                    );
+                    return Ok(Some(pid));
+                }
+                rejected = Some(pid);
+            }
+            if start_timed_out(srvc, conf, id, run_info, start_time, duration_timeout) {
+                let reason = match rejected {
+                    Some(pid) => format!(
+                        "PIDFile {:?} contains the pid {} which does not belong to the service",
+                        pid_file, pid
+                    ),
+                    None => format!("PIDFile {:?} was not written in time", pid_file),
+                };
+                return Err(RunCmdError::Timeout(conf.exec.to_string(), reason));
+            }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+    }
+
+    #[cfg(feature = "cgroups")]
+    let candidates = crate::platform::cgroups::get_all_procs(&conf.platform_specific.cgroup_path)
+        .unwrap_or_default();
+    #[cfg(not(feature = "cgroups"))]
+    let candidates = Vec::new();
+    let candidates = if candidates.is_empty() {
+        // the process group is saved negated so it can be passed to kill() directly
+        srvc.process_group
+            .map(|pgid| {
+                crate::platform::procfs::pids_in_process_group(nix::unistd::Pid::from_raw(
+                    -pgid.as_raw(),
+                ))
+            })
+            .unwrap_or_default()
+    } else {
+        candidates
+    };
+
+    if candidates.len() == 1 {
+        trace!(
+            "[FORK_PARENT] Guessed main pid {} of service {}",
+            candidates[0],
+            name
+        );
+        Ok(Some(candidates[0]))
+    } else {
+        warn!(
+            "[FORK_PARENT] Could not determine the main process of service {} ({} candidates). Set PIDFile= to make rustysd track it.",
+            name,
+            candidates.len()
+        );
+        Ok(None)
+    }
+}
+
+fn read_pid_file(pid_file: &std::path::Path) -> Option<nix::unistd::Pid> {
+    let content = std::fs::read_to_string(pid_file).ok()?;
+    match content.trim().parse::<i32>() {
+        Ok(pid) if pid > 0 => Some(nix::unistd::Pid::from_raw(pid)),
+        _ => None,
+    }
+}
+
+/// Only a process of the service may become its main process. A PIDFile= with a stale or foreign pid must not make
+/// rustysd supervise (and later kill) an unrelated process.
+fn belongs_to_service(srvc: &Service, conf: &ServiceConfig, pid: nix::unistd::Pid) -> bool {
+    if nix::sys::signal::kill(pid, None).is_err() {
+        return false;
+    }
+    #[cfg(feature = "cgroups")]
+    {
+        if let Ok(procs) =
+            crate::platform::cgroups::get_all_procs(&conf.platform_specific.cgroup_path)
+        {
+            return procs.contains(&pid);
+        }
+    }
+    let _ = conf;
+    // the process group is saved negated so it can be passed to kill() directly
+    if let Some(pgid) = srvc.process_group {
+        if nix::unistd::getpgid(Some(pid)) == Ok(nix::unistd::Pid::from_raw(-pgid.as_raw())) {
+            return true;
+        }
+    }
+    // daemons that left the process group with setsid are adopted by rustysd because it is their subreaper
+    crate::platform::procfs::is_descendant_of(pid, nix::unistd::getpid())
+}
+
+/// Make the exit handler follow the main process of a forking service instead of the initial process that already exited
+fn track_main_pid(
+    srvc: &mut Service,
+    id: &UnitId,
+    name: &str,
+    main_pid: Option<nix::unistd::Pid>,
+    run_info: &RuntimeInfo,
+) -> Result<(), RunCmdError> {
+    srvc.pid = main_pid;
+    let main_pid = match main_pid {
+        Some(main_pid) => main_pid,
+        None => return Ok(()),
+    };
+    let mut pid_table_locked = run_info.pid_table.lock().unwrap();
+    // From here on the main process is handled like the one of a simple service
+    pid_table_locked.insert(main_pid, PidEntry::Service(id.clone(), ServiceType::Simple));
+    // If it already exited and got reaped before the entry existed, the exit handler ignored it
+    if nix::sys::signal::kill(main_pid, None).is_err() {
+        pid_table_locked.remove(&main_pid);
+        srvc.pid = None;
+        return Err(RunCmdError::Generic(format!(
+            "The main process {} of service {} exited during startup",
+            main_pid, name
+        )));
+    }
+    Ok(())
+}
+
+/// Read the exec status pipe until it is closed. If the exec succeeded nothing was written to it, otherwise it contains
+/// the reason why the exec failed.
+fn wait_for_exec(
+    mut exec_status: std::fs::File,
+    conf: &ServiceConfig,
+    name: &str,
+    start_time: std::time::Instant,
+    duration_timeout: Option<std::time::Duration>,
+) -> Result<(), RunCmdError> {
+    use std::io::Read;
+    use std::os::unix::io::AsRawFd;
+
+    let mut msg = Vec::new();
+    let mut buf = [0u8; 512];
+    loop {
+        let poll_timeout = match duration_timeout {
+            Some(time_out) => {
+                let elapsed = start_time.elapsed();
+                if elapsed >= time_out {
+                    error!("service {} reached timeout while execing", name);
+                    return Err(RunCmdError::Timeout(
+                        conf.exec.to_string(),
+                        format!("{:?}", duration_timeout),
+                    ));
+                }
+                (time_out - elapsed).as_millis().min(i32::MAX as u128) as i32
+            }
+            None => -1,
+        };
+        let mut poll_fds = [nix::poll::PollFd::new(
+            exec_status.as_raw_fd(),
+            nix::poll::PollFlags::POLLIN,
+        )];
+        match nix::poll::poll(&mut poll_fds, poll_timeout) {
+            Ok(0) | Err(nix::Error::EINTR) => continue,
+            Ok(_) => {}
+            Err(e) => {
+                return Err(RunCmdError::WaitError(
+                    conf.exec.to_string(),
+                    format!("Could not poll the exec status pipe: {}", e),
+                ))
+            }
+        }
+        match exec_status.read(&mut buf) {
+            Ok(0) => break,
+            Ok(bytes) => msg.extend_from_slice(&buf[..bytes]),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                return Err(RunCmdError::WaitError(
+                    conf.exec.to_string(),
+                    format!("Could not read the exec status pipe: {}", e),
+                ))
+            }
+        }
+    }
+    if msg.is_empty() {
+        Ok(())
+    } else {
+        Err(RunCmdError::SpawnError(
+            conf.exec.to_string(),
+            String::from_utf8_lossy(&msg).into_owned(),
+        ))
+    }
+}
+
+/// Wait for the READY=1 that ends a reload of a service of type notify. Services that did not send RELOADING=1 are
+/// done reloading when the ExecReload= commands exited.
+pub fn wait_for_reload(
+    srvc: &mut Service,
+    conf: &ServiceConfig,
+    id: &UnitId,
+    run_info: &RuntimeInfo,
+) -> Result<(), RunCmdError> {
+    let name = &id.name;
+    let stream = if let Some(stream) = &srvc.notifications {
+        stream.try_clone().map_err(|e| {
+            RunCmdError::Generic(format!("Could not use the notification socket: {}", e))
+        })?
+    } else {
+        return Ok(());
+    };
+    let start_time = std::time::Instant::now();
+    let duration_timeout = srvc.get_start_timeout(conf);
+
+    // collect the notifications that were sent while the ExecReload= commands ran
+    crate::notification_handler::read_pending_notifications(srvc, conf, id, run_info);
+
+    while srvc.signaled_reloading {
+        trace!("[FORK_PARENT] Service {} still reloading", name);
+        if let Some(duration_timeout) = duration_timeout {
+            match srvc.remaining_timeout(start_time, duration_timeout) {
+                Some(duration_till_timeout) => {
+                    stream
+                        .set_read_timeout(Some(duration_till_timeout))
+                        .unwrap();
+                }
+                None => {
+                    trace!("[FORK_PARENT] Service {} reload timed out", name);
+                    return Err(RunCmdError::Timeout(
+                        "Waiting for READY=1 after RELOADING=1".into(),
+                        format!("{:?}", duration_timeout),
+                    ));
+                }
+            }
+        }
+        match crate::notification_handler::receive_notifications(srvc, conf, id, run_info, true) {
+            Ok(_) => {}
+            Err(e) => match e.kind() {
+                std::io::ErrorKind::WouldBlock => {}
+                std::io::ErrorKind::Interrupted => {}
+                _ => {
+                    return Err(RunCmdError::Generic(format!(
+                        "Could not read from the notification socket: {}",
+                        e
+                    )))
+                }
+            },
+        };
+    }
+    stream.set_read_timeout(None).unwrap();
+    srvc.signaled_ready = false;
+    trace!("[FORK_PARENT] Service {} finished reloading", name);
+    Ok(())
+}
@@ -3,14 +3,79 @@ use crate::units::ServiceConfig;
 #[cfg(feature = "cgroups")]
 use crate::platform::cgroups;
 
+/// How long the killed processes get to leave the cgroup before it is removed
+#[cfg(feature = "cgroups")]
+const CGROUP_EMPTY_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
 pub fn kill(srvc: &ServiceConfig, sig: nix::sys::signal::Signal) -> Result<(), String> {
     #[cfg(feature = "cgroups")]
     {
-        cgroups::freeze_kill_thaw_cgroup(&srvc.platform_specific.cgroup_path, sig)
-            .map_err(|e| format!("{}", e))?;
-        std::fs::remove_dir(&srvc.platform_specific.cgroup_path).map_err(|e| format!("{}", e))?;
+        let cgroup_path = &srvc.platform_specific.cgroup_path;
+        // already removed or never started
+        if !cgroup_path.exists() {
+            return Ok(());
+        }
+        cgroups::terminate_cgroup(cgroup_path, sig).map_err(|e| format!("{}", e))?;
+        // the cgroup can only be removed after all processes in it exited
+        let empty =
+            cgroups::wait_empty(cgroup_path, CGROUP_EMPTY_TIMEOUT).map_err(|e| format!("{}", e))?;
+        if !empty {
+            return Err(format!(
+                "There are still processes in the cgroup {:?} after {:?}",
+                cgroup_path, CGROUP_EMPTY_TIMEOUT
+            ));
+        }
+        cgroups::remove_cgroup(cgroup_path).map_err(|e| format!("{}", e))?;
     }
     let _ = srvc;
     let _ = sig;
     Ok(())
 }
+
+/// Send the signal to all processes of the service without waiting for them to exit. Returns false if the processes
+/// can not be found this way, then only the process group and the main process can be signaled.
+#[cfg(feature = "cgroups")]
+pub fn signal(srvc: &ServiceConfig, sig: nix::sys::signal::Signal) -> Result<bool, String> {
+    let cgroup_path = &srvc.platform_specific.cgroup_path;
+    // without the cgroup there are no processes left
+    if cgroup_path.exists() {
+        cgroups::terminate_cgroup(cgroup_path, sig).map_err(|e| format!("{}", e))?;
+    }
+    Ok(true)
+}
+
+#[cfg(not(feature = "cgroups"))]
+pub fn signal(_srvc: &ServiceConfig, _sig: nix::sys::signal::Signal) -> Result<bool, String> {
+    Ok(false)
+}
+
+/// Whether there are processes of the service left. None if that can not be found out this way.
+#[cfg(feature = "cgroups")]
+pub fn processes_left(srvc: &ServiceConfig) -> Option<bool> {
+    let cgroup_path = &srvc.platform_specific.cgroup_path;
+    if !cgroup_path.exists() {
+        return Some(false);
+    }
+    cgroups::is_populated(cgroup_path).ok()
+}
+
+#[cfg(not(feature = "cgroups"))]
+pub fn processes_left(_srvc: &ServiceConfig) -> Option<bool> {
+    None
+}
+
+/// Remove what is left of the service after its processes exited. Processes that are left on purpose keep it alive.
+#[cfg(feature = "cgroups")]
+pub fn remove_if_empty(srvc: &ServiceConfig) {
+    let cgroup_path = &srvc.platform_specific.cgroup_path;
+    if cgroup_path.exists() {
+        if let Ok(false) = cgroups::is_populated(cgroup_path) {
+            if let Err(e) = cgroups::remove_cgroup(cgroup_path) {
+                log::warn!("Could not remove cgroup {:?}: {}", cgroup_path, e);
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "cgroups"))]
+pub fn remove_if_empty(_srvc: &ServiceConfig) {}
@@ -1,31 +1,88 @@
 use super::StdIo;
+use crate::reactor::{Reactor, StreamKind};
 use crate::services::Service;
 use crate::units::ServiceConfig;
+use crate::units::StandardInput;
 use crate::units::StdIoOption;
+use crate::units::UnitId;
+use std::os::unix::fs::OpenOptionsExt;
 use std::os::unix::io::AsRawFd;
 use std::os::unix::net::UnixDatagram;
 
-fn open_stdio(setting: &Option<StdIoOption>) -> Result<StdIo, String> {
+fn open_stdio_file(
+    path: &std::path::Path,
+    options: &mut std::fs::OpenOptions,
+) -> Result<std::fs::File, String> {
+    options
+        .open(path)
+        .map_err(|e| format!("Error opening file: {:?}: {}", path, e))
+}
+
+fn open_stdio(
+    setting: &Option<StdIoOption>,
+    conf: &ServiceConfig,
+    is_stderr: bool,
+) -> Result<StdIo, String> {
     match setting {
         Some(StdIoOption::File(path)) => {
-            let file = std::fs::OpenOptions::new()
-                .create(true)
-                .write(true)
-                .read(true)
-                .open(path)
-                .map_err(|e| format!("Error opening file: {:?}: {}", path, e))?;
+            let file = open_stdio_file(
+                path,
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .read(true),
+            )?;
             Ok(StdIo::File(file))
         }
         Some(StdIoOption::AppendFile(path)) => {
-            let file = std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .read(true)
-                .open(path)
-                .map_err(|e| format!("Error opening file: {:?}: {}", path, e))?;
+            let file = open_stdio_file(
+                path,
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .read(true),
+            )?;
+            Ok(StdIo::File(file))
+        }
+        Some(StdIoOption::TruncateFile(path)) => {
+            let file = open_stdio_file(
+                path,
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .truncate(true)
+                    .read(true),
+            )?;
             Ok(StdIo::File(file))
         }
-        None => {
+        Some(StdIoOption::Null) => {
+            let file = open_stdio_file(
+                std::path::Path::new("/dev/null"),
+                std::fs::OpenOptions::new().write(true),
+            )?;
+            Ok(StdIo::Null(file))
+        }
+        Some(StdIoOption::Tty) => {
+            // the tty must not become the controlling terminal of rustysd
+            let file = open_stdio_file(
+                &conf.exec_config.tty_path,
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .custom_flags(libc::O_NOCTTY),
+            )?;
+            Ok(StdIo::File(file))
+        }
+        Some(StdIoOption::Socket) => Ok(StdIo::Socket),
+        Some(StdIoOption::Fd(name)) => Ok(StdIo::Fd(name.clone())),
+        Some(StdIoOption::Inherit) if is_stderr => Ok(StdIo::Inherit),
+        // stdout inherits stdin, which is either the connection or nothing
+        Some(StdIoOption::Inherit) => match conf.standard_input {
+            StandardInput::Socket => Ok(StdIo::Socket),
+            StandardInput::Null => open_stdio(&Some(StdIoOption::Null), conf, is_stderr),
+        },
+        // like in systemd the output goes to the connection if the input comes from it
+        None if !is_stderr && conf.standard_input == StandardInput::Socket => Ok(StdIo::Socket),
+        Some(StdIoOption::Journal) | None => {
             let (r, w) = nix::unistd::pipe().unwrap();
             Ok(super::StdIo::Piped(r, w))
         }
@@ -35,8 +92,10 @@ fn open_stdio(setting: &Option<StdIoOption>) -> Result<StdIo, String> {
 pub fn prepare_service(
     srvc: &mut Service,
     conf: &ServiceConfig,
+    id: &UnitId,
     name: &str,
     notification_socket_path: &std::path::PathBuf,
+    reactor: &Reactor,
 ) -> Result<(), String> {
     // setup socket for notifications from the service
     if !notification_socket_path.exists() {
@@ -64,15 +123,34 @@ pub fn prepare_service(
             nix::fcntl::FcntlArg::F_SETFD(nix::fcntl::FdFlag::FD_CLOEXEC),
         )
         .unwrap();
+        // the kernel tells us the sender of each notification, this is needed to enforce NotifyAccess=
+        #[cfg(target_os = "linux")]
+        nix::sys::socket::setsockopt(new_listener_fd, nix::sys::socket::sockopt::PassCred, &true)
+            .map_err(|e| {
+            format!(
+                "Could not set SO_PASSCRED on the notification socket: {}",
+                e
+            )
+        })?;
 
+        reactor.register(new_listener_fd, StreamKind::Notifications, id.clone());
         srvc.notifications = Some(stream);
     }
 
+    // only pipes need to be read by rustysd, files are written to directly by the service
     if srvc.stdout.is_none() {
-        srvc.stdout = Some(open_stdio(&conf.exec_config.stdout_path)?);
+        let stdout = open_stdio(&conf.exec_config.stdout_path, conf, false)?;
+        if let StdIo::Piped(r, _w) = &stdout {
+            reactor.register(*r, StreamKind::Stdout, id.clone());
+        }
+        srvc.stdout = Some(stdout);
     }
     if srvc.stderr.is_none() {
-        srvc.stderr = Some(open_stdio(&conf.exec_config.stderr_path)?);
+        let stderr = open_stdio(&conf.exec_config.stderr_path, conf, true)?;
+        if let StdIo::Piped(r, _w) = &stderr {
+            reactor.register(*r, StreamKind::Stderr, id.clone());
+        }
+        srvc.stderr = Some(stderr);
     }
 
     srvc.notifications_path = Some(notify_socket_env_var);
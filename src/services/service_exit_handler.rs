@@ -1,26 +1,145 @@
 use log::{error, trace};
 
+use crate::hook_activation::UnitEnded;
 use crate::runtime_info::*;
 use crate::signal_handler::ChildTermination;
 use crate::units::*;
 
+use std::time::{Duration, Instant};
+
+/// A service that exited and needs to be restarted after a delay
+pub struct PendingRestart {
+    id: UnitId,
+    delay: Duration,
+    /// The start of the run that ended. If this changed when the delay is over the service was restarted by something else.
+    up_since: Option<Instant>,
+}
+
 pub fn service_exit_handler_new_thread(
     pid: nix::unistd::Pid,
     code: ChildTermination,
     run_info: ArcMutRuntimeInfo,
 ) {
     std::thread::spawn(move || {
-        if let Err(e) = service_exit_handler(pid, code, &*run_info.read().unwrap()) {
-            error!("{}", e);
+        let res = service_exit_handler(pid, code, &*run_info.read().unwrap());
+        match res {
+            Ok(Some(restart)) => restart_after_delay(restart, run_info),
+            Ok(None) => {}
+            Err(e) => error!("{}", e),
+        }
+    });
+}
+
+/// All processes in the cgroup of a service whose main process rustysd does not know are gone. There is no exit code, so
+/// like in systemd this counts as a clean exit.
+pub fn service_cgroup_empty_new_thread(id: UnitId, run_info: ArcMutRuntimeInfo) {
+    std::thread::spawn(move || {
+        let res = {
+            let run_info_locked = &*run_info.read().unwrap();
+            // the unit might have been removed since the event
+            if !run_info_locked.unit_table.contains_key(&id) {
+                return;
+            }
+            trace!("The cgroup of service {} is empty", id.name);
+            handle_service_exit(id, ChildTermination::Exit(0), run_info_locked)
+        };
+        match res {
+            Ok(Some(restart)) => restart_after_delay(restart, run_info),
+            Ok(None) => {}
+            Err(e) => error!("{}", e),
         }
     });
 }
 
+/// Like in systemd SIGHUP, SIGINT, SIGTERM and SIGPIPE count as a clean exit
+pub fn is_clean_exit(code: &ChildTermination) -> bool {
+    use nix::sys::signal::Signal;
+    match code {
+        ChildTermination::Exit(code) => *code == 0,
+        ChildTermination::Signal(signal) => matches!(
+            signal,
+            Signal::SIGHUP | Signal::SIGINT | Signal::SIGTERM | Signal::SIGPIPE
+        ),
+    }
+}
+
+/// Whether the Restart= setting says that the service should be restarted after exiting like this. watchdog_timeout is
+/// set if the service was killed because its watchdog expired.
+pub fn restart_wanted(
+    restart: &ServiceRestart,
+    code: &ChildTermination,
+    watchdog_timeout: bool,
+) -> bool {
+    let clean_exit = is_clean_exit(code);
+    let unclean_signal = matches!(code, ChildTermination::Signal(_)) && !clean_exit;
+    match restart {
+        ServiceRestart::No => false,
+        ServiceRestart::Always => true,
+        ServiceRestart::OnSuccess => clean_exit && !watchdog_timeout,
+        ServiceRestart::OnFailure => !clean_exit || watchdog_timeout,
+        ServiceRestart::OnAbnormal => unclean_signal || watchdog_timeout,
+        ServiceRestart::OnAbort => unclean_signal && !watchdog_timeout,
+        ServiceRestart::OnWatchdog => watchdog_timeout,
+    }
+}
+
+/// The delay before the next automatic restart. With RestartSteps= and RestartMaxDelaySec= the delay grows exponentially
+/// from RestartSec= to RestartMaxDelaySec= over that many restarts.
+pub fn restart_delay(conf: &ServiceConfig, restart_count: u64) -> Duration {
+    let max_delay = match conf.restart_max_delay {
+        Some(max_delay) if conf.restart_steps > 0 && conf.restart_sec.as_nanos() > 0 => max_delay,
+        _ => return conf.restart_sec,
+    };
+    if max_delay <= conf.restart_sec {
+        return max_delay;
+    }
+    let step = restart_count.min(u64::from(conf.restart_steps)) as f64;
+    let factor = max_delay.as_secs_f64() / conf.restart_sec.as_secs_f64();
+    let delay = conf.restart_sec.as_secs_f64() * factor.powf(step / f64::from(conf.restart_steps));
+    Duration::from_secs_f64(delay).min(max_delay)
+}
+
+fn restart_after_delay(restart: PendingRestart, run_info: ArcMutRuntimeInfo) {
+    trace!("Restart service {} in {:?}", restart.id.name, restart.delay);
+    std::thread::sleep(restart.delay);
+
+    let run_info = &*run_info.read().unwrap();
+    let unit = match run_info.unit_table.get(&restart.id) {
+        Some(unit) => unit,
+        None => {
+            trace!(
+                "Service {} was removed before it could be restarted",
+                restart.id.name
+            );
+            return;
+        }
+    };
+    // Only restart if nothing else stopped or restarted the service in the meantime
+    let up_since = match &unit.specific {
+        Specific::Service(srvc) => srvc.state.read().unwrap().common.up_since,
+        _ => None,
+    };
+    let still_pending = *unit.common.status.read().unwrap() == UnitStatus::Restarting
+        && up_since == restart.up_since;
+    if !still_pending {
+        trace!(
+            "Service {} changed its state while waiting for the restart. Do not restart it.",
+            restart.id.name
+        );
+        return;
+    }
+    if let Err(e) =
+        crate::units::activate_unit(restart.id.clone(), run_info, ActivationSource::AutoRestart)
+    {
+        error!("Error while restarting service {}: {}", restart.id.name, e);
+    }
+}
+
 pub fn service_exit_handler(
     pid: nix::unistd::Pid,
     code: ChildTermination,
     run_info: &RuntimeInfo,
-) -> Result<(), String> {
+) -> Result<Option<PendingRestart>, String> {
     trace!("Exit handler with pid: {}", pid);
 
     // Handle exiting of helper processes and oneshot processes
@@ -40,17 +159,17 @@ pub fn service_exit_handler(
                     );
                     // this will be collected by the thread that waits for the helper process to exit
                     pid_table_locked.insert(pid, PidEntry::HelperExited(code));
-                    return Ok(());
+                    return Ok(None);
                 }
                 PidEntry::HelperExited(_) => {
                     // TODO is this sensibel? How do we handle this?
                     error!("Pid exited that was already saved as exited");
-                    return Ok(());
+                    return Ok(None);
                 }
                 PidEntry::ServiceExited(_) => {
                     // TODO is this sensibel? How do we handle this?
                     error!("Pid exited that was already saved as exited");
-                    return Ok(());
+                    return Ok(None);
                 }
             },
             None => {
@@ -58,7 +177,7 @@ pub fn service_exit_handler(
                     "All processes spawned by rustysd have a pid entry. This did not: {}. Probably a rerooted orphan that got killed.",
                     pid
                 );
-                return Ok(());
+                return Ok(None);
             }
         }
     }
@@ -69,9 +188,14 @@ pub fn service_exit_handler(
         let entry = pid_table_locked.remove(&pid);
         match entry {
             Some(entry) => match entry {
-                PidEntry::Service(id, _srvctype) => {
+                PidEntry::Service(id, srvctype) => {
                     trace!("Save service as exited. PID: {}", pid);
                     pid_table_locked.insert(pid, PidEntry::ServiceExited(code));
+                    if srvctype == ServiceType::Forking {
+                        // This is the initial process of a forking service. It is collected by wait_for_service, which
+                        // then tracks the main process of the daemon instead.
+                        return Ok(None);
+                    }
                     id
                 }
                 PidEntry::Helper(_id, _srvc_name) => {
@@ -89,7 +213,15 @@ pub fn service_exit_handler(
             }
         }
     };
+    handle_service_exit(srvc_id, code, run_info)
+}
 
+/// The service exited with this code. Restart it or stop it and all units that require it.
+fn handle_service_exit(
+    srvc_id: UnitId,
+    code: ChildTermination,
+    run_info: &RuntimeInfo,
+) -> Result<Option<PendingRestart>, String> {
     let unit = match run_info.unit_table.get(&srvc_id) {
         Some(unit) => unit,
         None => {
@@ -105,68 +237,147 @@ pub fn service_exit_handler(
                 mut_state
                     .srvc
                     .kill_all_remaining_processes(&srvc.conf, &unit.id.name);
-                return Ok(());
+                return Ok(None);
             }
         }
     }
 
     trace!("Check if we want to restart the unit");
     let name = &unit.id.name;
-    let restart_unit = {
+    let (restart_unit, stopping_itself, watchdog_timeout) = {
         if let Specific::Service(srvc) = &unit.specific {
             trace!(
-                "Service with id: {:?}, name: {} pid: {} exited with: {:?}",
+                "Service with id: {:?}, name: {} exited with: {:?}",
                 srvc_id,
                 unit.id.name,
-                pid,
                 code
             );
-
-            if srvc.conf.restart == ServiceRestart::Always {
-                true
-            } else {
-                false
+            let (watchdog_timeout, stopping_itself) = {
+                let state = srvc.state.read().unwrap();
+                (
+                    state.srvc.watchdog.fired.is_some(),
+                    state.srvc.signaled_stopping,
+                )
+            };
+            if watchdog_timeout {
+                trace!("Service {} was killed by its watchdog", unit.id.name);
             }
+            (
+                restart_wanted(&srvc.conf.restart, &code, watchdog_timeout),
+                stopping_itself,
+                watchdog_timeout,
+            )
         } else {
-            false
+            (false, false, false)
         }
     };
 
     // check that the status is "Started". If thats not the case this service got killed by something else (control interface for example) so dont interfere
-    {
+    // Services that sent STOPPING=1 are 'Stopping' but their exit still needs to be handled here
+    let was_starting = {
         let status_locked = &*unit.common.status.read().unwrap();
-        if !(status_locked.is_started() || *status_locked == UnitStatus::Starting) {
+        if !(status_locked.is_started()
+            || *status_locked == UnitStatus::Starting
+            || (stopping_itself && *status_locked == UnitStatus::Stopping))
+        {
             trace!("Exit handler ignores exit of service {}. Its status is not 'Started'/'Starting', it is: {:?}", name, *status_locked);
-            return Ok(());
+            return Ok(None);
         }
-    }
+        *status_locked == UnitStatus::Starting
+    };
 
     if restart_unit {
-        trace!("Restart service {} after it died", name);
-        crate::units::reactivate_unit(srvc_id, run_info).map_err(|e| format!("{}", e))?;
-    } else {
-        trace!(
-            "Recursively killing all services requiring service {}",
-            name
-        );
-        loop {
-            let res = crate::units::deactivate_unit_recursive(&srvc_id, run_info.clone());
-            let retry = if let Err(e) = &res {
-                if let UnitOperationErrorReason::DependencyError(_) = e.reason {
-                    // Only retry if this is the case. This only occurs if, while the units are being deactivated,
-                    // another unit got activated that would not be able to run with this unit deactivated.
-                    // This should generally be pretty rare but it should be handled properly.
-                    true
-                } else {
-                    false
+        if let Specific::Service(srvc) = &unit.specific {
+            // Clean up after the exited process now, the start happens after RestartSec=. If the process exited
+            // while the service was starting, the failed start already cleaned up.
+            let state = &mut *srvc.state.write().unwrap();
+            let need_cleanup = {
+                let status_locked = &mut *unit.common.status.write().unwrap();
+                let start_failed = was_starting
+                    && matches!(
+                        status_locked,
+                        UnitStatus::Stopped(StatusStopped::StoppedUnexpected, _)
+                    );
+                let stopped_itself =
+                    state.srvc.signaled_stopping && *status_locked == UnitStatus::Stopping;
+                if !(status_locked.is_started() || start_failed || stopped_itself) {
+                    trace!("Service {} was stopped while its exit was handled", name);
+                    return Ok(None);
                 }
+                let need_cleanup = status_locked.is_started() || stopped_itself;
+                *status_locked = UnitStatus::Restarting;
+                need_cleanup
+            };
+            if need_cleanup {
+                if let Err(e) = state
+                    .srvc
+                    .kill(&srvc.conf, srvc_id.clone(), name, run_info, true)
+                {
+                    error!(
+                        "Error while cleaning up after service {} exited: {}",
+                        name, e
+                    );
+                }
+            }
+            state.common.down_since = Some(Instant::now());
+            return Ok(Some(PendingRestart {
+                id: srvc_id.clone(),
+                delay: restart_delay(&srvc.conf, state.common.restart_count),
+                up_since: state.common.up_since,
+            }));
+        }
+    }
+
+    trace!(
+        "Recursively killing all services requiring or bound to service {}",
+        name
+    );
+    loop {
+        let res = crate::units::deactivate_unit_recursive(&srvc_id, run_info);
+        let retry = if let Err(e) = &res {
+            if let UnitOperationErrorReason::DependencyError(_) = e.reason {
+                // Only retry if this is the case. This only occurs if, while the units are being deactivated,
+                // another unit got activated that would not be able to run with this unit deactivated.
+                // This should generally be pretty rare but it should be handled properly.
+                true
             } else {
                 false
-            };
-            if !retry {
-                res.map_err(|e| format!("{}", e))?;
             }
+        } else {
+            false
+        };
+        if !retry {
+            res.map_err(|e| format!("{}", e))?;
+            break;
         }
     }
-    Ok(())
+
+    let ended = if is_clean_exit(&code) && !watchdog_timeout {
+        UnitEnded {
+            id: srvc_id,
+            result: "success",
+            exit: Some(code),
+        }
+    } else {
+        {
+            let status_locked = &mut *unit.common.status.write().unwrap();
+            if let UnitStatus::Stopped(_, errors) = status_locked {
+                let mut errors = errors.clone();
+                errors.push(UnitOperationErrorReason::UnexpectedExit(code));
+                *status_locked = UnitStatus::Stopped(StatusStopped::StoppedUnexpected, errors);
+            }
+        }
+        let result = match code {
+            _ if watchdog_timeout => "watchdog",
+            ChildTermination::Exit(_) => "exit-code",
+            ChildTermination::Signal(_) => "signal",
+        };
+        UnitEnded {
+            id: srvc_id,
+            result,
+            exit: Some(code),
+        }
+    };
+    run_info.hooks.push(ended);
+    Ok(None)
 }
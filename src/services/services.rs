@@ -1,7 +1,10 @@
 use log::error;
 use log::trace;
+use log::warn;
 
 use super::start_service::*;
+use crate::fd_store::FDStore;
+use crate::log_store::{LogEntry, LogStream};
 use crate::runtime_info::*;
 use crate::units::*;
 
@@ -20,21 +23,31 @@ pub enum StdIo {
 
     /// just like the regular file but will always point to /dev/null
     Null(std::fs::File),
+
+    /// The connection of an instance started by a socket with Accept=yes
+    Socket,
+    /// A named fd of a socket unit. It is looked up when the service is started.
+    Fd(String),
+    /// Only for stderr, use the same as stdout
+    Inherit,
 }
 
 impl StdIo {
-    pub fn write_fd(&self) -> RawFd {
+    /// The fd the service writes to. None for sockets and inherit, they are resolved in `Service::stdio_fds`.
+    pub fn write_fd(&self) -> Option<RawFd> {
         match self {
-            StdIo::File(f) => f.as_raw_fd(),
-            StdIo::Null(f) => f.as_raw_fd(),
-            StdIo::Piped(_r, w) => *w,
+            StdIo::File(f) => Some(f.as_raw_fd()),
+            StdIo::Null(f) => Some(f.as_raw_fd()),
+            StdIo::Piped(_r, w) => Some(*w),
+            StdIo::Socket | StdIo::Fd(_) | StdIo::Inherit => None,
         }
     }
-    pub fn read_fd(&self) -> RawFd {
+    pub fn read_fd(&self) -> Option<RawFd> {
         match self {
-            StdIo::File(f) => f.as_raw_fd(),
-            StdIo::Null(f) => f.as_raw_fd(),
-            StdIo::Piped(r, _w) => *r,
+            StdIo::File(f) => Some(f.as_raw_fd()),
+            StdIo::Null(f) => Some(f.as_raw_fd()),
+            StdIo::Piped(r, _w) => Some(*r),
+            StdIo::Socket | StdIo::Fd(_) | StdIo::Inherit => None,
         }
     }
 }
@@ -47,12 +60,34 @@ pub struct Service {
     pub process_group: Option<nix::unistd::Pid>,
 
     pub signaled_ready: bool,
+    /// The service sent RELOADING=1 and did not send READY=1 yet
+    pub signaled_reloading: bool,
+    /// The service sent STOPPING=1 and is shutting down on its own
+    pub signaled_stopping: bool,
+    /// Set with EXTEND_TIMEOUT_USEC=. The current start/stop/reload does not time out before this deadline
+    pub extend_timeout: Option<std::time::Instant>,
+    /// The last error the service reported with ERRNO=
+    pub errno: Option<i32>,
+    /// The connection this instance serves if it was started by a socket with Accept=yes. rustysd closes its copy as
+    /// soon as the process of the instance has it, so the peer sees EOF when the instance exits.
+    pub connection: Option<RawFd>,
+    /// Set for instances started by a socket with Accept=yes. They are removed when they are done.
+    pub serves_connection: bool,
+    /// The $MONITOR_* variables if this service was started by OnFailure= or OnSuccess= of another unit
+    pub monitor_env: Vec<(String, String)>,
 
     pub notifications: Option<UnixDatagram>,
     pub notifications_path: Option<std::path::PathBuf>,
 
     pub stdout: Option<StdIo>,
     pub stderr: Option<StdIo>,
+    /// Read end of the pipe that tells if the exec of a service of type exec succeeded. It is closed by the exec
+    /// without anything being written to it, otherwise it contains the error.
+    pub exec_status: Option<std::fs::File>,
+    /// Tells when all processes of the service are gone. Only set with cgroups v2.
+    pub cgroup_events: Option<crate::platform::cgroups::CgroupEvents>,
+    pub watchdog: crate::watchdog::Watchdog,
+
     pub notifications_buffer: String,
     pub stdout_buffer: Vec<u8>,
     pub stderr_buffer: Vec<u8>,
@@ -103,6 +138,7 @@ pub enum ServiceErrorReason {
     StartFailed(RunCmdError),
     PoststopFailed(RunCmdError),
     StopFailed(RunCmdError),
+    ReloadFailed(RunCmdError),
 
     PrestartAndPoststopFailed(RunCmdError, RunCmdError),
     PoststartAndPoststopFailed(RunCmdError, RunCmdError),
@@ -123,6 +159,7 @@ impl std::fmt::Display for ServiceErrorReason {
             ServiceErrorReason::StartFailed(e) => format!("Start failed: {}", e),
             ServiceErrorReason::StopFailed(e) => format!("Stop failed: {}", e),
             ServiceErrorReason::PoststopFailed(e) => format!("Poststop failed: {}", e),
+            ServiceErrorReason::ReloadFailed(e) => format!("Reload failed: {}", e),
 
             // Both failed
             ServiceErrorReason::PrestartAndPoststopFailed(e, e2) => {
@@ -154,6 +191,47 @@ impl std::fmt::Display for ServiceErrorReason {
     }
 }
 
+/// How often the processes of a stopping service are checked
+const STOP_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// Services of type idle wait at most this long for the other units to finish starting
+const IDLE_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(5);
+
+/// Wait until no other units are starting anymore, so the output of services of type idle does not get mixed
+/// with the output of the boot.
+fn wait_until_idle(id: &UnitId, run_info: &RuntimeInfo) {
+    let start = std::time::Instant::now();
+    loop {
+        let busy = run_info.unit_table.values().any(|unit| {
+            if unit.id == *id {
+                return false;
+            }
+            // other idle services would wait for each other
+            if let Specific::Service(srvc) = &unit.specific {
+                if srvc.conf.srcv_type == ServiceType::Idle {
+                    return false;
+                }
+            }
+            match unit.common.status.try_read() {
+                Ok(status) => *status == UnitStatus::Starting,
+                // someone is changing the status right now
+                Err(_) => true,
+            }
+        });
+        if !busy {
+            return;
+        }
+        if start.elapsed() >= IDLE_TIMEOUT {
+            trace!(
+                "Stop waiting for the other units to start up for {}",
+                id.name
+            );
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+}
+
 impl Service {
     pub fn start(
         &mut self,
@@ -169,21 +247,33 @@ impl Service {
         if let Some(pgid) = self.process_group {
             return Err(ServiceErrorReason::AlreadyHasPID(pgid));
         }
-        if conf.accept {
+        if conf.standard_input == StandardInput::Socket && self.connection.is_none() {
             return Err(ServiceErrorReason::Generic(
-                "Inetd style activation is not supported".into(),
+                "StandardInput=socket only works for instances started by sockets with Accept=yes"
+                    .into(),
             ));
         }
-        if source.is_socket_activation() || conf.sockets.is_empty() {
+        if source.is_socket_activation()
+            || source.is_triggered_activation()
+            || conf.sockets.is_empty()
+        {
             trace!("Start service {}", name);
+            self.signaled_stopping = false;
+            self.extend_timeout = None;
+            self.errno = None;
 
             super::prepare_service::prepare_service(
                 self,
                 conf,
+                &id,
                 name,
                 &run_info.config.notification_sockets_dir,
+                &run_info.reactor,
             )
             .map_err(|e| ServiceErrorReason::PreparingFailed(e))?;
+            if conf.srcv_type == ServiceType::Idle {
+                wait_until_idle(&id, run_info);
+            }
             self.run_prestart(conf, id.clone(), name, run_info.clone())
                 .map_err(|prestart_err| {
                     match self.run_poststop(conf, id.clone(), name, run_info.clone()) {
@@ -194,6 +284,7 @@ impl Service {
                         ),
                     }
                 })?;
+            self.remove_pid_file(conf, name);
             {
                 let mut pid_table_locked = run_info.pid_table.lock().unwrap();
                 // This mainly just forks the process. The waiting (if necessary) is done below
@@ -211,8 +302,10 @@ impl Service {
                     pid_table_locked.insert(new_pid, PidEntry::Service(id.clone(), conf.srcv_type));
                 }
             }
+            #[cfg(feature = "cgroups")]
+            self.watch_cgroup(conf, &id, name, run_info);
 
-            super::fork_parent::wait_for_service(self, conf, name, run_info).map_err(
+            super::fork_parent::wait_for_service(self, conf, &id, name, run_info).map_err(
                 |start_err| match self.run_poststop(conf, id.clone(), name, run_info.clone()) {
                     Ok(_) => ServiceErrorReason::StartFailed(start_err),
                     Err(poststop_err) => {
@@ -230,6 +323,9 @@ impl Service {
                         ),
                     }
                 })?;
+            if conf.srcv_type != ServiceType::OneShot {
+                self.watchdog.arm(conf.watchdog_sec);
+            }
             Ok(StartResult::Started)
         } else {
             trace!(
@@ -240,18 +336,40 @@ impl Service {
         }
     }
 
+    /// Kill the processes that are left after the service stopped, as far as the KillMode= allows it. With
+    /// SendSIGKILL=no they are left alone.
     pub fn kill_all_remaining_processes(&mut self, conf: &ServiceConfig, name: &str) {
         trace!("Kill all process for {}", name);
+        let kill = &conf.kill;
+        if kill.mode == KillMode::None || !kill.send_sigkill {
+            trace!(
+                "Leave the remaining processes of service {} alone because of KillMode=none or SendSIGKILL=no",
+                name
+            );
+            super::kill_os_specific::remove_if_empty(conf);
+            return;
+        }
+        if kill.mode == KillMode::Process {
+            self.signal_main_process(name, kill.final_signal);
+            super::kill_os_specific::remove_if_empty(conf);
+            return;
+        }
         if let Some(proc_group) = self.process_group {
             // TODO handle these errors
-            match nix::sys::signal::kill(proc_group, nix::sys::signal::Signal::SIGKILL) {
+            match nix::sys::signal::kill(proc_group, kill.final_signal) {
                 Ok(_) => trace!("Success killing process group for service {}", name,),
+                // the processes usually exited already when they were stopped
+                Err(nix::Error::ESRCH) => {}
                 Err(e) => error!("Error killing process group for service {}: {}", name, e,),
             }
         } else {
             trace!("Tried to kill service that didn't have a process-group. This might have resulted in orphan processes.");
         }
-        match super::kill_os_specific::kill(conf, nix::sys::signal::Signal::SIGKILL) {
+        // the main process of a forking service might have left the process group by calling setsid
+        if !self.main_process_in_group() {
+            self.signal_main_process(name, kill.final_signal);
+        }
+        match super::kill_os_specific::kill(conf, kill.final_signal) {
             Ok(_) => trace!("Success killing process os specificly for service {}", name,),
             Err(e) => error!(
                 "Error killing process os specificly for service {}: {}",
@@ -260,6 +378,221 @@ impl Service {
         }
     }
 
+    /// Send the KillSignal= (or the RestartKillSignal= on restarts) to the processes the KillMode= selects and wait
+    /// until they exited. If they are still running after the stop timeout they get the FinalKillSignal=, unless
+    /// SendSIGKILL=no is set.
+    fn stop_processes(
+        &mut self,
+        conf: &ServiceConfig,
+        id: &UnitId,
+        run_info: &RuntimeInfo,
+        restarting: bool,
+    ) {
+        let name = &id.name;
+        let kill = &conf.kill;
+        if kill.mode == KillMode::None {
+            return;
+        }
+        let sig = match kill.restart_signal {
+            Some(restart_signal) if restarting => restart_signal,
+            _ => kill.signal,
+        };
+        let mut signals = vec![sig];
+        if kill.send_sighup {
+            signals.push(nix::sys::signal::Signal::SIGHUP);
+        }
+        for sig in signals {
+            trace!(
+                "Send {} to service {} (KillMode={:?})",
+                sig,
+                name,
+                kill.mode
+            );
+            if kill.mode == KillMode::ControlGroup {
+                self.signal_all_processes(conf, name, sig);
+            } else {
+                self.signal_main_process(name, sig);
+            }
+        }
+
+        if self.wait_for_processes(conf, id, run_info) {
+            return;
+        }
+        if kill.send_sigkill {
+            warn!(
+                "Service {} did not stop in time. Sending {}",
+                name, kill.final_signal
+            );
+            if kill.mode == KillMode::Process {
+                self.signal_main_process(name, kill.final_signal);
+            } else {
+                self.signal_all_processes(conf, name, kill.final_signal);
+            }
+        } else {
+            warn!(
+                "Service {} did not stop in time. Its processes keep running because of SendSIGKILL=no",
+                name
+            );
+        }
+    }
+
+    fn main_process_in_group(&self) -> bool {
+        // the process group is saved negated so it can be passed to kill() directly
+        self.pid
+            .map(|pid| nix::unistd::Pid::from_raw(-pid.as_raw()))
+            == self.process_group
+    }
+
+    fn signal_main_process(&self, name: &str, sig: nix::sys::signal::Signal) {
+        if let Some(pid) = self.pid {
+            match nix::sys::signal::kill(pid, sig) {
+                Ok(_) | Err(nix::Error::ESRCH) => {}
+                Err(e) => error!(
+                    "Error sending {} to the main process of service {}: {}",
+                    sig, name, e
+                ),
+            }
+        }
+    }
+
+    /// Signal all processes in the cgroup of the service. Without cgroups the process group and the main process are
+    /// signaled instead.
+    fn signal_all_processes(
+        &self,
+        conf: &ServiceConfig,
+        name: &str,
+        sig: nix::sys::signal::Signal,
+    ) {
+        match super::kill_os_specific::signal(conf, sig) {
+            Ok(true) => return,
+            Ok(false) => {}
+            Err(e) => error!(
+                "Error sending {} to the processes of service {} os specificly: {}",
+                sig, name, e
+            ),
+        }
+        if let Some(proc_group) = self.process_group {
+            match nix::sys::signal::kill(proc_group, sig) {
+                Ok(_) | Err(nix::Error::ESRCH) => {}
+                Err(e) => error!(
+                    "Error sending {} to the process group of service {}: {}",
+                    sig, name, e
+                ),
+            }
+        }
+        if !self.main_process_in_group() {
+            self.signal_main_process(name, sig);
+        }
+    }
+
+    /// Whether the processes that the KillMode= selects are still running
+    fn processes_left(&self, conf: &ServiceConfig) -> bool {
+        let main_running = self
+            .pid
+            .map(crate::platform::procfs::is_running)
+            .unwrap_or(false);
+        if conf.kill.mode != KillMode::ControlGroup {
+            return main_running;
+        }
+        match super::kill_os_specific::processes_left(conf) {
+            Some(left) => left,
+            None => {
+                main_running
+                    || self
+                        .process_group
+                        .map(|pgid| {
+                            !crate::platform::procfs::pids_in_process_group(
+                                nix::unistd::Pid::from_raw(-pgid.as_raw()),
+                            )
+                            .is_empty()
+                        })
+                        .unwrap_or(false)
+            }
+        }
+    }
+
+    /// Wait until the signaled processes exited, at most for the stop timeout. Returns false if some are still running.
+    fn wait_for_processes(
+        &mut self,
+        conf: &ServiceConfig,
+        id: &UnitId,
+        run_info: &RuntimeInfo,
+    ) -> bool {
+        let start_time = std::time::Instant::now();
+        let timeout = self.get_stop_timeout(conf);
+        loop {
+            if !self.processes_left(conf) {
+                return true;
+            }
+            // The service is locked while it stops. It might have asked for more time with EXTEND_TIMEOUT_USEC=.
+            crate::notification_handler::read_pending_notifications(self, conf, id, run_info);
+            if let Some(timeout) = timeout {
+                if self.remaining_timeout(start_time, timeout).is_none() {
+                    return false;
+                }
+            }
+            std::thread::sleep(STOP_POLL_INTERVAL);
+        }
+    }
+
+    /// Watch the cgroup.events of the service. This notices that the service is gone even if rustysd does not know its
+    /// main process, like with forking services whose main process could not be found.
+    #[cfg(feature = "cgroups")]
+    fn watch_cgroup(
+        &mut self,
+        conf: &ServiceConfig,
+        id: &UnitId,
+        name: &str,
+        run_info: &RuntimeInfo,
+    ) {
+        self.unwatch_cgroup(run_info);
+        match crate::platform::cgroups::CgroupEvents::watch(&conf.platform_specific.cgroup_path) {
+            Ok(events) => {
+                run_info.reactor.register(
+                    events.as_raw_fd(),
+                    crate::reactor::StreamKind::CgroupEvents,
+                    id.clone(),
+                );
+                self.cgroup_events = Some(events);
+            }
+            Err(e) => trace!("Can not watch the cgroup of service {}: {}", name, e),
+        }
+    }
+
+    fn unwatch_cgroup(&mut self, run_info: &RuntimeInfo) {
+        if let Some(events) = self.cgroup_events.take() {
+            run_info.reactor.deregister(events.as_raw_fd());
+        }
+    }
+
+    /// Run the ExecReload= commands. Services of type notify may bracket the reload with RELOADING=1 and READY=1, in that
+    /// case the reload is only done after the READY=1.
+    pub fn reload(
+        &mut self,
+        conf: &ServiceConfig,
+        id: UnitId,
+        name: &str,
+        run_info: &RuntimeInfo,
+    ) -> Result<(), ServiceErrorReason> {
+        if conf.reload.is_empty() {
+            return Err(ServiceErrorReason::Generic(format!(
+                "Service {} has no ExecReload= commands",
+                name
+            )));
+        }
+        trace!("Reload service {}", name);
+        self.extend_timeout = None;
+        let timeout = self.get_start_timeout(conf);
+        let cmds = conf.reload.clone();
+        self.run_all_cmds(&cmds, id.clone(), name, timeout, run_info)
+            .map_err(ServiceErrorReason::ReloadFailed)?;
+        if conf.srcv_type == ServiceType::Notify {
+            super::fork_parent::wait_for_reload(self, conf, &id, run_info)
+                .map_err(ServiceErrorReason::ReloadFailed)?;
+        }
+        Ok(())
+    }
+
     fn stop(
         &mut self,
         conf: &ServiceConfig,
@@ -269,14 +602,20 @@ impl Service {
     ) -> Result<(), RunCmdError> {
         self.run_stop_cmd(conf, id, name, run_info.clone())
     }
+    /// Run the ExecStop= commands, stop the processes of the service as the KillMode= says and run the ExecStopPost=
+    /// commands. If the service is restarted it gets the RestartKillSignal=.
     pub fn kill(
         &mut self,
         conf: &ServiceConfig,
         id: UnitId,
         name: &str,
         run_info: &RuntimeInfo,
+        restarting: bool,
     ) -> Result<(), ServiceErrorReason> {
-        self.stop(conf, id.clone(), name, run_info)
+        self.extend_timeout = None;
+        let stop_result = self.stop(conf, id.clone(), name, run_info);
+        self.stop_processes(conf, &id, run_info, restarting);
+        stop_result
             .map_err(|stop_err| {
                 trace!(
                     "Stop process failed with: {:?} for service: {}. Running poststop commands",
@@ -319,7 +658,7 @@ impl Service {
         }
     }
 
-    fn get_stop_timeout(&self, conf: &ServiceConfig) -> Option<std::time::Duration> {
+    pub fn get_stop_timeout(&self, conf: &ServiceConfig) -> Option<std::time::Duration> {
         if let Some(timeout) = &conf.stoptimeout {
             match timeout {
                 Timeout::Duration(dur) => Some(*dur),
@@ -338,6 +677,25 @@ impl Service {
         }
     }
 
+    /// How much time is left of a timeout that started at start_time. The service can move the deadline with
+    /// EXTEND_TIMEOUT_USEC=. None if the time is up.
+    pub fn remaining_timeout(
+        &self,
+        start_time: std::time::Instant,
+        timeout: std::time::Duration,
+    ) -> Option<std::time::Duration> {
+        let now = std::time::Instant::now();
+        let deadline = match self.extend_timeout {
+            Some(extended) => extended.max(start_time + timeout),
+            None => start_time + timeout,
+        };
+        if deadline > now {
+            Some(deadline - now)
+        } else {
+            None
+        }
+    }
+
     fn run_cmd(
         &mut self,
         cmdline: &Commandline,
@@ -348,24 +706,34 @@ impl Service {
     ) -> Result<(), RunCmdError> {
         let mut cmd = Command::new(&cmdline.cmd);
         for part in &cmdline.args {
-            cmd.arg(part);
+            // mostly used by ExecReload= to send a signal to the main process
+            match self.pid {
+                Some(pid) => cmd.arg(
+                    part.replace("${MAINPID}", &pid.to_string())
+                        .replace("$MAINPID", &pid.to_string()),
+                ),
+                None => cmd.arg(part),
+            };
         }
+        if let Some(pid) = self.pid {
+            cmd.env("MAINPID", pid.to_string());
+        }
+        cmd.envs(self.monitor_env.iter().cloned());
         use std::os::unix::io::FromRawFd;
-        let stdout = if let Some(stdio) = &self.stdout {
+        let (stdout, stderr) = if self.stdout.is_some() && self.stderr.is_some() {
+            let (stdout_fd, stderr_fd) = self
+                .stdio_fds(&run_info.fd_store.read().unwrap())
+                .map_err(RunCmdError::Generic)?;
             unsafe {
-                let duped = nix::unistd::dup(stdio.write_fd()).unwrap();
-                Stdio::from(std::fs::File::from_raw_fd(duped))
-            }
-        } else {
-            Stdio::piped()
-        };
-        let stderr = if let Some(stdio) = &self.stderr {
-            unsafe {
-                let duped = nix::unistd::dup(stdio.write_fd()).unwrap();
-                Stdio::from(std::fs::File::from_raw_fd(duped))
+                let stdout = nix::unistd::dup(stdout_fd).unwrap();
+                let stderr = nix::unistd::dup(stderr_fd).unwrap();
+                (
+                    Stdio::from(std::fs::File::from_raw_fd(stdout)),
+                    Stdio::from(std::fs::File::from_raw_fd(stderr)),
+                )
             }
         } else {
-            Stdio::piped()
+            (Stdio::piped(), Stdio::piped())
         };
 
         cmd.stdout(stdout);
@@ -387,7 +755,7 @@ impl Service {
             Ok(mut child) => {
                 trace!("Wait for {:?} for service: {}", cmdline, name);
                 let wait_result: Result<(), RunCmdError> = match wait_for_helper_child(
-                    &mut child, run_info, timeout,
+                    self, &mut child, &id, run_info, timeout,
                 ) {
                     WaitResult::InTime(Err(e)) => {
                         return Err(RunCmdError::WaitError(
@@ -432,17 +800,18 @@ impl Service {
                     let unit = run_info.unit_table.get(&id).unwrap();
                     let status = &*unit.common.status.read().unwrap();
                     use std::io::Read;
+                    let pid = Some(nix::unistd::Pid::from_raw(child.id() as i32));
                     if let Some(stream) = &mut child.stderr {
                         let mut buf = Vec::new();
                         let _bytes = stream.read_to_end(&mut buf).unwrap();
                         self.stderr_buffer.extend(buf);
-                        self.log_stderr_lines(name, status).unwrap();
+                        self.log_stderr_lines(unit, pid, status, run_info).unwrap();
                     }
                     if let Some(stream) = &mut child.stdout {
                         let mut buf = Vec::new();
                         let _bytes = stream.read_to_end(&mut buf).unwrap();
                         self.stdout_buffer.extend(buf);
-                        self.log_stdout_lines(name, status).unwrap();
+                        self.log_stdout_lines(unit, pid, status, run_info).unwrap();
                     }
                 }
 
@@ -532,65 +901,144 @@ impl Service {
             // already happened when the oneshot process exited in the exit handler
             self.kill_all_remaining_processes(conf, name);
         }
+        self.remove_pid_file(conf, name);
+        self.unwatch_cgroup(run_info);
         self.pid = None;
         self.process_group = None;
+        self.watchdog.disarm();
+        self.signaled_stopping = false;
         res
     }
 
-    pub fn log_stdout_lines(&mut self, name: &str, status: &UnitStatus) -> std::io::Result<()> {
-        let mut prefix = String::new();
-        prefix.push('[');
-        prefix.push_str(name);
-        prefix.push(']');
-        prefix.push_str(&format!("[{:?}]", *status));
-        prefix.push(' ');
-        let mut outbuf: Vec<u8> = Vec::new();
-        while self.stdout_buffer.contains(&b'\n') {
-            let split_pos = self.stdout_buffer.iter().position(|r| *r == b'\n').unwrap();
-            let (line, lines) = self.stdout_buffer.split_at(split_pos + 1);
-
-            // drop \n at the end of the line
-            let line = &line[0..line.len() - 1].to_vec();
-            self.stdout_buffer = lines.to_vec();
-            if line.is_empty() {
-                continue;
-            }
-            outbuf.clear();
-            outbuf.extend(prefix.as_bytes());
-            outbuf.extend(line);
-            outbuf.push(b'\n');
-            std::io::stdout().write_all(&outbuf)?;
+    /// A PIDFile= that is left over from an earlier run must not be mistaken for the one of the new main process
+    fn remove_pid_file(&self, conf: &ServiceConfig, name: &str) {
+        if let Some(pid_file) = &conf.pid_file {
+            match std::fs::remove_file(pid_file) {
+                Ok(()) => trace!("Removed PIDFile {:?} of service {}", pid_file, name),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => warn!(
+                    "Could not remove PIDFile {:?} of service {}: {}",
+                    pid_file, name, e
+                ),
+            }
         }
-        Ok(())
     }
-    pub fn log_stderr_lines(&mut self, name: &str, status: &UnitStatus) -> std::io::Result<()> {
-        let mut prefix = String::new();
-        prefix.push('[');
-        prefix.push_str(&name);
-        prefix.push(']');
-        prefix.push_str(&format!("[{:?}]", *status));
+
+    /// The fds that are put at stdout and stderr of the processes of this service
+    pub fn stdio_fds(&self, fd_store: &FDStore) -> Result<(RawFd, RawFd), String> {
+        let resolve = |stdio: &StdIo| match stdio {
+            StdIo::Socket => self.connection.ok_or_else(|| {
+                "Output to a socket only works for instances started by sockets with Accept=yes"
+                    .to_owned()
+            }),
+            StdIo::Fd(fd_name) => fd_store
+                .find_global_by_fd_name(fd_name)
+                .ok_or_else(|| format!("No socket has an open fd with the name {}", fd_name)),
+            StdIo::Inherit => Err("Only stderr can inherit from stdout".to_owned()),
+            _ => Ok(stdio.write_fd().unwrap()),
+        };
+        let stdout = match &self.stdout {
+            Some(stdio) => resolve(stdio)?,
+            None => return Err("stdout of the service was not prepared".to_owned()),
+        };
+        let stderr = match &self.stderr {
+            Some(StdIo::Inherit) => stdout,
+            Some(stdio) => resolve(stdio)?,
+            None => return Err("stderr of the service was not prepared".to_owned()),
+        };
+        Ok((stdout, stderr))
+    }
+
+    pub fn log_stdout_lines(
+        &mut self,
+        unit: &Unit,
+        pid: Option<nix::unistd::Pid>,
+        status: &UnitStatus,
+        run_info: &RuntimeInfo,
+    ) -> std::io::Result<()> {
+        log_lines(
+            &mut self.stdout_buffer,
+            LogStream::Stdout,
+            unit,
+            pid,
+            status,
+            run_info,
+        )
+    }
+    pub fn log_stderr_lines(
+        &mut self,
+        unit: &Unit,
+        pid: Option<nix::unistd::Pid>,
+        status: &UnitStatus,
+        run_info: &RuntimeInfo,
+    ) -> std::io::Result<()> {
+        log_lines(
+            &mut self.stderr_buffer,
+            LogStream::Stderr,
+            unit,
+            pid,
+            status,
+            run_info,
+        )
+    }
+}
+
+/// Output all complete lines in the buffer on the stdout/stderr of rustysd, prefixed with the SyslogIdentifier= or the
+/// name of the service, and put them into the log store if there is one
+fn log_lines(
+    buffer: &mut Vec<u8>,
+    stream: LogStream,
+    unit: &Unit,
+    pid: Option<nix::unistd::Pid>,
+    status: &UnitStatus,
+    run_info: &RuntimeInfo,
+) -> std::io::Result<()> {
+    let identifier = match &unit.specific {
+        Specific::Service(srvc) => srvc.conf.exec_config.syslog_identifier.as_deref(),
+        _ => None,
+    };
+    let name = identifier.unwrap_or(&unit.id.name);
+    let mut prefix = String::new();
+    prefix.push('[');
+    prefix.push_str(name);
+    prefix.push(']');
+    prefix.push_str(&format!("[{:?}]", *status));
+    if stream == LogStream::Stderr {
         prefix.push_str("[STDERR]");
-        prefix.push(' ');
+    }
+    prefix.push(' ');
 
-        let mut outbuf: Vec<u8> = Vec::new();
-        while self.stderr_buffer.contains(&b'\n') {
-            let split_pos = self.stderr_buffer.iter().position(|r| *r == b'\n').unwrap();
-            let (line, lines) = self.stderr_buffer.split_at(split_pos + 1);
+    let mut outbuf: Vec<u8> = Vec::new();
+    while buffer.contains(&b'\n') {
+        let split_pos = buffer.iter().position(|r| *r == b'\n').unwrap();
+        let (line, lines) = buffer.split_at(split_pos + 1);
 
-            // drop \n at the end of the line
-            let line = &line[0..line.len() - 1].to_vec();
-            self.stderr_buffer = lines.to_vec();
-            if line.is_empty() {
-                continue;
-            }
-            outbuf.clear();
-            outbuf.extend(prefix.as_bytes());
-            outbuf.extend(line);
-            outbuf.push(b'\n');
-            std::io::stderr().write_all(&outbuf).unwrap();
+        // drop \n at the end of the line
+        let line = &line[0..line.len() - 1].to_vec();
+        *buffer = lines.to_vec();
+        if line.is_empty() {
+            continue;
+        }
+        outbuf.clear();
+        outbuf.extend(prefix.as_bytes());
+        outbuf.extend(line);
+        outbuf.push(b'\n');
+        if stream == LogStream::Stderr {
+            std::io::stderr().write_all(&outbuf)?;
+        } else {
+            std::io::stdout().write_all(&outbuf)?;
+        }
+        if let Some(log_store) = &run_info.log_store {
+            log_store.append(LogEntry::from_service_line(
+                &unit.id.name,
+                identifier,
+                pid.map(|pid| pid.as_raw()),
+                stream,
+                &String::from_utf8_lossy(line),
+            ));
         }
-        Ok(())
     }
+    Ok(())
 }
 
 enum WaitResult {
@@ -604,7 +1052,9 @@ enum WaitResult {
 /// This could be fixed by using the waitid() with WNOWAIT in the signal handler but
 /// that has not been ported to rust
 fn wait_for_helper_child(
+    srvc: &mut Service,
     child: &mut std::process::Child,
+    id: &UnitId,
     run_info: &RuntimeInfo,
     time_out: Option<std::time::Duration>,
 ) -> WaitResult {
@@ -614,7 +1064,20 @@ fn wait_for_helper_child(
     loop {
         if let Some(time_out) = time_out {
             if start_time.elapsed() >= time_out {
-                return WaitResult::TimedOut;
+                // The service might have asked for more time while the helper was running
+                if let Some(Specific::Service(specific)) =
+                    run_info.unit_table.get(id).map(|unit| &unit.specific)
+                {
+                    crate::notification_handler::read_pending_notifications(
+                        srvc,
+                        &specific.conf,
+                        id,
+                        run_info,
+                    );
+                }
+                if srvc.remaining_timeout(start_time, time_out).is_none() {
+                    return WaitResult::TimedOut;
+                }
             }
         }
         {
@@ -7,7 +7,10 @@ use crate::fd_store::FDStore;
 use crate::services::RunCmdError;
 use crate::services::Service;
 use crate::units::ServiceConfig;
+use crate::units::ServiceType;
+use crate::units::StandardInput;
 
+use std::os::unix::io::RawFd;
 use std::path::Path;
 
 fn start_service_with_filedescriptors(
@@ -65,6 +68,11 @@ fn start_service_with_filedescriptors(
 
     super::fork_os_specific::pre_fork_os_specific(conf).map_err(|e| RunCmdError::Generic(e))?;
 
+    // resolved before forking so errors can be reported
+    let (stdout, stderr) = srvc
+        .stdio_fds(fd_store)
+        .map_err(|e| RunCmdError::Generic(format!("Service {}: {}", name, e)))?;
+
     let mut fds = Vec::new();
     let mut names = Vec::new();
 
@@ -87,17 +95,55 @@ fn start_service_with_filedescriptors(
         names.extend(sock_names);
     }
 
+    // fds the service stored with FDSTORE=1 before it was restarted
+    for (fd_name, fd) in fd_store.get_all_service_stored(name) {
+        fds.push(fd);
+        names.push(fd_name);
+    }
+
+    // The connection of instances started by sockets with Accept=yes. With StandardInput=socket it is not passed as
+    // LISTEN_FDS but the exec helper moves it to stdin.
+    let stdin_socket_fd = match srvc.connection {
+        Some(fd) if conf.standard_input == StandardInput::Socket => {
+            let stdin_socket_fd = libc::STDERR_FILENO + 1 + fds.len() as RawFd;
+            fds.push(fd);
+            Some(stdin_socket_fd)
+        }
+        Some(fd) => {
+            fds.push(fd);
+            names.push("connection".to_owned());
+            None
+        }
+        None => None,
+    };
+
+    // For services of type exec the write end of the status pipe is passed after the sockets
+    let exec_status_fd = if conf.srcv_type == ServiceType::Exec {
+        Some(libc::STDERR_FILENO + 1 + fds.len() as RawFd)
+    } else {
+        None
+    };
+
+    let mut env = vec![
+        ("LISTEN_FDS".to_owned(), format!("{}", names.len())),
+        ("LISTEN_FDNAMES".to_owned(), names.join(":")),
+        ("NOTIFY_SOCKET".to_owned(), notifications_path.clone()),
+    ];
+    if let Some(watchdog_sec) = conf.watchdog_sec {
+        env.push((
+            "WATCHDOG_USEC".to_owned(),
+            format!("{}", watchdog_sec.as_micros()),
+        ));
+    }
+    env.extend(srvc.monitor_env.iter().cloned());
+
     // We first exec into our own executable again and apply this config
     // We transfer the config via a anonymous shared memory file
     let exec_helper_conf = crate::entrypoints::ExecHelperConfig {
         name: name.to_owned(),
         cmd: cmd,
         args: conf.exec.args.clone(),
-        env: vec![
-            ("LISTEN_FDS".to_owned(), format!("{}", names.len())),
-            ("LISTEN_FDNAMES".to_owned(), names.join(":")),
-            ("NOTIFY_SOCKET".to_owned(), notifications_path.clone()),
-        ],
+        env,
         group: conf.exec_config.group.as_raw(),
         supplementary_groups: conf
             .exec_config
@@ -106,6 +152,8 @@ fn start_service_with_filedescriptors(
             .map(|gid| gid.as_raw())
             .collect(),
         user: conf.exec_config.user.as_raw(),
+        exec_status_fd,
+        stdin_socket_fd,
 
         platform_specific: conf.platform_specific.clone(),
     };
@@ -142,6 +190,22 @@ fn start_service_with_filedescriptors(
     let name_arg = std::ffi::CString::new("exec_helper").unwrap();
     let self_args = [name_arg.as_ptr(), std::ptr::null()];
 
+    // The exec helper reports errors through this pipe. A successful exec closes it because the exec helper marks
+    // it CLOEXEC again, so the read end gets EOF without any data.
+    let exec_status_pipe = if exec_status_fd.is_some() {
+        let (read_end, write_end) =
+            nix::unistd::pipe2(nix::fcntl::OFlag::O_CLOEXEC).map_err(|e| {
+                RunCmdError::Generic(format!(
+                    "Could not create the exec status pipe for service {}: {}",
+                    name, e
+                ))
+            })?;
+        fds.push(write_end);
+        Some((read_end, write_end))
+    } else {
+        None
+    };
+
     trace!("Start main executable for service: {name}: {:?} {:?}", exec_helper_conf.cmd, exec_helper_conf.args);
     match unsafe { nix::unistd::fork() } {
         Ok(nix::unistd::ForkResult::Parent { child, .. }) => {
@@ -149,23 +213,17 @@ fn start_service_with_filedescriptors(
             drop(exec_helper_conf_file);
             srvc.pid = Some(child);
             srvc.process_group = Some(nix::unistd::Pid::from_raw(-child.as_raw()));
+            // the instance has its own copy of the connection now
+            if let Some(fd) = srvc.connection.take() {
+                let _ = nix::unistd::close(fd);
+            }
+            if let Some((read_end, write_end)) = exec_status_pipe {
+                // only the child may hold the write end, otherwise we never see EOF
+                let _ = nix::unistd::close(write_end);
+                srvc.exec_status = Some(unsafe { std::fs::File::from_raw_fd(read_end) });
+            }
         }
         Ok(nix::unistd::ForkResult::Child) => {
-            let stdout = {
-                if let Some(stdio) = &srvc.stdout {
-                    stdio.write_fd()
-                } else {
-                    unreachable!();
-                }
-            };
-            let stderr = {
-                if let Some(stdio) = &srvc.stderr {
-                    stdio.write_fd()
-                } else {
-                    unreachable!();
-                }
-            };
-
             fork_child::after_fork_child(
                 &self_path_cstr,
                 self_args.as_slice(),
@@ -173,9 +231,16 @@ fn start_service_with_filedescriptors(
                 stdout,
                 stderr,
                 exec_helper_conf_fd,
+                exec_status_fd,
             );
         }
-        Err(e) => error!("Fork for service: {} failed with: {}", name, e),
+        Err(e) => {
+            error!("Fork for service: {} failed with: {}", name, e);
+            if let Some((read_end, write_end)) = exec_status_pipe {
+                let _ = nix::unistd::close(read_end);
+                let _ = nix::unistd::close(write_end);
+            }
+        }
     }
     Ok(())
 }
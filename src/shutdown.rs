@@ -6,6 +6,8 @@ use log::warn;
 use crate::runtime_info::*;
 use crate::units::*;
 
+use std::os::unix::io::AsRawFd;
+
 fn get_next_service_to_shutdown(unit_table: &UnitTable) -> Option<UnitId> {
     for (_, unit) in unit_table.iter() {
         let status = &unit.common.status;
@@ -53,10 +55,13 @@ fn shutdown_unit(shutdown_id: &UnitId, run_info: &RuntimeInfo) {
     match &unit.specific {
         Specific::Service(specific) => {
             let mut_state = &mut *specific.state.write().unwrap();
-            let kill_res =
-                mut_state
-                    .srvc
-                    .kill(&specific.conf, unit.id.clone(), &unit.id.name, run_info);
+            let kill_res = mut_state.srvc.kill(
+                &specific.conf,
+                unit.id.clone(),
+                &unit.id.name,
+                run_info,
+                false,
+            );
             match kill_res {
                 Ok(()) => {
                     trace!("Killed service unit: {}", unit.id.name);
@@ -64,6 +69,7 @@ fn shutdown_unit(shutdown_id: &UnitId, run_info: &RuntimeInfo) {
                 Err(e) => error!("{}", e),
             }
             if let Some(datagram) = &mut_state.srvc.notifications {
+                run_info.reactor.deregister(datagram.as_raw_fd());
                 match datagram.shutdown(std::net::Shutdown::Both) {
                     Ok(()) => {
                         trace!(
@@ -103,6 +109,7 @@ fn shutdown_unit(shutdown_id: &UnitId, run_info: &RuntimeInfo) {
                 &specific.conf,
                 unit.id.name.clone(),
                 &mut *run_info.fd_store.write().unwrap(),
+                &run_info.reactor,
             ) {
                 Err(e) => error!("Error while closing sockets: {}", e),
                 Ok(()) => {}
@@ -112,6 +119,27 @@ fn shutdown_unit(shutdown_id: &UnitId, run_info: &RuntimeInfo) {
         Specific::Target(_) => {
             // Nothing to do
         }
+        Specific::Timer(specific) => {
+            let mut_state = &mut *specific.state.write().unwrap();
+            mut_state.timer.deactivate();
+        }
+        Specific::Path(specific) => {
+            let mut_state = &mut *specific.state.write().unwrap();
+            mut_state.path.deactivate();
+        }
+        Specific::Slice(specific) => {
+            // the units in the slice have been shut down before it, so its cgroup is empty now
+            #[cfg(feature = "cgroups")]
+            {
+                let cgroup_path = &specific.conf.platform_specific.cgroup_path;
+                if cgroup_path.exists() {
+                    if let Err(e) = crate::platform::cgroups::remove_cgroup(cgroup_path) {
+                        error!("Error removing the cgroup of slice {}: {}", unit.id.name, e);
+                    }
+                }
+            }
+            let _ = specific;
+        }
     }
     {
         trace!("Set unit status: {}", unit.id.name);
@@ -1,143 +1,316 @@
-//! Wait for sockets to activate their respective services
+//! Wait for sockets to activate their respective services. Sockets with Accept=yes start a new instance of their
+//! template service for each connection instead.
 use log::error;
 use log::trace;
+use log::warn;
 
+use crate::reactor::{ReactorEvent, StreamKind};
 use crate::runtime_info::*;
+use crate::sockets::*;
 use crate::units::*;
 
+use std::convert::TryInto;
+use std::os::unix::io::RawFd;
+
 pub fn start_socketactivation_thread(run_info: ArcMutRuntimeInfo) {
+    let events = run_info
+        .read()
+        .unwrap()
+        .reactor
+        .subscribe(StreamKind::Socket);
     std::thread::spawn(move || loop {
-        let wait_result = wait_for_socket(run_info.clone());
-        match wait_result {
-            Ok(ids) => {
-                let run_info = run_info.read().unwrap();
-                let unit_table = &run_info.unit_table;
-                for socket_id in ids {
+        remove_stopped_instances(&run_info);
+        let first = match events.recv() {
+            Ok(event) => event,
+            Err(_) => {
+                error!("Error in socket activation loop: The reactor stopped sending events");
+                break;
+            }
+        };
+        // a wakeup only means that instances might have stopped, which is handled at the top of the loop
+        let activated: Vec<(RawFd, UnitId)> = std::iter::once(first)
+            .chain(events.try_iter())
+            .filter_map(|event| match event {
+                ReactorEvent::Readable(fd, id) => Some((fd, id)),
+                ReactorEvent::Wakeup => None,
+            })
+            .collect();
+
+        let mut new_instances = Vec::new();
+        let run_info_locked = run_info.read().unwrap();
+        let unit_table = &run_info_locked.unit_table;
+        for (fd, socket_id) in activated {
+            let sock_unit = match unit_table.get(&socket_id) {
+                Some(unit) => unit,
+                None => continue,
+            };
+            if let Specific::Socket(sock) = &sock_unit.specific {
+                if sock.conf.accept {
+                    if let Some((instance_name, conn_fd)) =
+                        accept_for_instance(sock_unit, sock, fd, unit_table)
                     {
-                        // search the service this socket belongs to.
-                        // Note that this differs from systemd behaviour where one socket may belong to multiple services
-                        let mut srvc_unit = None;
-                        for unit in unit_table.values() {
-                            if let crate::units::Specific::Service(specific) = &unit.specific {
-                                if specific.has_socket(&socket_id.name) {
-                                    srvc_unit = Some(unit);
-                                    trace!("Start service {} by socket activation", unit.id.name);
-                                    break;
-                                }
-                            }
+                        new_instances.push((socket_id, instance_name, conn_fd));
+                    }
+                    run_info_locked.reactor.rearm(fd);
+                    continue;
+                }
+                if sock.state.read().unwrap().sock.activated {
+                    // another fd of this socket activated the service already. All fds of the socket
+                    // are rearmed when the service waits for the socket again
+                    continue;
+                }
+            }
+            {
+                // search the service this socket belongs to.
+                // Note that this differs from systemd behaviour where one socket may belong to multiple services
+                let mut srvc_unit = None;
+                for unit in unit_table.values() {
+                    if let crate::units::Specific::Service(specific) = &unit.specific {
+                        if specific.has_socket(&socket_id.name) {
+                            srvc_unit = Some(unit);
+                            trace!("Start service {} by socket activation", unit.id.name);
+                            break;
                         }
+                    }
+                }
 
-                        // mark socket as activated, removing it from the set of
-                        // fds rustysd is actively listening on
-                        let sock_unit = unit_table.get(&socket_id).unwrap();
-                        if let Specific::Socket(specific) = &sock_unit.specific {
-                            let mut_state = &mut *specific.state.write().unwrap();
-                            mut_state.sock.activated = true;
-                        }
-                        if srvc_unit.is_none() {
-                            error!(
-                                "Socket unit {:?} activated, but the service could not be found",
-                                socket_id
-                            );
-                        }
-                        if let Some(srvc_unit) = srvc_unit {
-                            let srvc_status = {
-                                let status_locked = &*srvc_unit.common.status.read().unwrap();
-                                status_locked.clone()
-                            };
+                // mark socket as activated. Its fds are not rearmed until the service waits
+                // for the socket again
+                if let Specific::Socket(specific) = &sock_unit.specific {
+                    let mut_state = &mut *specific.state.write().unwrap();
+                    mut_state.sock.activated = true;
+                }
+                if srvc_unit.is_none() {
+                    error!(
+                        "Socket unit {:?} activated, but the service could not be found",
+                        socket_id
+                    );
+                }
+                if let Some(srvc_unit) = srvc_unit {
+                    let srvc_status = {
+                        let status_locked = &*srvc_unit.common.status.read().unwrap();
+                        status_locked.clone()
+                    };
 
-                            if srvc_status != UnitStatus::Started(StatusStarted::WaitingForSocket) {
-                                // This should not happen too often because the sockets of a service
-                                // should only be listened on if the service is currently waiting on socket activation
+                    if srvc_status != UnitStatus::Started(StatusStarted::WaitingForSocket) {
+                        // This should not happen too often because the sockets of a service
+                        // should only be listened on if the service is currently waiting on socket activation
+                        trace!(
+                            "Ignore socket activation. Service has status: {:?}",
+                            srvc_status
+                        );
+                    } else {
+                        // the service unit gets activated
+                        match crate::units::activate_unit(
+                            srvc_unit.id.clone(),
+                            &*run_info_locked,
+                            ActivationSource::SocketActivation,
+                        ) {
+                            Ok(_) => {
                                 trace!(
-                                    "Ignore socket activation. Service has status: {:?}",
-                                    srvc_status
+                                    "New status after socket activation: {:?}",
+                                    *unit_table
+                                        .get(&srvc_unit.id)
+                                        .unwrap()
+                                        .common
+                                        .status
+                                        .read()
+                                        .unwrap()
+                                );
+                            }
+                            Err(e) => {
+                                format!(
+                                    "Error while starting service from socket activation: {}",
+                                    e
                                 );
-                            } else {
-                                // the service unit gets activated
-                                match crate::units::activate_unit(
-                                    srvc_unit.id.clone(),
-                                    &*run_info,
-                                    ActivationSource::SocketActivation,
-                                ) {
-                                    Ok(_) => {
-                                        trace!(
-                                            "New status after socket activation: {:?}",
-                                            *unit_table
-                                                .get(&srvc_unit.id)
-                                                .unwrap()
-                                                .common
-                                                .status
-                                                .read()
-                                                .unwrap()
-                                        );
-                                    }
-                                    Err(e) => {
-                                        format!(
-                                                "Error while starting service from socket activation: {}",
-                                                e
-                                            );
-                                    }
-                                }
                             }
                         }
                     }
                 }
             }
-            Err(e) => {
-                error!("Error in socket activation loop: {}", e);
-                break;
-            }
+        }
+        drop(run_info_locked);
+        for (socket_id, instance_name, conn_fd) in new_instances {
+            start_instance(&socket_id, &instance_name, conn_fd, &run_info);
         }
     });
 }
 
-pub fn wait_for_socket(run_info: ArcMutRuntimeInfo) -> Result<Vec<UnitId>, String> {
-    let eventfd = { run_info.read().unwrap().socket_activation_eventfd };
-    let (mut fdset, fd_to_sock_id) = {
-        let run_info_locked = &*run_info.read().unwrap();
+/// Instances are done when they stopped. Oneshot instances stay started after their process exited.
+fn instance_is_done(instance: &Unit) -> bool {
+    instance.common.status.read().unwrap().is_stopped() || instance.is_finished_oneshot()
+}
 
-        let fd_to_sock_id = run_info_locked.fd_store.read().unwrap().global_fds_to_ids();
-        let mut fdset = nix::sys::select::FdSet::new();
-        {
-            let unit_table_locked = &run_info_locked.unit_table;
-            for (fd, id) in &fd_to_sock_id {
-                let unit = unit_table_locked.get(id).unwrap();
-                if let Specific::Socket(specific) = &unit.specific {
-                    let mut_state = &*specific.state.read().unwrap();
-                    if !mut_state.sock.activated {
-                        fdset.insert(*fd);
-                    }
-                }
+/// Accept a connection on a socket with Accept=yes. Returns the name of the instance that should serve it if the
+/// connection limits allow another connection.
+fn accept_for_instance(
+    sock_unit: &Unit,
+    sock: &SocketSpecific,
+    listen_fd: RawFd,
+    unit_table: &UnitTable,
+) -> Option<(String, RawFd)> {
+    let conn = match accept_connection(listen_fd) {
+        Ok(conn) => conn,
+        Err(e) => {
+            error!("Socket {}: {}", sock_unit.id.name, e);
+            return None;
+        }
+    };
+    let state = &mut *sock.state.write().unwrap();
+    // instances that are done only free their slot here if they were not removed yet
+    let live: Vec<&AcceptedConnection> = state
+        .sock
+        .connections
+        .iter()
+        .filter(|accepted| match unit_table.get(&accepted.instance) {
+            Some(instance) => !instance_is_done(instance),
+            // accepted in this round, the instance is started afterwards
+            None => true,
+        })
+        .collect();
+    if live.len() >= sock.conf.max_connections {
+        warn!(
+            "Socket {} refused a connection from {}. It already has {} connections (MaxConnections=)",
+            sock_unit.id.name,
+            conn.peer,
+            live.len()
+        );
+        close_raw_fd(conn.fd);
+        return None;
+    }
+    let from_source = live
+        .iter()
+        .filter(|accepted| accepted.source == conn.source)
+        .count();
+    if from_source >= sock.conf.max_connections_per_source {
+        warn!(
+            "Socket {} refused a connection from {}. There are already {} connections from {} (MaxConnectionsPerSource=)",
+            sock_unit.id.name, conn.peer, from_source, conn.source
+        );
+        close_raw_fd(conn.fd);
+        return None;
+    }
+
+    let instance_name = format!(
+        "{}@{}-{}.service",
+        sock_unit.id.prefix(),
+        state.sock.accepted,
+        conn.peer
+    );
+    state.sock.accepted += 1;
+    let instance: UnitId = match instance_name.as_str().try_into() {
+        Ok(id) => id,
+        Err(e) => {
+            error!(
+                "Socket {} can not start an instance named {}: {}",
+                sock_unit.id.name, instance_name, e
+            );
+            close_raw_fd(conn.fd);
+            return None;
+        }
+    };
+    trace!(
+        "Socket {} accepted a connection from {}. Start instance {}",
+        sock_unit.id.name,
+        conn.peer,
+        instance_name
+    );
+    state.sock.connections.push(AcceptedConnection {
+        instance,
+        source: conn.source,
+    });
+    Some((instance_name, conn.fd))
+}
+
+/// Load the instance that serves an accepted connection from the template of the socket and start it
+fn start_instance(
+    socket_id: &UnitId,
+    instance_name: &str,
+    fd: RawFd,
+    run_info: &ArcMutRuntimeInfo,
+) {
+    let id = {
+        let run_info = &mut *run_info.write().unwrap();
+        let mut unit = match load_new_unit(&run_info.config.unit_dirs, instance_name) {
+            Ok(unit) => unit,
+            Err(e) => {
+                error!(
+                    "Could not load instance {} for socket {}: {}",
+                    instance_name, socket_id.name, e
+                );
+                close_raw_fd(fd);
+                return;
             }
-            fdset.insert(eventfd.read_end());
+        };
+        if let Specific::Service(srvc) = &mut unit.specific {
+            let state = srvc.state.get_mut().unwrap();
+            state.srvc.connection = Some(fd);
+            state.srvc.serves_connection = true;
+        }
+        unit.common.dependencies.after.push(socket_id.clone());
+        let id = unit.id.clone();
+        let mut new_units = std::collections::HashMap::new();
+        new_units.insert(id.clone(), unit);
+        if let Err(e) = insert_new_units(new_units, run_info) {
+            error!(
+                "Could not add instance {} for socket {}: {}",
+                instance_name, socket_id.name, e
+            );
+            close_raw_fd(fd);
+            return;
         }
-        (fdset, fd_to_sock_id)
+        id
     };
+    let run_info = &*run_info.read().unwrap();
+    if let Err(e) = activate_unit(id, run_info, ActivationSource::SocketActivation) {
+        error!("Error while starting instance {}: {}", instance_name, e);
+    }
+}
 
-    let result = nix::sys::select::select(None, Some(&mut fdset), None, None, None);
-    match result {
-        Ok(_) => {
-            let mut activated_ids = Vec::new();
-            if fdset.contains(eventfd.read_end()) {
-                trace!("Interrupted socketactivation select because the eventfd fired");
-                crate::platform::reset_event_fd(eventfd);
-                trace!("Reset eventfd value");
-            } else {
-                for (fd, id) in &fd_to_sock_id {
-                    if fdset.contains(*fd) {
-                        activated_ids.push(id.clone());
-                    }
+/// Remove the instances of sockets with Accept=yes that are done. Their connections are closed already, unless the
+/// instance failed before its process was started.
+fn remove_stopped_instances(run_info: &ArcMutRuntimeInfo) {
+    let run_info = &mut *run_info.write().unwrap();
+    let mut stopped = Vec::new();
+    for unit in run_info.unit_table.values() {
+        if let Specific::Socket(sock) = &unit.specific {
+            if !sock.conf.accept {
+                continue;
+            }
+            let state = &mut *sock.state.write().unwrap();
+            state.sock.connections.retain(|accepted| {
+                let is_done = match run_info.unit_table.get(&accepted.instance) {
+                    Some(instance) => instance_is_done(instance),
+                    // loading the instance failed
+                    None => true,
+                };
+                if is_done {
+                    stopped.push(accepted.instance.clone());
                 }
+                !is_done
+            });
+        }
+    }
+
+    for id in stopped {
+        let unit = match run_info.unit_table.get(&id) {
+            Some(unit) => unit,
+            None => continue,
+        };
+        if unit.is_finished_oneshot() {
+            // only stopped units can be removed
+            if let Err(e) = deactivate_unit(&id, run_info) {
+                error!("Could not stop finished instance {}: {}", id.name, e);
             }
-            Ok(activated_ids)
         }
-        Err(e) => {
-            if let nix::Error::EINTR = e {
-                Ok(Vec::new())
-            } else {
-                Err(format!("Error while selecting: {}", e))
+        if let Specific::Service(srvc) = &unit.specific {
+            if let Some(fd) = srvc.state.write().unwrap().srvc.connection.take() {
+                close_raw_fd(fd);
             }
         }
+        trace!("Remove stopped instance {}", id.name);
+        if let Err(e) = remove_unit_with_dependencies(id.clone(), run_info) {
+            error!("Could not remove instance {}: {}", id.name, e);
+        }
     }
 }
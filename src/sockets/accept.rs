@@ -0,0 +1,72 @@
+//! Accepting connections on sockets with Accept=yes. Each connection is served by its own instance of the template
+//! service of the socket.
+
+use std::os::unix::io::RawFd;
+
+use crate::units::UnitId;
+
+/// A connection that was accepted on a socket with Accept=yes and the instance that serves it
+#[derive(Clone, Debug)]
+pub struct AcceptedConnection {
+    pub instance: UnitId,
+    /// Connections from the same source count against MaxConnectionsPerSource=
+    pub source: String,
+}
+
+/// A connection that was just accepted
+pub struct NewConnection {
+    pub fd: RawFd,
+    /// Used in the name of the instance
+    pub peer: String,
+    /// The address of the peer for network sockets, the uid of the peer for unix sockets
+    pub source: String,
+}
+
+pub fn accept_connection(listen_fd: RawFd) -> Result<NewConnection, String> {
+    let fd = nix::sys::socket::accept(listen_fd)
+        .map_err(|e| format!("Could not accept connection: {}", e))?;
+    // only the instance gets this fd, it is passed to it explicitly
+    if let Err(e) = nix::fcntl::fcntl(
+        fd,
+        nix::fcntl::FcntlArg::F_SETFD(nix::fcntl::FdFlag::FD_CLOEXEC),
+    ) {
+        super::close_raw_fd(fd);
+        return Err(format!(
+            "Could not set CLOEXEC on accepted connection: {}",
+            e
+        ));
+    }
+    let (peer, source) = describe_peer(fd);
+    Ok(NewConnection { fd, peer, source })
+}
+
+fn describe_peer(fd: RawFd) -> (String, String) {
+    use nix::sys::socket::SockaddrStorage;
+    if let Ok(addr) = nix::sys::socket::getpeername::<SockaddrStorage>(fd) {
+        if let Some(addr) = addr.as_sockaddr_in() {
+            let addr = std::net::SocketAddrV4::from(*addr);
+            return (addr.to_string(), addr.ip().to_string());
+        }
+        if let Some(addr) = addr.as_sockaddr_in6() {
+            let addr = std::net::SocketAddrV6::from(*addr);
+            return (addr.to_string(), addr.ip().to_string());
+        }
+    }
+    describe_unix_peer(fd)
+}
+
+#[cfg(target_os = "linux")]
+fn describe_unix_peer(fd: RawFd) -> (String, String) {
+    match nix::sys::socket::getsockopt(fd, nix::sys::socket::sockopt::PeerCredentials) {
+        Ok(creds) => (
+            format!("{}-{}", creds.pid(), creds.uid()),
+            format!("uid {}", creds.uid()),
+        ),
+        Err(_) => ("unknown".to_owned(), "unknown".to_owned()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn describe_unix_peer(_fd: RawFd) -> (String, String) {
+    ("unix".to_owned(), "unix".to_owned())
+}
@@ -1,8 +1,10 @@
 //! Socket related code. Opening of all different kinds, match sockets to services etc
 
+mod accept;
 mod fifo;
 mod network_sockets;
 mod unix_sockets;
+pub use accept::*;
 pub use fifo::*;
 use log::trace;
 pub use network_sockets::*;
@@ -11,6 +13,7 @@ pub use unix_sockets::*;
 use std::{os::unix::io::AsRawFd, os::unix::io::RawFd};
 
 use crate::fd_store::FDStore;
+use crate::reactor::{Reactor, StreamKind};
 use crate::units::*;
 
 pub fn close_raw_fd(fd: RawFd) {
@@ -81,6 +84,7 @@ impl Socket {
         name: String,
         id: UnitId,
         fd_store: &mut FDStore,
+        reactor: &Reactor,
     ) -> std::io::Result<()> {
         let mut fds = Vec::new();
         for idx in 0..conf.sockets.len() {
@@ -103,18 +107,32 @@ impl Socket {
                 .map(|(_, _, fd)| fd.as_raw_fd())
                 .collect::<Vec<_>>(),
         );
+        for (id, _, fd) in &fds {
+            reactor.register(fd.as_raw_fd(), StreamKind::Socket, id.clone());
+        }
         fd_store.insert_global(name, fds);
         Ok(())
     }
 
+    /// Rearm the fds of the socket after the service that got activated by it waits for the socket again
+    pub fn listen_again(&self, name: &str, fd_store: &FDStore, reactor: &Reactor) {
+        if let Some(fds) = fd_store.get_global(name) {
+            for (_, _, fd) in fds {
+                reactor.rearm(fd.as_raw_fd());
+            }
+        }
+    }
+
     pub fn close_all(
         &mut self,
         conf: &SocketConfig,
         name: String,
         fd_store: &mut FDStore,
+        reactor: &Reactor,
     ) -> Result<(), String> {
         if let Some(fds) = fd_store.remove_global(&name) {
             for idx in 0..fds.len() {
+                reactor.deregister(fds[idx].2.as_raw_fd());
                 conf.sockets[idx]
                     .specialized
                     .close(fds[idx].2.as_raw_fd())?;
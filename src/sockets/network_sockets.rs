@@ -42,4 +42,8 @@ impl UdpSocketConfig {
 #[derive(Clone, Debug)]
 pub struct Socket {
     pub activated: bool,
+    /// How many connections were accepted on a socket with Accept=yes. Numbers the instances.
+    pub accepted: u64,
+    /// The connections whose instances have not been removed yet
+    pub connections: Vec<super::AcceptedConnection>,
 }
@@ -0,0 +1,132 @@
+use crate::platform::cgroups;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// A directory that looks like a cgroup v2 to the cgroup code
+fn fake_cgroup(name: &str, populated: bool) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "rustysd_cgroup_test_{}_{}",
+        name,
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("cgroup.freeze"), "0\n").unwrap();
+    std::fs::write(dir.join("cgroup.procs"), "").unwrap();
+    set_populated(&dir, populated);
+    dir
+}
+
+fn set_populated(dir: &Path, populated: bool) {
+    std::fs::write(
+        dir.join("cgroup.events"),
+        format!("populated {}\nfrozen 0\n", populated as u8),
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_cgroup_events() {
+    let events = "populated 1\nfrozen 0\n";
+    assert_eq!(cgroups::parse_flat_keyed(events, "populated"), Some(1));
+    assert_eq!(cgroups::parse_flat_keyed(events, "frozen"), Some(0));
+    assert_eq!(cgroups::parse_flat_keyed("", "populated"), None);
+
+    let dir = fake_cgroup("events", true);
+    assert!(cgroups::is_populated(&dir).unwrap());
+    set_populated(&dir, false);
+    assert!(!cgroups::is_populated(&dir).unwrap());
+
+    set_populated(&dir, true);
+    assert!(!cgroups::wait_empty(&dir, Duration::from_millis(100)).unwrap());
+    // the change of cgroup.events wakes up the waiter
+    let writer_dir = dir.clone();
+    let writer = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(200));
+        set_populated(&writer_dir, false);
+    });
+    let start = Instant::now();
+    assert!(cgroups::wait_empty(&dir, Duration::from_secs(10)).unwrap());
+    assert!(start.elapsed() < Duration::from_secs(5));
+    writer.join().unwrap();
+
+    let freeze_state = || std::fs::read_to_string(dir.join("cgroup.freeze")).unwrap();
+    cgroups::freeze(&dir).unwrap();
+    assert_eq!(freeze_state().trim(), "1");
+    cgroups::thaw(&dir).unwrap();
+    assert_eq!(freeze_state().trim(), "0");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+/// A service whose main process rustysd does not know ended once its cgroup is empty
+#[cfg(feature = "cgroups")]
+#[test]
+fn test_cgroup_empty_exit() {
+    use crate::reactor::ReactorEvent;
+    use crate::units::{Specific, StatusStarted, StatusStopped, Unit, UnitStatus};
+    use std::convert::TryInto;
+
+    let dir = fake_cgroup("exit", true);
+    let parsed_file =
+        crate::units::parse_file("[Service]\nType = forking\nExecStart = /bin/daemon").unwrap();
+    let mut unit: Unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/daemon.service"),
+    )
+    .unwrap()
+    .try_into()
+    .unwrap();
+    let events_fd = match &mut unit.specific {
+        Specific::Service(srvc) => {
+            srvc.conf.platform_specific.cgroup_path = dir.clone();
+            let events = cgroups::CgroupEvents::watch(&dir).unwrap();
+            let fd = events.as_raw_fd();
+            srvc.state.write().unwrap().srvc.cgroup_events = Some(events);
+            fd
+        }
+        _ => panic!("Not a service"),
+    };
+    *unit.common.status.write().unwrap() = UnitStatus::Started(StatusStarted::Running);
+    let id = unit.id.clone();
+
+    let mut run_info = super::test_run_info();
+    run_info.unit_table.insert(id.clone(), unit);
+    let run_info = std::sync::Arc::new(std::sync::RwLock::new(run_info));
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let handler_run_info = run_info.clone();
+    std::thread::spawn(move || {
+        crate::notification_handler::handle_all_cgroup_events(handler_run_info, receiver);
+    });
+
+    let status = || {
+        run_info.read().unwrap().unit_table[&id]
+            .common
+            .status
+            .read()
+            .unwrap()
+            .clone()
+    };
+    // still populated, nothing happens
+    sender
+        .send(ReactorEvent::Readable(events_fd, id.clone()))
+        .unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    assert_eq!(status(), UnitStatus::Started(StatusStarted::Running));
+
+    set_populated(&dir, false);
+    sender
+        .send(ReactorEvent::Readable(events_fd, id.clone()))
+        .unwrap();
+    let start = Instant::now();
+    while !matches!(status(), UnitStatus::Stopped(_, _)) {
+        assert!(start.elapsed() < Duration::from_secs(5));
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    // there is no exit code, so the service ended cleanly
+    assert_eq!(
+        status(),
+        UnitStatus::Stopped(StatusStopped::StoppedFinal, vec![])
+    );
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,85 @@
+use crate::log_store::*;
+
+#[test]
+fn test_log_store() {
+    let dir = std::env::temp_dir().join(format!("rustysd_log_store_test_{}", std::process::id()));
+    let store = LogStore::open(&dir).unwrap();
+
+    store.append(LogEntry::from_service_line(
+        "a.service",
+        None,
+        Some(10),
+        LogStream::Stdout,
+        "hello",
+    ));
+    store.append(LogEntry::from_service_line(
+        "b.service",
+        None,
+        Some(11),
+        LogStream::Stderr,
+        "<3>something broke",
+    ));
+    store.append(LogEntry::new(
+        None,
+        None,
+        LogStream::Manager,
+        PRIORITY_DEBUG,
+        "rustysd message".into(),
+    ));
+
+    let all = store.query(&LogFilter::default(), None);
+    assert_eq!(all.len(), 3);
+    assert_eq!(all[0].message, "hello");
+    assert_eq!(all[0].priority, PRIORITY_INFO);
+    assert_eq!(all[1].message, "something broke");
+    assert_eq!(all[1].priority, PRIORITY_ERR);
+    assert_eq!(all[1].pid, Some(11));
+
+    let by_unit = LogFilter {
+        unit: Some("b.service".into()),
+        ..Default::default()
+    };
+    assert_eq!(store.query(&by_unit, None).len(), 1);
+
+    let important = LogFilter {
+        priority: Some(PRIORITY_WARNING),
+        ..Default::default()
+    };
+    assert_eq!(store.query(&important, None).len(), 1);
+
+    let newest = store.query(&LogFilter::default(), Some(1));
+    assert_eq!(newest.len(), 1);
+    assert_eq!(newest[0].message, "rustysd message");
+
+    let too_late = LogFilter {
+        since: Some(all[2].timestamp + 1),
+        ..Default::default()
+    };
+    assert!(store.query(&too_late, None).is_empty());
+
+    // followers get the stored entries and then only new ones that match their filter
+    let (stored, receiver) = store.follow(&by_unit, None);
+    assert_eq!(stored.len(), 1);
+    store.append(LogEntry::from_service_line(
+        "a.service",
+        None,
+        Some(10),
+        LogStream::Stdout,
+        "not for b",
+    ));
+    store.append(LogEntry::from_service_line(
+        "b.service",
+        None,
+        Some(11),
+        LogStream::Stdout,
+        "for b",
+    ));
+    assert_eq!(receiver.try_recv().unwrap().message, "for b");
+    assert!(receiver.try_recv().is_err());
+
+    // entries survive reopening the store
+    drop(store);
+    let store = LogStore::open(&dir).unwrap();
+    assert_eq!(store.query(&LogFilter::default(), None).len(), 5);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,5 +1,34 @@
 #![cfg(test)]
 
+mod cgroups;
+mod log_store;
+mod notifications;
 mod ordering;
 mod parsing;
+mod resource_usage;
 mod state_transition;
+mod stopping;
+
+use crate::runtime_info::*;
+
+/// A RuntimeInfo without units for tests that work on single units
+fn test_run_info() -> RuntimeInfo {
+    RuntimeInfo {
+        config: crate::config::Config {
+            notification_sockets_dir: "./notifications".into(),
+            target_unit: "".into(),
+            unit_dirs: vec![],
+            self_path: std::path::PathBuf::from("./target/debug/rustysd"),
+            state_dir: "./state".into(),
+        },
+        fd_store: std::sync::RwLock::new(crate::fd_store::FDStore::default()),
+        pid_table: std::sync::Mutex::new(PidTable::default()),
+        unit_table: UnitTable::default(),
+        reactor: std::sync::Arc::new(crate::reactor::Reactor::new().unwrap()),
+        jobs: std::sync::Arc::new(crate::units::JobQueue::default()),
+        hooks: std::sync::Arc::new(crate::hook_activation::HookQueue::default()),
+        log_store: None,
+        timer_activation_eventfd: crate::platform::make_event_fd().unwrap(),
+        path_activation_eventfd: crate::platform::make_event_fd().unwrap(),
+    }
+}
@@ -0,0 +1,204 @@
+use super::test_run_info;
+use crate::notification_handler::handle_notification_message;
+use crate::runtime_info::*;
+use crate::units::{Specific, Unit};
+use std::convert::TryInto;
+
+fn notify_service() -> Unit {
+    let parsed_file =
+        crate::units::parse_file("[Service]\nType = notify\nExecStart = /bin/true").unwrap();
+    crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/notify.service"),
+    )
+    .unwrap()
+    .try_into()
+    .unwrap()
+}
+
+#[test]
+fn test_notification_messages() {
+    let run_info = test_run_info();
+    let unit = notify_service();
+    let srvc = match &unit.specific {
+        Specific::Service(srvc) => srvc,
+        _ => panic!("Not a service"),
+    };
+    let state = &mut *srvc.state.write().unwrap();
+    let notify = |msg: &str, state: &mut crate::units::ServiceState| {
+        handle_notification_message(msg, &mut state.srvc, &srvc.conf, &unit.id, &run_info)
+    };
+
+    // values may contain '='
+    notify("STATUS=key=value", state);
+    assert_eq!(state.srvc.status_msgs.last().unwrap(), "key=value");
+
+    // lines without a value are ignored instead of taking down the notification thread
+    notify("STOPPING", state);
+    notify("WATCHDOG", state);
+    notify("MAINPID", state);
+    assert!(!state.srvc.signaled_stopping);
+    assert_eq!(state.srvc.pid, None);
+
+    notify("STOPPING=1", state);
+    assert!(state.srvc.signaled_stopping);
+    notify("ERRNO=nope", state);
+    assert_eq!(state.srvc.errno, None);
+    notify("ERRNO=2", state);
+    assert_eq!(state.srvc.errno, Some(2));
+}
+
+#[test]
+fn test_extend_timeout() {
+    use std::time::{Duration, Instant};
+
+    let run_info = test_run_info();
+    let unit = notify_service();
+    let srvc = match &unit.specific {
+        Specific::Service(srvc) => srvc,
+        _ => panic!("Not a service"),
+    };
+    let state = &mut *srvc.state.write().unwrap();
+
+    let timeout = Duration::from_secs(1);
+    let start_time = Instant::now() - Duration::from_secs(2);
+    assert!(state.srvc.remaining_timeout(start_time, timeout).is_none());
+
+    handle_notification_message(
+        "EXTEND_TIMEOUT_USEC=5000000",
+        &mut state.srvc,
+        &srvc.conf,
+        &unit.id,
+        &run_info,
+    );
+    let remaining = state.srvc.remaining_timeout(start_time, timeout).unwrap();
+    assert!(remaining > Duration::from_secs(4) && remaining <= Duration::from_secs(5));
+
+    // an extension never shortens the timeout
+    let start_time = Instant::now();
+    let timeout = Duration::from_secs(10);
+    handle_notification_message(
+        "EXTEND_TIMEOUT_USEC=1",
+        &mut state.srvc,
+        &srvc.conf,
+        &unit.id,
+        &run_info,
+    );
+    assert!(state.srvc.remaining_timeout(start_time, timeout).unwrap() > Duration::from_secs(9));
+
+    // while the service is locked the waiting code reads the notifications itself
+    state.srvc.extend_timeout = None;
+    let (socket, service_end) = std::os::unix::net::UnixDatagram::pair().unwrap();
+    state.srvc.notifications = Some(socket);
+    service_end
+        .send(b"EXTEND_TIMEOUT_USEC=5000000\nSTATUS=still busy\n")
+        .unwrap();
+    crate::notification_handler::read_pending_notifications(
+        &mut state.srvc,
+        &srvc.conf,
+        &unit.id,
+        &run_info,
+    );
+    assert!(state.srvc.extend_timeout.is_some());
+    assert_eq!(state.srvc.status_msgs.last().unwrap(), "still busy");
+}
+
+#[test]
+fn test_main_pid_notification() {
+    let run_info = test_run_info();
+    let unit = notify_service();
+    let srvc = match &unit.specific {
+        Specific::Service(srvc) => srvc,
+        _ => panic!("Not a service"),
+    };
+    let state = &mut *srvc.state.write().unwrap();
+    let mut notify = |msg: &str| {
+        handle_notification_message(msg, &mut state.srvc, &srvc.conf, &unit.id, &run_info)
+    };
+
+    notify("MAINPID=0");
+    notify("MAINPID=notapid");
+    // pid_max is at most 2^22
+    notify("MAINPID=4194305");
+
+    let mut child = std::process::Command::new("/bin/sleep")
+        .arg("10")
+        .spawn()
+        .unwrap();
+    let child_pid = nix::unistd::Pid::from_raw(child.id() as i32);
+    notify(&format!("MAINPID={}", child_pid));
+
+    assert_eq!(state.srvc.pid, Some(child_pid));
+    assert_eq!(
+        run_info.pid_table.lock().unwrap().get(&child_pid),
+        Some(&PidEntry::Service(
+            unit.id.clone(),
+            crate::units::ServiceType::Notify
+        ))
+    );
+    child.kill().unwrap();
+    // the SIGCHLD handler of another test might have reaped it already
+    let _ = child.wait();
+}
+
+#[test]
+fn test_notify_access() {
+    use crate::notification_handler::sender_allowed;
+    use crate::units::{NotifyKind, UnitId, UnitIdKind};
+    use nix::unistd::Pid;
+
+    let run_info = test_run_info();
+    let mut unit = notify_service();
+    let other_id = UnitId {
+        kind: UnitIdKind::Service,
+        name: "other.service".into(),
+    };
+    let main_pid = Pid::from_raw(100);
+    let own_helper = Pid::from_raw(101);
+    let other_helper = Pid::from_raw(102);
+    let stranger = Pid::from_raw(103);
+    {
+        let pid_table = &mut *run_info.pid_table.lock().unwrap();
+        pid_table.insert(
+            own_helper,
+            PidEntry::Helper(unit.id.clone(), "ExecStartPre".into()),
+        );
+        pid_table.insert(
+            other_helper,
+            PidEntry::Helper(other_id, "ExecStartPre".into()),
+        );
+    }
+    let srvc = match &mut unit.specific {
+        Specific::Service(srvc) => srvc,
+        _ => panic!("Not a service"),
+    };
+    srvc.state.write().unwrap().srvc.pid = Some(main_pid);
+
+    // without credentials of the sender only NotifyAccess=none can be enforced
+    let senders = [
+        Some(main_pid),
+        Some(own_helper),
+        Some(other_helper),
+        Some(stranger),
+        None,
+    ];
+    let expected = [
+        (NotifyKind::Main, [true, false, false, false, true]),
+        (NotifyKind::Exec, [true, true, false, false, true]),
+        (NotifyKind::All, [true, true, true, true, true]),
+        (NotifyKind::None, [false, false, false, false, false]),
+    ];
+    for (kind, allowed) in &expected {
+        srvc.conf.notifyaccess = *kind;
+        let state = srvc.state.read().unwrap();
+        for (sender, allowed) in senders.iter().zip(allowed.iter()) {
+            assert_eq!(
+                sender_allowed(&state.srvc, &srvc.conf, &unit.id, *sender, &run_info),
+                *allowed,
+                "NotifyAccess={:?} sender {:?}",
+                kind,
+                sender
+            );
+        }
+    }
+}
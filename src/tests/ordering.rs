@@ -298,3 +298,249 @@ fn test_circle() {
         panic!("No circle found but there is one");
     }
 }
+
+#[test]
+fn test_transaction_jobs() {
+    use crate::units::{JobKind, Transaction, Unit};
+    use std::convert::TryInto;
+
+    let parse = |content: &str, name: &str| -> Unit {
+        let parsed_file = crate::units::parse_file(content).unwrap();
+        crate::units::parse_target(
+            parsed_file,
+            &std::path::PathBuf::from(format!("/path/to/{}", name)),
+        )
+        .unwrap()
+        .try_into()
+        .unwrap()
+    };
+    let target1_unit = parse(
+        "
+    [Unit]
+    Description = Target
+    Requires = 2.target
+    After = 2.target
+    ",
+        "1.target",
+    );
+    let target2_unit = parse(
+        "
+    [Unit]
+    Description = Target
+    ",
+        "2.target",
+    );
+    let id1 = target1_unit.id.clone();
+    let id2 = target2_unit.id.clone();
+
+    let mut unit_table = std::collections::HashMap::new();
+    unit_table.insert(id1.clone(), target1_unit);
+    unit_table.insert(id2.clone(), target2_unit);
+    crate::units::fill_dependencies(&mut unit_table).unwrap();
+    unit_table
+        .values_mut()
+        .for_each(|unit| unit.dedup_dependencies());
+
+    // starting 1.target pulls in 2.target
+    let jobs = Transaction::new(id1.clone(), JobKind::Start, true, &unit_table)
+        .unwrap()
+        .jobs();
+    assert_eq!(jobs.len(), 2);
+    assert_eq!(jobs[&id1], JobKind::Start);
+    assert_eq!(jobs[&id2], JobKind::Start);
+
+    // without pulling in dependencies 2.target only has to be active already
+    let jobs = Transaction::new(id1.clone(), JobKind::Restart, false, &unit_table)
+        .unwrap()
+        .jobs();
+    assert_eq!(jobs[&id1], JobKind::Restart);
+    assert_eq!(jobs[&id2], JobKind::VerifyActive);
+
+    // stopping 2.target stops 1.target which requires it
+    let jobs = Transaction::new(id2.clone(), JobKind::Stop, true, &unit_table)
+        .unwrap()
+        .jobs();
+    assert_eq!(jobs[&id1], JobKind::Stop);
+    assert_eq!(jobs[&id2], JobKind::Stop);
+
+    assert_eq!(
+        JobKind::VerifyActive.merge(JobKind::Start),
+        Some(JobKind::Start)
+    );
+    assert_eq!(
+        JobKind::Reload.merge(JobKind::Restart),
+        Some(JobKind::Restart)
+    );
+    assert_eq!(JobKind::Start.merge(JobKind::Stop), None);
+
+    // the jobs of units that are ordered after each other can not be ordered
+    let target3_unit = parse(
+        "
+    [Unit]
+    Description = Target
+    Wants = 4.target
+    After = 4.target
+    ",
+        "3.target",
+    );
+    let target4_unit = parse(
+        "
+    [Unit]
+    Description = Target
+    After = 3.target
+    ",
+        "4.target",
+    );
+    let id3 = target3_unit.id.clone();
+
+    let mut cycle_table = std::collections::HashMap::new();
+    cycle_table.insert(id3.clone(), target3_unit);
+    cycle_table.insert(target4_unit.id.clone(), target4_unit);
+    crate::units::fill_dependencies(&mut cycle_table).unwrap();
+    cycle_table
+        .values_mut()
+        .for_each(|unit| unit.dedup_dependencies());
+    assert!(Transaction::new(id3, JobKind::Start, true, &cycle_table).is_err());
+}
+
+#[test]
+fn test_transaction_dependency_types() {
+    use crate::units::{JobKind, Transaction, Unit, UnitStatus};
+    use std::convert::TryInto;
+
+    let parse = |content: &str, name: &str| -> Unit {
+        let parsed_file = crate::units::parse_file(content).unwrap();
+        crate::units::parse_target(
+            parsed_file,
+            &std::path::PathBuf::from(format!("/path/to/{}", name)),
+        )
+        .unwrap()
+        .try_into()
+        .unwrap()
+    };
+    let target1_unit = parse(
+        "
+    [Unit]
+    Description = Target
+    Conflicts = 2.target
+    BindsTo = 3.target
+    Requisite = 4.target
+    ",
+        "1.target",
+    );
+    let target2_unit = parse(
+        "
+    [Unit]
+    Description = Target
+    ",
+        "2.target",
+    );
+    let target3_unit = parse(
+        "
+    [Unit]
+    Description = Target
+    ",
+        "3.target",
+    );
+    let target4_unit = parse(
+        "
+    [Unit]
+    Description = Target
+    PartOf = 3.target
+    ",
+        "4.target",
+    );
+    let target5_unit = parse(
+        "
+    [Unit]
+    Description = Target
+    Wants = 1.target
+    Wants = 2.target
+    ",
+        "5.target",
+    );
+    let target6_unit = parse(
+        "
+    [Unit]
+    Description = Target
+    Requisite = 4.target
+    After = 4.target
+    ",
+        "6.target",
+    );
+    let id1 = target1_unit.id.clone();
+    let id2 = target2_unit.id.clone();
+    let id3 = target3_unit.id.clone();
+    let id4 = target4_unit.id.clone();
+    let id5 = target5_unit.id.clone();
+    let id6 = target6_unit.id.clone();
+
+    let mut unit_table = std::collections::HashMap::new();
+    unit_table.insert(id1.clone(), target1_unit);
+    unit_table.insert(id2.clone(), target2_unit);
+    unit_table.insert(id3.clone(), target3_unit);
+    unit_table.insert(id4.clone(), target4_unit);
+    unit_table.insert(id5.clone(), target5_unit);
+    unit_table.insert(id6.clone(), target6_unit);
+    crate::units::fill_dependencies(&mut unit_table).unwrap();
+
+    let deps = &unit_table[&id2].common.dependencies;
+    assert_eq!(deps.conflicted_by, vec![id1.clone()]);
+    let deps = &unit_table[&id3].common.dependencies;
+    assert_eq!(deps.bound_by, vec![id1.clone()]);
+    assert_eq!(deps.consists_of, vec![id4.clone()]);
+    let deps = &unit_table[&id4].common.dependencies;
+    assert_eq!(deps.requisite_of.len(), 2);
+    assert!(deps.requisite_of.contains(&id1) && deps.requisite_of.contains(&id6));
+
+    *unit_table[&id2].common.status.write().unwrap() =
+        UnitStatus::Started(crate::units::StatusStarted::Running);
+    *unit_table[&id4].common.status.write().unwrap() =
+        UnitStatus::Started(crate::units::StatusStarted::Running);
+
+    // starting 1.target pulls in 3.target, stops 2.target and only checks 4.target
+    let jobs = Transaction::new(id1.clone(), JobKind::Start, true, &unit_table)
+        .unwrap()
+        .jobs();
+    assert_eq!(jobs.len(), 4);
+    assert_eq!(jobs[&id2], JobKind::Stop);
+    assert_eq!(jobs[&id3], JobKind::Start);
+    assert_eq!(jobs[&id4], JobKind::VerifyActive);
+
+    // being ordered after a requisite does not start it either
+    let jobs = Transaction::new(id6, JobKind::Start, true, &unit_table)
+        .unwrap()
+        .jobs();
+    assert_eq!(jobs.len(), 2);
+    assert_eq!(jobs[&id4], JobKind::VerifyActive);
+
+    // stopping 3.target stops the units bound to it and part of it
+    let jobs = Transaction::new(id3.clone(), JobKind::Stop, false, &unit_table)
+        .unwrap()
+        .jobs();
+    assert_eq!(jobs.len(), 3);
+    assert_eq!(jobs[&id1], JobKind::Stop);
+    assert_eq!(jobs[&id4], JobKind::Stop);
+
+    // restarting 3.target only restarts the units that are running
+    let jobs = Transaction::new(id3.clone(), JobKind::Restart, false, &unit_table)
+        .unwrap()
+        .jobs();
+    assert_eq!(jobs.len(), 2);
+    assert_eq!(jobs[&id4], JobKind::Restart);
+
+    // conflicts go both ways, but units that never ran are not stopped
+    let jobs = Transaction::new(id2.clone(), JobKind::Start, true, &unit_table)
+        .unwrap()
+        .jobs();
+    assert_eq!(jobs.len(), 1);
+    *unit_table[&id1].common.status.write().unwrap() =
+        UnitStatus::Started(crate::units::StatusStarted::Running);
+    let jobs = Transaction::new(id2.clone(), JobKind::Start, true, &unit_table)
+        .unwrap()
+        .jobs();
+    assert_eq!(jobs[&id1], JobKind::Stop);
+
+    // starting both sides of a conflict is contradictory
+    assert!(Transaction::new(id5, JobKind::Start, true, &unit_table).is_err());
+}
@@ -1,3 +1,31 @@
+/// Parses the content as if it was read from the service file at the path
+fn parse_service_str(content: &str, path: &str) -> Result<crate::units::Unit, String> {
+    use std::convert::TryInto;
+
+    let parsed_file = crate::units::parse_file(content).map_err(|e| format!("{:?}", e))?;
+    crate::units::parse_service(parsed_file, &std::path::PathBuf::from(path))
+        .map_err(|e| format!("{:?}", e))?
+        .try_into()
+}
+
+fn service_conf(unit: &crate::units::Unit) -> crate::units::ServiceConfig {
+    if let crate::units::Specific::Service(srvc) = &unit.specific {
+        srvc.conf.clone()
+    } else {
+        panic!("Unit is not a service");
+    }
+}
+
+/// Parses the content as if it was read from the socket file at the path
+fn parse_socket_str(content: &str, path: &str) -> Result<crate::units::Unit, String> {
+    use std::convert::TryInto;
+
+    let parsed_file = crate::units::parse_file(content).map_err(|e| format!("{:?}", e))?;
+    crate::units::parse_socket(parsed_file, &std::path::PathBuf::from(path))
+        .map_err(|e| format!("{:?}", e))?
+        .try_into()
+}
+
 #[test]
 fn test_service_parsing() {
     let descr = "This is a description";
@@ -296,3 +324,863 @@ fn test_socket_parsing() {
         panic!("Not enough sockets parsed");
     }
 }
+
+#[test]
+fn test_timer_parsing() {
+    let test_timer_str = r#"
+    [Unit]
+    Description = Run the cleanup regularly
+
+    [Timer]
+    OnCalendar = Mon..Fri *-*-1,15 12:00
+    OnCalendar = daily
+    OnBootSec = 1h 30min
+    OnUnitActiveSec = 15min
+    AccuracySec = 1s
+    RandomizedDelaySec = 500ms
+    Persistent = yes
+
+    [Install]
+    WantedBy = timers.target
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_timer_str).unwrap();
+    let timer = crate::units::parse_timer(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/cleanup.timer"),
+    )
+    .unwrap();
+
+    assert_eq!(timer.timer.on_calendar.len(), 2);
+    assert_eq!(
+        timer.timer.on_boot,
+        vec![std::time::Duration::from_secs(90 * 60)]
+    );
+    assert_eq!(
+        timer.timer.on_unit_active,
+        vec![std::time::Duration::from_secs(15 * 60)]
+    );
+    assert!(timer.timer.on_unit_inactive.is_empty());
+    assert_eq!(
+        timer.timer.accuracy,
+        Some(std::time::Duration::from_secs(1))
+    );
+    assert_eq!(
+        timer.timer.randomized_delay,
+        Some(std::time::Duration::from_millis(500))
+    );
+    assert!(timer.timer.persistent);
+    assert_eq!(timer.timer.unit, None);
+
+    // the default unit is the service with the same name
+    let unit: crate::units::Unit = std::convert::TryInto::try_into(timer).unwrap();
+    if let crate::units::Specific::Timer(specific) = &unit.specific {
+        assert_eq!(specific.conf.unit.name, "cleanup.service");
+    } else {
+        panic!("Parsed timer is not a timer unit");
+    }
+
+    // a timer without any trigger is invalid
+    let parsed_file = crate::units::parse_file("[Timer]\nPersistent = yes").unwrap();
+    assert!(crate::units::parse_timer(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/cleanup.timer"),
+    )
+    .is_err());
+
+    // a timespan that overflows is an error, not a panic
+    let parsed_file =
+        crate::units::parse_file("[Timer]\nOnBootSec = 99999999999999999999y").unwrap();
+    assert!(crate::units::parse_timer(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/cleanup.timer"),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_calendar_spec() {
+    use crate::timers::CalendarSpec;
+    let at = |y, mo, d, h, mi, s| {
+        chrono::NaiveDate::from_ymd_opt(y, mo, d)
+            .unwrap()
+            .and_hms_opt(h, mi, s)
+            .unwrap()
+    };
+
+    // 2024-01-05 is a friday
+    let spec = CalendarSpec::parse("Mon..Fri *-*-* 12:00").unwrap();
+    assert_eq!(
+        spec.next_elapse(at(2024, 1, 5, 11, 0, 0)),
+        Some(at(2024, 1, 5, 12, 0, 0))
+    );
+    assert_eq!(
+        spec.next_elapse(at(2024, 1, 5, 12, 0, 0)),
+        Some(at(2024, 1, 8, 12, 0, 0))
+    );
+
+    let spec = CalendarSpec::parse("*:0/15").unwrap();
+    assert_eq!(
+        spec.next_elapse(at(2024, 1, 5, 23, 50, 0)),
+        Some(at(2024, 1, 6, 0, 0, 0))
+    );
+
+    let spec = CalendarSpec::parse("*-*-1,15 06:30:10").unwrap();
+    assert_eq!(
+        spec.next_elapse(at(2024, 1, 15, 6, 30, 10)),
+        Some(at(2024, 2, 1, 6, 30, 10))
+    );
+
+    let spec = CalendarSpec::parse("*-02-29").unwrap();
+    assert_eq!(
+        spec.next_elapse(at(2024, 3, 1, 0, 0, 0)),
+        Some(at(2028, 2, 29, 0, 0, 0))
+    );
+
+    let spec = CalendarSpec::parse("monthly").unwrap();
+    assert_eq!(
+        spec.next_elapse(at(2024, 12, 31, 23, 59, 59)),
+        Some(at(2025, 1, 1, 0, 0, 0))
+    );
+
+    assert!(CalendarSpec::parse("Funday 12:00").is_err());
+    assert!(CalendarSpec::parse("*-13-01").is_err());
+    assert!(CalendarSpec::parse("25:00").is_err());
+}
+
+#[test]
+fn test_path_parsing() {
+    use crate::paths::PathCondition;
+    use std::path::PathBuf;
+
+    let test_path_str = r#"
+    [Unit]
+    Description = Process new jobs in the spool
+
+    [Path]
+    DirectoryNotEmpty = /var/spool/jobs
+    PathExistsGlob = /var/spool/jobs/*.{job,urgent}
+    PathModified = /etc/jobs.conf
+    MakeDirectory = yes
+    Unit = job-runner.service
+
+    [Install]
+    WantedBy = paths.target
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_path_str).unwrap();
+    let path = crate::units::parse_path(parsed_file, &PathBuf::from("/path/to/jobs.path")).unwrap();
+
+    // the order from the file is kept and the ',' in the glob does not split the value
+    assert_eq!(
+        path.path.conditions,
+        vec![
+            PathCondition::DirectoryNotEmpty(PathBuf::from("/var/spool/jobs")),
+            PathCondition::ExistsGlob(PathBuf::from("/var/spool/jobs/*.{job,urgent}")),
+            PathCondition::Modified(PathBuf::from("/etc/jobs.conf")),
+        ]
+    );
+    assert!(path.path.make_directory);
+    assert_eq!(path.path.unit, Some("job-runner.service".to_owned()));
+
+    let unit: crate::units::Unit = std::convert::TryInto::try_into(path).unwrap();
+    if let crate::units::Specific::Path(specific) = &unit.specific {
+        assert_eq!(specific.conf.unit.name, "job-runner.service");
+    } else {
+        panic!("Parsed path is not a path unit");
+    }
+
+    // relative paths are not allowed
+    let parsed_file = crate::units::parse_file("[Path]\nPathExists = spool/jobs").unwrap();
+    assert!(crate::units::parse_path(parsed_file, &PathBuf::from("/path/to/jobs.path")).is_err());
+
+    // a path unit without any condition is invalid
+    let parsed_file = crate::units::parse_file("[Path]\nMakeDirectory = yes").unwrap();
+    assert!(crate::units::parse_path(parsed_file, &PathBuf::from("/path/to/jobs.path")).is_err());
+}
+
+#[test]
+fn test_template_instances() {
+    use crate::units::{expand_specifiers, parse_file, Specific, UnitId};
+    use std::convert::TryInto;
+
+    let id: UnitId = "worker@tenant-a.service".try_into().unwrap();
+    assert_eq!(id.prefix(), "worker");
+    assert_eq!(id.instance(), Some("tenant-a".to_owned()));
+    assert_eq!(id.template_name(), Some("worker@.service".to_owned()));
+    assert!(!id.is_template());
+    let template: UnitId = "worker@.service".try_into().unwrap();
+    assert!(template.is_template());
+    assert_eq!(template.template_name(), None);
+
+    let mut parsed_file =
+        parse_file("[Service]\nExecStart = /bin/worker %i %I %n %N %p %f 100%% %Z").unwrap();
+    expand_specifiers(&mut parsed_file, &id);
+    assert_eq!(
+        parsed_file["[Service]"]["EXECSTART"][0].1,
+        "/bin/worker tenant-a tenant/a worker@tenant-a.service worker@tenant-a worker /tenant/a 100% %Z"
+    );
+
+    // instances referenced by other units are loaded from the template file
+    let dir = std::env::temp_dir().join(format!("rustysd_template_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(
+        dir.join("default.target"),
+        "[Unit]\nWants = worker@a.service\nWants = worker@b.service",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("worker@.service"),
+        "[Service]\nExecStart = /bin/worker --tenant %i",
+    )
+    .unwrap();
+    let units = crate::units::load_all_units(&[dir.clone()], "default.target").unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(units.len(), 3);
+    for instance in &["a", "b"] {
+        let id: UnitId = format!("worker@{}.service", instance)
+            .as_str()
+            .try_into()
+            .unwrap();
+        let unit = units.get(&id).unwrap();
+        assert_eq!(unit.common.unit.template, Some(template.clone()));
+        if let Specific::Service(srvc) = &unit.specific {
+            assert_eq!(srvc.conf.exec.args, vec!["--tenant", instance]);
+        } else {
+            panic!("Instance is not a service");
+        }
+    }
+}
+
+#[test]
+fn test_dropins() {
+    use crate::units::{Specific, UnitId};
+    use std::convert::TryInto;
+
+    let dir = std::env::temp_dir().join(format!("rustysd_dropin_test_{}", std::process::id()));
+    let vendor_dir = dir.join("vendor");
+    let admin_dir = dir.join("admin");
+    std::fs::create_dir_all(vendor_dir.join("worker.service.d")).unwrap();
+    std::fs::create_dir_all(vendor_dir.join("service.d")).unwrap();
+    std::fs::create_dir_all(admin_dir.join("worker.service.d")).unwrap();
+    std::fs::write(
+        vendor_dir.join("default.target"),
+        "[Unit]\nWants = worker.service",
+    )
+    .unwrap();
+    std::fs::write(
+        vendor_dir.join("worker.service"),
+        "[Unit]\nDescription = Vendor worker\n[Service]\nExecStart = /bin/vendor\nExecStartPre = /bin/pre\nType = simple",
+    )
+    .unwrap();
+    // the admin dir comes first, so its 10-exec.conf masks the one of the vendor dir
+    std::fs::write(
+        vendor_dir.join("worker.service.d").join("10-exec.conf"),
+        "[Service]\nExecStart = /bin/masked",
+    )
+    .unwrap();
+    std::fs::write(
+        admin_dir.join("worker.service.d").join("10-exec.conf"),
+        "[Service]\nExecStart =\nExecStart = /bin/admin --flag",
+    )
+    .unwrap();
+    std::fs::write(
+        vendor_dir.join("service.d").join("20-type.conf"),
+        "[Unit]\nDescription = Overridden worker\n[Service]\nType = oneshot\nExecStartPre = /bin/pre2",
+    )
+    .unwrap();
+    std::fs::write(
+        admin_dir.join("worker.service.d").join("30-reset.conf"),
+        "# only reset the pre commands\n[Service]\nExecStartPre =",
+    )
+    .unwrap();
+    std::fs::write(admin_dir.join("worker.service.d").join("ignored.txt"), "").unwrap();
+
+    let units =
+        crate::units::load_all_units(&[admin_dir.clone(), vendor_dir.clone()], "default.target")
+            .unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let id: UnitId = "worker.service".try_into().unwrap();
+    let unit = units.get(&id).unwrap();
+    assert_eq!(
+        unit.common.unit.dropins,
+        vec![
+            admin_dir.join("worker.service.d").join("10-exec.conf"),
+            vendor_dir.join("service.d").join("20-type.conf"),
+            admin_dir.join("worker.service.d").join("30-reset.conf"),
+        ]
+    );
+    assert_eq!(unit.common.unit.description, "Overridden worker");
+    if let Specific::Service(srvc) = &unit.specific {
+        assert_eq!(srvc.conf.exec.cmd, "/bin/admin");
+        assert_eq!(srvc.conf.exec.args, vec!["--flag"]);
+        assert_eq!(srvc.conf.srcv_type, crate::units::ServiceType::OneShot);
+        assert!(srvc.conf.startpre.is_empty());
+    } else {
+        panic!("Unit is not a service");
+    }
+}
+
+#[test]
+fn test_restart_settings() {
+    use crate::services::{restart_delay, restart_wanted};
+    use crate::signal_handler::ChildTermination;
+    use crate::units::{ServiceRestart, Specific};
+    use nix::sys::signal::Signal;
+    use std::convert::TryInto;
+    use std::time::Duration;
+
+    let parsed_file = crate::units::parse_file(
+        "[Unit]\nStartLimitIntervalSec = 30s\nStartLimitBurst = 3\n[Service]\nExecStart = /bin/worker\nRestart = on-abnormal\nRestartSec = 1s\nRestartSteps = 4\nRestartMaxDelaySec = 16s",
+    )
+    .unwrap();
+    let unit: crate::units::Unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/worker.service"),
+    )
+    .unwrap()
+    .try_into()
+    .unwrap();
+    assert_eq!(
+        unit.common.unit.start_limit_interval,
+        Duration::from_secs(30)
+    );
+    assert_eq!(unit.common.unit.start_limit_burst, 3);
+    let conf = if let Specific::Service(srvc) = &unit.specific {
+        &srvc.conf
+    } else {
+        panic!("Unit is not a service");
+    };
+    assert_eq!(conf.restart, ServiceRestart::OnAbnormal);
+
+    // the delay grows exponentially from RestartSec= to RestartMaxDelaySec= over RestartSteps= restarts
+    assert_eq!(restart_delay(conf, 0), Duration::from_secs(1));
+    assert_eq!(restart_delay(conf, 2), Duration::from_secs(4));
+    assert_eq!(restart_delay(conf, 4), Duration::from_secs(16));
+    assert_eq!(restart_delay(conf, 100), Duration::from_secs(16));
+
+    let exit_ok = ChildTermination::Exit(0);
+    let exit_err = ChildTermination::Exit(1);
+    let sigterm = ChildTermination::Signal(Signal::SIGTERM);
+    let sigsegv = ChildTermination::Signal(Signal::SIGSEGV);
+    let wanted = |restart: ServiceRestart| {
+        [&exit_ok, &exit_err, &sigterm, &sigsegv]
+            .iter()
+            .map(|code| restart_wanted(&restart, code, false))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(wanted(ServiceRestart::No), vec![false, false, false, false]);
+    assert_eq!(wanted(ServiceRestart::Always), vec![true, true, true, true]);
+    assert_eq!(
+        wanted(ServiceRestart::OnSuccess),
+        vec![true, false, true, false]
+    );
+    assert_eq!(
+        wanted(ServiceRestart::OnFailure),
+        vec![false, true, false, true]
+    );
+    assert_eq!(
+        wanted(ServiceRestart::OnAbnormal),
+        vec![false, false, false, true]
+    );
+    assert_eq!(
+        wanted(ServiceRestart::OnAbort),
+        vec![false, false, false, true]
+    );
+}
+
+#[test]
+fn test_forking_service() {
+    use crate::units::ServiceType;
+
+    let parse = |content: &str| {
+        service_conf(&parse_service_str(content, "/path/to/daemon.service").unwrap())
+    };
+
+    let conf = parse("[Service]\nExecStart = /bin/daemon\nType = forking\nPIDFile = daemon.pid");
+    assert_eq!(conf.srcv_type, ServiceType::Forking);
+    // relative paths are relative to /run
+    assert_eq!(
+        conf.pid_file,
+        Some(std::path::PathBuf::from("/run/daemon.pid"))
+    );
+
+    let conf =
+        parse("[Service]\nExecStart = /bin/daemon\nType = forking\nPIDFile = /var/run/daemon.pid");
+    assert_eq!(
+        conf.pid_file,
+        Some(std::path::PathBuf::from("/var/run/daemon.pid"))
+    );
+
+    let conf = parse("[Service]\nExecStart = /bin/daemon\nType = forking");
+    assert_eq!(conf.pid_file, None);
+}
+
+#[test]
+fn test_exec_and_idle_service_types() {
+    use crate::units::ServiceType;
+
+    for (type_name, srcv_type) in &[("exec", ServiceType::Exec), ("idle", ServiceType::Idle)] {
+        let parsed_file = crate::units::parse_file(&format!(
+            "[Service]\nExecStart = /bin/server\nType = {}",
+            type_name
+        ))
+        .unwrap();
+        let service = crate::units::parse_service(
+            parsed_file,
+            &std::path::PathBuf::from("/path/to/server.service"),
+        )
+        .unwrap();
+        assert_eq!(service.srvc.srcv_type, *srcv_type);
+    }
+}
+
+#[test]
+fn test_exec_reload() {
+    use std::convert::TryInto;
+
+    let parsed_file = crate::units::parse_file(
+        "[Service]\nExecStart = /bin/server\nExecReload = /bin/kill -HUP $MAINPID\nExecReload = /bin/touch /tmp/reloaded",
+    )
+    .unwrap();
+    let unit: crate::units::Unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/server.service"),
+    )
+    .unwrap()
+    .try_into()
+    .unwrap();
+    assert!(unit.can_reload());
+    if let crate::units::Specific::Service(srvc) = &unit.specific {
+        assert_eq!(srvc.conf.reload.len(), 2);
+        assert_eq!(srvc.conf.reload[0].cmd, "/bin/kill");
+        assert_eq!(srvc.conf.reload[0].args, vec!["-HUP", "$MAINPID"]);
+        assert_eq!(srvc.conf.reload[1].cmd, "/bin/touch");
+    } else {
+        panic!("Unit is not a service");
+    }
+}
+
+#[test]
+fn test_watchdog_settings() {
+    use crate::services::restart_wanted;
+    use crate::signal_handler::ChildTermination;
+    use crate::units::ServiceRestart;
+    use nix::sys::signal::Signal;
+    use std::time::Duration;
+
+    let parse = |content: &str| {
+        service_conf(&parse_service_str(content, "/path/to/watched.service").unwrap())
+    };
+
+    let conf =
+        parse("[Service]\nExecStart = /bin/watched\nWatchdogSec = 2s\nWatchdogSignal = TERM");
+    assert_eq!(conf.watchdog_sec, Some(Duration::from_secs(2)));
+    assert_eq!(conf.watchdog_signal, Signal::SIGTERM);
+
+    let conf = parse("[Service]\nExecStart = /bin/watched\nWatchdogSec = infinity");
+    assert_eq!(conf.watchdog_sec, None);
+    assert_eq!(conf.watchdog_signal, Signal::SIGABRT);
+
+    let conf = parse("[Service]\nExecStart = /bin/watched\nWatchdogSec = 0");
+    assert_eq!(conf.watchdog_sec, None);
+
+    // the watchdog kills the service with SIGABRT
+    let sigabrt = ChildTermination::Signal(Signal::SIGABRT);
+    assert!(restart_wanted(&ServiceRestart::OnWatchdog, &sigabrt, true));
+    assert!(!restart_wanted(
+        &ServiceRestart::OnWatchdog,
+        &sigabrt,
+        false
+    ));
+    assert!(restart_wanted(&ServiceRestart::OnFailure, &sigabrt, true));
+    assert!(restart_wanted(&ServiceRestart::OnAbnormal, &sigabrt, true));
+    assert!(!restart_wanted(&ServiceRestart::OnAbort, &sigabrt, true));
+    let sigterm = ChildTermination::Signal(Signal::SIGTERM);
+    assert!(!restart_wanted(&ServiceRestart::OnSuccess, &sigterm, true));
+
+    let mut watchdog = crate::watchdog::Watchdog::default();
+    assert_eq!(watchdog.time_left(), None);
+    watchdog.arm(Some(Duration::from_secs(10)));
+    assert!(watchdog.time_left().unwrap() > Duration::from_secs(9));
+    watchdog.trigger();
+    assert_eq!(watchdog.time_left(), Some(Duration::from_secs(0)));
+    watchdog.arm(Some(Duration::from_secs(10)));
+    watchdog.set_timeout(0);
+    assert_eq!(watchdog.time_left(), None);
+}
+
+#[test]
+fn test_fd_store_settings() {
+    use crate::fd_store::FDStore;
+
+    let parse = |content: &str| {
+        service_conf(&parse_service_str(content, "/path/to/proxy.service").unwrap())
+    };
+
+    let conf = parse("[Service]\nExecStart = /bin/proxy\nFileDescriptorStoreMax = 16");
+    assert_eq!(conf.fd_store_max, 16);
+    let conf = parse("[Service]\nExecStart = /bin/proxy");
+    assert_eq!(conf.fd_store_max, 0);
+
+    // the fds are not real, they are never closed here
+    let mut fd_store = FDStore::default();
+    fd_store.insert_service_stored(
+        "proxy.service".into(),
+        "listener".into(),
+        vec![Box::new(100), Box::new(101)],
+    );
+    fd_store.insert_service_stored("proxy.service".into(), "conn".into(), vec![Box::new(102)]);
+    let mut stored = fd_store.get_all_service_stored("proxy.service");
+    stored.sort();
+    assert_eq!(
+        stored,
+        vec![
+            ("conn".to_owned(), 102),
+            ("listener".to_owned(), 100),
+            ("listener".to_owned(), 101)
+        ]
+    );
+    assert!(fd_store.get_all_service_stored("other.service").is_empty());
+    assert_eq!(fd_store.remove_all_service_stored("proxy.service").len(), 3);
+    assert!(fd_store.get_all_service_stored("proxy.service").is_empty());
+}
+
+#[test]
+fn test_accept_settings() {
+    use crate::units::Specific;
+    use std::convert::TryInto;
+
+    let parse = |content: &str| parse_socket_str(content, "/path/to/echo.socket");
+
+    let unit =
+        parse("[Socket]\nListenStream = 127.0.0.1:7\nAccept = yes\nMaxConnections = 10").unwrap();
+    if let Specific::Socket(sock) = &unit.specific {
+        assert!(sock.conf.accept);
+        assert_eq!(sock.conf.max_connections, 10);
+        assert_eq!(sock.conf.max_connections_per_source, 10);
+    } else {
+        panic!("Unit is not a socket");
+    }
+
+    let unit =
+        parse("[Socket]\nListenStream = /run/echo.sock\nMaxConnectionsPerSource = 2").unwrap();
+    if let Specific::Socket(sock) = &unit.specific {
+        assert!(!sock.conf.accept);
+        assert_eq!(sock.conf.max_connections, 64);
+        assert_eq!(sock.conf.max_connections_per_source, 2);
+    } else {
+        panic!("Unit is not a socket");
+    }
+
+    // datagrams can not be accepted and the instances always come from the template
+    assert!(parse("[Socket]\nListenDatagram = 127.0.0.1:7\nAccept = yes").is_err());
+    assert!(
+        parse("[Socket]\nListenStream = 127.0.0.1:7\nAccept = yes\nService = other.service")
+            .is_err()
+    );
+    assert!(parse("[Socket]\nListenStream = 127.0.0.1:7\nMaxConnections = 0").is_err());
+
+    let parsed_file =
+        crate::units::parse_file("[Service]\nExecStart = /bin/cat\nStandardInput = socket")
+            .unwrap();
+    let unit: crate::units::Unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/echo@.service"),
+    )
+    .unwrap()
+    .try_into()
+    .unwrap();
+    if let Specific::Service(srvc) = &unit.specific {
+        assert_eq!(
+            srvc.conf.standard_input,
+            crate::units::StandardInput::Socket
+        );
+    } else {
+        panic!("Unit is not a service");
+    }
+}
+
+#[test]
+fn test_on_failure_settings() {
+    use crate::units::JobMode;
+
+    let parse = |content: &str| parse_service_str(content, "/path/to/fail.service");
+
+    let unit = parse(
+        "[Unit]\nOnFailure = alert@fail.service.service\nOnFailure = log.service\nOnSuccess = next.service\nOnFailureJobMode = ignore-dependencies\n[Service]\nExecStart = /bin/false",
+    )
+    .unwrap();
+    let names = |ids: &[crate::units::UnitId]| -> Vec<String> {
+        ids.iter().map(|id| id.name.clone()).collect()
+    };
+    assert_eq!(
+        names(&unit.common.unit.on_failure),
+        vec!["alert@fail.service.service", "log.service"]
+    );
+    assert_eq!(names(&unit.common.unit.on_success), vec!["next.service"]);
+    assert_eq!(
+        unit.common.unit.on_failure_job_mode,
+        JobMode::IgnoreDependencies
+    );
+    assert_eq!(unit.common.unit.on_success_job_mode, JobMode::Replace);
+    assert!(unit
+        .common
+        .unit
+        .refs_by_name
+        .iter()
+        .any(|id| id.name == "alert@fail.service.service"));
+
+    assert!(
+        parse("[Unit]\nOnFailureJobMode = sometimes\n[Service]\nExecStart = /bin/false").is_err()
+    );
+}
+
+#[test]
+fn test_condition_settings() {
+    use crate::units::{check_conditions, ConditionKind, UnitCondition};
+
+    let parse = |content: &str| parse_service_str(content, "/path/to/cond.service");
+
+    let unit = parse(
+        "[Unit]\nConditionPathExists = !/does/not/exist\nConditionVirtualization = |container\nConditionHost = |!other-host\nAssertPathIsDirectory = /\n[Service]\nExecStart = /bin/true",
+    )
+    .unwrap();
+    let conditions = &unit.common.unit.conditions;
+    assert_eq!(conditions.len(), 3);
+    assert_eq!(
+        conditions[0],
+        UnitCondition {
+            kind: ConditionKind::PathExists("/does/not/exist".into()),
+            negate: true,
+            triggering: false,
+        }
+    );
+    assert_eq!(format!("{}", conditions[2]), "Host=|!other-host".to_owned());
+    assert_eq!(unit.common.unit.asserts.len(), 1);
+    // the negated path does not exist and one of the triggering conditions holds
+    assert!(check_conditions(conditions).is_ok());
+    assert!(check_conditions(&unit.common.unit.asserts).is_ok());
+
+    let unit = parse(
+        "[Unit]\nConditionPathIsDirectory = /\nConditionFileNotEmpty = /does/not/exist\n[Service]\nExecStart = /bin/true",
+    )
+    .unwrap();
+    let failed = check_conditions(&unit.common.unit.conditions).unwrap_err();
+    assert_eq!(format!("{}", failed), "FileNotEmpty=/does/not/exist");
+
+    // only one of the triggering conditions has to hold, but at least one
+    let unit = parse(
+        "[Unit]\nConditionPathExists = |/does/not/exist\nConditionPathExists = |/also/missing\n[Service]\nExecStart = /bin/true",
+    )
+    .unwrap();
+    assert!(check_conditions(&unit.common.unit.conditions).is_err());
+
+    assert!(
+        parse("[Unit]\nConditionPathExists = relative/path\n[Service]\nExecStart = /bin/true")
+            .is_err()
+    );
+}
+
+#[test]
+fn test_stdio_settings() {
+    use crate::units::{Specific, StdIoOption};
+
+    let parse = |content: &str| parse_service_str(content, "/path/to/stdio.service");
+    let exec_config = |unit: &crate::units::Unit| {
+        if let Specific::Service(srvc) = &unit.specific {
+            srvc.conf.exec_config.clone()
+        } else {
+            panic!("Not a service");
+        }
+    };
+
+    let unit = parse(
+        "[Service]\nExecStart = /bin/true\nStandardOutput = journal\nStandardError = inherit\nSyslogIdentifier = worker",
+    )
+    .unwrap();
+    let conf = exec_config(&unit);
+    assert_eq!(conf.stdout_path, Some(StdIoOption::Journal));
+    assert_eq!(conf.stderr_path, Some(StdIoOption::Inherit));
+    assert_eq!(conf.syslog_identifier, Some("worker".to_owned()));
+    assert_eq!(conf.tty_path, std::path::PathBuf::from("/dev/console"));
+
+    let unit = parse(
+        "[Service]\nExecStart = /bin/true\nStandardOutput = tty\nStandardError = truncate:/tmp/err.log\nTTYPath = /dev/tty3",
+    )
+    .unwrap();
+    let conf = exec_config(&unit);
+    assert_eq!(conf.stdout_path, Some(StdIoOption::Tty));
+    assert_eq!(
+        conf.stderr_path,
+        Some(StdIoOption::TruncateFile("/tmp/err.log".into()))
+    );
+    assert_eq!(conf.tty_path, std::path::PathBuf::from("/dev/tty3"));
+
+    let unit =
+        parse("[Service]\nExecStart = /bin/true\nStandardOutput = fd:logger\nStandardError = null")
+            .unwrap();
+    let conf = exec_config(&unit);
+    assert_eq!(conf.stdout_path, Some(StdIoOption::Fd("logger".to_owned())));
+    assert_eq!(conf.stderr_path, Some(StdIoOption::Null));
+
+    assert!(parse("[Service]\nExecStart = /bin/true\nStandardOutput = syslog").is_err());
+}
+
+#[test]
+fn test_resource_control_settings() {
+    use crate::units::{set_resource_property, ResourceLimit, Specific};
+    use std::convert::TryInto;
+
+    let parsed_file = crate::units::parse_file(
+        "[Service]\nExecStart = /bin/true\nMemoryMax = 512M\nMemoryHigh = 50%\nMemoryLow = infinity\nCPUWeight = 200\nCPUQuota = 150%\nTasksMax = 64\nIOWeight = 10\nAllowedCPUs = 0-1 3",
+    )
+    .unwrap();
+    let unit: crate::units::Unit = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/limited.service"),
+    )
+    .unwrap()
+    .try_into()
+    .unwrap();
+    let mut resources = if let Specific::Service(srvc) = &unit.specific {
+        srvc.conf.resources.clone()
+    } else {
+        panic!("Not a service");
+    };
+    assert_eq!(
+        resources.memory_max,
+        Some(ResourceLimit::Absolute(512 * 1024 * 1024))
+    );
+    assert_eq!(resources.memory_high, Some(ResourceLimit::Percent(50)));
+    assert_eq!(resources.memory_low, Some(ResourceLimit::Infinity));
+    assert_eq!(resources.cpu_weight, Some(200));
+    assert_eq!(resources.cpu_quota, Some(150));
+    assert_eq!(resources.tasks_max, Some(ResourceLimit::Absolute(64)));
+    assert_eq!(resources.io_weight, Some(10));
+    assert_eq!(resources.allowed_cpus, Some("0-1,3".to_owned()));
+
+    // set-property uses the same parsing, an empty value unsets the setting
+    set_resource_property(&mut resources, "TasksMax", "infinity").unwrap();
+    assert_eq!(resources.tasks_max, Some(ResourceLimit::Infinity));
+    set_resource_property(&mut resources, "MemoryMax", "").unwrap();
+    assert_eq!(resources.memory_max, None);
+    assert!(set_resource_property(&mut resources, "CPUWeight", "0").is_err());
+    assert!(set_resource_property(&mut resources, "CPUQuota", "20").is_err());
+    assert!(set_resource_property(&mut resources, "AllowedCPUs", "3-1").is_err());
+    assert!(set_resource_property(&mut resources, "Restart", "always").is_err());
+}
+
+#[test]
+fn test_kill_settings() {
+    use crate::units::{KillMode, Specific};
+    use nix::sys::signal::Signal;
+
+    let parse = |content: &str| parse_service_str(content, "/path/to/kill.service");
+    let kill_config = |unit: &crate::units::Unit| {
+        if let Specific::Service(srvc) = &unit.specific {
+            srvc.conf.kill.clone()
+        } else {
+            panic!("Not a service");
+        }
+    };
+
+    let kill = kill_config(&parse("[Service]\nExecStart = /bin/true").unwrap());
+    assert_eq!(kill.mode, KillMode::ControlGroup);
+    assert_eq!(kill.signal, Signal::SIGTERM);
+    assert_eq!(kill.restart_signal, None);
+    assert!(!kill.send_sighup);
+    assert!(kill.send_sigkill);
+    assert_eq!(kill.final_signal, Signal::SIGKILL);
+
+    let kill = kill_config(
+        &parse(
+            "[Service]\nExecStart = /bin/true\nKillMode = mixed\nKillSignal = SIGINT\nRestartKillSignal = SIGUSR1\nSendSIGHUP = yes\nSendSIGKILL = no\nFinalKillSignal = SIGQUIT",
+        )
+        .unwrap(),
+    );
+    assert_eq!(kill.mode, KillMode::Mixed);
+    assert_eq!(kill.signal, Signal::SIGINT);
+    assert_eq!(kill.restart_signal, Some(Signal::SIGUSR1));
+    assert!(kill.send_sighup);
+    assert!(!kill.send_sigkill);
+    assert_eq!(kill.final_signal, Signal::SIGQUIT);
+
+    assert!(parse("[Service]\nExecStart = /bin/true\nKillMode = all").is_err());
+    assert!(parse("[Service]\nExecStart = /bin/true\nKillSignal = SIGNOPE").is_err());
+}
+
+#[test]
+fn test_slice_units() {
+    use crate::units::{is_valid_slice_name, ResourceLimit, Specific, UnitId};
+    use std::convert::TryInto;
+
+    assert!(is_valid_slice_name("-.slice"));
+    assert!(is_valid_slice_name("tenant-a.slice"));
+    assert!(!is_valid_slice_name("tenant--a.slice"));
+    assert!(!is_valid_slice_name("-tenant.slice"));
+    assert!(!is_valid_slice_name("tenant.service"));
+    let id: UnitId = "batch-low-nightly.slice".try_into().unwrap();
+    assert_eq!(id.parent_slice().unwrap().name, "batch-low.slice");
+    let id: UnitId = "batch.slice".try_into().unwrap();
+    assert_eq!(id.parent_slice(), None);
+
+    // batch-low.slice has no file and is created, its parent batch.slice is loaded from its file
+    let dir = std::env::temp_dir().join(format!("rustysd_slice_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("default.target"), "[Unit]\nWants = job.service").unwrap();
+    std::fs::write(
+        dir.join("job.service"),
+        "[Service]\nExecStart = /bin/job\nSlice = batch-low.slice",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("batch.slice"),
+        "[Slice]\nMemoryMax = 1G\nTasksMax = 20",
+    )
+    .unwrap();
+    std::fs::write(dir.join("unused.slice"), "[Slice]\nCPUWeight = 10").unwrap();
+    let units = crate::units::load_all_units(&[dir.clone()], "default.target").unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(units.len(), 4);
+    let service_id: UnitId = "job.service".try_into().unwrap();
+    let low_id: UnitId = "batch-low.slice".try_into().unwrap();
+    let batch_id: UnitId = "batch.slice".try_into().unwrap();
+
+    let service = units.get(&service_id).unwrap();
+    if let Specific::Service(srvc) = &service.specific {
+        assert_eq!(srvc.conf.slice, Some(low_id.clone()));
+    } else {
+        panic!("Not a service");
+    }
+    assert!(service.common.dependencies.requires.contains(&low_id));
+    assert!(service.common.dependencies.after.contains(&low_id));
+
+    let low = units.get(&low_id).unwrap();
+    assert!(low.common.dependencies.requires.contains(&batch_id));
+    assert!(low.common.dependencies.required_by.contains(&service_id));
+
+    let batch = units.get(&batch_id).unwrap();
+    assert!(batch.common.dependencies.before.contains(&low_id));
+    if let Specific::Slice(slice) = &batch.specific {
+        assert_eq!(
+            slice.conf.resources.memory_max,
+            Some(ResourceLimit::Absolute(1024 * 1024 * 1024))
+        );
+        assert_eq!(
+            slice.conf.resources.tasks_max,
+            Some(ResourceLimit::Absolute(20))
+        );
+    } else {
+        panic!("Not a slice");
+    }
+
+    assert!(crate::units::parse_slice(
+        crate::units::parse_file("[Slice]\nRestart = always").unwrap(),
+        &std::path::PathBuf::from("/path/to/batch.slice"),
+    )
+    .is_err());
+}
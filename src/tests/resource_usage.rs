@@ -0,0 +1,22 @@
+use crate::platform::cgroups::{parse_flat_keyed, parse_io_stat, IoUsage};
+
+#[test]
+fn test_parse_cgroup_stats() {
+    let cpu_stat = "usage_usec 123456\nuser_usec 100000\nsystem_usec 23456\n";
+    assert_eq!(parse_flat_keyed(cpu_stat, "usage_usec"), Some(123456));
+    assert_eq!(parse_flat_keyed(cpu_stat, "system_usec"), Some(23456));
+    assert_eq!(parse_flat_keyed(cpu_stat, "nr_periods"), None);
+
+    // all devices are summed up, unknown keys are ignored
+    let io_stat = "8:0 rbytes=4096 wbytes=1024 rios=2 wios=1 dbytes=0 dios=0\n259:0 rbytes=100 wbytes=0 rios=1 wios=0\n";
+    assert_eq!(
+        parse_io_stat(io_stat),
+        IoUsage {
+            read_bytes: 4196,
+            write_bytes: 1024,
+            read_operations: 3,
+            write_operations: 1,
+        }
+    );
+    assert_eq!(parse_io_stat(""), IoUsage::default());
+}
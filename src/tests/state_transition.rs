@@ -4,21 +4,7 @@ use std::convert::TryInto;
 
 #[test]
 fn test_service_state_transitions() {
-    let run_info = std::sync::Arc::new(std::sync::RwLock::new(RuntimeInfo {
-        config: crate::config::Config {
-            notification_sockets_dir: "./notifications".into(),
-            target_unit: "".into(),
-            unit_dirs: vec![],
-            self_path: std::path::PathBuf::from("./target/debug/rustysd"),
-        },
-        fd_store: std::sync::RwLock::new(crate::fd_store::FDStore::default()),
-        pid_table: std::sync::Mutex::new(PidTable::default()),
-        unit_table: UnitTable::default(),
-        stdout_eventfd: crate::platform::make_event_fd().unwrap(),
-        stderr_eventfd: crate::platform::make_event_fd().unwrap(),
-        notification_eventfd: crate::platform::make_event_fd().unwrap(),
-        socket_activation_eventfd: crate::platform::make_event_fd().unwrap(),
-    }));
+    let run_info = std::sync::Arc::new(std::sync::RwLock::new(super::test_run_info()));
 
     let signals = signal_hook::iterator::Signals::new(&[signal_hook::consts::SIGCHLD]).unwrap();
 
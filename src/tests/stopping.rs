@@ -0,0 +1,113 @@
+use super::test_run_info;
+use crate::platform::procfs::is_running;
+use crate::runtime_info::*;
+use crate::units::{Specific, Unit};
+use nix::sys::signal::{SigHandler, Signal};
+use nix::unistd::Pid;
+use std::convert::TryInto;
+use std::os::unix::process::CommandExt;
+use std::time::{Duration, Instant};
+
+fn service(kill_settings: &str) -> Unit {
+    let parsed_file = crate::units::parse_file(&format!(
+        "[Service]\nExecStart = /bin/sleep 10\nKillMode = process\nTimeoutStopSec = 1\n{}",
+        kill_settings
+    ))
+    .unwrap();
+    crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/stop.service"),
+    )
+    .unwrap()
+    .try_into()
+    .unwrap()
+}
+
+/// A main process that ignores SIGTERM, like a service that does not want to stop
+fn spawn_stubborn() -> std::process::Child {
+    let mut cmd = std::process::Command::new("/bin/sleep");
+    cmd.arg("10");
+    unsafe {
+        cmd.pre_exec(|| {
+            nix::sys::signal::signal(Signal::SIGTERM, SigHandler::SigIgn)
+                .map(|_| ())
+                .map_err(|e| std::io::Error::from_raw_os_error(e as i32))
+        });
+    }
+    cmd.spawn().unwrap()
+}
+
+/// Stop the service with pid as its main process and return how long that took. The notification is sent by the
+/// service before it is stopped.
+fn stop(unit: &Unit, run_info: &RuntimeInfo, pid: Pid, notification: Option<&[u8]>) -> Duration {
+    let srvc = match &unit.specific {
+        Specific::Service(srvc) => srvc,
+        _ => panic!("Not a service"),
+    };
+    let state = &mut *srvc.state.write().unwrap();
+    state.srvc.pid = Some(pid);
+    if let Some(notification) = notification {
+        let (socket, service_end) = std::os::unix::net::UnixDatagram::pair().unwrap();
+        service_end.send(notification).unwrap();
+        state.srvc.notifications = Some(socket);
+    }
+    let start = Instant::now();
+    state
+        .srvc
+        .kill(&srvc.conf, unit.id.clone(), &unit.id.name, run_info, false)
+        .unwrap();
+    start.elapsed()
+}
+
+/// The final signal is not waited for, the process might need a moment to die
+fn exits_soon(pid: Pid) -> bool {
+    let start = Instant::now();
+    while is_running(pid) {
+        if start.elapsed() > Duration::from_secs(1) {
+            return false;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    true
+}
+
+#[test]
+fn test_stop_escalation() {
+    let run_info = test_run_info();
+
+    // SIGTERM is ignored, so the process gets the FinalKillSignal= after TimeoutStopSec=
+    let child = spawn_stubborn();
+    let pid = Pid::from_raw(child.id() as i32);
+    let took = stop(&service(""), &run_info, pid, None);
+    assert!(took >= Duration::from_secs(1) && took < Duration::from_secs(3));
+    assert!(exits_soon(pid));
+
+    // EXTEND_TIMEOUT_USEC= gives the service more time to stop
+    let child = spawn_stubborn();
+    let pid = Pid::from_raw(child.id() as i32);
+    let took = stop(
+        &service(""),
+        &run_info,
+        pid,
+        Some(b"EXTEND_TIMEOUT_USEC=2000000"),
+    );
+    assert!(took >= Duration::from_millis(1900) && took < Duration::from_secs(4));
+    assert!(exits_soon(pid));
+
+    // the KillSignal= is not ignored
+    let child = spawn_stubborn();
+    let pid = Pid::from_raw(child.id() as i32);
+    let took = stop(&service("KillSignal = SIGINT"), &run_info, pid, None);
+    assert!(took < Duration::from_secs(1));
+    assert!(exits_soon(pid));
+
+    // with SendSIGKILL=no the process is left running
+    let mut child = spawn_stubborn();
+    let pid = Pid::from_raw(child.id() as i32);
+    let took = stop(&service("SendSIGKILL = no"), &run_info, pid, None);
+    assert!(took >= Duration::from_secs(1));
+    assert!(is_running(pid));
+    child.kill().unwrap();
+    // the SIGCHLD handler of another test might have reaped it already
+    let _ = child.wait();
+}
@@ -0,0 +1,172 @@
+//! Wait for timers to elapse and activate their respective units
+use log::error;
+use log::trace;
+
+use crate::runtime_info::*;
+use crate::timers::TriggeredUnitTimes;
+use crate::units::*;
+
+use std::time::{Duration, Instant, SystemTime};
+
+/// If the unit of a timer is busy (e.g. starting) its timestamps can not be read. Check again after this time
+const BUSY_UNIT_RECHECK: Duration = Duration::from_secs(1);
+
+pub fn start_timeractivation_thread(run_info: ArcMutRuntimeInfo) {
+    // OnBootSec= is relative to the start of rustysd
+    let boot_time = SystemTime::now();
+    std::thread::spawn(move || {
+        let eventfd = { run_info.read().unwrap().timer_activation_eventfd };
+        let poller = match crate::platform::Poller::new()
+            .and_then(|poller| poller.add(eventfd.read_end()).map(|_| poller))
+        {
+            Ok(poller) => poller,
+            Err(e) => {
+                error!("Could not start waiting for timers: {}", e);
+                return;
+            }
+        };
+        loop {
+            let (elapsed, wait_time) = collect_elapsed_timers(&run_info, boot_time);
+            for (timer_id, unit_id) in elapsed {
+                trigger_unit_new_thread(timer_id, unit_id, run_info.clone());
+            }
+            if let Err(e) = wait_for_timer(&poller, eventfd, wait_time) {
+                error!("Error in timer activation loop: {}", e);
+                break;
+            }
+        }
+    });
+}
+
+fn instant_to_systemtime(instant: Instant) -> SystemTime {
+    SystemTime::now() - instant.elapsed()
+}
+
+/// Returns None if the state of the unit is currently locked because it is in a state transition
+fn triggered_unit_times(unit: &Unit) -> Option<TriggeredUnitTimes> {
+    unit.try_activity_times()
+        .map(|(up_since, down_since)| TriggeredUnitTimes {
+            active_since: up_since.map(instant_to_systemtime),
+            inactive_since: down_since.map(instant_to_systemtime),
+        })
+}
+
+/// Find all started timers that have elapsed and mark them as triggered. Also returns how long the scheduler may
+/// sleep until the next timer needs to be looked at.
+fn collect_elapsed_timers(
+    run_info: &ArcMutRuntimeInfo,
+    boot_time: SystemTime,
+) -> (Vec<(UnitId, UnitId)>, Option<Duration>) {
+    let run_info = &*run_info.read().unwrap();
+    let now = SystemTime::now();
+    let mut elapsed = Vec::new();
+    let mut wait_time: Option<Duration> = None;
+    let mut wait_at_most = |dur: Duration| {
+        wait_time = Some(wait_time.map(|old| old.min(dur)).unwrap_or(dur));
+    };
+
+    for unit in run_info.unit_table.values() {
+        let specific = if let Specific::Timer(specific) = &unit.specific {
+            specific
+        } else {
+            continue;
+        };
+        if !unit.common.status.read().unwrap().is_started() {
+            continue;
+        }
+
+        let unit_times = match run_info.unit_table.get(&specific.conf.unit) {
+            Some(triggered_unit) => match triggered_unit_times(triggered_unit) {
+                Some(times) => times,
+                None => {
+                    wait_at_most(BUSY_UNIT_RECHECK);
+                    continue;
+                }
+            },
+            None => {
+                error!(
+                    "Unit {:?} of timer {} can not be found",
+                    specific.conf.unit, unit.id.name
+                );
+                continue;
+            }
+        };
+
+        let state = &mut *specific.state.write().unwrap();
+        let next = state
+            .timer
+            .calc_next_elapse(&specific.conf, boot_time, unit_times);
+        state.timer.next_elapse = next;
+
+        if let Some(next) = next {
+            if next <= now {
+                trace!("Timer {} elapsed", unit.id.name);
+                state.timer.triggered(
+                    &specific.conf,
+                    &unit.id.name,
+                    &run_info.config.state_dir,
+                    now,
+                );
+                elapsed.push((unit.id.clone(), specific.conf.unit.clone()));
+                // the next elapse is calculated in the next iteration
+                wait_at_most(Duration::from_secs(0));
+            } else {
+                // Elapsing anywhere within the accuracy window is fine. Waiting till the end of the window
+                // lets timers that elapse close together share one wakeup.
+                let until_elapse = next.duration_since(now).unwrap_or_default();
+                wait_at_most(until_elapse + specific.conf.accuracy);
+            }
+        }
+    }
+
+    (elapsed, wait_time)
+}
+
+fn trigger_unit_new_thread(timer_id: UnitId, unit_id: UnitId, run_info: ArcMutRuntimeInfo) {
+    std::thread::spawn(move || {
+        trace!("Start unit {} by timer {}", unit_id.name, timer_id.name);
+        let run_info = &*run_info.read().unwrap();
+        match crate::units::activate_unit(
+            unit_id.clone(),
+            run_info,
+            ActivationSource::TimerActivation,
+        ) {
+            Ok(_) => {
+                trace!(
+                    "New status after timer activation: {:?}",
+                    *run_info
+                        .unit_table
+                        .get(&unit_id)
+                        .unwrap()
+                        .common
+                        .status
+                        .read()
+                        .unwrap()
+                );
+            }
+            Err(e) => {
+                error!("Error while starting unit from timer activation: {}", e);
+            }
+        }
+        // the timestamps of the unit changed, which might change the next elapse of the timer
+        crate::platform::notify_event_fd(run_info.timer_activation_eventfd);
+    });
+}
+
+/// Sleep until either the wait_time passed or the eventfd fired because the timers need to be looked at again
+fn wait_for_timer(
+    poller: &crate::platform::Poller,
+    eventfd: crate::platform::EventFd,
+    wait_time: Option<Duration>,
+) -> Result<(), String> {
+    if wait_time == Some(Duration::from_secs(0)) {
+        return Ok(());
+    }
+    if poller.wait(wait_time)?.contains(&eventfd.read_end()) {
+        trace!("Interrupted timer wait because the eventfd fired");
+        crate::platform::reset_event_fd(eventfd);
+        trace!("Reset eventfd value");
+        poller.rearm(eventfd.read_end())?;
+    }
+    Ok(())
+}
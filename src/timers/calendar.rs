@@ -0,0 +1,272 @@
+//! Calendar event expressions as used by OnCalendar= (see systemd.time(7)). The supported subset is:
+//!
+//! `[Weekdays] [Year-Month-Day] [Hour:Minute[:Second]]`
+//!
+//! Each component can be `*`, a value, a list (`1,15`), a range (`Mon..Fri`, `1..5`) or a
+//! repetition (`0/15`, `*/2`). The shorthands minutely, hourly, daily, weekly, monthly, yearly/annually,
+//! quarterly and semiannually are supported too. Fractional seconds and timezones are not.
+
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, Timelike};
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+enum CalendarValue {
+    Single(u32),
+    /// Inclusive range
+    Range(u32, u32),
+    /// Start value and step
+    Repeat(u32, u32),
+}
+
+impl CalendarValue {
+    fn matches(&self, value: u32) -> bool {
+        match *self {
+            CalendarValue::Single(v) => v == value,
+            CalendarValue::Range(from, to) => from <= value && value <= to,
+            CalendarValue::Repeat(start, step) => {
+                value >= start && (value - start).checked_rem(step) == Some(0)
+            }
+        }
+    }
+}
+
+/// One component of a calendar expression. None means `*`
+#[derive(Clone, Eq, PartialEq, Debug)]
+struct CalendarComponent(Option<Vec<CalendarValue>>);
+
+impl CalendarComponent {
+    fn matches(&self, value: u32) -> bool {
+        match &self.0 {
+            None => true,
+            Some(values) => values.iter().any(|v| v.matches(value)),
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct CalendarSpec {
+    /// 0 is monday, 6 is sunday
+    weekdays: CalendarComponent,
+    year: CalendarComponent,
+    month: CalendarComponent,
+    day: CalendarComponent,
+    hour: CalendarComponent,
+    minute: CalendarComponent,
+    second: CalendarComponent,
+    original: String,
+}
+
+impl std::fmt::Display for CalendarSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.original)
+    }
+}
+
+const WEEKDAYS: [(&str, &str); 7] = [
+    ("mon", "monday"),
+    ("tue", "tuesday"),
+    ("wed", "wednesday"),
+    ("thu", "thursday"),
+    ("fri", "friday"),
+    ("sat", "saturday"),
+    ("sun", "sunday"),
+];
+
+fn parse_weekday(name: &str) -> Result<u32, String> {
+    let lower = name.to_lowercase();
+    WEEKDAYS
+        .iter()
+        .position(|(short, long)| lower == *short || lower == *long)
+        .map(|pos| pos as u32)
+        .ok_or_else(|| format!("Unknown weekday: {}", name))
+}
+
+fn parse_number(value: &str, min: u32, max: u32) -> Result<u32, String> {
+    let num = value
+        .parse::<u32>()
+        .map_err(|_| format!("Not a number: {}", value))?;
+    if num < min || num > max {
+        return Err(format!(
+            "Value {} out of range (must be in {}..{})",
+            num, min, max
+        ));
+    }
+    Ok(num)
+}
+
+fn parse_component(
+    descr: &str,
+    min: u32,
+    parse_single: &dyn Fn(&str) -> Result<u32, String>,
+) -> Result<CalendarComponent, String> {
+    if descr == "*" {
+        return Ok(CalendarComponent(None));
+    }
+    let mut values = Vec::new();
+    for part in descr.split(',') {
+        let value = if let Some(pos) = part.find('/') {
+            let (start, step) = part.split_at(pos);
+            let step = step[1..]
+                .parse::<u32>()
+                .map_err(|_| format!("Not a valid repetition: {}", part))?;
+            if step == 0 {
+                return Err(format!("Repetition must not be zero: {}", part));
+            }
+            let start = if start == "*" {
+                min
+            } else {
+                parse_single(start)?
+            };
+            CalendarValue::Repeat(start, step)
+        } else if let Some(pos) = part.find("..") {
+            let (from, to) = part.split_at(pos);
+            let from = parse_single(from)?;
+            let to = parse_single(&to[2..])?;
+            if from > to {
+                return Err(format!("Range is reversed: {}", part));
+            }
+            CalendarValue::Range(from, to)
+        } else {
+            CalendarValue::Single(parse_single(part)?)
+        };
+        values.push(value);
+    }
+    Ok(CalendarComponent(Some(values)))
+}
+
+fn expand_shorthand(descr: &str) -> Option<&'static str> {
+    match descr.to_lowercase().as_str() {
+        "minutely" => Some("*-*-* *:*:00"),
+        "hourly" => Some("*-*-* *:00:00"),
+        "daily" => Some("*-*-* 00:00:00"),
+        "weekly" => Some("Mon *-*-* 00:00:00"),
+        "monthly" => Some("*-*-01 00:00:00"),
+        "yearly" | "annually" => Some("*-01-01 00:00:00"),
+        "quarterly" => Some("*-01,04,07,10-01 00:00:00"),
+        "semiannually" => Some("*-01,07-01 00:00:00"),
+        _ => None,
+    }
+}
+
+impl CalendarSpec {
+    pub fn parse(descr: &str) -> Result<CalendarSpec, String> {
+        let original = descr.trim().to_owned();
+        let expanded = expand_shorthand(&original).unwrap_or(&original);
+
+        let mut weekdays = CalendarComponent(None);
+        let mut date: Option<&str> = None;
+        let mut time: Option<&str> = None;
+
+        for (idx, token) in expanded.split_whitespace().enumerate() {
+            if idx == 0 && token.starts_with(|c: char| c.is_ascii_alphabetic()) {
+                weekdays = parse_component(token, 0, &parse_weekday)?;
+            } else if token.contains(':') && time.is_none() {
+                time = Some(token);
+            } else if token.contains('-') && date.is_none() && time.is_none() {
+                date = Some(token);
+            } else {
+                return Err(format!("Unexpected part in calendar spec: {}", token));
+            }
+        }
+
+        if date.is_none() && time.is_none() && weekdays.0.is_none() {
+            return Err(format!("Empty calendar spec: {}", original));
+        }
+
+        let (year, month, day) = match date {
+            None => (
+                CalendarComponent(None),
+                CalendarComponent(None),
+                CalendarComponent(None),
+            ),
+            Some(date) => {
+                let parts: Vec<&str> = date.split('-').collect();
+                let (year, month, day) = match parts.len() {
+                    3 => (parts[0], parts[1], parts[2]),
+                    2 => ("*", parts[0], parts[1]),
+                    _ => return Err(format!("Not a valid date: {}", date)),
+                };
+                (
+                    parse_component(year, 1970, &|v| parse_number(v, 1970, 9999))?,
+                    parse_component(month, 1, &|v| parse_number(v, 1, 12))?,
+                    parse_component(day, 1, &|v| parse_number(v, 1, 31))?,
+                )
+            }
+        };
+
+        let (hour, minute, second) = match time {
+            // only a date/weekday given means midnight
+            None => (
+                CalendarComponent(Some(vec![CalendarValue::Single(0)])),
+                CalendarComponent(Some(vec![CalendarValue::Single(0)])),
+                CalendarComponent(Some(vec![CalendarValue::Single(0)])),
+            ),
+            Some(time) => {
+                let parts: Vec<&str> = time.split(':').collect();
+                let (hour, minute, second) = match parts.len() {
+                    3 => (parts[0], parts[1], parts[2]),
+                    2 => (parts[0], parts[1], "00"),
+                    _ => return Err(format!("Not a valid time: {}", time)),
+                };
+                (
+                    parse_component(hour, 0, &|v| parse_number(v, 0, 23))?,
+                    parse_component(minute, 0, &|v| parse_number(v, 0, 59))?,
+                    parse_component(second, 0, &|v| parse_number(v, 0, 59))?,
+                )
+            }
+        };
+
+        Ok(CalendarSpec {
+            weekdays,
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+            original,
+        })
+    }
+
+    /// Find the first point in time strictly after `after` that matches this spec. Returns None if there is no
+    /// such point in the next hundred years.
+    pub fn next_elapse(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut t = after.with_nanosecond(0)? + Duration::seconds(1);
+        let limit = after.year() + 100;
+
+        while t.year() <= limit {
+            if !self.year.matches(t.year() as u32) {
+                t = NaiveDate::from_ymd_opt(t.year() + 1, 1, 1)?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !self.month.matches(t.month()) {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !self.day.matches(t.day())
+                || !self.weekdays.matches(t.weekday().num_days_from_monday())
+            {
+                t = t.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+                continue;
+            }
+            if !self.hour.matches(t.hour()) {
+                t = t.date().and_hms_opt(t.hour(), 0, 0)? + Duration::hours(1);
+                continue;
+            }
+            if !self.minute.matches(t.minute()) {
+                t = t.date().and_hms_opt(t.hour(), t.minute(), 0)? + Duration::minutes(1);
+                continue;
+            }
+            if !self.second.matches(t.second()) {
+                t += Duration::seconds(1);
+                continue;
+            }
+            return Some(t);
+        }
+        None
+    }
+}
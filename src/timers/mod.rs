@@ -0,0 +1,168 @@
+//! Everything needed to find out when a timer unit elapses next. The scheduling itself happens in timer_activation.rs
+
+mod calendar;
+pub use calendar::*;
+
+use log::{trace, warn};
+
+use crate::units::TimerConfig;
+
+use chrono::TimeZone;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// The timestamps of the unit a timer triggers, needed for OnUnitActiveSec= and OnUnitInactiveSec=
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TriggeredUnitTimes {
+    pub active_since: Option<SystemTime>,
+    pub inactive_since: Option<SystemTime>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Timer {
+    /// When the timer unit itself was last activated
+    pub activated_at: Option<SystemTime>,
+    pub last_trigger: Option<SystemTime>,
+    /// Only used for reporting, the scheduler recalculates this whenever it wakes up
+    pub next_elapse: Option<SystemTime>,
+    /// Offset for RandomizedDelaySec=. This is rolled again after each trigger
+    pub randomized_delay: Duration,
+}
+
+fn stamp_path(state_dir: &Path, name: &str) -> PathBuf {
+    state_dir.join("timers").join(format!("{}.stamp", name))
+}
+
+fn read_stamp(state_dir: &Path, name: &str) -> Option<SystemTime> {
+    let content = std::fs::read_to_string(stamp_path(state_dir, name)).ok()?;
+    let secs = content.trim().parse::<u64>().ok()?;
+    Some(SystemTime::UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+fn write_stamp(state_dir: &Path, name: &str, time: SystemTime) -> Result<(), String> {
+    let path = stamp_path(state_dir, name);
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Could not create dir {:?}: {}", parent, e))?;
+    }
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    std::fs::write(&path, format!("{}\n", secs))
+        .map_err(|e| format!("Could not write timer stamp {:?}: {}", path, e))
+}
+
+/// There is no dependency on a rng crate, so this just takes the clocks nanoseconds. This is good enough to spread
+/// the timers of multiple machines.
+fn roll_randomized_delay(max: Duration) -> Duration {
+    let max_nanos = max.as_nanos() as u64;
+    if max_nanos == 0 {
+        return Duration::from_secs(0);
+    }
+    let seed = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64 ^ d.as_secs().rotate_left(17))
+        .unwrap_or(0)
+        ^ (std::process::id() as u64).rotate_left(41);
+    // scramble the bits a bit (splitmix64 finalizer)
+    let mut x = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^= x >> 31;
+    Duration::from_nanos(x % max_nanos)
+}
+
+/// Next calendar elapse strictly after 'after'. Points in time that do not exist in the local timezone (because of DST changes)
+/// are skipped.
+fn next_calendar_elapse(spec: &CalendarSpec, after: SystemTime) -> Option<SystemTime> {
+    let mut after = chrono::DateTime::<chrono::Local>::from(after).naive_local();
+    // only retry a few times, there are never many non-existing local times in a row
+    for _ in 0..10 {
+        let next = spec.next_elapse(after)?;
+        if let Some(local) = chrono::Local.from_local_datetime(&next).earliest() {
+            return Some(SystemTime::from(local));
+        }
+        after = next;
+    }
+    None
+}
+
+impl Timer {
+    pub fn activate(&mut self, conf: &TimerConfig, name: &str, state_dir: &Path) {
+        self.activated_at = Some(SystemTime::now());
+        if conf.persistent && self.last_trigger.is_none() {
+            self.last_trigger = read_stamp(state_dir, name);
+            trace!(
+                "Loaded last trigger for timer {}: {:?}",
+                name,
+                self.last_trigger
+            );
+        }
+        self.randomized_delay = roll_randomized_delay(conf.randomized_delay);
+    }
+
+    pub fn deactivate(&mut self) {
+        self.activated_at = None;
+        self.next_elapse = None;
+    }
+
+    /// Record that the timer elapsed at 'now'
+    pub fn triggered(&mut self, conf: &TimerConfig, name: &str, state_dir: &Path, now: SystemTime) {
+        self.last_trigger = Some(now);
+        if conf.persistent {
+            if let Err(e) = write_stamp(state_dir, name, now) {
+                warn!("{}", e);
+            }
+        }
+        self.randomized_delay = roll_randomized_delay(conf.randomized_delay);
+    }
+
+    /// Calculate the next point in time this timer elapses. Might be in the past, which means
+    /// the timer elapsed and should trigger its unit.
+    pub fn calc_next_elapse(
+        &self,
+        conf: &TimerConfig,
+        boot_time: SystemTime,
+        unit_times: TriggeredUnitTimes,
+    ) -> Option<SystemTime> {
+        let activated_at = self.activated_at?;
+        let not_triggered_since =
+            |t: &SystemTime| self.last_trigger.map(|last| last < *t).unwrap_or(true);
+
+        let mut candidates = Vec::new();
+        for offset in &conf.on_boot {
+            candidates.push(boot_time + *offset);
+        }
+        if let Some(active_since) = unit_times.active_since {
+            for offset in &conf.on_unit_active {
+                candidates.push(active_since + *offset);
+            }
+        }
+        if let Some(inactive_since) = unit_times.inactive_since {
+            for offset in &conf.on_unit_inactive {
+                candidates.push(inactive_since + *offset);
+            }
+        }
+        // the monotonic triggers elapse only once for each point in time they are relative to
+        let mut candidates: Vec<SystemTime> =
+            candidates.into_iter().filter(not_triggered_since).collect();
+
+        // Persistent timers catch up on elapses that were missed while the timer was not running
+        let calendar_base = match self.last_trigger {
+            Some(last) if conf.persistent => last,
+            Some(last) => last.max(activated_at),
+            None => activated_at,
+        };
+        for spec in &conf.on_calendar {
+            if let Some(next) = next_calendar_elapse(spec, calendar_base) {
+                candidates.push(next);
+            }
+        }
+
+        candidates
+            .into_iter()
+            .min()
+            .map(|next| next + self.randomized_delay)
+    }
+}
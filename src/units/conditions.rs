@@ -0,0 +1,206 @@
+//! The Condition*= and Assert*= settings of the [Unit] section. They are checked right before a unit is started. A
+//! failed condition skips the unit, a failed assert makes the start fail.
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+/// Users with a uid up to this are system users for ConditionUser=@system
+const SYSTEM_UID_MAX: u32 = 999;
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum ConditionKind {
+    PathExists(PathBuf),
+    PathIsDirectory(PathBuf),
+    DirectoryNotEmpty(PathBuf),
+    FileNotEmpty(PathBuf),
+    FileIsExecutable(PathBuf),
+    /// Either a single word or a key=value pair that must be on the kernel command line
+    KernelCommandLine(String),
+    /// A boolean, "vm", "container" or the name of a specific hypervisor or container manager
+    Virtualization(String),
+    /// A user name, a uid or "@system"
+    User(String),
+    /// A group name or a gid
+    Group(String),
+    /// A hostname or a machine id
+    Host(String),
+    /// Either the name of a variable or a key=value pair that must be in the environment of rustysd
+    Environment(String),
+    FirstBoot(bool),
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct UnitCondition {
+    pub kind: ConditionKind,
+    /// Set by a leading "!", the check has to fail for the condition to hold
+    pub negate: bool,
+    /// Set by a leading "|". Of all triggering conditions only one has to hold.
+    pub triggering: bool,
+}
+
+impl std::fmt::Display for UnitCondition {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let value = match &self.kind {
+            ConditionKind::PathExists(path)
+            | ConditionKind::PathIsDirectory(path)
+            | ConditionKind::DirectoryNotEmpty(path)
+            | ConditionKind::FileNotEmpty(path)
+            | ConditionKind::FileIsExecutable(path) => path.to_string_lossy().into_owned(),
+            ConditionKind::KernelCommandLine(value)
+            | ConditionKind::Virtualization(value)
+            | ConditionKind::User(value)
+            | ConditionKind::Group(value)
+            | ConditionKind::Host(value)
+            | ConditionKind::Environment(value) => value.clone(),
+            ConditionKind::FirstBoot(first_boot) => {
+                if *first_boot { "yes" } else { "no" }.to_owned()
+            }
+        };
+        write!(
+            f,
+            "{}={}{}{}",
+            self.setting_name(),
+            if self.triggering { "|" } else { "" },
+            if self.negate { "!" } else { "" },
+            value
+        )
+    }
+}
+
+impl UnitCondition {
+    /// The name of the setting without the Condition or Assert prefix
+    pub fn setting_name(&self) -> &'static str {
+        match self.kind {
+            ConditionKind::PathExists(_) => "PathExists",
+            ConditionKind::PathIsDirectory(_) => "PathIsDirectory",
+            ConditionKind::DirectoryNotEmpty(_) => "DirectoryNotEmpty",
+            ConditionKind::FileNotEmpty(_) => "FileNotEmpty",
+            ConditionKind::FileIsExecutable(_) => "FileIsExecutable",
+            ConditionKind::KernelCommandLine(_) => "KernelCommandLine",
+            ConditionKind::Virtualization(_) => "Virtualization",
+            ConditionKind::User(_) => "User",
+            ConditionKind::Group(_) => "Group",
+            ConditionKind::Host(_) => "Host",
+            ConditionKind::Environment(_) => "Environment",
+            ConditionKind::FirstBoot(_) => "FirstBoot",
+        }
+    }
+
+    pub fn holds(&self) -> bool {
+        self.check() != self.negate
+    }
+
+    fn check(&self) -> bool {
+        match &self.kind {
+            ConditionKind::PathExists(path) => path.exists(),
+            ConditionKind::PathIsDirectory(path) => path.is_dir(),
+            ConditionKind::DirectoryNotEmpty(path) => std::fs::read_dir(path)
+                .map(|mut entries| entries.next().is_some())
+                .unwrap_or(false),
+            ConditionKind::FileNotEmpty(path) => std::fs::metadata(path)
+                .map(|meta| meta.is_file() && meta.len() > 0)
+                .unwrap_or(false),
+            ConditionKind::FileIsExecutable(path) => std::fs::metadata(path)
+                .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false),
+            ConditionKind::KernelCommandLine(value) => {
+                let cmdline = std::fs::read_to_string("/proc/cmdline").unwrap_or_default();
+                cmdline.split_whitespace().any(|word| {
+                    // a single word also matches key=value pairs with that key
+                    word == value
+                        || (!value.contains('=') && word.starts_with(&format!("{}=", value)))
+                })
+            }
+            ConditionKind::Virtualization(value) => check_virtualization(value),
+            ConditionKind::User(value) => check_user(value),
+            ConditionKind::Group(value) => check_group(value),
+            ConditionKind::Host(value) => check_host(value),
+            ConditionKind::Environment(value) => match value.split_once('=') {
+                Some((key, expected)) => std::env::var_os(key)
+                    .map(|actual| actual == *expected)
+                    .unwrap_or(false),
+                None => std::env::var_os(value).is_some(),
+            },
+            ConditionKind::FirstBoot(first_boot) => is_first_boot() == *first_boot,
+        }
+    }
+}
+
+/// Check a list of conditions like systemd does: all normal conditions have to hold and if there are triggering
+/// conditions at least one of them has to hold. Returns the condition that failed.
+pub fn check_conditions(conditions: &[UnitCondition]) -> Result<(), &UnitCondition> {
+    let mut triggering = conditions.iter().filter(|cond| cond.triggering).peekable();
+    if let Some(first_triggering) = triggering.peek().copied() {
+        if !triggering.any(|cond| cond.holds()) {
+            return Err(first_triggering);
+        }
+    }
+    match conditions
+        .iter()
+        .filter(|cond| !cond.triggering)
+        .find(|cond| !cond.holds())
+    {
+        Some(failed) => Err(failed),
+        None => Ok(()),
+    }
+}
+
+fn check_virtualization(value: &str) -> bool {
+    let virt = crate::platform::virtualization::detect_virtualization();
+    match value {
+        "vm" => matches!(virt, Some(virt) if virt.is_vm),
+        "container" => matches!(virt, Some(virt) if !virt.is_vm),
+        "1" | "yes" | "true" | "on" => virt.is_some(),
+        "0" | "no" | "false" | "off" => virt.is_none(),
+        _ => matches!(virt, Some(virt) if virt.name == value),
+    }
+}
+
+fn check_user(value: &str) -> bool {
+    let uid = nix::unistd::getuid();
+    if value == "@system" {
+        return uid.as_raw() <= SYSTEM_UID_MAX;
+    }
+    match value.parse::<u32>() {
+        Ok(expected) => uid.as_raw() == expected,
+        Err(_) => crate::platform::pwnam::getpwnam_r(value)
+            .map(|user| user.uid == uid)
+            .unwrap_or(false),
+    }
+}
+
+fn check_group(value: &str) -> bool {
+    let expected = match value.parse::<u32>() {
+        Ok(gid) => nix::unistd::Gid::from_raw(gid),
+        Err(_) => match crate::platform::grnam::getgrnam_r(value) {
+            Ok(group) => group.gid,
+            Err(_) => return false,
+        },
+    };
+    nix::unistd::getgid() == expected
+        || nix::unistd::getegid() == expected
+        || nix::unistd::getgroups()
+            .map(|groups| groups.contains(&expected))
+            .unwrap_or(false)
+}
+
+fn check_host(value: &str) -> bool {
+    let hostname_matches = nix::unistd::gethostname()
+        .map(|hostname| hostname.to_string_lossy().eq_ignore_ascii_case(value))
+        .unwrap_or(false);
+    hostname_matches || machine_id().map(|id| id == value).unwrap_or(false)
+}
+
+fn machine_id() -> Option<String> {
+    std::fs::read_to_string("/etc/machine-id")
+        .ok()
+        .map(|id| id.trim().to_owned())
+}
+
+/// Like systemd this is the first boot if /etc/machine-id is not yet populated
+fn is_first_boot() -> bool {
+    match machine_id() {
+        Some(id) => id.is_empty() || id == "uninitialized",
+        None => true,
+    }
+}
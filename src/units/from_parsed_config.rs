@@ -9,31 +9,59 @@ use std::convert::TryInto;
 use std::path::PathBuf;
 use std::sync::RwLock;
 
+/// Slices nest by their names: the cgroup of a-b.slice is a.slice/a-b.slice. The root slice (-.slice) is the cgroup of
+/// rustysd itself.
 #[cfg(feature = "cgroups")]
-fn make_cgroup_path(srvc_name: &str) -> Result<PathBuf, String> {
+fn slice_cgroup_dir(slice: &UnitId) -> PathBuf {
+    if slice.name == "-.slice" {
+        return PathBuf::new();
+    }
+    match slice.parent_slice() {
+        Some(parent) => slice_cgroup_dir(&parent).join(&slice.name),
+        None => PathBuf::from(&slice.name),
+    }
+}
+
+#[cfg(feature = "cgroups")]
+fn make_cgroup_path(unit_name: &str, slice: Option<&UnitId>) -> Result<PathBuf, String> {
     let rustysd_cgroup =
         crate::platform::cgroups::get_own_freezer(&PathBuf::from("/sys/fs/cgroup"))
             .map_err(|e| format!("Couldnt get own cgroup: {}", e))?;
-    let service_cgroup = rustysd_cgroup.join(srvc_name);
+    let parent_cgroup = match slice {
+        Some(slice) => rustysd_cgroup.join(slice_cgroup_dir(slice)),
+        None => rustysd_cgroup,
+    };
+    let unit_cgroup = parent_cgroup.join(unit_name);
     trace!(
-        "Service {} will be moved into cgroup: {:?}",
-        srvc_name,
-        service_cgroup
+        "Unit {} will be moved into cgroup: {:?}",
+        unit_name,
+        unit_cgroup
     );
-    Ok(service_cgroup)
+    Ok(unit_cgroup)
 }
 
 #[cfg(not(feature = "cgroups"))]
-fn make_cgroup_path(_srvc_name: &str) -> Result<PathBuf, String> {
+fn make_cgroup_path(_unit_name: &str, _slice: Option<&UnitId>) -> Result<PathBuf, String> {
     // doesnt matter, wont be used anyways
     Ok(PathBuf::from("/ree"))
 }
 
+/// Units in a slice require it and are started after it
+fn add_slice_relations(common: &mut Common, slice: &UnitId) {
+    common.dependencies.requires.push(slice.clone());
+    common.dependencies.after.push(slice.clone());
+    common.unit.refs_by_name.push(slice.clone());
+}
+
 pub fn unit_from_parsed_service(conf: ParsedServiceConfig) -> Result<Unit, String> {
+    let slice: Option<UnitId> = match &conf.srvc.slice {
+        Some(name) => Some(name.as_str().try_into()?),
+        None => None,
+    };
     // TODO make the cgroup path dynamic so multiple rustysd instances can exist
     let platform_specific = PlatformSpecificServiceFields {
         #[cfg(target_os = "linux")]
-        cgroup_path: make_cgroup_path(&conf.common.name)?,
+        cgroup_path: make_cgroup_path(&conf.common.name, slice.as_ref())?,
     };
 
     let mut sockets: Vec<UnitId> = Vec::new();
@@ -43,6 +71,9 @@ pub fn unit_from_parsed_service(conf: ParsedServiceConfig) -> Result<Unit, Strin
 
     let mut common = make_common_from_parsed(conf.common.unit, conf.common.install)?;
     common.unit.refs_by_name.extend(sockets.iter().cloned());
+    if let Some(slice) = &slice {
+        add_slice_relations(&mut common, slice);
+    }
 
     Ok(Unit {
         id: UnitId {
@@ -54,19 +85,33 @@ pub fn unit_from_parsed_service(conf: ParsedServiceConfig) -> Result<Unit, Strin
             conf: ServiceConfig {
                 exec_config: conf.srvc.exec_section.try_into()?,
                 sockets: sockets,
-                accept: conf.srvc.accept,
+                standard_input: conf.srvc.standard_input,
                 dbus_name: conf.srvc.dbus_name,
+                pid_file: conf.srvc.pid_file,
+                watchdog_sec: conf.srvc.watchdog_sec,
+                watchdog_signal: conf.srvc.watchdog_signal,
+                fd_store_max: conf.srvc.fd_store_max,
                 restart: conf.srvc.restart,
+                restart_sec: conf
+                    .srvc
+                    .restart_sec
+                    .unwrap_or_else(|| std::time::Duration::from_millis(100)),
+                restart_steps: conf.srvc.restart_steps,
+                restart_max_delay: conf.srvc.restart_max_delay,
                 notifyaccess: conf.srvc.notifyaccess,
                 exec: conf.srvc.exec,
                 startpre: conf.srvc.startpre,
                 startpost: conf.srvc.startpost,
                 stop: conf.srvc.stop,
+                reload: conf.srvc.reload,
                 stoppost: conf.srvc.stoppost,
                 srcv_type: conf.srvc.srcv_type,
                 starttimeout: conf.srvc.starttimeout,
                 stoptimeout: conf.srvc.stoptimeout,
                 generaltimeout: conf.srvc.generaltimeout,
+                slice,
+                resources: conf.srvc.resources,
+                kill: conf.srvc.kill,
                 platform_specific,
             },
             state: RwLock::new(ServiceState {
@@ -76,10 +121,20 @@ pub fn unit_from_parsed_service(conf: ParsedServiceConfig) -> Result<Unit, Strin
                     status_msgs: Vec::new(),
                     process_group: None,
                     signaled_ready: false,
+                    signaled_reloading: false,
+                    signaled_stopping: false,
+                    extend_timeout: None,
+                    errno: None,
+                    connection: None,
+                    serves_connection: false,
+                    monitor_env: Vec::new(),
                     notifications: None,
                     notifications_path: None,
                     stdout: None,
                     stderr: None,
+                    exec_status: None,
+                    cgroup_events: None,
+                    watchdog: Default::default(),
                     notifications_buffer: String::new(),
                     stdout_buffer: Vec::new(),
                     stderr_buffer: Vec::new(),
@@ -95,6 +150,32 @@ pub fn unit_from_parsed_socket(conf: ParsedSocketConfig) -> Result<Unit, String>
         services.push(srvc.as_str().try_into()?);
     }
 
+    if conf.sock.accept {
+        if !services.is_empty() {
+            return Err(format!(
+                "Socket {} sets Accept=yes and Service=, the instances are always started from the template {}@.service",
+                conf.common.name,
+                conf.common.name.trim_end_matches(".socket")
+            ));
+        }
+        let only_streams = conf.sock.sockets.iter().all(|sock| match &sock.kind {
+            SocketKind::Stream(_) | SocketKind::Sequential(_) => true,
+            SocketKind::Datagram(_) | SocketKind::Fifo(_) => false,
+        });
+        if !only_streams {
+            return Err(format!(
+                "Socket {} sets Accept=yes, that only works with ListenStream= and ListenSequentialPacket=",
+                conf.common.name
+            ));
+        }
+    }
+    // like in systemd
+    let max_connections = conf.sock.max_connections.unwrap_or(64);
+    let max_connections_per_source = conf
+        .sock
+        .max_connections_per_source
+        .unwrap_or(max_connections);
+
     let mut common = make_common_from_parsed(conf.common.unit, conf.common.install)?;
     common.unit.refs_by_name.extend(services.iter().cloned());
 
@@ -109,11 +190,18 @@ pub fn unit_from_parsed_socket(conf: ParsedSocketConfig) -> Result<Unit, String>
                 exec_config: conf.sock.exec_section.try_into()?,
                 filedesc_name: conf.sock.filedesc_name.unwrap_or("unknown".to_owned()),
                 services: services,
+                accept: conf.sock.accept,
+                max_connections,
+                max_connections_per_source,
                 sockets: conf.sock.sockets.into_iter().map(Into::into).collect(),
             },
             state: RwLock::new(SocketState {
                 common: CommonState::default(),
-                sock: Socket { activated: false },
+                sock: Socket {
+                    activated: false,
+                    accepted: 0,
+                    connections: Vec::new(),
+                },
             }),
         }),
     })
@@ -133,6 +221,113 @@ pub fn unit_from_parsed_target(conf: ParsedTargetConfig) -> Result<Unit, String>
     })
 }
 
+pub fn unit_from_parsed_timer(conf: ParsedTimerConfig) -> Result<Unit, String> {
+    let unit: UnitId = match &conf.timer.unit {
+        Some(name) => name.as_str().try_into()?,
+        None => {
+            // by default a timer activates the service with the same name
+            let timer_id: UnitId = conf.common.name.as_str().try_into()?;
+            format!("{}.service", timer_id.name_without_suffix())
+                .as_str()
+                .try_into()?
+        }
+    };
+
+    let mut common = make_common_from_parsed(conf.common.unit, conf.common.install)?;
+    common.unit.refs_by_name.push(unit.clone());
+
+    Ok(Unit {
+        id: UnitId {
+            kind: UnitIdKind::Timer,
+            name: conf.common.name,
+        },
+        common,
+        specific: Specific::Timer(TimerSpecific {
+            conf: TimerConfig {
+                on_calendar: conf.timer.on_calendar,
+                on_boot: conf.timer.on_boot,
+                on_unit_active: conf.timer.on_unit_active,
+                on_unit_inactive: conf.timer.on_unit_inactive,
+                // same defaults as systemd
+                accuracy: conf
+                    .timer
+                    .accuracy
+                    .unwrap_or_else(|| std::time::Duration::from_secs(60)),
+                randomized_delay: conf.timer.randomized_delay.unwrap_or_default(),
+                persistent: conf.timer.persistent,
+                unit,
+            },
+            state: RwLock::new(TimerState {
+                common: CommonState::default(),
+                timer: crate::timers::Timer::default(),
+            }),
+        }),
+    })
+}
+
+pub fn unit_from_parsed_path(conf: ParsedPathConfig) -> Result<Unit, String> {
+    let unit: UnitId = match &conf.path.unit {
+        Some(name) => name.as_str().try_into()?,
+        None => {
+            // by default a path unit activates the service with the same name
+            let path_id: UnitId = conf.common.name.as_str().try_into()?;
+            format!("{}.service", path_id.name_without_suffix())
+                .as_str()
+                .try_into()?
+        }
+    };
+
+    let mut common = make_common_from_parsed(conf.common.unit, conf.common.install)?;
+    common.unit.refs_by_name.push(unit.clone());
+
+    Ok(Unit {
+        id: UnitId {
+            kind: UnitIdKind::Path,
+            name: conf.common.name,
+        },
+        common,
+        specific: Specific::Path(PathSpecific {
+            conf: PathConfig {
+                conditions: conf.path.conditions,
+                make_directory: conf.path.make_directory,
+                unit,
+            },
+            state: RwLock::new(PathState {
+                common: CommonState::default(),
+                path: crate::paths::PathWatch::default(),
+            }),
+        }),
+    })
+}
+
+pub fn unit_from_parsed_slice(conf: ParsedSliceConfig) -> Result<Unit, String> {
+    let id: UnitId = conf.common.name.as_str().try_into()?;
+    let parent = id.parent_slice();
+    let platform_specific = PlatformSpecificServiceFields {
+        #[cfg(target_os = "linux")]
+        cgroup_path: make_cgroup_path(&id.name, parent.as_ref())?,
+    };
+
+    let mut common = make_common_from_parsed(conf.common.unit, conf.common.install)?;
+    if let Some(parent) = &parent {
+        add_slice_relations(&mut common, parent);
+    }
+
+    Ok(Unit {
+        id,
+        common,
+        specific: Specific::Slice(SliceSpecific {
+            conf: SliceConfig {
+                resources: conf.resources,
+                platform_specific,
+            },
+            state: RwLock::new(SliceState {
+                common: CommonState::default(),
+            }),
+        }),
+    })
+}
+
 impl From<ParsedSingleSocketConfig> for SingleSocketConfig {
     fn from(parsed: ParsedSingleSocketConfig) -> SingleSocketConfig {
         SingleSocketConfig {
@@ -200,6 +395,10 @@ impl std::convert::TryFrom<ParsedExecSection> for ExecConfig {
             supplementary_groups: supp_gids,
             stderr_path: parsed.stderr_path,
             stdout_path: parsed.stdout_path,
+            tty_path: parsed
+                .tty_path
+                .unwrap_or_else(|| std::path::PathBuf::from("/dev/console")),
+            syslog_identifier: parsed.syslog_identifier,
             environment: parsed.environment,
         })
     }
@@ -233,6 +432,31 @@ fn make_common_from_parsed(
     for name in unit.before {
         before.push(name.as_str().try_into()?);
     }
+    let mut conflicts = Vec::new();
+    for name in unit.conflicts {
+        conflicts.push(name.as_str().try_into()?);
+    }
+    let mut binds_to = Vec::new();
+    for name in unit.binds_to {
+        binds_to.push(name.as_str().try_into()?);
+    }
+    let mut part_of = Vec::new();
+    for name in unit.part_of {
+        part_of.push(name.as_str().try_into()?);
+    }
+    let mut requisite = Vec::new();
+    for name in unit.requisite {
+        requisite.push(name.as_str().try_into()?);
+    }
+
+    let mut on_failure = Vec::new();
+    for name in unit.on_failure {
+        on_failure.push(name.as_str().try_into()?);
+    }
+    let mut on_success = Vec::new();
+    for name in unit.on_success {
+        on_success.push(name.as_str().try_into()?);
+    }
 
     let mut refs_by_name = Vec::new();
     refs_by_name.extend(wants.iter().cloned());
@@ -241,12 +465,30 @@ fn make_common_from_parsed(
     refs_by_name.extend(required_by.iter().cloned());
     refs_by_name.extend(before.iter().cloned());
     refs_by_name.extend(after.iter().cloned());
+    refs_by_name.extend(conflicts.iter().cloned());
+    refs_by_name.extend(binds_to.iter().cloned());
+    refs_by_name.extend(part_of.iter().cloned());
+    refs_by_name.extend(requisite.iter().cloned());
+    refs_by_name.extend(on_failure.iter().cloned());
+    refs_by_name.extend(on_success.iter().cloned());
 
     Ok(Common {
         status: RwLock::new(UnitStatus::NeverStarted),
         unit: UnitConfig {
             description: unit.description,
             refs_by_name,
+            template: None,
+            dropins: Vec::new(),
+            start_limit_interval: unit
+                .start_limit_interval
+                .unwrap_or_else(|| std::time::Duration::from_secs(10)),
+            start_limit_burst: unit.start_limit_burst.unwrap_or(5),
+            on_failure,
+            on_success,
+            on_failure_job_mode: unit.on_failure_job_mode.unwrap_or_default(),
+            on_success_job_mode: unit.on_success_job_mode.unwrap_or_default(),
+            conditions: unit.conditions,
+            asserts: unit.asserts,
         },
         dependencies: Dependencies {
             wants,
@@ -255,6 +497,14 @@ fn make_common_from_parsed(
             required_by,
             after,
             before,
+            conflicts,
+            conflicted_by: Vec::new(),
+            binds_to,
+            bound_by: Vec::new(),
+            part_of,
+            consists_of: Vec::new(),
+            requisite,
+            requisite_of: Vec::new(),
         },
     })
 }
@@ -277,6 +527,21 @@ impl std::convert::TryInto<UnitId> for &str {
                 name: self.to_owned(),
                 kind: UnitIdKind::Socket,
             })
+        } else if self.ends_with(".timer") {
+            Ok(UnitId {
+                name: self.to_owned(),
+                kind: UnitIdKind::Timer,
+            })
+        } else if self.ends_with(".path") {
+            Ok(UnitId {
+                name: self.to_owned(),
+                kind: UnitIdKind::Path,
+            })
+        } else if self.ends_with(".slice") {
+            Ok(UnitId {
+                name: self.to_owned(),
+                kind: UnitIdKind::Slice,
+            })
         } else {
             Err(format!(
                 "{} is not a valid unit name. The suffix is not supported.",
@@ -304,3 +569,21 @@ impl std::convert::TryFrom<ParsedTargetConfig> for Unit {
         unit_from_parsed_target(conf)
     }
 }
+impl std::convert::TryFrom<ParsedTimerConfig> for Unit {
+    type Error = String;
+    fn try_from(conf: ParsedTimerConfig) -> Result<Unit, String> {
+        unit_from_parsed_timer(conf)
+    }
+}
+impl std::convert::TryFrom<ParsedPathConfig> for Unit {
+    type Error = String;
+    fn try_from(conf: ParsedPathConfig) -> Result<Unit, String> {
+        unit_from_parsed_path(conf)
+    }
+}
+impl std::convert::TryFrom<ParsedSliceConfig> for Unit {
+    type Error = String;
+    fn try_from(conf: ParsedSliceConfig) -> Result<Unit, String> {
+        unit_from_parsed_slice(conf)
+    }
+}
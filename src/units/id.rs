@@ -5,6 +5,9 @@ pub enum UnitIdKind {
     Target,
     Socket,
     Service,
+    Timer,
+    Path,
+    Slice,
 }
 
 #[derive(Clone, Eq, PartialEq, Hash)]
@@ -17,6 +20,49 @@ impl UnitId {
         let split: Vec<_> = self.name.split('.').collect();
         split[0..split.len() - 1].join(".")
     }
+
+    /// The part before the '@' for templates and their instances, the name without the suffix otherwise
+    pub fn prefix(&self) -> String {
+        let name = self.name_without_suffix();
+        match name.find('@') {
+            Some(pos) => name[..pos].to_owned(),
+            None => name,
+        }
+    }
+
+    /// The part between the '@' and the suffix (bar for foo@bar.service). Templates have an empty instance.
+    pub fn instance(&self) -> Option<String> {
+        let name = self.name_without_suffix();
+        name.find('@').map(|pos| name[pos + 1..].to_owned())
+    }
+
+    pub fn is_template(&self) -> bool {
+        self.instance().map(|i| i.is_empty()).unwrap_or(false)
+    }
+
+    /// Slices nest by their names, the parent of a-b.slice is a.slice. Top level slices and the root slice (-.slice)
+    /// have no parent.
+    pub fn parent_slice(&self) -> Option<UnitId> {
+        if self.kind != UnitIdKind::Slice {
+            return None;
+        }
+        let name = self.name_without_suffix();
+        name.rfind('-').filter(|pos| *pos > 0).map(|pos| UnitId {
+            kind: UnitIdKind::Slice,
+            name: format!("{}.slice", &name[..pos]),
+        })
+    }
+
+    /// The name of the template this unit is an instance of (foo@.service for foo@bar.service)
+    pub fn template_name(&self) -> Option<String> {
+        match self.instance() {
+            Some(instance) if !instance.is_empty() => {
+                let suffix = &self.name[self.name_without_suffix().len()..];
+                Some(format!("{}@{}", self.prefix(), suffix))
+            }
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Debug for UnitId {
@@ -27,6 +27,35 @@ pub fn prune_units(
     let mut ids_to_keep = vec![startunit_id.clone()];
     crate::units::collect_unit_start_subgraph(&mut ids_to_keep, unit_table);
 
+    // units that are triggered by timers, path units, OnFailure= or OnSuccess= are not started directly but need to be
+    // kept too. The same goes for units that are only checked by Requisite=, otherwise the check would silently
+    // disappear.
+    loop {
+        let triggered_ids: Vec<UnitId> = ids_to_keep
+            .iter()
+            .flat_map(|id| {
+                let unit = unit_table.get(id).unwrap();
+                let mut ids = unit.common.dependencies.requisite.clone();
+                ids.extend(unit.common.unit.on_failure.iter().cloned());
+                ids.extend(unit.common.unit.on_success.iter().cloned());
+                match &unit.specific {
+                    Specific::Timer(specific) => ids.push(specific.conf.unit.clone()),
+                    Specific::Path(specific) => ids.push(specific.conf.unit.clone()),
+                    _ => {}
+                }
+                ids
+            })
+            .filter(|id| unit_table.contains_key(id) && !ids_to_keep.contains(id))
+            .collect();
+        if triggered_ids.is_empty() {
+            break;
+        }
+        ids_to_keep.extend(triggered_ids);
+        crate::units::collect_unit_start_subgraph(&mut ids_to_keep, unit_table);
+        ids_to_keep.sort();
+        ids_to_keep.dedup();
+    }
+
     // walk the tree along the wants/requires/before/... relations and record which ids are needed
     //find_needed_units_recursive(startunit_id, unit_table, &mut ids_to_keep);
 
@@ -64,6 +93,9 @@ pub fn prune_units(
                     .collect()
             }
             Specific::Target(_) => { /**/ }
+            Specific::Timer(_) => { /**/ }
+            Specific::Path(_) => { /**/ }
+            Specific::Slice(_) => { /**/ }
         }
 
         unit.common.dependencies.before = unit
@@ -120,12 +152,85 @@ pub fn prune_units(
             .map(|id| id.clone())
             .collect();
 
+        unit.common.dependencies.conflicts = unit
+            .common
+            .dependencies
+            .conflicts
+            .iter()
+            .filter(|id| ids_to_keep.contains(id))
+            .cloned()
+            .collect();
+
+        unit.common.dependencies.conflicted_by = unit
+            .common
+            .dependencies
+            .conflicted_by
+            .iter()
+            .filter(|id| ids_to_keep.contains(id))
+            .cloned()
+            .collect();
+
+        unit.common.dependencies.binds_to = unit
+            .common
+            .dependencies
+            .binds_to
+            .iter()
+            .filter(|id| ids_to_keep.contains(id))
+            .cloned()
+            .collect();
+
+        unit.common.dependencies.bound_by = unit
+            .common
+            .dependencies
+            .bound_by
+            .iter()
+            .filter(|id| ids_to_keep.contains(id))
+            .cloned()
+            .collect();
+
+        unit.common.dependencies.part_of = unit
+            .common
+            .dependencies
+            .part_of
+            .iter()
+            .filter(|id| ids_to_keep.contains(id))
+            .cloned()
+            .collect();
+
+        unit.common.dependencies.consists_of = unit
+            .common
+            .dependencies
+            .consists_of
+            .iter()
+            .filter(|id| ids_to_keep.contains(id))
+            .cloned()
+            .collect();
+
+        unit.common.dependencies.requisite = unit
+            .common
+            .dependencies
+            .requisite
+            .iter()
+            .filter(|id| ids_to_keep.contains(id))
+            .cloned()
+            .collect();
+
+        unit.common.dependencies.requisite_of = unit
+            .common
+            .dependencies
+            .requisite_of
+            .iter()
+            .filter(|id| ids_to_keep.contains(id))
+            .cloned()
+            .collect();
+
         unit.dedup_dependencies();
     }
     Ok(())
 }
 
 /// make edges between units visible on bot sides: required <-> required_by  after <-> before
+/// conflicts <-> conflicted_by  binds_to <-> bound_by  part_of <-> consists_of  requisite <-> requisite_of
 ///
 /// Also adds all implicit dependencies between units (currently only a subset of the ones defined
 /// by systemd)
@@ -134,6 +239,10 @@ pub fn fill_dependencies(units: &mut HashMap<UnitId, Unit>) -> Result<(), String
     let mut wanted_by: Vec<(UnitId, UnitId)> = Vec::new();
     let mut before = Vec::new();
     let mut after = Vec::new();
+    let mut conflicted_by = Vec::new();
+    let mut bound_by = Vec::new();
+    let mut consists_of = Vec::new();
+    let mut requisite_of = Vec::new();
 
     for unit in (*units).values_mut() {
         trace!("Fill deps for unit: {:?}", unit.id);
@@ -156,6 +265,18 @@ pub fn fill_dependencies(units: &mut HashMap<UnitId, Unit>) -> Result<(), String
         for id in &conf.required_by {
             required_by.push((unit.id.clone(), id.clone()));
         }
+        for id in &conf.conflicts {
+            conflicted_by.push((id.clone(), unit.id.clone()));
+        }
+        for id in &conf.binds_to {
+            bound_by.push((id.clone(), unit.id.clone()));
+        }
+        for id in &conf.part_of {
+            consists_of.push((id.clone(), unit.id.clone()));
+        }
+        for id in &conf.requisite {
+            requisite_of.push((id.clone(), unit.id.clone()));
+        }
     }
 
     for (wanted, wanting) in wanted_by {
@@ -173,6 +294,23 @@ pub fn fill_dependencies(units: &mut HashMap<UnitId, Unit>) -> Result<(), String
         unit.common.dependencies.required_by.push(requiring);
     }
 
+    for (conflicted, conflicting) in conflicted_by {
+        let unit = units.get_mut(&conflicted).unwrap();
+        unit.common.dependencies.conflicted_by.push(conflicting);
+    }
+    for (bound, binding) in bound_by {
+        let unit = units.get_mut(&bound).unwrap();
+        unit.common.dependencies.bound_by.push(binding);
+    }
+    for (whole, part) in consists_of {
+        let unit = units.get_mut(&whole).unwrap();
+        unit.common.dependencies.consists_of.push(part);
+    }
+    for (requisite, requiring) in requisite_of {
+        let unit = units.get_mut(&requisite).unwrap();
+        unit.common.dependencies.requisite_of.push(requiring);
+    }
+
     for (before, after) in before {
         let unit = units.get_mut(&after).unwrap();
         unit.common.dependencies.before.push(before);
@@ -196,6 +334,8 @@ pub fn fill_dependencies(units: &mut HashMap<UnitId, Unit>) -> Result<(), String
 /// This is currently only a subset of all implicit relations systemd applies
 fn add_all_implicit_relations(units: &mut UnitTable) -> Result<(), String> {
     add_socket_target_relations(units);
+    add_implicit_target_relations(units, "timers.target", UnitIdKind::Timer);
+    add_implicit_target_relations(units, "paths.target", UnitIdKind::Path);
     apply_sockets_to_services(units)?;
     Ok(())
 }
@@ -224,6 +364,26 @@ fn add_socket_target_relations(units: &mut UnitTable) {
     }
 }
 
+/// There are implicit *.timer before timers.target and *.path before paths.target relations
+///
+/// Like the sockets.target relation these are only applied if the target exists.
+fn add_implicit_target_relations(units: &mut UnitTable, target_name: &str, kind: UnitIdKind) {
+    let target_id: UnitId = target_name.try_into().unwrap();
+    let mut unit_ids = Vec::new();
+    if units.contains_key(&target_id) {
+        for unit in units.values_mut() {
+            if kind == unit.id.kind {
+                unit.common.dependencies.before.push(target_id.clone());
+                unit.common.dependencies.dedup();
+                unit_ids.push(unit.id.clone());
+            }
+        }
+        let target = units.get_mut(&target_id).unwrap();
+        target.common.dependencies.after.extend(unit_ids);
+        target.common.dependencies.dedup();
+    }
+}
+
 fn add_sock_srvc_relations(
     srvc_id: UnitId,
     srvc_install: &mut Dependencies,
@@ -261,8 +421,8 @@ fn apply_sockets_to_services(unit_table: &mut UnitTable) -> Result<(), String> {
             UnitIdKind::Socket => {
                 socket_ids.push(id.clone());
             }
-            UnitIdKind::Target => {
-                // ignore targets here
+            UnitIdKind::Target | UnitIdKind::Timer | UnitIdKind::Path | UnitIdKind::Slice => {
+                // ignore targets, timers, path units and slices here
             }
         }
     }
@@ -272,6 +432,15 @@ fn apply_sockets_to_services(unit_table: &mut UnitTable) -> Result<(), String> {
         let mut counter = 0;
 
         if let Specific::Socket(sock) = &mut sock_unit.specific {
+            if sock.conf.accept {
+                trace!(
+                    "Socket {} has Accept=yes, it starts instances of {}@.service",
+                    sock_unit.id.name,
+                    sock_unit.id.prefix()
+                );
+                unit_table.insert(sock_unit.id.clone(), sock_unit);
+                continue;
+            }
             trace!("Searching services for socket: {}", sock_unit.id.name);
             for srvc_unit in &service_ids {
                 let mut srvc_unit = unit_table.remove(srvc_unit).unwrap();
@@ -7,7 +7,7 @@ use crate::units::*;
 
 use std::collections::HashMap;
 use std::convert::TryInto;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 #[derive(Debug)]
 pub enum LoadingError {
@@ -48,22 +48,24 @@ pub fn load_all_units(
     paths: &[PathBuf],
     target_unit: &str,
 ) -> Result<HashMap<UnitId, Unit>, LoadingError> {
-    let mut service_unit_table = HashMap::new();
-    let mut socket_unit_table = HashMap::new();
-    let mut target_unit_table = HashMap::new();
+    let mut unit_table = std::collections::HashMap::new();
+    let mut templates = std::collections::HashMap::new();
     for path in paths {
-        parse_all_units(
-            &mut service_unit_table,
-            &mut socket_unit_table,
-            &mut target_unit_table,
-            path,
-        )?;
+        parse_all_units(&mut unit_table, &mut templates, path, paths)?;
+    }
+    load_referenced_instances(&mut unit_table, &templates, paths, target_unit)?;
+    loop {
+        let missing = missing_slices(&unit_table, &HashMap::new());
+        if missing.is_empty() {
+            break;
+        }
+        for id in missing {
+            trace!("Create slice {} without a unit file", id.name);
+            let slice = make_implicit_slice(&id)
+                .map_err(|e| ParsingError::new(ParsingErrorReason::Generic(e), PathBuf::new()))?;
+            unit_table.insert(id, slice);
+        }
     }
-
-    let mut unit_table = std::collections::HashMap::new();
-    unit_table.extend(service_unit_table);
-    unit_table.extend(socket_unit_table);
-    unit_table.extend(target_unit_table);
 
     trace!("Units found: {}", unit_table.len());
 
@@ -95,7 +97,8 @@ fn prune_unused_sockets(sockets: &mut UnitTable) -> Vec<UnitId> {
     let mut ids_to_remove = Vec::new();
     for unit in sockets.values() {
         if let Specific::Socket(sock) = &unit.specific {
-            if sock.conf.services.is_empty() {
+            // sockets with Accept=yes start instances of their template when connections come in
+            if sock.conf.services.is_empty() && !sock.conf.accept {
                 trace!(
                     "Prune socket {} because it was not added to any service",
                     unit.id.name
@@ -110,53 +113,163 @@ fn prune_unused_sockets(sockets: &mut UnitTable) -> Vec<UnitId> {
     ids_to_remove
 }
 
+/// Load the unit with the given name from a unit file and merge the drop-ins found in the unit_dirs into it. Instances
+/// of templates are loaded from the file of their template, so the name of the unit can differ from the file name.
+pub fn load_unit_file(
+    file_path: &Path,
+    name: &str,
+    unit_dirs: &[PathBuf],
+) -> Result<Unit, ParsingError> {
+    let id: UnitId = name
+        .try_into()
+        .map_err(|e| ParsingError::new(ParsingErrorReason::Generic(e), file_path.to_owned()))?;
+    let raw = std::fs::read_to_string(file_path).map_err(|e| {
+        ParsingError::new(ParsingErrorReason::from(Box::new(e)), file_path.to_owned())
+    })?;
+    let mut parsed_file =
+        parse_file(&raw).map_err(|e| ParsingError::new(e, file_path.to_owned()))?;
+    let dropins = load_dropins(&mut parsed_file, unit_dirs, &id)?;
+    expand_specifiers(&mut parsed_file, &id);
+
+    // the parsers take the name of the unit from the path
+    let name_path = file_path.with_file_name(name);
+    let unit: Result<Unit, String> = match id.kind {
+        UnitIdKind::Service => parse_service(parsed_file, &name_path)
+            .map_err(|e| ParsingError::new(e, file_path.to_owned()))?
+            .try_into(),
+        UnitIdKind::Socket => parse_socket(parsed_file, &name_path)
+            .map_err(|e| ParsingError::new(e, file_path.to_owned()))?
+            .try_into(),
+        UnitIdKind::Target => parse_target(parsed_file, &name_path)
+            .map_err(|e| ParsingError::new(e, file_path.to_owned()))?
+            .try_into(),
+        UnitIdKind::Timer => parse_timer(parsed_file, &name_path)
+            .map_err(|e| ParsingError::new(e, file_path.to_owned()))?
+            .try_into(),
+        UnitIdKind::Path => parse_path(parsed_file, &name_path)
+            .map_err(|e| ParsingError::new(e, file_path.to_owned()))?
+            .try_into(),
+        UnitIdKind::Slice => parse_slice(parsed_file, &name_path)
+            .map_err(|e| ParsingError::new(e, file_path.to_owned()))?
+            .try_into(),
+    };
+    let mut unit = unit
+        .map_err(|err| ParsingError::new(ParsingErrorReason::Generic(err), file_path.to_owned()))?;
+
+    unit.common.unit.dropins = dropins;
+    // remember the template if this instance has no file of its own
+    if file_path.file_name() != Some(std::ffi::OsStr::new(name)) {
+        unit.common.unit.template = id
+            .template_name()
+            .and_then(|template_name| template_name.as_str().try_into().ok());
+    }
+    Ok(unit)
+}
+
+/// The slices that the units use (with Slice= or as the parent of a slice) but that are neither in the units nor in the
+/// known units
+pub fn missing_slices(units: &UnitTable, known_units: &UnitTable) -> Vec<UnitId> {
+    let mut missing: Vec<UnitId> = units
+        .values()
+        .filter_map(|unit| match &unit.specific {
+            Specific::Service(srvc) => srvc.conf.slice.clone(),
+            Specific::Slice(_) => unit.id.parent_slice(),
+            _ => None,
+        })
+        .filter(|id| !units.contains_key(id) && !known_units.contains_key(id))
+        .collect();
+    missing.sort();
+    missing.dedup();
+    missing
+}
+
+/// Like systemd slices do not need a unit file. A slice that is used but has no file gets the default settings.
+pub fn make_implicit_slice(id: &UnitId) -> Result<Unit, String> {
+    ParsedSliceConfig {
+        common: ParsedCommonConfig {
+            name: id.name.clone(),
+            unit: ParsedUnitSection::default(),
+            install: ParsedInstallSection::default(),
+        },
+        resources: ResourceControl::default(),
+    }
+    .try_into()
+}
+
+/// Instances of templates do not have their own files. They are loaded from the template file when other units
+/// (or the target unit) reference them.
+fn load_referenced_instances(
+    unit_table: &mut UnitTable,
+    templates: &HashMap<String, PathBuf>,
+    unit_dirs: &[PathBuf],
+    target_unit: &str,
+) -> Result<(), ParsingError> {
+    loop {
+        let mut referenced: Vec<UnitId> = unit_table
+            .values()
+            .flat_map(|unit| unit.common.unit.refs_by_name.iter().cloned())
+            .collect();
+        if let Ok(target_id) = target_unit.try_into() {
+            referenced.push(target_id);
+        }
+        referenced.sort();
+        referenced.dedup();
+
+        let mut loaded_any = false;
+        for id in referenced {
+            if unit_table.contains_key(&id) {
+                continue;
+            }
+            let template_path = id
+                .template_name()
+                .and_then(|template_name| templates.get(&template_name));
+            if let Some(template_path) = template_path {
+                trace!(
+                    "Load instance {} from template {:?}",
+                    id.name,
+                    template_path
+                );
+                let unit = load_unit_file(template_path, &id.name, unit_dirs)?;
+                unit_table.insert(unit.id.clone(), unit);
+                loaded_any = true;
+            }
+        }
+        // new instances might reference other instances
+        if !loaded_any {
+            return Ok(());
+        }
+    }
+}
+
 fn parse_all_units(
-    services: &mut std::collections::HashMap<UnitId, Unit>,
-    sockets: &mut std::collections::HashMap<UnitId, Unit>,
-    targets: &mut std::collections::HashMap<UnitId, Unit>,
+    units: &mut UnitTable,
+    templates: &mut HashMap<String, PathBuf>,
     path: &PathBuf,
+    unit_dirs: &[PathBuf],
 ) -> Result<(), ParsingError> {
     let files = get_file_list(path)
         .map_err(|e| ParsingError::new(ParsingErrorReason::from(e), path.clone()))?;
     for entry in files {
         if entry.path().is_dir() {
-            parse_all_units(services, sockets, targets, path)?;
+            // drop-in directories are read together with the units they belong to
+            if entry.path().extension() != Some(std::ffi::OsStr::new("d")) {
+                parse_all_units(units, templates, &entry.path(), unit_dirs)?;
+            }
         } else {
-            let raw = std::fs::read_to_string(&entry.path()).map_err(|e| {
-                ParsingError::new(ParsingErrorReason::from(Box::new(e)), path.clone())
-            })?;
-
-            let parsed_file = parse_file(&raw)
-                .map_err(|e| ParsingError::new(ParsingErrorReason::from(e), path.clone()))?;
-
-            if entry.path().to_str().unwrap().ends_with(".service") {
-                trace!("Service found: {:?}", entry.path());
-                let unit: Unit = parse_service(parsed_file, &entry.path())
-                    .map_err(|e| ParsingError::new(ParsingErrorReason::from(e), path.clone()))?
-                    .try_into()
-                    .map_err(|err| {
-                        ParsingError::new(ParsingErrorReason::Generic(err), path.clone())
-                    })?;
-                services.insert(unit.id.clone(), unit);
-            } else if entry.path().to_str().unwrap().ends_with(".socket") {
-                trace!("Socket found: {:?}", entry.path());
-                let unit: Unit = parse_socket(parsed_file, &entry.path())
-                    .map_err(|e| ParsingError::new(ParsingErrorReason::from(e), path.clone()))?
-                    .try_into()
-                    .map_err(|err| {
-                        ParsingError::new(ParsingErrorReason::Generic(err), path.clone())
-                    })?;
-                sockets.insert(unit.id.clone(), unit);
-            } else if entry.path().to_str().unwrap().ends_with(".target") {
-                trace!("Target found: {:?}", entry.path());
-                let unit: Unit = parse_target(parsed_file, &entry.path())
-                    .map_err(|e| ParsingError::new(ParsingErrorReason::from(e), path.clone()))?
-                    .try_into()
-                    .map_err(|err| {
-                        ParsingError::new(ParsingErrorReason::Generic(err), path.clone())
-                    })?;
-                targets.insert(unit.id.clone(), unit);
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let id: UnitId = match name.as_str().try_into() {
+                Ok(id) => id,
+                // not a unit file
+                Err(_) => continue,
+            };
+            if id.is_template() {
+                trace!("Template found: {:?}", entry.path());
+                templates.insert(name, entry.path());
+                continue;
             }
+            trace!("{:?} found: {:?}", id.kind, entry.path());
+            let unit = load_unit_file(&entry.path(), &name, unit_dirs)?;
+            units.insert(unit.id.clone(), unit);
         }
     }
     Ok(())
@@ -1,5 +1,6 @@
 //! The different parts of unit handling: parsing and activating
 
+mod conditions;
 mod from_parsed_config;
 mod id;
 mod loading;
@@ -8,6 +9,7 @@ mod unit;
 mod unit_parsing;
 mod unitset_manipulation;
 
+pub use conditions::*;
 pub use id::*;
 pub use loading::*;
 pub use status::*;
@@ -6,6 +6,8 @@ pub enum UnitStatus {
     Starting,
     Stopping,
     Restarting,
+    /// The service keeps running while it reloads its configuration
+    Reloading,
     Started(StatusStarted),
     Stopped(StatusStopped, Vec<UnitOperationErrorReason>),
 }
@@ -20,6 +22,8 @@ pub enum StatusStarted {
 pub enum StatusStopped {
     StoppedFinal,
     StoppedUnexpected,
+    /// A condition of the unit did not hold when it should have been started
+    Skipped,
 }
 
 impl UnitStatus {
@@ -31,8 +35,12 @@ impl UnitStatus {
     }
     pub fn is_started(&self) -> bool {
         match self {
-            UnitStatus::Started(_) => true,
+            UnitStatus::Started(_) | UnitStatus::Reloading => true,
             _ => false,
         }
     }
+    /// Skipped units do not make the units that require them fail
+    pub fn is_skipped(&self) -> bool {
+        matches!(self, UnitStatus::Stopped(StatusStopped::Skipped, _))
+    }
 }
@@ -1,8 +1,11 @@
 use log::trace;
 
+use crate::paths::{PathCondition, PathWatch};
+use crate::reactor::StreamKind;
 use crate::runtime_info::*;
 use crate::services::Service;
 use crate::sockets::{Socket, SocketKind, SpecializedSocketConfig};
+use crate::timers::{CalendarSpec, Timer};
 use crate::units::*;
 
 use std::sync::RwLock;
@@ -27,6 +30,9 @@ pub enum Specific {
     Service(ServiceSpecific),
     Socket(SocketSpecific),
     Target(TargetSpecific),
+    Timer(TimerSpecific),
+    Path(PathSpecific),
+    Slice(SliceSpecific),
 }
 
 pub struct ServiceSpecific {
@@ -49,6 +55,7 @@ impl SocketState {
                 id.name.clone(),
                 id.clone(),
                 &mut *run_info.fd_store.write().unwrap(),
+                &run_info.reactor,
             )
             .map_err(|e| UnitOperationError {
                 unit_name: id.name.clone(),
@@ -59,7 +66,6 @@ impl SocketState {
             Ok(_) => {
                 let mut status = status.write().unwrap();
                 *status = UnitStatus::Started(StatusStarted::Running);
-                run_info.notify_eventfds();
                 Ok(UnitStatus::Started(StatusStarted::Running))
             }
             Err(e) => {
@@ -84,6 +90,7 @@ impl SocketState {
                 &conf,
                 id.name.clone(),
                 &mut *run_info.fd_store.write().unwrap(),
+                &run_info.reactor,
             )
             .map_err(|e| UnitOperationError {
                 unit_name: id.name.clone(),
@@ -116,6 +123,7 @@ impl SocketState {
                 &conf,
                 id.name.clone(),
                 &mut *run_info.fd_store.write().unwrap(),
+                &run_info.reactor,
             )
             .map_err(|e| UnitOperationError {
                 unit_name: id.name.clone(),
@@ -138,6 +146,7 @@ impl SocketState {
                 id.name.clone(),
                 id.clone(),
                 &mut *run_info.fd_store.write().unwrap(),
+                &run_info.reactor,
             )
             .map_err(|e| UnitOperationError {
                 unit_name: id.name.clone(),
@@ -148,7 +157,6 @@ impl SocketState {
             Ok(_) => {
                 let mut status = status.write().unwrap();
                 *status = UnitStatus::Started(StatusStarted::Running);
-                run_info.notify_eventfds();
                 Ok(())
             }
             Err(e) => {
@@ -162,14 +170,48 @@ impl SocketState {
 }
 
 impl ServiceState {
+    /// Enforce the start limit and count the automatic restarts. Must be called right before the service gets started.
+    /// Returns false if the start has to be refused.
+    fn prepare_start(
+        &mut self,
+        unit_conf: &UnitConfig,
+        status: &RwLock<UnitStatus>,
+        source: ActivationSource,
+    ) -> bool {
+        if !self.common.check_start_limit(unit_conf) {
+            let mut status = status.write().unwrap();
+            *status = UnitStatus::Stopped(
+                StatusStopped::StoppedUnexpected,
+                vec![UnitOperationErrorReason::StartLimitHit],
+            );
+            return false;
+        }
+        match source {
+            ActivationSource::AutoRestart => self.common.restart_count += 1,
+            // the service was waiting for its socket, this is not a new start
+            ActivationSource::SocketActivation => {}
+            _ => self.common.restart_count = 0,
+        }
+        true
+    }
+
     fn activate(
         &mut self,
         id: &UnitId,
         conf: &ServiceConfig,
+        unit_conf: &UnitConfig,
         status: &RwLock<UnitStatus>,
         run_info: &RuntimeInfo,
         source: ActivationSource,
     ) -> Result<UnitStatus, UnitOperationError> {
+        if !self.prepare_start(unit_conf, status, source) {
+            return Err(UnitOperationError {
+                unit_name: id.name.clone(),
+                unit_id: id.clone(),
+                reason: UnitOperationErrorReason::StartLimitHit,
+            });
+        }
+        let start_time = std::time::Instant::now();
         let start_res = self
             .srvc
             .start(conf, id.clone(), &id.name, run_info, source)
@@ -184,6 +226,7 @@ impl ServiceState {
                     let mut status = status.write().unwrap();
                     *status = UnitStatus::Started(StatusStarted::Running);
                 }
+                self.record_started(id, conf, start_time, run_info);
                 Ok(UnitStatus::Started(StatusStarted::Running))
             }
             Ok(crate::services::StartResult::WaitingForSocket) => {
@@ -192,15 +235,7 @@ impl ServiceState {
                     *status = UnitStatus::Started(StatusStarted::WaitingForSocket);
                 }
                 // tell socket activation to listen to these sockets again
-                for socket_id in &conf.sockets {
-                    if let Some(unit) = run_info.unit_table.get(socket_id) {
-                        if let Specific::Socket(sock) = &unit.specific {
-                            let mut_state = &mut *sock.state.write().unwrap();
-                            mut_state.sock.activated = false;
-                        }
-                    }
-                }
-                run_info.notify_eventfds();
+                listen_on_sockets_again(conf, run_info);
                 Ok(UnitStatus::Started(StatusStarted::WaitingForSocket))
             }
             Err(e) => {
@@ -221,7 +256,7 @@ impl ServiceState {
     ) -> Result<(), UnitOperationError> {
         let kill_result = self
             .srvc
-            .kill(&conf, id.clone(), &id.name, run_info)
+            .kill(&conf, id.clone(), &id.name, run_info, false)
             .map_err(|e| UnitOperationError {
                 unit_name: id.name.clone(),
                 unit_id: id.clone(),
@@ -237,19 +272,35 @@ impl ServiceState {
                 *status = UnitStatus::Stopped(StatusStopped::StoppedFinal, vec![e.reason.clone()]);
             }
         }
+        self.common.down_since = Some(std::time::Instant::now());
+        self.srvc.monitor_env.clear();
+        // stored fds are only kept over restarts
+        let stored_fds = run_info
+            .fd_store
+            .write()
+            .unwrap()
+            .remove_all_service_stored(&id.name);
+        for fd in stored_fds {
+            let _ = nix::unistd::close(*fd);
+        }
+        if self.srvc.serves_connection {
+            // the socket activation thread removes stopped instances of sockets with Accept=yes
+            run_info.reactor.wake(StreamKind::Socket);
+        }
         kill_result
     }
     fn reactivate(
         &mut self,
         id: &UnitId,
         conf: &ServiceConfig,
+        unit_conf: &UnitConfig,
         status: &RwLock<UnitStatus>,
         run_info: &RuntimeInfo,
         source: ActivationSource,
     ) -> Result<(), UnitOperationError> {
         let kill_result = self
             .srvc
-            .kill(&conf, id.clone(), &id.name, run_info)
+            .kill(&conf, id.clone(), &id.name, run_info, true)
             .map_err(|e| UnitOperationError {
                 unit_name: id.name.clone(),
                 unit_id: id.clone(),
@@ -264,6 +315,14 @@ impl ServiceState {
         }
 
         // Restart and set the status according to the result
+        if !self.prepare_start(unit_conf, status, source) {
+            return Err(UnitOperationError {
+                unit_name: id.name.clone(),
+                unit_id: id.clone(),
+                reason: UnitOperationErrorReason::StartLimitHit,
+            });
+        }
+        let start_time = std::time::Instant::now();
         let start_res = self
             .srvc
             .start(conf, id.clone(), &id.name, run_info, source)
@@ -278,6 +337,7 @@ impl ServiceState {
                     let mut status = status.write().unwrap();
                     *status = UnitStatus::Started(StatusStarted::Running);
                 }
+                self.record_started(id, conf, start_time, run_info);
                 Ok(())
             }
             Ok(crate::services::StartResult::WaitingForSocket) => {
@@ -286,15 +346,7 @@ impl ServiceState {
                     *status = UnitStatus::Started(StatusStarted::WaitingForSocket);
                 }
                 // tell socket activation to listen to these sockets again
-                for socket_id in &conf.sockets {
-                    if let Some(unit) = run_info.unit_table.get(socket_id) {
-                        if let Specific::Socket(sock) = &unit.specific {
-                            let mut_state = &mut *sock.state.write().unwrap();
-                            mut_state.sock.activated = false;
-                        }
-                    }
-                }
-                run_info.notify_eventfds();
+                listen_on_sockets_again(conf, run_info);
                 Ok(())
             }
             Err(e) => {
@@ -305,6 +357,164 @@ impl ServiceState {
             }
         }
     }
+
+    /// Oneshot services have already exited when the start returns, so they are inactive again right away and their
+    /// OnSuccess= units get started
+    fn record_started(
+        &mut self,
+        id: &UnitId,
+        conf: &ServiceConfig,
+        start_time: std::time::Instant,
+        run_info: &RuntimeInfo,
+    ) {
+        self.common.up_since = Some(start_time);
+        if conf.srcv_type == ServiceType::OneShot {
+            self.common.down_since = Some(std::time::Instant::now());
+            run_info.hooks.push(crate::hook_activation::UnitEnded {
+                id: id.clone(),
+                result: "success",
+                exit: Some(crate::signal_handler::ChildTermination::Exit(0)),
+            });
+        }
+    }
+}
+
+impl TimerState {
+    fn activate(
+        &mut self,
+        id: &UnitId,
+        conf: &TimerConfig,
+        status: &RwLock<UnitStatus>,
+        run_info: &RuntimeInfo,
+    ) -> UnitStatus {
+        self.timer
+            .activate(conf, &id.name, &run_info.config.state_dir);
+        self.common.up_since = Some(std::time::Instant::now());
+        {
+            let mut status = status.write().unwrap();
+            *status = UnitStatus::Started(StatusStarted::Running);
+        }
+        // let the scheduler pick up this timer
+        run_info.notify_eventfds();
+        UnitStatus::Started(StatusStarted::Running)
+    }
+
+    fn deactivate(&mut self, status: &RwLock<UnitStatus>, run_info: &RuntimeInfo) {
+        self.timer.deactivate();
+        self.common.down_since = Some(std::time::Instant::now());
+        {
+            let mut status = status.write().unwrap();
+            *status = UnitStatus::Stopped(StatusStopped::StoppedFinal, vec![]);
+        }
+        run_info.notify_eventfds();
+    }
+}
+
+impl PathState {
+    fn activate(
+        &mut self,
+        conf: &PathConfig,
+        status: &RwLock<UnitStatus>,
+        run_info: &RuntimeInfo,
+    ) -> UnitStatus {
+        self.path.activate(&conf.conditions, conf.make_directory);
+        self.common.up_since = Some(std::time::Instant::now());
+        {
+            let mut status = status.write().unwrap();
+            *status = UnitStatus::Started(StatusStarted::Running);
+        }
+        // let the watcher pick up the paths of this unit
+        run_info.notify_eventfds();
+        UnitStatus::Started(StatusStarted::Running)
+    }
+
+    fn deactivate(&mut self, status: &RwLock<UnitStatus>, run_info: &RuntimeInfo) {
+        self.path.deactivate();
+        self.common.down_since = Some(std::time::Instant::now());
+        {
+            let mut status = status.write().unwrap();
+            *status = UnitStatus::Stopped(StatusStopped::StoppedFinal, vec![]);
+        }
+        run_info.notify_eventfds();
+    }
+}
+
+impl SliceState {
+    fn activate(
+        &mut self,
+        id: &UnitId,
+        conf: &SliceConfig,
+        status: &RwLock<UnitStatus>,
+    ) -> Result<UnitStatus, UnitOperationError> {
+        // the units in the slice are started after it, so its cgroup and limits are in place before their cgroups
+        // are created below it
+        #[cfg(feature = "cgroups")]
+        {
+            let cgroup_path = &conf.platform_specific.cgroup_path;
+            std::fs::create_dir_all(cgroup_path)
+                .map_err(|e| format!("Couldnt create slice cgroup ({:?}): {}", cgroup_path, e))
+                .and_then(|_| {
+                    crate::platform::cgroups::enable_accounting(cgroup_path);
+                    crate::platform::cgroups::apply_resource_control(cgroup_path, &conf.resources)
+                        .map_err(|e| {
+                            format!(
+                                "Couldnt apply the resource control of the slice ({:?}): {}",
+                                cgroup_path, e
+                            )
+                        })
+                })
+                .map_err(|e| UnitOperationError {
+                    reason: UnitOperationErrorReason::GenericStartError(e),
+                    unit_name: id.name.clone(),
+                    unit_id: id.clone(),
+                })?;
+        }
+        let _ = (id, conf);
+        self.common.up_since = Some(std::time::Instant::now());
+        {
+            let mut status = status.write().unwrap();
+            *status = UnitStatus::Started(StatusStarted::Running);
+        }
+        Ok(UnitStatus::Started(StatusStarted::Running))
+    }
+
+    fn deactivate(&mut self, id: &UnitId, conf: &SliceConfig, status: &RwLock<UnitStatus>) {
+        // the units in the slice have been stopped before, their cgroups are gone
+        #[cfg(feature = "cgroups")]
+        {
+            let cgroup_path = &conf.platform_specific.cgroup_path;
+            if cgroup_path.exists() {
+                if let Err(e) = crate::platform::cgroups::remove_cgroup(cgroup_path) {
+                    log::warn!("Could not remove the cgroup of slice {}: {}", id.name, e);
+                }
+            }
+        }
+        let _ = (id, conf);
+        self.common.down_since = Some(std::time::Instant::now());
+        {
+            let mut status = status.write().unwrap();
+            *status = UnitStatus::Stopped(StatusStopped::StoppedFinal, vec![]);
+        }
+    }
+}
+
+/// The service waits for its sockets again, so socket activation has to listen on their fds again
+fn listen_on_sockets_again(conf: &ServiceConfig, run_info: &RuntimeInfo) {
+    for socket_id in &conf.sockets {
+        if let Some(unit) = run_info.unit_table.get(socket_id) {
+            if let Specific::Socket(sock) = &unit.specific {
+                let mut_state = &mut *sock.state.write().unwrap();
+                if mut_state.sock.activated {
+                    mut_state.sock.activated = false;
+                    mut_state.sock.listen_again(
+                        &socket_id.name,
+                        &run_info.fd_store.read().unwrap(),
+                        &run_info.reactor,
+                    );
+                }
+            }
+        }
+    }
 }
 
 impl ServiceSpecific {
@@ -328,11 +538,46 @@ pub struct TargetSpecific {
     pub state: RwLock<TargetState>,
 }
 
+pub struct TimerSpecific {
+    pub conf: TimerConfig,
+    pub state: RwLock<TimerState>,
+}
+
+pub struct PathSpecific {
+    pub conf: PathConfig,
+    pub state: RwLock<PathState>,
+}
+
+pub struct SliceSpecific {
+    pub conf: SliceConfig,
+    pub state: RwLock<SliceState>,
+}
+
 #[derive(Default)]
 /// All units have some common mutable state
 pub struct CommonState {
     pub up_since: Option<std::time::Instant>,
+    pub down_since: Option<std::time::Instant>,
+    /// Automatic restarts since the unit was last started explicitly
     pub restart_count: u64,
+    /// The start attempts within the current start limit interval
+    pub recent_starts: Vec<std::time::Instant>,
+}
+
+impl CommonState {
+    /// Record a start attempt. Returns false if the unit was started too often and the start must be refused.
+    pub fn check_start_limit(&mut self, conf: &UnitConfig) -> bool {
+        if conf.start_limit_interval.as_nanos() == 0 || conf.start_limit_burst == 0 {
+            return true;
+        }
+        self.recent_starts
+            .retain(|start| start.elapsed() < conf.start_limit_interval);
+        if self.recent_starts.len() >= conf.start_limit_burst as usize {
+            return false;
+        }
+        self.recent_starts.push(std::time::Instant::now());
+        true
+    }
 }
 
 pub struct ServiceState {
@@ -346,6 +591,17 @@ pub struct SocketState {
 pub struct TargetState {
     pub common: CommonState,
 }
+pub struct TimerState {
+    pub common: CommonState,
+    pub timer: Timer,
+}
+pub struct PathState {
+    pub common: CommonState,
+    pub path: PathWatch,
+}
+pub struct SliceState {
+    pub common: CommonState,
+}
 
 enum LockedState<'a> {
     Service(
@@ -357,6 +613,9 @@ enum LockedState<'a> {
         &'a SocketConfig,
     ),
     Target(std::sync::RwLockWriteGuard<'a, TargetState>),
+    Timer(std::sync::RwLockWriteGuard<'a, TimerState>, &'a TimerConfig),
+    Path(std::sync::RwLockWriteGuard<'a, PathState>, &'a PathConfig),
+    Slice(std::sync::RwLockWriteGuard<'a, SliceState>, &'a SliceConfig),
 }
 
 impl Unit {
@@ -381,6 +640,84 @@ impl Unit {
             false
         }
     }
+    pub fn is_timer(&self) -> bool {
+        matches!(self.id.kind, UnitIdKind::Timer)
+    }
+    pub fn is_path(&self) -> bool {
+        matches!(self.id.kind, UnitIdKind::Path)
+    }
+    pub fn is_slice(&self) -> bool {
+        matches!(self.id.kind, UnitIdKind::Slice)
+    }
+
+    /// Services and slices have a cgroup, it only exists while they are active and rustysd uses cgroups
+    #[cfg(target_os = "linux")]
+    pub fn cgroup_path(&self) -> Option<&std::path::PathBuf> {
+        match &self.specific {
+            Specific::Service(srvc) => Some(&srvc.conf.platform_specific.cgroup_path),
+            Specific::Slice(slice) => Some(&slice.conf.platform_specific.cgroup_path),
+            _ => None,
+        }
+    }
+
+    /// Oneshot services that ran (and exited) stay in the Started state
+    pub fn is_finished_oneshot(&self) -> bool {
+        if let Specific::Service(srvc) = &self.specific {
+            srvc.conf.srcv_type == ServiceType::OneShot
+                && *self.common.status.read().unwrap()
+                    == UnitStatus::Started(StatusStarted::Running)
+        } else {
+            false
+        }
+    }
+
+    /// Services with ExecReload= commands can reload their configuration without being restarted
+    pub fn can_reload(&self) -> bool {
+        if let Specific::Service(srvc) = &self.specific {
+            !srvc.conf.reload.is_empty()
+        } else {
+            false
+        }
+    }
+
+    /// When the unit last became active and inactive. Returns None if the state of the unit is currently locked
+    /// because it is in a state transition.
+    pub fn try_activity_times(
+        &self,
+    ) -> Option<(Option<std::time::Instant>, Option<std::time::Instant>)> {
+        match &self.specific {
+            Specific::Service(specific) => specific
+                .state
+                .try_read()
+                .ok()
+                .map(|state| (state.common.up_since, state.common.down_since)),
+            Specific::Socket(specific) => specific
+                .state
+                .try_read()
+                .ok()
+                .map(|state| (state.common.up_since, state.common.down_since)),
+            Specific::Target(specific) => specific
+                .state
+                .try_read()
+                .ok()
+                .map(|state| (state.common.up_since, state.common.down_since)),
+            Specific::Timer(specific) => specific
+                .state
+                .try_read()
+                .ok()
+                .map(|state| (state.common.up_since, state.common.down_since)),
+            Specific::Path(specific) => specific
+                .state
+                .try_read()
+                .ok()
+                .map(|state| (state.common.up_since, state.common.down_since)),
+            Specific::Slice(specific) => specific
+                .state
+                .try_read()
+                .ok()
+                .map(|state| (state.common.up_since, state.common.down_since)),
+        }
+    }
 
     pub fn name_without_suffix(&self) -> String {
         let split: Vec<_> = self.id.name.split('.').collect();
@@ -406,9 +743,9 @@ impl Unit {
         let unstarted_deps = others
             .iter()
             .fold(Vec::new(), |mut acc, (id, status_locked)| {
-                let required = self.common.dependencies.requires.contains(id);
+                let required = self.common.dependencies.needs_active(id);
                 let ready = if required {
-                    status_locked.is_started()
+                    status_locked.is_started() || status_locked.is_skipped()
                 } else {
                     **status_locked != UnitStatus::NeverStarted
                 };
@@ -444,9 +781,9 @@ impl Unit {
         let unstarted_deps = others
             .iter()
             .fold(Vec::new(), |mut acc, (id, status_locked)| {
-                let required = self.common.dependencies.requires.contains(id);
+                let required = self.common.dependencies.needs_active(id);
                 let ready = if required {
-                    status_locked.is_started()
+                    status_locked.is_started() || status_locked.is_skipped()
                 } else {
                     **status_locked != UnitStatus::NeverStarted
                 };
@@ -496,6 +833,51 @@ impl Unit {
         // All locks are released again here
     }
 
+    /// Check the Condition*= and Assert*= settings. Returns the status the unit gets instead of being started if one
+    /// of them does not hold.
+    fn failed_start_condition(&self) -> Option<UnitStatus> {
+        if let Err(failed) = check_conditions(&self.common.unit.conditions) {
+            trace!(
+                "Skip unit {} because Condition{} does not hold",
+                self.id.name,
+                failed
+            );
+            return Some(UnitStatus::Stopped(
+                StatusStopped::Skipped,
+                vec![UnitOperationErrorReason::ConditionFailed(format!(
+                    "Condition{}",
+                    failed
+                ))],
+            ));
+        }
+        if let Err(failed) = check_conditions(&self.common.unit.asserts) {
+            return Some(UnitStatus::Stopped(
+                StatusStopped::StoppedUnexpected,
+                vec![UnitOperationErrorReason::AssertionFailed(format!(
+                    "Assert{}",
+                    failed
+                ))],
+            ));
+        }
+        None
+    }
+
+    /// Record the status of a unit that is not started because of its conditions. A skipped unit counts as started
+    /// for the units that depend on it, a failed assert is an error.
+    fn skip_start(&self, status: UnitStatus) -> Option<UnitOperationError> {
+        *self.common.status.write().unwrap() = status.clone();
+        match status {
+            UnitStatus::Stopped(StatusStopped::StoppedUnexpected, mut reasons) => {
+                Some(UnitOperationError {
+                    reason: reasons.remove(0),
+                    unit_name: self.id.name.clone(),
+                    unit_id: self.id.clone(),
+                })
+            }
+            _ => None,
+        }
+    }
+
     /// This activates the unit and manages the state transitions. It reports back the new unit status or any
     /// errors encountered while starting the unit. Note that these errors are also recorded in the units status.
     pub fn activate(
@@ -503,6 +885,13 @@ impl Unit {
         run_info: &RuntimeInfo,
         source: ActivationSource,
     ) -> Result<UnitStatus, UnitOperationError> {
+        if source.is_triggered_activation() && self.is_finished_oneshot() {
+            // Oneshot services stay started after their process exited. An elapsed timer or a path
+            // change means they should run again.
+            self.reactivate(run_info, source)?;
+            return Ok(self.common.status.read().unwrap().clone());
+        }
+
         let state = match &self.specific {
             Specific::Service(specific) => {
                 LockedState::Service(specific.state.write().unwrap(), &specific.conf)
@@ -511,13 +900,24 @@ impl Unit {
                 LockedState::Socket(specific.state.write().unwrap(), &specific.conf)
             }
             Specific::Target(specific) => LockedState::Target(specific.state.write().unwrap()),
+            Specific::Timer(specific) => {
+                LockedState::Timer(specific.state.write().unwrap(), &specific.conf)
+            }
+            Specific::Path(specific) => {
+                LockedState::Path(specific.state.write().unwrap(), &specific.conf)
+            }
+            Specific::Slice(specific) => {
+                LockedState::Slice(specific.state.write().unwrap(), &specific.conf)
+            }
         };
 
         {
             let self_status = &*self.common.status.read().unwrap();
             match self_status {
                 UnitStatus::Started(StatusStarted::WaitingForSocket) => {
-                    if source == ActivationSource::SocketActivation {
+                    if source == ActivationSource::SocketActivation
+                        || source.is_triggered_activation()
+                    {
                         // Need activation
                     } else {
                         // Dont need activation
@@ -555,6 +955,13 @@ impl Unit {
             }
         })?;
 
+        if let Some(status) = self.failed_start_condition() {
+            return match self.skip_start(status.clone()) {
+                Some(err) => Err(err),
+                None => Ok(status),
+            };
+        }
+
         match state {
             LockedState::Target(_state) => {
                 {
@@ -573,7 +980,26 @@ impl Unit {
             }
             LockedState::Service(mut state, conf) => {
                 let state = &mut *state;
-                state.activate(&self.id, conf, &self.common.status, run_info, source)
+                state.activate(
+                    &self.id,
+                    conf,
+                    &self.common.unit,
+                    &self.common.status,
+                    run_info,
+                    source,
+                )
+            }
+            LockedState::Timer(mut state, conf) => {
+                let state = &mut *state;
+                Ok(state.activate(&self.id, conf, &self.common.status, run_info))
+            }
+            LockedState::Path(mut state, conf) => {
+                let state = &mut *state;
+                Ok(state.activate(conf, &self.common.status, run_info))
+            }
+            LockedState::Slice(mut state, conf) => {
+                let state = &mut *state;
+                state.activate(&self.id, conf, &self.common.status)
             }
         }
     }
@@ -589,6 +1015,15 @@ impl Unit {
                 LockedState::Socket(specific.state.write().unwrap(), &specific.conf)
             }
             Specific::Target(specific) => LockedState::Target(specific.state.write().unwrap()),
+            Specific::Timer(specific) => {
+                LockedState::Timer(specific.state.write().unwrap(), &specific.conf)
+            }
+            Specific::Path(specific) => {
+                LockedState::Path(specific.state.write().unwrap(), &specific.conf)
+            }
+            Specific::Slice(specific) => {
+                LockedState::Slice(specific.state.write().unwrap(), &specific.conf)
+            }
         };
 
         {
@@ -631,6 +1066,21 @@ impl Unit {
                 let state = &mut *state;
                 state.deactivate(&self.id, conf, &self.common.status, run_info)
             }
+            LockedState::Timer(mut state, _conf) => {
+                let state = &mut *state;
+                state.deactivate(&self.common.status, run_info);
+                Ok(())
+            }
+            LockedState::Path(mut state, _conf) => {
+                let state = &mut *state;
+                state.deactivate(&self.common.status, run_info);
+                Ok(())
+            }
+            LockedState::Slice(mut state, conf) => {
+                let state = &mut *state;
+                state.deactivate(&self.id, conf, &self.common.status);
+                Ok(())
+            }
         }
     }
 
@@ -645,6 +1095,15 @@ impl Unit {
     ) -> Result<(), UnitOperationError> {
         trace!("Reactivate unit: {}", self.id.name);
 
+        // the unit is stopped and not started again if its conditions do not hold anymore
+        if let Some(status) = self.failed_start_condition() {
+            self.deactivate(run_info)?;
+            return match self.skip_start(status) {
+                Some(err) => Err(err),
+                None => Ok(()),
+            };
+        }
+
         let state = match &self.specific {
             Specific::Service(specific) => {
                 LockedState::Service(specific.state.write().unwrap(), &specific.conf)
@@ -653,6 +1112,15 @@ impl Unit {
                 LockedState::Socket(specific.state.write().unwrap(), &specific.conf)
             }
             Specific::Target(specific) => LockedState::Target(specific.state.write().unwrap()),
+            Specific::Timer(specific) => {
+                LockedState::Timer(specific.state.write().unwrap(), &specific.conf)
+            }
+            Specific::Path(specific) => {
+                LockedState::Path(specific.state.write().unwrap(), &specific.conf)
+            }
+            Specific::Slice(specific) => {
+                LockedState::Slice(specific.state.write().unwrap(), &specific.conf)
+            }
         };
 
         let need_full_restart = self.state_transition_restarting(run_info).map_err(|bad_ids| {
@@ -681,7 +1149,30 @@ impl Unit {
                 }
                 LockedState::Service(mut state, conf) => {
                     let state = &mut *state;
-                    state.reactivate(&self.id, conf, &self.common.status, run_info, source)
+                    state.reactivate(
+                        &self.id,
+                        conf,
+                        &self.common.unit,
+                        &self.common.status,
+                        run_info,
+                        source,
+                    )
+                }
+                LockedState::Timer(mut state, conf) => {
+                    let state = &mut *state;
+                    state.activate(&self.id, conf, &self.common.status, run_info);
+                    Ok(())
+                }
+                LockedState::Path(mut state, conf) => {
+                    let state = &mut *state;
+                    state.activate(conf, &self.common.status, run_info);
+                    Ok(())
+                }
+                LockedState::Slice(mut state, conf) => {
+                    let state = &mut *state;
+                    state
+                        .activate(&self.id, conf, &self.common.status)
+                        .map(|_| ())
                 }
             }
         } else {
@@ -700,12 +1191,78 @@ impl Unit {
                 LockedState::Service(mut state, conf) => {
                     let state = &mut *state;
                     state
-                        .activate(&self.id, conf, &self.common.status, run_info, source)
+                        .activate(
+                            &self.id,
+                            conf,
+                            &self.common.unit,
+                            &self.common.status,
+                            run_info,
+                            source,
+                        )
+                        .map(|_| ())
+                }
+                LockedState::Timer(mut state, conf) => {
+                    let state = &mut *state;
+                    state.activate(&self.id, conf, &self.common.status, run_info);
+                    Ok(())
+                }
+                LockedState::Path(mut state, conf) => {
+                    let state = &mut *state;
+                    state.activate(conf, &self.common.status, run_info);
+                    Ok(())
+                }
+                LockedState::Slice(mut state, conf) => {
+                    let state = &mut *state;
+                    state
+                        .activate(&self.id, conf, &self.common.status)
                         .map(|_| ())
                 }
             }
         }
     }
+
+    /// Make a running service reload its configuration. The unit is 'Reloading' while the ExecReload= commands run.
+    pub fn reload(&self, run_info: &RuntimeInfo) -> Result<(), UnitOperationError> {
+        trace!("Reload unit: {}", self.id.name);
+        let reload_error = |reason: crate::services::ServiceErrorReason| UnitOperationError {
+            reason: UnitOperationErrorReason::ServiceReloadError(reason),
+            unit_name: self.id.name.clone(),
+            unit_id: self.id.clone(),
+        };
+        let srvc = match &self.specific {
+            Specific::Service(srvc) => srvc,
+            _ => {
+                return Err(reload_error(crate::services::ServiceErrorReason::Generic(
+                    "Only services can be reloaded".into(),
+                )))
+            }
+        };
+
+        let mut state = srvc.state.write().unwrap();
+        {
+            let mut status = self.common.status.write().unwrap();
+            if *status != UnitStatus::Started(StatusStarted::Running) {
+                return Err(reload_error(crate::services::ServiceErrorReason::Generic(
+                    format!(
+                        "Only running services can be reloaded. Status is: {:?}",
+                        *status
+                    ),
+                )));
+            }
+            *status = UnitStatus::Reloading;
+        }
+        let reload_result = state
+            .srvc
+            .reload(&srvc.conf, self.id.clone(), &self.id.name, run_info);
+        {
+            // a failed reload does not stop the service
+            let mut status = self.common.status.write().unwrap();
+            if *status == UnitStatus::Reloading {
+                *status = UnitStatus::Started(StatusStarted::Running);
+            }
+        }
+        reload_result.map_err(reload_error)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -715,6 +1272,28 @@ pub struct UnitConfig {
     /// This is needed for adding/removing units. All units in this set must be present
     /// or this unit is considered invalid os it has to be removed too / cannot be added.
     pub refs_by_name: Vec<UnitId>,
+
+    /// Set for instances that were loaded from the file of their template
+    pub template: Option<UnitId>,
+
+    /// The drop-ins that were merged into the unit file, in the order they were applied
+    pub dropins: Vec<std::path::PathBuf>,
+
+    /// The unit may only be started start_limit_burst times within start_limit_interval. A zero interval
+    /// disables the limit.
+    pub start_limit_interval: std::time::Duration,
+    pub start_limit_burst: u32,
+
+    /// Units that are started when this unit fails or finishes successfully
+    pub on_failure: Vec<UnitId>,
+    pub on_success: Vec<UnitId>,
+    pub on_failure_job_mode: JobMode,
+    pub on_success_job_mode: JobMode,
+
+    /// Checked right before the unit is started. If a condition fails the unit is skipped, if an assert fails the
+    /// start fails.
+    pub conditions: Vec<UnitCondition>,
+    pub asserts: Vec<UnitCondition>,
 }
 
 #[derive(Debug, Clone)]
@@ -732,6 +1311,15 @@ pub struct Dependencies {
     pub required_by: Vec<UnitId>,
     pub before: Vec<UnitId>,
     pub after: Vec<UnitId>,
+    /// Conflicts go both ways, starting one of the units stops the other
+    pub conflicts: Vec<UnitId>,
+    pub conflicted_by: Vec<UnitId>,
+    pub binds_to: Vec<UnitId>,
+    pub bound_by: Vec<UnitId>,
+    pub part_of: Vec<UnitId>,
+    pub consists_of: Vec<UnitId>,
+    pub requisite: Vec<UnitId>,
+    pub requisite_of: Vec<UnitId>,
 }
 
 impl Dependencies {
@@ -742,6 +1330,14 @@ impl Dependencies {
         self.before.sort();
         self.after.sort();
         self.requires.sort();
+        self.conflicts.sort();
+        self.conflicted_by.sort();
+        self.binds_to.sort();
+        self.bound_by.sort();
+        self.part_of.sort();
+        self.consists_of.sort();
+        self.requisite.sort();
+        self.requisite_of.sort();
         // dedup after sorting
         self.wants.dedup();
         self.requires.dedup();
@@ -749,6 +1345,14 @@ impl Dependencies {
         self.required_by.dedup();
         self.before.dedup();
         self.after.dedup();
+        self.conflicts.dedup();
+        self.conflicted_by.dedup();
+        self.binds_to.dedup();
+        self.bound_by.dedup();
+        self.part_of.dedup();
+        self.consists_of.dedup();
+        self.requisite.dedup();
+        self.requisite_of.dedup();
     }
 
     pub fn kill_before_this(&self) -> Vec<UnitId> {
@@ -761,10 +1365,25 @@ impl Dependencies {
         ids.extend(self.after.iter().cloned());
         ids
     }
+    /// Units that are stopped and restarted together with this unit because they are bound to it or part of it
+    pub fn stop_with_this(&self) -> Vec<UnitId> {
+        let mut ids = Vec::new();
+        ids.extend(self.bound_by.iter().cloned());
+        ids.extend(self.consists_of.iter().cloned());
+        ids
+    }
+    /// Units that are stopped when this unit is started
+    pub fn stop_when_starting_this(&self) -> Vec<UnitId> {
+        let mut ids = Vec::new();
+        ids.extend(self.conflicts.iter().cloned());
+        ids.extend(self.conflicted_by.iter().cloned());
+        ids
+    }
     pub fn start_concurrently_with_this(&self) -> Vec<UnitId> {
         let mut ids = Vec::new();
         ids.extend(self.wants.iter().cloned());
         ids.extend(self.requires.iter().cloned());
+        ids.extend(self.binds_to.iter().cloned());
         let ids = ids
             .into_iter()
             .filter(|id| !self.after.contains(&id))
@@ -786,6 +1405,19 @@ impl Dependencies {
         Self::remove_from_vec(&mut self.required_by, id);
         Self::remove_from_vec(&mut self.before, id);
         Self::remove_from_vec(&mut self.after, id);
+        Self::remove_from_vec(&mut self.conflicts, id);
+        Self::remove_from_vec(&mut self.conflicted_by, id);
+        Self::remove_from_vec(&mut self.binds_to, id);
+        Self::remove_from_vec(&mut self.bound_by, id);
+        Self::remove_from_vec(&mut self.part_of, id);
+        Self::remove_from_vec(&mut self.consists_of, id);
+        Self::remove_from_vec(&mut self.requisite, id);
+        Self::remove_from_vec(&mut self.requisite_of, id);
+    }
+
+    /// Whether the unit has to be started before this unit can start, not only run once
+    pub fn needs_active(&self, id: &UnitId) -> bool {
+        self.requires.contains(id) || self.binds_to.contains(id) || self.requisite.contains(id)
     }
 
     pub fn comes_after(&self, name: &str) -> bool {
@@ -853,6 +1485,10 @@ pub struct ExecConfig {
     pub supplementary_groups: Vec<nix::unistd::Gid>,
     pub stdout_path: Option<StdIoOption>,
     pub stderr_path: Option<StdIoOption>,
+    /// Used by StandardOutput=tty and StandardError=tty. Defaults to /dev/console.
+    pub tty_path: std::path::PathBuf,
+    /// The name the output of the service is logged with instead of the unit name
+    pub syslog_identifier: Option<String>,
     pub environment: Option<EnvVars>,
 }
 
@@ -870,10 +1506,16 @@ pub struct PlatformSpecificServiceFields {}
 /// The immutable config of a service unit
 pub struct ServiceConfig {
     pub restart: ServiceRestart,
-    pub accept: bool,
+    pub restart_sec: std::time::Duration,
+    /// Number of restarts in which the delay grows from restart_sec to restart_max_delay
+    pub restart_steps: u32,
+    pub restart_max_delay: Option<std::time::Duration>,
+    pub standard_input: StandardInput,
     pub notifyaccess: NotifyKind,
     pub exec: Commandline,
     pub stop: Vec<Commandline>,
+    /// Commands that make the service reload its configuration
+    pub reload: Vec<Commandline>,
     pub stoppost: Vec<Commandline>,
     pub startpre: Vec<Commandline>,
     pub startpost: Vec<Commandline>,
@@ -884,7 +1526,48 @@ pub struct ServiceConfig {
     pub exec_config: ExecConfig,
     pub platform_specific: PlatformSpecificServiceFields,
     pub dbus_name: Option<String>,
+    /// Where services of type forking write the PID of their main process
+    pub pid_file: Option<std::path::PathBuf>,
+    /// The service must send WATCHDOG=1 at least this often or it gets the watchdog_signal
+    pub watchdog_sec: Option<std::time::Duration>,
+    pub watchdog_signal: nix::sys::signal::Signal,
+    /// How many fds the service may put into the fd store with FDSTORE=1. Zero disables the fd store.
+    pub fd_store_max: usize,
     pub sockets: Vec<UnitId>,
+    /// The slice whose cgroup contains the cgroup of the service. Without a slice it is placed directly in the cgroup
+    /// of rustysd.
+    pub slice: Option<UnitId>,
+    /// Written to the cgroup of the service before it is started. Can be changed at runtime with set-property.
+    pub resources: ResourceControl,
+    /// KillMode=, KillSignal= and the other settings for stopping the processes
+    pub kill: KillConfig,
+}
+
+/// The immutable config of a timer unit
+pub struct TimerConfig {
+    pub on_calendar: Vec<CalendarSpec>,
+    pub on_boot: Vec<std::time::Duration>,
+    pub on_unit_active: Vec<std::time::Duration>,
+    pub on_unit_inactive: Vec<std::time::Duration>,
+    pub accuracy: std::time::Duration,
+    pub randomized_delay: std::time::Duration,
+    pub persistent: bool,
+    /// The unit that gets activated when the timer elapses
+    pub unit: UnitId,
+}
+
+/// The immutable config of a path unit
+pub struct PathConfig {
+    pub conditions: Vec<PathCondition>,
+    pub make_directory: bool,
+    /// The unit that gets activated when a condition triggers
+    pub unit: UnitId,
+}
+
+/// The config of a slice unit. The resources can be changed at runtime with set-property.
+pub struct SliceConfig {
+    pub resources: ResourceControl,
+    pub platform_specific: PlatformSpecificServiceFields,
 }
 
 /// The immutable config of a socket unit
@@ -892,6 +1575,12 @@ pub struct SocketConfig {
     pub sockets: Vec<SingleSocketConfig>,
     pub filedesc_name: String,
     pub services: Vec<UnitId>,
+    /// Accept connections and start an instance of the template service for each of them (inetd style)
+    pub accept: bool,
+    /// How many instances may run at the same time if accept is set
+    pub max_connections: usize,
+    /// How many instances may run for the same peer address (or uid for unix sockets) if accept is set
+    pub max_connections_per_source: usize,
 
     pub exec_config: ExecConfig,
 }
@@ -0,0 +1,164 @@
+//! Drop-in files (foo.service.d/*.conf) that are merged on top of the unit file they belong to (see systemd.unit(5)).
+
+use crate::units::*;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Settings that can be given multiple times. Drop-ins append to these, all other settings are replaced.
+const LIST_SETTINGS: &[&str] = &[
+    "WANTS",
+    "REQUIRES",
+    "AFTER",
+    "BEFORE",
+    "CONFLICTS",
+    "BINDSTO",
+    "PARTOF",
+    "REQUISITE",
+    "ONFAILURE",
+    "ONSUCCESS",
+    "CONDITIONPATHEXISTS",
+    "CONDITIONPATHISDIRECTORY",
+    "CONDITIONDIRECTORYNOTEMPTY",
+    "CONDITIONFILENOTEMPTY",
+    "CONDITIONFILEISEXECUTABLE",
+    "CONDITIONKERNELCOMMANDLINE",
+    "CONDITIONVIRTUALIZATION",
+    "CONDITIONUSER",
+    "CONDITIONGROUP",
+    "CONDITIONHOST",
+    "CONDITIONENVIRONMENT",
+    "CONDITIONFIRSTBOOT",
+    "ASSERTPATHEXISTS",
+    "ASSERTPATHISDIRECTORY",
+    "ASSERTDIRECTORYNOTEMPTY",
+    "ASSERTFILENOTEMPTY",
+    "ASSERTFILEISEXECUTABLE",
+    "ASSERTKERNELCOMMANDLINE",
+    "ASSERTVIRTUALIZATION",
+    "ASSERTUSER",
+    "ASSERTGROUP",
+    "ASSERTHOST",
+    "ASSERTENVIRONMENT",
+    "ASSERTFIRSTBOOT",
+    "WANTEDBY",
+    "REQUIREDBY",
+    "EXECSTART",
+    "EXECSTARTPRE",
+    "EXECSTARTPOST",
+    "EXECSTOP",
+    "EXECRELOAD",
+    "EXECSTOPPOST",
+    "SOCKETS",
+    "SUPPLEMENTARYGROUPS",
+    "ENVIRONMENT",
+    "LISTENSTREAM",
+    "LISTENDATAGRAM",
+    "LISTENSEQUENTIALPACKET",
+    "LISTENFIFO",
+    "ONCALENDAR",
+    "ONBOOTSEC",
+    "ONUNITACTIVESEC",
+    "ONUNITINACTIVESEC",
+    "PATHEXISTS",
+    "PATHEXISTSGLOB",
+    "PATHCHANGED",
+    "PATHMODIFIED",
+    "DIRECTORYNOTEMPTY",
+];
+
+/// The drop-in directories for this unit in the order of their priority: foo@bar.service.d, foo@.service.d and
+/// the type-wide service.d
+fn dropin_dir_names(id: &UnitId) -> Vec<String> {
+    let mut names = vec![format!("{}.d", id.name)];
+    if let Some(template_name) = id.template_name() {
+        names.push(format!("{}.d", template_name));
+    }
+    let suffix = id.name.rsplit('.').next().unwrap_or_default();
+    names.push(format!("{}.d", suffix));
+    names
+}
+
+/// Find all *.conf files in the drop-in directories of this unit in all unit dirs. They are sorted by their file name,
+/// regardless of the directory they are in. If the same file name exists multiple times only the first one found is used,
+/// so earlier unit dirs can mask drop-ins of later ones.
+pub fn find_dropins(unit_dirs: &[PathBuf], id: &UnitId) -> Vec<PathBuf> {
+    let mut dropins: HashMap<std::ffi::OsString, PathBuf> = HashMap::new();
+    for unit_dir in unit_dirs {
+        for dir_name in dropin_dir_names(id) {
+            let entries = match std::fs::read_dir(unit_dir.join(dir_name)) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.is_file() && path.extension() == Some(std::ffi::OsStr::new("conf")) {
+                    dropins.entry(entry.file_name()).or_insert(path);
+                }
+            }
+        }
+    }
+    let mut names: Vec<_> = dropins.keys().cloned().collect();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| dropins.remove(&name).unwrap())
+        .collect()
+}
+
+/// Merge the settings of a drop-in into the parsed unit file. Entries of list settings are appended, all other
+/// settings replace the ones of the unit file.
+pub fn merge_dropin(parsed_file: &mut ParsedFile, dropin: ParsedFile) {
+    for (section_name, dropin_section) in dropin {
+        let section = parsed_file.entry(section_name).or_default();
+        // keep the order of the entries, the dropin entries come after the ones already in the section
+        let offset = section
+            .values()
+            .flat_map(|entries| entries.iter().map(|(entry_number, _)| *entry_number + 1))
+            .max()
+            .unwrap_or(0);
+        for (name, entries) in dropin_section {
+            let entries = entries
+                .into_iter()
+                .map(|(entry_number, value)| (entry_number + offset, value));
+            if LIST_SETTINGS.contains(&name.as_str()) {
+                section.entry(name).or_default().extend(entries);
+            } else {
+                section.insert(name, entries.collect());
+            }
+        }
+    }
+}
+
+/// An empty assignment (e.g. "ExecStart=") resets the setting, so all entries up to the last empty one are removed.
+/// Settings that are empty after that are removed completely so they get their default value.
+pub fn apply_empty_assignments(parsed_file: &mut ParsedFile) {
+    for section in parsed_file.values_mut() {
+        for entries in section.values_mut() {
+            entries.sort_by_key(|(entry_number, _)| *entry_number);
+            if let Some(last_reset) = entries.iter().rposition(|(_, value)| value.is_empty()) {
+                entries.drain(..=last_reset);
+            }
+        }
+        section.retain(|_, entries| !entries.is_empty());
+    }
+}
+
+/// Read all drop-ins and merge them into the parsed unit file. Returns the paths of the drop-ins that were applied.
+pub fn load_dropins(
+    parsed_file: &mut ParsedFile,
+    unit_dirs: &[PathBuf],
+    id: &UnitId,
+) -> Result<Vec<PathBuf>, ParsingError> {
+    let dropins = find_dropins(unit_dirs, id);
+    for path in &dropins {
+        merge_dropin(parsed_file, parse_dropin(path)?);
+    }
+    apply_empty_assignments(parsed_file);
+    Ok(dropins)
+}
+
+fn parse_dropin(path: &Path) -> Result<ParsedFile, ParsingError> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| ParsingError::new(ParsingErrorReason::from(Box::new(e)), path.to_owned()))?;
+    parse_file(&raw).map_err(|e| ParsingError::new(e, path.to_owned()))
+}
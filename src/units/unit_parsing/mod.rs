@@ -1,11 +1,21 @@
+mod dropins;
+mod path_unit;
 mod service_unit;
+mod slice_unit;
 mod socket_unit;
+mod specifiers;
 mod target_unit;
+mod timer_unit;
 mod unit_parser;
 
+pub use dropins::*;
+pub use path_unit::*;
 pub use service_unit::*;
+pub use slice_unit::*;
 pub use socket_unit::*;
+pub use specifiers::*;
 pub use target_unit::*;
+pub use timer_unit::*;
 pub use unit_parser::*;
 
 use std::path::PathBuf;
@@ -26,6 +36,18 @@ pub struct ParsedSocketConfig {
 pub struct ParsedTargetConfig {
     pub common: ParsedCommonConfig,
 }
+pub struct ParsedTimerConfig {
+    pub common: ParsedCommonConfig,
+    pub timer: ParsedTimerSection,
+}
+pub struct ParsedPathConfig {
+    pub common: ParsedCommonConfig,
+    pub path: ParsedPathSection,
+}
+pub struct ParsedSliceConfig {
+    pub common: ParsedCommonConfig,
+    pub resources: ResourceControl,
+}
 
 #[derive(Default)]
 pub struct ParsedUnitSection {
@@ -35,6 +57,21 @@ pub struct ParsedUnitSection {
     pub requires: Vec<String>,
     pub before: Vec<String>,
     pub after: Vec<String>,
+    pub conflicts: Vec<String>,
+    pub binds_to: Vec<String>,
+    pub part_of: Vec<String>,
+    pub requisite: Vec<String>,
+
+    pub on_failure: Vec<String>,
+    pub on_success: Vec<String>,
+    pub on_failure_job_mode: Option<JobMode>,
+    pub on_success_job_mode: Option<JobMode>,
+
+    pub start_limit_interval: Option<std::time::Duration>,
+    pub start_limit_burst: Option<u32>,
+
+    pub conditions: Vec<crate::units::UnitCondition>,
+    pub asserts: Vec<crate::units::UnitCondition>,
 }
 #[derive(Clone)]
 pub struct ParsedSingleSocketConfig {
@@ -57,15 +94,22 @@ pub struct ParsedSocketSection {
     pub sockets: Vec<ParsedSingleSocketConfig>,
     pub filedesc_name: Option<String>,
     pub services: Vec<String>,
+    pub accept: bool,
+    pub max_connections: Option<usize>,
+    pub max_connections_per_source: Option<usize>,
 
     pub exec_section: ParsedExecSection,
 }
 pub struct ParsedServiceSection {
     pub restart: ServiceRestart,
-    pub accept: bool,
+    pub restart_sec: Option<std::time::Duration>,
+    pub restart_steps: u32,
+    pub restart_max_delay: Option<std::time::Duration>,
+    pub standard_input: StandardInput,
     pub notifyaccess: NotifyKind,
     pub exec: Commandline,
     pub stop: Vec<Commandline>,
+    pub reload: Vec<Commandline>,
     pub stoppost: Vec<Commandline>,
     pub startpre: Vec<Commandline>,
     pub startpost: Vec<Commandline>,
@@ -75,10 +119,34 @@ pub struct ParsedServiceSection {
     pub generaltimeout: Option<Timeout>,
 
     pub dbus_name: Option<String>,
+    pub pid_file: Option<PathBuf>,
+    pub watchdog_sec: Option<std::time::Duration>,
+    pub watchdog_signal: nix::sys::signal::Signal,
+    pub fd_store_max: usize,
 
     pub sockets: Vec<String>,
+    pub slice: Option<String>,
 
     pub exec_section: ParsedExecSection,
+    pub resources: ResourceControl,
+    pub kill: KillConfig,
+}
+
+pub struct ParsedTimerSection {
+    pub on_calendar: Vec<crate::timers::CalendarSpec>,
+    pub on_boot: Vec<std::time::Duration>,
+    pub on_unit_active: Vec<std::time::Duration>,
+    pub on_unit_inactive: Vec<std::time::Duration>,
+    pub accuracy: Option<std::time::Duration>,
+    pub randomized_delay: Option<std::time::Duration>,
+    pub persistent: bool,
+    pub unit: Option<String>,
+}
+
+pub struct ParsedPathSection {
+    pub conditions: Vec<crate::paths::PathCondition>,
+    pub make_directory: bool,
+    pub unit: Option<String>,
 }
 
 #[derive(Default)]
@@ -91,6 +159,8 @@ pub struct ParsedExecSection {
     pub group: Option<String>,
     pub stdout_path: Option<StdIoOption>,
     pub stderr_path: Option<StdIoOption>,
+    pub tty_path: Option<PathBuf>,
+    pub syslog_identifier: Option<String>,
     pub supplementary_groups: Vec<String>,
     pub environment: Option<EnvVars>,
 }
@@ -101,6 +171,9 @@ pub enum ServiceType {
     Notify,
     Dbus,
     OneShot,
+    Forking,
+    Exec,
+    Idle,
 }
 
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
@@ -111,10 +184,76 @@ pub enum NotifyKind {
     None,
 }
 
+/// Where stdin of the service is connected to. Socket only works for instances started by sockets with Accept=yes.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum StandardInput {
+    Null,
+    Socket,
+}
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum ServiceRestart {
     Always,
     No,
+    OnSuccess,
+    OnFailure,
+    OnAbnormal,
+    OnAbort,
+    OnWatchdog,
+}
+
+/// Which processes of a service get the KillSignal= when it is stopped
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum KillMode {
+    /// All processes in the cgroup of the service, or in its process group without cgroups
+    ControlGroup,
+    /// Only the main process
+    Process,
+    /// Only the main process, the remaining processes get the FinalKillSignal= after it exited
+    Mixed,
+    /// No processes are signaled, only the ExecStop= commands run
+    None,
+}
+
+/// How the processes of a service are stopped
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct KillConfig {
+    pub mode: KillMode,
+    pub signal: nix::sys::signal::Signal,
+    /// Sent instead of the signal if the service is restarted
+    pub restart_signal: Option<nix::sys::signal::Signal>,
+    /// Send SIGHUP right after the signal, for shells and other programs that only react to that
+    pub send_sighup: bool,
+    /// Send the final_signal to the processes that are left after the stop timeout
+    pub send_sigkill: bool,
+    pub final_signal: nix::sys::signal::Signal,
+}
+
+impl Default for KillConfig {
+    fn default() -> Self {
+        KillConfig {
+            mode: KillMode::ControlGroup,
+            signal: nix::sys::signal::Signal::SIGTERM,
+            restart_signal: None,
+            send_sighup: false,
+            send_sigkill: true,
+            final_signal: nix::sys::signal::Signal::SIGKILL,
+        }
+    }
+}
+
+/// How the jobs of a transaction are merged with the jobs that are already queued (see OnFailureJobMode=)
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub enum JobMode {
+    /// Replace queued jobs that contradict the new ones
+    #[default]
+    Replace,
+    /// Fail if a new job contradicts a queued job
+    Fail,
+    /// Like replace, but also stop all units that are not needed by the new jobs
+    Isolate,
+    /// Only queue the job for the unit itself, do not pull in or check its dependencies
+    IgnoreDependencies,
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -123,10 +262,88 @@ pub enum Timeout {
     Infinity,
 }
 
+/// Where stdout/stderr of a service go (see StandardOutput= and StandardError=)
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum StdIoOption {
     File(PathBuf),
     AppendFile(PathBuf),
+    TruncateFile(PathBuf),
+    /// The log of rustysd, like if the setting was not set
+    Journal,
+    Null,
+    /// stdout gets the same as stdin, stderr the same as stdout
+    Inherit,
+    /// The terminal from TTYPath=
+    Tty,
+    /// The connection of instances started by sockets with Accept=yes
+    Socket,
+    /// A fd with this name (FileDescriptorName=) of a socket unit
+    Fd(String),
+}
+
+/// A limit like MemoryMax= or TasksMax=
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ResourceLimit {
+    Absolute(u64),
+    /// Percent of the physical memory or of the maximum number of tasks of the system
+    Percent(u64),
+    Infinity,
+}
+
+/// The resource control settings that are written to the cgroup of a unit. Unset settings are not written.
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct ResourceControl {
+    pub memory_max: Option<ResourceLimit>,
+    pub memory_high: Option<ResourceLimit>,
+    pub memory_low: Option<ResourceLimit>,
+    pub cpu_weight: Option<u64>,
+    /// Percent of the time of one cpu, more than 100 allows using multiple cpus
+    pub cpu_quota: Option<u64>,
+    pub tasks_max: Option<ResourceLimit>,
+    pub io_weight: Option<u64>,
+    /// Like cpuset.cpus expects it, e.g. "0-3,6"
+    pub allowed_cpus: Option<String>,
+}
+
+impl std::fmt::Display for ResourceLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ResourceLimit::Absolute(value) => write!(f, "{}", value),
+            ResourceLimit::Percent(percent) => write!(f, "{}%", percent),
+            ResourceLimit::Infinity => write!(f, "infinity"),
+        }
+    }
+}
+
+impl ResourceControl {
+    /// The settings that are set, with their values in the syntax of the unit files
+    pub fn properties(&self) -> Vec<(&'static str, String)> {
+        let mut properties = Vec::new();
+        let limits = [
+            ("MemoryMax", self.memory_max),
+            ("MemoryHigh", self.memory_high),
+            ("MemoryLow", self.memory_low),
+            ("TasksMax", self.tasks_max),
+        ];
+        for (name, limit) in limits.iter() {
+            if let Some(limit) = limit {
+                properties.push((*name, limit.to_string()));
+            }
+        }
+        if let Some(weight) = self.cpu_weight {
+            properties.push(("CPUWeight", weight.to_string()));
+        }
+        if let Some(quota) = self.cpu_quota {
+            properties.push(("CPUQuota", format!("{}%", quota)));
+        }
+        if let Some(weight) = self.io_weight {
+            properties.push(("IOWeight", weight.to_string()));
+        }
+        if let Some(cpus) = &self.allowed_cpus {
+            properties.push(("AllowedCPUs", cpus.clone()));
+        }
+        properties
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
@@ -0,0 +1,140 @@
+use crate::paths::PathCondition;
+use crate::units::*;
+use std::path::{Path, PathBuf};
+
+pub fn parse_path(
+    parsed_file: ParsedFile,
+    path: &Path,
+) -> Result<ParsedPathConfig, ParsingErrorReason> {
+    let mut path_config = None;
+    let mut install_config = None;
+    let mut unit_config = None;
+
+    for (name, section) in parsed_file {
+        match name.as_str() {
+            "[Path]" => {
+                path_config = Some(parse_path_section(section)?);
+            }
+            "[Unit]" => {
+                unit_config = Some(parse_unit_section(section)?);
+            }
+            "[Install]" => {
+                install_config = Some(parse_install_section(section)?);
+            }
+
+            _ => return Err(ParsingErrorReason::UnknownSection(name.to_owned())),
+        }
+    }
+
+    let path_config = if let Some(path_config) = path_config {
+        path_config
+    } else {
+        return Err(ParsingErrorReason::SectionNotFound("Path".to_owned()));
+    };
+
+    Ok(ParsedPathConfig {
+        common: ParsedCommonConfig {
+            name: path.file_name().unwrap().to_str().unwrap().to_owned(),
+            unit: unit_config.unwrap_or_else(Default::default),
+            install: install_config.unwrap_or_else(Default::default),
+        },
+        path: path_config,
+    })
+}
+
+fn parse_absolute_paths(
+    setting: &str,
+    raw: Option<Vec<(u32, String)>>,
+) -> Result<Vec<(u32, PathBuf)>, ParsingErrorReason> {
+    let mut paths = Vec::new();
+    for (entry_number, value) in raw.unwrap_or_default() {
+        let path = PathBuf::from(&value);
+        if !path.is_absolute() {
+            return Err(ParsingErrorReason::UnknownSetting(
+                setting.to_owned(),
+                value,
+            ));
+        }
+        paths.push((entry_number, path));
+    }
+    Ok(paths)
+}
+
+fn parse_path_section(mut section: ParsedSection) -> Result<ParsedPathSection, ParsingErrorReason> {
+    let exists = section.remove("PATHEXISTS");
+    let exists_glob = section.remove("PATHEXISTSGLOB");
+    let changed = section.remove("PATHCHANGED");
+    let modified = section.remove("PATHMODIFIED");
+    let dir_not_empty = section.remove("DIRECTORYNOTEMPTY");
+    let make_directory = section.remove("MAKEDIRECTORY");
+    let unit = section.remove("UNIT");
+
+    if !section.is_empty() {
+        return Err(ParsingErrorReason::UnusedSetting(
+            section.keys().next().unwrap().to_owned(),
+        ));
+    }
+
+    // keep the order of the settings in the file
+    let mut conditions = Vec::new();
+    for (entry_number, path) in parse_absolute_paths("PathExists", exists)? {
+        conditions.push((entry_number, PathCondition::Exists(path)));
+    }
+    for (entry_number, path) in parse_absolute_paths("PathExistsGlob", exists_glob)? {
+        conditions.push((entry_number, PathCondition::ExistsGlob(path)));
+    }
+    for (entry_number, path) in parse_absolute_paths("PathChanged", changed)? {
+        conditions.push((entry_number, PathCondition::Changed(path)));
+    }
+    for (entry_number, path) in parse_absolute_paths("PathModified", modified)? {
+        conditions.push((entry_number, PathCondition::Modified(path)));
+    }
+    for (entry_number, path) in parse_absolute_paths("DirectoryNotEmpty", dir_not_empty)? {
+        conditions.push((entry_number, PathCondition::DirectoryNotEmpty(path)));
+    }
+    conditions.sort_by_key(|(entry_number, _)| *entry_number);
+    let conditions: Vec<PathCondition> = conditions
+        .into_iter()
+        .map(|(_, condition)| condition)
+        .collect();
+
+    if conditions.is_empty() {
+        return Err(ParsingErrorReason::MissingSetting(
+            "PathExists/PathExistsGlob/PathChanged/PathModified/DirectoryNotEmpty".to_owned(),
+        ));
+    }
+
+    let make_directory = match make_directory {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "MakeDirectory".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
+
+    let unit = match unit {
+        Some(mut vec) => {
+            if vec.len() == 1 {
+                Some(vec.remove(0).1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "Unit".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+
+    Ok(ParsedPathSection {
+        conditions,
+        make_directory,
+        unit,
+    })
+}
@@ -41,6 +41,66 @@ pub fn parse_service(
     })
 }
 
+/// Signals can be given with or without the SIG prefix
+fn parse_signal(name: &str) -> Option<nix::sys::signal::Signal> {
+    use std::str::FromStr;
+    let name = name.trim().to_uppercase();
+    if name.starts_with("SIG") {
+        nix::sys::signal::Signal::from_str(&name).ok()
+    } else {
+        nix::sys::signal::Signal::from_str(&format!("SIG{}", name)).ok()
+    }
+}
+
+/// The settings that control how the processes of the service are stopped
+fn parse_kill_config(section: &mut ParsedSection) -> Result<KillConfig, ParsingErrorReason> {
+    let mut kill = KillConfig::default();
+    let settings = [
+        "KILLMODE",
+        "KILLSIGNAL",
+        "RESTARTKILLSIGNAL",
+        "SENDSIGHUP",
+        "SENDSIGKILL",
+        "FINALKILLSIGNAL",
+    ];
+    for name in settings.iter() {
+        let value = match section.remove(*name) {
+            Some(mut vec) => {
+                if vec.len() == 1 {
+                    vec.remove(0).1
+                } else {
+                    return Err(ParsingErrorReason::SettingTooManyValues(
+                        name.to_string(),
+                        super::map_tupels_to_second(vec),
+                    ));
+                }
+            }
+            None => continue,
+        };
+        let err = || ParsingErrorReason::UnknownSetting(name.to_string(), value.clone());
+        match *name {
+            "KILLMODE" => {
+                kill.mode = match value.as_str() {
+                    "control-group" => KillMode::ControlGroup,
+                    "process" => KillMode::Process,
+                    "mixed" => KillMode::Mixed,
+                    "none" => KillMode::None,
+                    _ => return Err(err()),
+                }
+            }
+            "KILLSIGNAL" => kill.signal = parse_signal(&value).ok_or_else(err)?,
+            "RESTARTKILLSIGNAL" => {
+                kill.restart_signal = Some(parse_signal(&value).ok_or_else(err)?)
+            }
+            "SENDSIGHUP" => kill.send_sighup = string_to_bool(&value),
+            "SENDSIGKILL" => kill.send_sigkill = string_to_bool(&value),
+            "FINALKILLSIGNAL" => kill.final_signal = parse_signal(&value).ok_or_else(err)?,
+            _ => unreachable!(),
+        }
+    }
+    Ok(kill)
+}
+
 fn parse_timeout(descr: &str) -> Timeout {
     if descr.to_uppercase() == "INFINITY" {
         Timeout::Infinity
@@ -143,6 +203,7 @@ fn parse_service_section(
 ) -> Result<ParsedServiceSection, ParsingErrorReason> {
     let exec = section.remove("EXECSTART");
     let stop = section.remove("EXECSTOP");
+    let reload = section.remove("EXECRELOAD");
     let stoppost = section.remove("EXECSTOPPOST");
     let startpre = section.remove("EXECSTARTPRE");
     let startpost = section.remove("EXECSTARTPOST");
@@ -151,13 +212,23 @@ fn parse_service_section(
     let generaltimeout = section.remove("TIMEOUTSEC");
 
     let restart = section.remove("RESTART");
+    let restart_sec = section.remove("RESTARTSEC");
+    let restart_steps = section.remove("RESTARTSTEPS");
+    let restart_max_delay = section.remove("RESTARTMAXDELAYSEC");
     let sockets = section.remove("SOCKETS");
     let notify_access = section.remove("NOTIFYACCESS");
     let srcv_type = section.remove("TYPE");
-    let accept = section.remove("ACCEPT");
+    let standard_input = section.remove("STANDARDINPUT");
     let dbus_name = section.remove("BUSNAME");
+    let pid_file = section.remove("PIDFILE");
+    let watchdog_sec = section.remove("WATCHDOGSEC");
+    let watchdog_signal = section.remove("WATCHDOGSIGNAL");
+    let fd_store_max = section.remove("FILEDESCRIPTORSTOREMAX");
+    let slice = section.remove("SLICE");
 
     let exec_config = super::parse_exec_section(&mut section)?;
+    let resources = super::parse_resource_control(&mut section)?;
+    let kill = parse_kill_config(&mut section)?;
 
     if !section.is_empty() {
         return Err(ParsingErrorReason::UnusedSetting(
@@ -226,6 +297,9 @@ fn parse_service_section(
                     "simple" => ServiceType::Simple,
                     "notify" => ServiceType::Notify,
                     "oneshot" => ServiceType::OneShot,
+                    "forking" => ServiceType::Forking,
+                    "exec" => ServiceType::Exec,
+                    "idle" => ServiceType::Idle,
                     "dbus" => {
                         if cfg!(feature = "dbus_support") {
                             ServiceType::Dbus
@@ -283,6 +357,10 @@ fn parse_service_section(
         Some(vec) => parse_cmdlines(&vec)?,
         None => Vec::new(),
     };
+    let reload = match reload {
+        Some(vec) => parse_cmdlines(&vec)?,
+        None => Vec::new(),
+    };
     let stoppost = match stoppost {
         Some(vec) => parse_cmdlines(&vec)?,
         None => Vec::new(),
@@ -302,6 +380,11 @@ fn parse_service_section(
                 match vec[0].1.to_uppercase().as_str() {
                     "ALWAYS" => ServiceRestart::Always,
                     "NO" => ServiceRestart::No,
+                    "ON-SUCCESS" => ServiceRestart::OnSuccess,
+                    "ON-FAILURE" => ServiceRestart::OnFailure,
+                    "ON-ABNORMAL" => ServiceRestart::OnAbnormal,
+                    "ON-ABORT" => ServiceRestart::OnAbort,
+                    "ON-WATCHDOG" => ServiceRestart::OnWatchdog,
 
                     name => {
                         return Err(ParsingErrorReason::UnknownSetting(
@@ -319,18 +402,121 @@ fn parse_service_section(
         }
         None => ServiceRestart::No,
     };
-    let accept = match accept {
+    let restart_sec = match restart_sec {
+        Some(vec) => {
+            if vec.len() == 1 {
+                Some(parse_timespan(&vec[0].1)?)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "RestartSec".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let restart_steps = match restart_steps {
+        Some(vec) => {
+            if vec.len() == 1 {
+                vec[0].1.parse::<u32>().map_err(|_| {
+                    ParsingErrorReason::UnknownSetting("RestartSteps".to_owned(), vec[0].1.clone())
+                })?
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "RestartSteps".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => 0,
+    };
+    let fd_store_max = match fd_store_max {
         Some(vec) => {
             if vec.len() == 1 {
-                string_to_bool(&vec[0].1)
+                vec[0].1.parse::<usize>().map_err(|_| {
+                    ParsingErrorReason::UnknownSetting(
+                        "FileDescriptorStoreMax".to_owned(),
+                        vec[0].1.clone(),
+                    )
+                })?
             } else {
                 return Err(ParsingErrorReason::SettingTooManyValues(
-                    "Accept".to_owned(),
+                    "FileDescriptorStoreMax".to_owned(),
                     super::map_tupels_to_second(vec),
                 ));
             }
         }
-        None => false,
+        None => 0,
+    };
+    let restart_max_delay = match restart_max_delay {
+        Some(vec) => {
+            if vec.len() == 1 {
+                Some(parse_timespan(&vec[0].1)?)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "RestartMaxDelaySec".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let watchdog_sec = match watchdog_sec {
+        Some(vec) => {
+            if vec.len() == 1 {
+                if vec[0].1.to_uppercase() == "INFINITY" {
+                    None
+                } else {
+                    Some(parse_timespan(&vec[0].1)?).filter(|dur| dur.as_nanos() > 0)
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "WatchdogSec".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let watchdog_signal = match watchdog_signal {
+        Some(vec) => {
+            if vec.len() == 1 {
+                parse_signal(&vec[0].1).ok_or_else(|| {
+                    ParsingErrorReason::UnknownSetting(
+                        "WatchdogSignal".to_owned(),
+                        vec[0].1.clone(),
+                    )
+                })?
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "WatchdogSignal".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => nix::sys::signal::Signal::SIGABRT,
+    };
+    let standard_input = match standard_input {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.as_str() {
+                    "null" => StandardInput::Null,
+                    "socket" => StandardInput::Socket,
+                    name => {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "StandardInput".to_owned(),
+                            name.to_owned(),
+                        ))
+                    }
+                }
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "StandardInput".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => StandardInput::Null,
     };
     let dbus_name = match dbus_name {
         Some(vec) => {
@@ -346,6 +532,42 @@ fn parse_service_section(
         None => None,
     };
 
+    // relative paths are relative to /run like in systemd
+    let pid_file = match pid_file {
+        Some(vec) => {
+            if vec.len() == 1 {
+                Some(PathBuf::from("/run").join(&vec[0].1))
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "PIDFile".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+
+    let slice = match slice {
+        Some(mut vec) => {
+            if vec.len() == 1 {
+                let slice = vec.remove(0).1;
+                if !super::is_valid_slice_name(&slice) {
+                    return Err(ParsingErrorReason::UnknownSetting(
+                        "Slice".to_owned(),
+                        slice,
+                    ));
+                }
+                Some(slice)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "Slice".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+
     if let ServiceType::Dbus = srcv_type {
         if dbus_name.is_none() {
             return Err(ParsingErrorReason::MissingSetting("BusName".to_owned()));
@@ -356,10 +578,18 @@ fn parse_service_section(
         srcv_type,
         notifyaccess,
         restart,
-        accept,
+        restart_sec,
+        restart_steps,
+        restart_max_delay,
+        standard_input,
         dbus_name,
+        pid_file,
+        watchdog_sec,
+        watchdog_signal,
+        fd_store_max,
         exec,
         stop,
+        reload,
         stoppost,
         startpre,
         startpost,
@@ -367,6 +597,9 @@ fn parse_service_section(
         stoptimeout,
         generaltimeout,
         sockets: map_tupels_to_second(sockets.unwrap_or_default()),
+        slice,
         exec_section: exec_config,
+        resources,
+        kill,
     })
 }
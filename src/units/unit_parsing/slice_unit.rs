@@ -0,0 +1,70 @@
+use crate::units::*;
+use std::path::Path;
+
+pub fn parse_slice(
+    parsed_file: ParsedFile,
+    path: &Path,
+) -> Result<ParsedSliceConfig, ParsingErrorReason> {
+    let mut resources = None;
+    let mut install_config = None;
+    let mut unit_config = None;
+
+    for (name, section) in parsed_file {
+        match name.as_str() {
+            "[Slice]" => {
+                resources = Some(parse_slice_section(section)?);
+            }
+            "[Unit]" => {
+                unit_config = Some(parse_unit_section(section)?);
+            }
+            "[Install]" => {
+                install_config = Some(parse_install_section(section)?);
+            }
+
+            _ => return Err(ParsingErrorReason::UnknownSection(name.to_owned())),
+        }
+    }
+
+    let name = path.file_name().unwrap().to_str().unwrap().to_owned();
+    if !is_valid_slice_name(&name) {
+        return Err(ParsingErrorReason::Generic(format!(
+            "{} is not a valid name for a slice",
+            name
+        )));
+    }
+
+    Ok(ParsedSliceConfig {
+        common: ParsedCommonConfig {
+            name,
+            unit: unit_config.unwrap_or_else(Default::default),
+            install: install_config.unwrap_or_else(Default::default),
+        },
+        // a slice without settings only groups its units
+        resources: resources.unwrap_or_default(),
+    })
+}
+
+/// The parts of a slice name are separated by single dashes, each part is one level in the hierarchy. The root slice
+/// is called -.slice.
+pub fn is_valid_slice_name(name: &str) -> bool {
+    if !name.ends_with(".slice") {
+        return false;
+    }
+    let prefix = &name[..name.len() - ".slice".len()];
+    prefix == "-"
+        || !(prefix.is_empty()
+            || prefix.starts_with('-')
+            || prefix.ends_with('-')
+            || prefix.contains("--"))
+}
+
+fn parse_slice_section(mut section: ParsedSection) -> Result<ResourceControl, ParsingErrorReason> {
+    let resources = super::parse_resource_control(&mut section)?;
+
+    if !section.is_empty() {
+        return Err(ParsingErrorReason::UnusedSetting(
+            section.keys().next().unwrap().to_owned(),
+        ));
+    }
+    Ok(resources)
+}
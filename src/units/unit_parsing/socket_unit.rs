@@ -62,6 +62,31 @@ fn parse_unix_addr(addr: &str) -> Result<String, ()> {
     }
 }
 
+fn parse_connection_limit(
+    setting: Option<Vec<(u32, String)>>,
+    name: &str,
+) -> Result<Option<usize>, ParsingErrorReason> {
+    match setting {
+        Some(vec) => {
+            if vec.len() == 1 {
+                match vec[0].1.parse::<usize>() {
+                    Ok(limit) if limit > 0 => Ok(Some(limit)),
+                    _ => Err(ParsingErrorReason::UnknownSetting(
+                        name.to_owned(),
+                        vec[0].1.clone(),
+                    )),
+                }
+            } else {
+                Err(ParsingErrorReason::SettingTooManyValues(
+                    name.to_owned(),
+                    super::map_tupels_to_second(vec),
+                ))
+            }
+        }
+        None => Ok(None),
+    }
+}
+
 fn parse_socket_section(
     mut section: ParsedSection,
 ) -> Result<ParsedSocketSection, ParsingErrorReason> {
@@ -71,6 +96,9 @@ fn parse_socket_section(
     let datagrams = section.remove("LISTENDATAGRAM");
     let seqpacks = section.remove("LISTENSEQUENTIALPACKET");
     let fifos = section.remove("LISTENFIFO");
+    let accept = section.remove("ACCEPT");
+    let max_connections = section.remove("MAXCONNECTIONS");
+    let max_connections_per_source = section.remove("MAXCONNECTIONSPERSOURCE");
 
     let exec_config = super::parse_exec_section(&mut section)?;
 
@@ -99,6 +127,23 @@ fn parse_socket_section(
         .map(|vec| super::map_tupels_to_second(vec))
         .unwrap_or_default();
 
+    let accept = match accept {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "Accept".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
+    let max_connections = parse_connection_limit(max_connections, "MaxConnections")?;
+    let max_connections_per_source =
+        parse_connection_limit(max_connections_per_source, "MaxConnectionsPerSource")?;
+
     let mut socket_kinds: Vec<(u32, SocketKind)> = Vec::new();
     if let Some(mut streams) = streams {
         for _ in 0..streams.len() {
@@ -187,6 +232,9 @@ fn parse_socket_section(
     Ok(ParsedSocketSection {
         filedesc_name: fdname,
         services,
+        accept,
+        max_connections,
+        max_connections_per_source,
         sockets: socket_configs,
         exec_section: exec_config,
     })
@@ -0,0 +1,86 @@
+//! Specifiers like %i or %n that can be used in the settings of unit files (see systemd.unit(5)).
+//! Only the specifiers that are derived from the unit name are supported, unknown specifiers are left as they are.
+
+use crate::units::*;
+
+/// Reverse the escaping used in unit names: '-' stands for '/' and "\xNN" for the byte NN
+pub fn unescape_unit_name(escaped: &str) -> String {
+    let raw = escaped.as_bytes();
+    let mut bytes = Vec::new();
+    let mut idx = 0;
+    while idx < raw.len() {
+        match raw[idx] {
+            b'-' => {
+                bytes.push(b'/');
+                idx += 1;
+            }
+            b'\\' if raw.len() >= idx + 4 && raw[idx + 1] == b'x' => {
+                let byte = std::str::from_utf8(&raw[idx + 2..idx + 4])
+                    .ok()
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+                match byte {
+                    Some(byte) => {
+                        bytes.push(byte);
+                        idx += 4;
+                    }
+                    None => {
+                        bytes.push(b'\\');
+                        idx += 1;
+                    }
+                }
+            }
+            byte => {
+                bytes.push(byte);
+                idx += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn expand_value(value: &str, id: &UnitId) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => expanded.push_str(&id.name),
+            Some('N') => expanded.push_str(&id.name_without_suffix()),
+            Some('p') => expanded.push_str(&id.prefix()),
+            Some('P') => expanded.push_str(&unescape_unit_name(&id.prefix())),
+            Some('i') => expanded.push_str(&id.instance().unwrap_or_default()),
+            Some('I') => expanded.push_str(&unescape_unit_name(&id.instance().unwrap_or_default())),
+            Some('f') => {
+                let name = match id.instance() {
+                    Some(instance) => unescape_unit_name(&instance),
+                    None => unescape_unit_name(&id.prefix()),
+                };
+                if !name.starts_with('/') {
+                    expanded.push('/');
+                }
+                expanded.push_str(&name);
+            }
+            Some('%') => expanded.push('%'),
+            Some(other) => {
+                expanded.push('%');
+                expanded.push(other);
+            }
+            None => expanded.push('%'),
+        }
+    }
+    expanded
+}
+
+/// Replace the specifiers in all settings with the values for the unit with this id
+pub fn expand_specifiers(parsed_file: &mut ParsedFile, id: &UnitId) {
+    for section in parsed_file.values_mut() {
+        for entries in section.values_mut() {
+            for (_, value) in entries.iter_mut() {
+                *value = expand_value(value, id);
+            }
+        }
+    }
+}
@@ -0,0 +1,157 @@
+use crate::timers::CalendarSpec;
+use crate::units::*;
+use std::path::Path;
+
+pub fn parse_timer(
+    parsed_file: ParsedFile,
+    path: &Path,
+) -> Result<ParsedTimerConfig, ParsingErrorReason> {
+    let mut timer_config = None;
+    let mut install_config = None;
+    let mut unit_config = None;
+
+    for (name, section) in parsed_file {
+        match name.as_str() {
+            "[Timer]" => {
+                timer_config = Some(parse_timer_section(section)?);
+            }
+            "[Unit]" => {
+                unit_config = Some(parse_unit_section(section)?);
+            }
+            "[Install]" => {
+                install_config = Some(parse_install_section(section)?);
+            }
+
+            _ => return Err(ParsingErrorReason::UnknownSection(name.to_owned())),
+        }
+    }
+
+    let timer_config = if let Some(timer_config) = timer_config {
+        timer_config
+    } else {
+        return Err(ParsingErrorReason::SectionNotFound("Timer".to_owned()));
+    };
+
+    Ok(ParsedTimerConfig {
+        common: ParsedCommonConfig {
+            name: path.file_name().unwrap().to_str().unwrap().to_owned(),
+            unit: unit_config.unwrap_or_else(Default::default),
+            install: install_config.unwrap_or_else(Default::default),
+        },
+        timer: timer_config,
+    })
+}
+
+fn parse_timespans(
+    setting: &str,
+    raw: Option<Vec<(u32, String)>>,
+) -> Result<Vec<std::time::Duration>, ParsingErrorReason> {
+    let mut spans = Vec::new();
+    for (_, value) in raw.unwrap_or_default() {
+        spans.push(
+            parse_timespan(&value)
+                .map_err(|_| ParsingErrorReason::UnknownSetting(setting.to_owned(), value))?,
+        );
+    }
+    Ok(spans)
+}
+
+fn parse_single_timespan(
+    setting: &str,
+    raw: Option<Vec<(u32, String)>>,
+) -> Result<Option<std::time::Duration>, ParsingErrorReason> {
+    match raw {
+        None => Ok(None),
+        Some(vec) => {
+            if vec.len() > 1 {
+                Err(ParsingErrorReason::SettingTooManyValues(
+                    setting.to_owned(),
+                    super::map_tupels_to_second(vec),
+                ))
+            } else {
+                Ok(parse_timespans(setting, Some(vec))?.pop())
+            }
+        }
+    }
+}
+
+fn parse_timer_section(
+    mut section: ParsedSection,
+) -> Result<ParsedTimerSection, ParsingErrorReason> {
+    let on_calendar = section.remove("ONCALENDAR");
+    let on_boot = section.remove("ONBOOTSEC");
+    let on_unit_active = section.remove("ONUNITACTIVESEC");
+    let on_unit_inactive = section.remove("ONUNITINACTIVESEC");
+    let accuracy = section.remove("ACCURACYSEC");
+    let randomized_delay = section.remove("RANDOMIZEDDELAYSEC");
+    let persistent = section.remove("PERSISTENT");
+    let unit = section.remove("UNIT");
+
+    if !section.is_empty() {
+        return Err(ParsingErrorReason::UnusedSetting(
+            section.keys().next().unwrap().to_owned(),
+        ));
+    }
+
+    let mut calendar_specs = Vec::new();
+    for (_, value) in on_calendar.unwrap_or_default() {
+        let spec = CalendarSpec::parse(&value)
+            .map_err(|_| ParsingErrorReason::UnknownSetting("OnCalendar".to_owned(), value))?;
+        calendar_specs.push(spec);
+    }
+
+    let on_boot = parse_timespans("OnBootSec", on_boot)?;
+    let on_unit_active = parse_timespans("OnUnitActiveSec", on_unit_active)?;
+    let on_unit_inactive = parse_timespans("OnUnitInactiveSec", on_unit_inactive)?;
+    let accuracy = parse_single_timespan("AccuracySec", accuracy)?;
+    let randomized_delay = parse_single_timespan("RandomizedDelaySec", randomized_delay)?;
+
+    if calendar_specs.is_empty()
+        && on_boot.is_empty()
+        && on_unit_active.is_empty()
+        && on_unit_inactive.is_empty()
+    {
+        return Err(ParsingErrorReason::MissingSetting(
+            "OnCalendar/OnBootSec/OnUnitActiveSec/OnUnitInactiveSec".to_owned(),
+        ));
+    }
+
+    let persistent = match persistent {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "Persistent".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
+
+    let unit = match unit {
+        Some(mut vec) => {
+            if vec.len() == 1 {
+                Some(vec.remove(0).1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "Unit".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+
+    Ok(ParsedTimerSection {
+        on_calendar: calendar_specs,
+        on_boot,
+        on_unit_active,
+        on_unit_inactive,
+        accuracy,
+        randomized_delay,
+        persistent,
+        unit,
+    })
+}
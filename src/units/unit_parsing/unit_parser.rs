@@ -21,6 +21,10 @@ pub fn parse_file(content: &str) -> Result<ParsedFile, ParsingErrorReason> {
     while !lines_left.is_empty() && !lines_left[0].starts_with('[') {
         lines_left = &lines_left[1..];
     }
+    // drop-ins might only contain comments
+    if lines_left.is_empty() {
+        return Ok(sections);
+    }
     let mut current_section_name: String = lines_left[0].into();
     let mut current_section_lines = Vec::new();
 
@@ -76,6 +80,49 @@ pub fn string_to_bool(s: &str) -> bool {
     *s_upper == *"YES" || *s_upper == *"TRUE" || is_num_and_one
 }
 
+/// Parse a timespan like "5min", "1h 30min" or "2.5s". A number without a unit is interpreted as seconds.
+pub fn parse_timespan(descr: &str) -> Result<std::time::Duration, ParsingErrorReason> {
+    let err = || ParsingErrorReason::Generic(format!("Not a valid timespan: {}", descr));
+    let descr = descr.trim();
+    if descr.is_empty() {
+        return Err(err());
+    }
+
+    let mut total_secs = 0f64;
+    let mut rest = descr;
+    while !rest.is_empty() {
+        let num_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        if num_len == 0 {
+            return Err(err());
+        }
+        let num = rest[..num_len].parse::<f64>().map_err(|_| err())?;
+        rest = rest[num_len..].trim_start();
+
+        let unit_len = rest
+            .find(|c: char| !c.is_alphabetic())
+            .unwrap_or(rest.len());
+        let factor = match &rest[..unit_len] {
+            "" | "s" | "sec" | "second" | "seconds" => 1.0,
+            "us" | "usec" | "µs" => 0.000_001,
+            "ms" | "msec" => 0.001,
+            "m" | "min" | "minute" | "minutes" => 60.0,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60.0 * 60.0,
+            "d" | "day" | "days" => 24.0 * 60.0 * 60.0,
+            "w" | "week" | "weeks" => 7.0 * 24.0 * 60.0 * 60.0,
+            "M" | "month" | "months" => 30.44 * 24.0 * 60.0 * 60.0,
+            "y" | "year" | "years" => 365.25 * 24.0 * 60.0 * 60.0,
+            _ => return Err(err()),
+        };
+        total_secs += num * factor;
+        rest = rest[unit_len..].trim_start();
+    }
+
+    // values that do not fit into a duration are rejected instead of panicking
+    std::time::Duration::try_from_secs_f64(total_secs).map_err(|_| err())
+}
+
 fn parse_environment(raw_line: &str) -> Result<EnvVars, ParsingErrorReason> {
     debug!("raw line: {}", raw_line);
     let split = shlex::split(raw_line).ok_or(ParsingErrorReason::Generic(format!(
@@ -102,7 +149,19 @@ pub fn parse_unit_section(
     let requires = section.remove("REQUIRES");
     let after = section.remove("AFTER");
     let before = section.remove("BEFORE");
+    let conflicts = section.remove("CONFLICTS");
+    let binds_to = section.remove("BINDSTO");
+    let part_of = section.remove("PARTOF");
+    let requisite = section.remove("REQUISITE");
+    let on_failure = section.remove("ONFAILURE");
+    let on_success = section.remove("ONSUCCESS");
+    let on_failure_job_mode = section.remove("ONFAILUREJOBMODE");
+    let on_success_job_mode = section.remove("ONSUCCESSJOBMODE");
     let description = section.remove("DESCRIPTION");
+    let start_limit_interval = section.remove("STARTLIMITINTERVALSEC");
+    let start_limit_burst = section.remove("STARTLIMITBURST");
+    let conditions = parse_conditions(&mut section, "Condition")?;
+    let asserts = parse_conditions(&mut section, "Assert")?;
 
     if !section.is_empty() {
         return Err(ParsingErrorReason::UnusedSetting(
@@ -110,27 +169,187 @@ pub fn parse_unit_section(
         ));
     }
 
+    let start_limit_interval = match start_limit_interval {
+        Some(vec) => {
+            if vec.len() == 1 {
+                Some(parse_timespan(&vec[0].1)?)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "StartLimitIntervalSec".to_owned(),
+                    map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+    let start_limit_burst = match start_limit_burst {
+        Some(vec) => {
+            if vec.len() == 1 {
+                Some(vec[0].1.parse::<u32>().map_err(|_| {
+                    ParsingErrorReason::UnknownSetting(
+                        "StartLimitBurst".to_owned(),
+                        vec[0].1.clone(),
+                    )
+                })?)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "StartLimitBurst".to_owned(),
+                    map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => None,
+    };
+
+    let on_failure_job_mode = match on_failure_job_mode {
+        Some(vec) => Some(parse_job_mode("OnFailureJobMode", vec)?),
+        None => None,
+    };
+    let on_success_job_mode = match on_success_job_mode {
+        Some(vec) => Some(parse_job_mode("OnSuccessJobMode", vec)?),
+        None => None,
+    };
+
     Ok(ParsedUnitSection {
         description: description.map(|x| (x[0]).1.clone()).unwrap_or_default(),
         wants: map_tupels_to_second(wants.unwrap_or_default()),
         requires: map_tupels_to_second(requires.unwrap_or_default()),
         after: map_tupels_to_second(after.unwrap_or_default()),
         before: map_tupels_to_second(before.unwrap_or_default()),
+        conflicts: map_tupels_to_second(conflicts.unwrap_or_default()),
+        binds_to: map_tupels_to_second(binds_to.unwrap_or_default()),
+        part_of: map_tupels_to_second(part_of.unwrap_or_default()),
+        requisite: map_tupels_to_second(requisite.unwrap_or_default()),
+        on_failure: map_tupels_to_second(on_failure.unwrap_or_default()),
+        on_success: map_tupels_to_second(on_success.unwrap_or_default()),
+        on_failure_job_mode,
+        on_success_job_mode,
+        start_limit_interval,
+        start_limit_burst,
+        conditions,
+        asserts,
     })
 }
 
+/// The supported checks, they exist as Condition*= and Assert*=
+const CONDITION_NAMES: &[&str] = &[
+    "PathExists",
+    "PathIsDirectory",
+    "DirectoryNotEmpty",
+    "FileNotEmpty",
+    "FileIsExecutable",
+    "KernelCommandLine",
+    "Virtualization",
+    "User",
+    "Group",
+    "Host",
+    "Environment",
+    "FirstBoot",
+];
+
+/// Remove all Condition*= or Assert*= settings (depending on the prefix) from the section. They are returned in the
+/// order they appeared in the unit file.
+fn parse_conditions(
+    section: &mut ParsedSection,
+    prefix: &str,
+) -> Result<Vec<UnitCondition>, ParsingErrorReason> {
+    let mut conditions = Vec::new();
+    for name in CONDITION_NAMES {
+        let setting = format!("{}{}", prefix, name);
+        let entries = match section.remove(&setting.to_uppercase()) {
+            Some(entries) => entries,
+            None => continue,
+        };
+        for (entry_number, value) in entries {
+            // like in systemd the trigger marker comes before the negation
+            let mut rest = value.trim();
+            let triggering = rest.starts_with('|');
+            rest = rest.trim_start_matches('|').trim_start();
+            let negate = rest.starts_with('!');
+            rest = rest.trim_start_matches('!').trim_start();
+            let path = || {
+                let path = PathBuf::from(rest);
+                if path.is_absolute() {
+                    Ok(path)
+                } else {
+                    Err(ParsingErrorReason::UnknownSetting(
+                        setting.clone(),
+                        value.clone(),
+                    ))
+                }
+            };
+            let kind = match *name {
+                "PathExists" => ConditionKind::PathExists(path()?),
+                "PathIsDirectory" => ConditionKind::PathIsDirectory(path()?),
+                "DirectoryNotEmpty" => ConditionKind::DirectoryNotEmpty(path()?),
+                "FileNotEmpty" => ConditionKind::FileNotEmpty(path()?),
+                "FileIsExecutable" => ConditionKind::FileIsExecutable(path()?),
+                "KernelCommandLine" => ConditionKind::KernelCommandLine(rest.to_owned()),
+                "Virtualization" => ConditionKind::Virtualization(rest.to_lowercase()),
+                "User" => ConditionKind::User(rest.to_owned()),
+                "Group" => ConditionKind::Group(rest.to_owned()),
+                "Host" => ConditionKind::Host(rest.to_owned()),
+                "Environment" => ConditionKind::Environment(rest.to_owned()),
+                "FirstBoot" => ConditionKind::FirstBoot(string_to_bool(rest)),
+                _ => unreachable!(),
+            };
+            conditions.push((
+                entry_number,
+                UnitCondition {
+                    kind,
+                    negate,
+                    triggering,
+                },
+            ));
+        }
+    }
+    conditions.sort_by_key(|(entry_number, _)| *entry_number);
+    Ok(conditions
+        .into_iter()
+        .map(|(_, condition)| condition)
+        .collect())
+}
+
+fn parse_job_mode(setting: &str, vec: Vec<(u32, String)>) -> Result<JobMode, ParsingErrorReason> {
+    if vec.len() != 1 {
+        return Err(ParsingErrorReason::SettingTooManyValues(
+            setting.to_owned(),
+            map_tupels_to_second(vec),
+        ));
+    }
+    match vec[0].1.to_lowercase().as_str() {
+        "replace" => Ok(JobMode::Replace),
+        "fail" => Ok(JobMode::Fail),
+        "isolate" => Ok(JobMode::Isolate),
+        "ignore-dependencies" => Ok(JobMode::IgnoreDependencies),
+        mode => Err(ParsingErrorReason::UnknownSetting(
+            setting.to_owned(),
+            mode.to_owned(),
+        )),
+    }
+}
+
 fn make_stdio_option(setting: &str) -> Result<StdIoOption, ParsingErrorReason> {
-    if setting.starts_with("file:") {
-        let p = setting.trim_start_matches("file:");
+    if let Some(p) = setting.strip_prefix("file:") {
         Ok(StdIoOption::File(p.into()))
-    } else if setting.starts_with("append:") {
-        let p = setting.trim_start_matches("append:");
+    } else if let Some(p) = setting.strip_prefix("append:") {
         Ok(StdIoOption::AppendFile(p.into()))
+    } else if let Some(p) = setting.strip_prefix("truncate:") {
+        Ok(StdIoOption::TruncateFile(p.into()))
+    } else if let Some(name) = setting.strip_prefix("fd:") {
+        Ok(StdIoOption::Fd(name.to_owned()))
     } else {
-        return Err(ParsingErrorReason::UnsupportedSetting(format!(
-            "StandardOutput: {}",
-            setting
-        )));
+        match setting {
+            "journal" => Ok(StdIoOption::Journal),
+            "null" => Ok(StdIoOption::Null),
+            "inherit" => Ok(StdIoOption::Inherit),
+            "tty" => Ok(StdIoOption::Tty),
+            "socket" => Ok(StdIoOption::Socket),
+            _ => Err(ParsingErrorReason::UnsupportedSetting(format!(
+                "StandardOutput: {}",
+                setting
+            ))),
+        }
     }
 }
 
@@ -141,6 +360,8 @@ pub fn parse_exec_section(
     let group = section.remove("GROUP");
     let stdout = section.remove("STANDARDOUTPUT");
     let stderr = section.remove("STANDARDERROR");
+    let tty_path = section.remove("TTYPATH");
+    let syslog_identifier = section.remove("SYSLOGIDENTIFIER");
     let supplementary_groups = section.remove("SUPPLEMENTARYGROUPS");
     let environment = section.remove("ENVIRONMENT");
 
@@ -217,6 +438,37 @@ pub fn parse_exec_section(
         None
     };
 
+    let tty_path = match tty_path {
+        None => None,
+        Some(mut vec) => {
+            if vec.len() == 1 {
+                Some(PathBuf::from(vec.remove(0).1))
+            } else if vec.len() > 1 {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "TTYPath".into(),
+                    super::map_tupels_to_second(vec),
+                ));
+            } else {
+                None
+            }
+        }
+    };
+    let syslog_identifier = match syslog_identifier {
+        None => None,
+        Some(mut vec) => {
+            if vec.len() == 1 {
+                Some(vec.remove(0).1)
+            } else if vec.len() > 1 {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "SyslogIdentifier".into(),
+                    super::map_tupels_to_second(vec),
+                ));
+            } else {
+                None
+            }
+        }
+    };
+
     let supplementary_groups = match supplementary_groups {
         None => Vec::new(),
         Some(vec) => vec.iter().fold(Vec::new(), |mut acc, (_id, list)| {
@@ -228,7 +480,11 @@ pub fn parse_exec_section(
     let environment = match environment {
         Some(vec) => {
             debug!("Env vec: {:?}", vec);
-            Some(parse_environment(&vec[0].1)?)
+            let mut vars = Vec::new();
+            for (_, raw_line) in &vec {
+                vars.extend(parse_environment(raw_line)?.vars);
+            }
+            Some(EnvVars { vars })
         }
         None => None,
     };
@@ -238,11 +494,167 @@ pub fn parse_exec_section(
         group,
         stderr_path,
         stdout_path,
+        tty_path,
+        syslog_identifier,
         supplementary_groups,
         environment,
     })
 }
 
+/// The settings of the resource control that can be used in units and with set-property
+pub const RESOURCE_CONTROL_SETTINGS: [&str; 8] = [
+    "MEMORYMAX",
+    "MEMORYHIGH",
+    "MEMORYLOW",
+    "CPUWEIGHT",
+    "CPUQUOTA",
+    "TASKSMAX",
+    "IOWEIGHT",
+    "ALLOWEDCPUS",
+];
+
+pub fn parse_resource_control(
+    section: &mut ParsedSection,
+) -> Result<ResourceControl, ParsingErrorReason> {
+    let mut resources = ResourceControl::default();
+    for name in RESOURCE_CONTROL_SETTINGS.iter() {
+        if let Some(mut vec) = section.remove(*name) {
+            if vec.len() == 1 {
+                set_resource_property(&mut resources, name, &vec.remove(0).1)?;
+            } else if vec.len() > 1 {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    name.to_string(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+    }
+    Ok(resources)
+}
+
+/// Set one setting of the resource control. The name is matched case insensitive. An empty value unsets the setting.
+pub fn set_resource_property(
+    resources: &mut ResourceControl,
+    name: &str,
+    value: &str,
+) -> Result<(), ParsingErrorReason> {
+    let value = value.trim();
+    let err = || ParsingErrorReason::UnknownSetting(name.to_owned(), value.to_owned());
+    match name.to_uppercase().as_str() {
+        "MEMORYMAX" => {
+            resources.memory_max = parse_unless_empty(value, parse_memory_limit).ok_or_else(err)?
+        }
+        "MEMORYHIGH" => {
+            resources.memory_high = parse_unless_empty(value, parse_memory_limit).ok_or_else(err)?
+        }
+        "MEMORYLOW" => {
+            resources.memory_low = parse_unless_empty(value, parse_memory_limit).ok_or_else(err)?
+        }
+        "TASKSMAX" => {
+            resources.tasks_max = parse_unless_empty(value, parse_tasks_limit).ok_or_else(err)?
+        }
+        "CPUWEIGHT" => {
+            resources.cpu_weight = parse_unless_empty(value, parse_weight).ok_or_else(err)?
+        }
+        "IOWEIGHT" => {
+            resources.io_weight = parse_unless_empty(value, parse_weight).ok_or_else(err)?
+        }
+        "CPUQUOTA" => {
+            resources.cpu_quota = parse_unless_empty(value, parse_cpu_quota).ok_or_else(err)?
+        }
+        "ALLOWEDCPUS" => {
+            resources.allowed_cpus = parse_unless_empty(value, parse_cpu_set).ok_or_else(err)?
+        }
+        _ => return Err(ParsingErrorReason::UnusedSetting(name.to_owned())),
+    }
+    Ok(())
+}
+
+/// Returns Some(None) for an empty value and None if the value is not valid
+fn parse_unless_empty<T>(value: &str, parse: fn(&str) -> Option<T>) -> Option<Option<T>> {
+    if value.is_empty() {
+        Some(None)
+    } else {
+        parse(value).map(Some)
+    }
+}
+
+fn parse_memory_limit(value: &str) -> Option<ResourceLimit> {
+    parse_limit(value, true)
+}
+
+fn parse_tasks_limit(value: &str) -> Option<ResourceLimit> {
+    parse_limit(value, false)
+}
+
+/// A percentage like "150%"
+fn parse_cpu_quota(value: &str) -> Option<u64> {
+    value
+        .strip_suffix('%')
+        .and_then(|percent| percent.parse::<u64>().ok())
+        .filter(|percent| *percent > 0)
+}
+
+/// "infinity", a percentage or a number. Memory sizes can have a K, M, G or T suffix (base 1024).
+fn parse_limit(value: &str, is_memory: bool) -> Option<ResourceLimit> {
+    if value == "infinity" {
+        return Some(ResourceLimit::Infinity);
+    }
+    if let Some(percent) = value.strip_suffix('%') {
+        return percent
+            .parse::<u64>()
+            .ok()
+            .filter(|percent| *percent <= 100)
+            .map(ResourceLimit::Percent);
+    }
+    let (number, factor) = match value.chars().last()? {
+        'K' if is_memory => (&value[..value.len() - 1], 1024),
+        'M' if is_memory => (&value[..value.len() - 1], 1024 * 1024),
+        'G' if is_memory => (&value[..value.len() - 1], 1024 * 1024 * 1024),
+        'T' if is_memory => (&value[..value.len() - 1], 1024 * 1024 * 1024 * 1024),
+        _ => (value, 1),
+    };
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|number| number.checked_mul(factor))
+        .map(ResourceLimit::Absolute)
+}
+
+/// CPUWeight= and IOWeight= go from 1 to 10000
+fn parse_weight(value: &str) -> Option<u64> {
+    value
+        .parse::<u64>()
+        .ok()
+        .filter(|weight| (1..=10000).contains(weight))
+}
+
+/// A list of cpus and ranges like "0-3 6", separated by spaces or commas. Returns it separated by commas.
+fn parse_cpu_set(value: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    for part in value.split(|c: char| c == ',' || c.is_whitespace()) {
+        if part.is_empty() {
+            continue;
+        }
+        let valid = match part.split_once('-') {
+            Some((start, end)) => match (start.parse::<u32>(), end.parse::<u32>()) {
+                (Ok(start), Ok(end)) => start <= end,
+                _ => false,
+            },
+            None => part.parse::<u32>().is_ok(),
+        };
+        if !valid {
+            return None;
+        }
+        parts.push(part);
+    }
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join(","))
+    }
+}
+
 pub fn parse_install_section(
     mut section: ParsedSection,
 ) -> Result<ParsedInstallSection, ParsingErrorReason> {
@@ -322,7 +734,21 @@ pub fn parse_section(lines: &[&str]) -> ParsedSection {
         let value = value.trim_start_matches('=');
         let value = value.trim();
         let name = name.trim().to_uppercase();
-        let values: Vec<String> = value.split(',').map(|x| x.into()).collect();
+        // calendar expressions use ',' for lists themselves and paths may contain a ','. Empty assignments
+        // are kept as they are, they reset the setting.
+        let values: Vec<String> = if value.is_empty()
+            || name == "ONCALENDAR"
+            || name.starts_with("PATH")
+            || name == "DIRECTORYNOTEMPTY"
+        {
+            vec![value.into()]
+        } else {
+            value
+                .split(',')
+                .filter(|x| !x.is_empty())
+                .map(|x| x.into())
+                .collect()
+        };
 
         let vec = entries.entry(name).or_insert_with(Vec::new);
         for value in values {
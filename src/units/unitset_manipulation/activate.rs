@@ -5,8 +5,6 @@ use crate::services::ServiceErrorReason;
 use crate::units::*;
 
 use log::{error, trace};
-use std::sync::{Arc, Mutex};
-use threadpool::ThreadPool;
 
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub struct UnitOperationError {
@@ -23,7 +21,19 @@ pub enum UnitOperationErrorReason {
     SocketCloseError(String),
     ServiceStartError(ServiceErrorReason),
     ServiceStopError(ServiceErrorReason),
+    ServiceReloadError(ServiceErrorReason),
     DependencyError(Vec<UnitId>),
+    StartLimitHit,
+    /// A Condition*= setting did not hold so the unit was skipped
+    ConditionFailed(String),
+    /// An Assert*= setting did not hold so the start failed
+    AssertionFailed(String),
+    /// The main process of the service exited and the service was not restarted
+    UnexpectedExit(crate::signal_handler::ChildTermination),
+    /// The transaction for a request could not be installed
+    TransactionRejected(String),
+    /// The job was cancelled or replaced by a contradicting job before it ran
+    JobCancelled,
 }
 
 impl std::fmt::Display for UnitOperationError {
@@ -57,6 +67,13 @@ impl std::fmt::Display for UnitOperationError {
                     self.unit_name, self.unit_id, msg
                 )?;
             }
+            UnitOperationErrorReason::ServiceReloadError(msg) => {
+                write!(
+                    f,
+                    "Service {} (ID {}) failed to reload because: {}",
+                    self.unit_name, self.unit_id, msg
+                )?;
+            }
             UnitOperationErrorReason::SocketOpenError(msg) => {
                 write!(
                     f,
@@ -71,6 +88,48 @@ impl std::fmt::Display for UnitOperationError {
                     self.unit_name, self.unit_id, msg
                 )?;
             }
+            UnitOperationErrorReason::StartLimitHit => {
+                write!(
+                    f,
+                    "Unit {} (ID {}) was started too often in a short time and is not started again",
+                    self.unit_name, self.unit_id
+                )?;
+            }
+            UnitOperationErrorReason::ConditionFailed(condition) => {
+                write!(
+                    f,
+                    "Unit {} (ID {}) was skipped because {} does not hold",
+                    self.unit_name, self.unit_id, condition
+                )?;
+            }
+            UnitOperationErrorReason::AssertionFailed(assert) => {
+                write!(
+                    f,
+                    "Unit {} (ID {}) failed to start because {} does not hold",
+                    self.unit_name, self.unit_id, assert
+                )?;
+            }
+            UnitOperationErrorReason::UnexpectedExit(code) => {
+                write!(
+                    f,
+                    "Service {} (ID {}) exited unexpectedly with: {:?}",
+                    self.unit_name, self.unit_id, code
+                )?;
+            }
+            UnitOperationErrorReason::TransactionRejected(msg) => {
+                write!(
+                    f,
+                    "The request for unit {} (ID {}) was rejected because: {}",
+                    self.unit_name, self.unit_id, msg
+                )?;
+            }
+            UnitOperationErrorReason::JobCancelled => {
+                write!(
+                    f,
+                    "The job for unit {} (ID {}) was cancelled before it ran",
+                    self.unit_name, self.unit_id
+                )?;
+            }
             UnitOperationErrorReason::DependencyError(ids) => {
                 write!(
                     f,
@@ -101,11 +160,11 @@ pub fn unstarted_deps(id: &UnitId, run_info: &RuntimeInfo) -> Vec<UnitId> {
         .after
         .iter()
         .fold(Vec::new(), |mut acc, elem| {
-            let required = unit.common.dependencies.requires.contains(elem);
+            let required = unit.common.dependencies.needs_active(elem);
             let elem_unit = run_info.unit_table.get(elem).unwrap();
             let status_locked = elem_unit.common.status.read().unwrap();
             let ready = if required {
-                status_locked.is_started()
+                status_locked.is_started() || status_locked.is_skipped()
             } else {
                 *status_locked != UnitStatus::NeverStarted
             };
@@ -127,6 +186,10 @@ pub enum StartResult {
 pub enum ActivationSource {
     Regular,
     SocketActivation,
+    TimerActivation,
+    PathActivation,
+    /// The service exited and gets restarted according to its Restart= setting
+    AutoRestart,
 }
 
 impl ActivationSource {
@@ -136,6 +199,14 @@ impl ActivationSource {
             _ => false,
         }
     }
+
+    /// The activation was triggered by a timer or path unit
+    pub fn is_triggered_activation(&self) -> bool {
+        matches!(
+            self,
+            ActivationSource::TimerActivation | ActivationSource::PathActivation
+        )
+    }
 }
 
 /// Activate the unit and return all units that are ordered later than this unit
@@ -167,6 +238,22 @@ pub fn activate_unit(
 
     unit.activate(run_info.clone(), source)
         .map(|_| StartResult::Started(next_services_ids))
+        .inspect_err(|e| queue_start_failure(unit, e, run_info))
+}
+
+/// If the start failed in a way that made the unit fail, its OnFailure= units get started
+pub fn queue_start_failure(unit: &Unit, err: &UnitOperationError, run_info: &RuntimeInfo) {
+    let failed = matches!(
+        *unit.common.status.read().unwrap(),
+        UnitStatus::Stopped(StatusStopped::StoppedUnexpected, _)
+    );
+    if failed {
+        run_info.hooks.push(crate::hook_activation::UnitEnded {
+            id: unit.id.clone(),
+            result: crate::hook_activation::start_failure_result(&err.reason),
+            exit: None,
+        });
+    }
 }
 
 /// Walk the unit graph and find all units that need to be started to be able to start all units in ids_to_start.
@@ -203,111 +290,26 @@ pub fn activate_needed_units(
     target_id: UnitId,
     run_info: ArcMutRuntimeInfo,
 ) -> Vec<UnitOperationError> {
-    let mut needed_ids = vec![target_id.clone()];
-    {
+    let transaction = {
         let run_info = run_info.read().unwrap();
-        collect_unit_start_subgraph(&mut needed_ids, &run_info.unit_table);
-    }
-    trace!("Needed units to start {:?}: {:?}", target_id, needed_ids);
-
-    // collect all 'root' units. These are units that do not have any 'after' relations to other unstarted units.
-    // These can be started and the the graph can be traversed and other units can be started as soon as
-    // all other units they depend on are started. This works because the units form an DAG if only
-    // the 'after' relations are considered for traversal.
-    let root_units = { find_startable_units(&needed_ids, &*run_info.read().unwrap()) };
-    trace!("Root units found: {:?}", root_units);
-
-    // TODO make configurable or at least make guess about amount of threads
-    let tpool = ThreadPool::new(6);
-    let errors = Arc::new(Mutex::new(Vec::new()));
-    activate_units_recursive(
-        root_units,
-        Arc::new(needed_ids),
-        run_info,
-        tpool.clone(),
-        errors.clone(),
-    );
-
-    tpool.join();
+        Transaction::new(
+            target_id.clone(),
+            JobKind::Start,
+            true,
+            &run_info.unit_table,
+        )
+    };
+    let errs = match transaction {
+        Ok(transaction) => run_transaction(transaction, run_info),
+        Err(e) => vec![UnitOperationError {
+            reason: UnitOperationErrorReason::TransactionRejected(e),
+            unit_name: target_id.name.clone(),
+            unit_id: target_id,
+        }],
+    };
     // TODO can we handle errors in a more meaningful way?
-    let errs = (&*errors.lock().unwrap()).clone();
     for err in &errs {
         error!("Error while activating unit graph: {}", err);
     }
     errs
 }
-
-/// Check for all units in this Vec, if all units this depends on are running
-fn find_startable_units(ids: &Vec<UnitId>, run_info: &RuntimeInfo) -> Vec<UnitId> {
-    let mut startable = Vec::new();
-
-    for id in ids {
-        if unstarted_deps(id, run_info).is_empty() {
-            startable.push(id.clone());
-        }
-    }
-    startable
-}
-
-/// Start all units in ids_to_start and push jobs into the threadpool to start all following units.
-///
-/// Only do so for the units in filter_ids
-fn activate_units_recursive(
-    ids_to_start: Vec<UnitId>,
-    filter_ids: Arc<Vec<UnitId>>,
-    run_info: ArcMutRuntimeInfo,
-    tpool: ThreadPool,
-    errors: Arc<Mutex<Vec<UnitOperationError>>>,
-) {
-    let startables = { find_startable_units(&ids_to_start, &*run_info.read().unwrap()) };
-    let startables: Vec<UnitId> = startables
-        .into_iter()
-        .filter(|id| filter_ids.contains(&id))
-        .collect();
-
-    for id in startables {
-        // make copies to move into the closure
-        let run_info_copy = run_info.clone();
-        let tpool_copy = tpool.clone();
-        let errors_copy = errors.clone();
-        let filter_ids_copy = filter_ids.clone();
-        tpool.execute(move || {
-            match activate_unit(
-                id,
-                &*run_info_copy.read().unwrap(),
-                ActivationSource::Regular,
-            ) {
-                Ok(StartResult::Started(next_services_ids)) => {
-                    // make copies to move into the closure
-                    let run_info_copy2 = run_info_copy.clone();
-                    let tpool_copy2 = tpool_copy.clone();
-                    let errors_copy2 = errors_copy.clone();
-                    let filter_ids_copy2 = filter_ids_copy.clone();
-
-                    let next_services_job = move || {
-                        activate_units_recursive(
-                            next_services_ids,
-                            filter_ids_copy2,
-                            run_info_copy2,
-                            tpool_copy2,
-                            errors_copy2,
-                        );
-                    };
-                    tpool_copy.execute(next_services_job);
-                }
-                Err(e) => {
-                    if let UnitOperationErrorReason::DependencyError(_) = e.reason {
-                        // Thats ok. The unit is waiting for more dependencies and will be
-                        // activated again when another dependency has finished starting
-
-                        // This should not happen though, since we filter the units beforehand
-                        // to only get the startables
-                    } else {
-                        error!("Error while activating unit {}", e);
-                        errors_copy.lock().unwrap().push(e);
-                    }
-                }
-            }
-        });
-    }
-}
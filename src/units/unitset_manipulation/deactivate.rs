@@ -23,6 +23,9 @@ pub fn deactivate_unit_recursive(
     };
 
     deactivate_units_recursive(&unit.common.dependencies.required_by, run_info)?;
+    // units bound to this unit can not keep running without it. Units that are only part of it are not stopped here,
+    // that only happens when this unit is stopped or restarted explicitly.
+    deactivate_units_recursive(&unit.common.dependencies.bound_by, run_info)?;
 
     deactivate_unit(id_to_kill, run_info.clone())
 }
@@ -90,4 +93,25 @@ pub fn reactivate_unit(
         }
     };
     unit.reactivate(run_info, crate::units::ActivationSource::Regular)
+        .inspect_err(|e| queue_start_failure(unit, e, run_info))
+}
+
+pub fn reload_unit(
+    id_to_reload: UnitId,
+    run_info: &RuntimeInfo,
+) -> std::result::Result<(), UnitOperationError> {
+    trace!("Reload unit: {:?}", id_to_reload);
+    let unit = match run_info.unit_table.get(&id_to_reload) {
+        Some(unit) => unit,
+        None => {
+            return Err(UnitOperationError {
+                reason: UnitOperationErrorReason::GenericStartError(
+                    "Tried to reload a unit that can not be found".into(),
+                ),
+                unit_name: id_to_reload.name.clone(),
+                unit_id: id_to_reload.clone(),
+            });
+        }
+    };
+    unit.reload(run_info)
 }
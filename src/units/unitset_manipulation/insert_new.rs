@@ -31,40 +31,18 @@ fn find_new_unit_path(unit_dirs: &[PathBuf], find_name: &str) -> Result<Option<P
     Ok(None)
 }
 
-/// Loads a unit with a given name. It searches all pathes recursively until it finds a file with a matching name
+/// Loads a unit with a given name. It searches all pathes recursively until it finds a file with a matching name.
+/// Instances of templates (foo@bar.service) are loaded from the template file (foo@.service) if they have no file of their own.
 pub fn load_new_unit(unit_dirs: &[PathBuf], find_name: &str) -> Result<units::Unit, String> {
-    if let Some(unit_path) = find_new_unit_path(unit_dirs, find_name)? {
-        let content = fs::read_to_string(&unit_path).map_err(|e| {
-            format!(
-                "{}",
-                units::ParsingError::new(
-                    units::ParsingErrorReason::from(Box::new(e)),
-                    unit_path.clone()
-                )
-            )
-        })?;
-        let parsed = units::parse_file(&content)
-            .map_err(|e| format!("{}", units::ParsingError::new(e, unit_path.clone())))?;
-        let unit = if find_name.ends_with(".service") {
-            units::parse_service(parsed, &unit_path)
-                .map_err(|e| format!("{}", units::ParsingError::new(e, unit_path)))?
-                .try_into()?
-        } else if find_name.ends_with(".socket") {
-            units::parse_socket(parsed, &unit_path)
-                .map_err(|e| format!("{}", units::ParsingError::new(e, unit_path)))?
-                .try_into()?
-        } else if find_name.ends_with(".target") {
-            units::parse_target(parsed, &unit_path)
-                .map_err(|e| format!("{}", units::ParsingError::new(e, unit_path)))?
-                .try_into()?
-        } else {
-            return Err(format!(
-                "File suffix not recognized for file {:?}",
-                unit_path
-            ));
-        };
-
-        Ok(unit)
+    let mut unit_path = find_new_unit_path(unit_dirs, find_name)?;
+    if unit_path.is_none() {
+        let id: units::UnitId = find_name.try_into()?;
+        if let Some(template_name) = id.template_name() {
+            unit_path = find_new_unit_path(unit_dirs, &template_name)?;
+        }
+    }
+    if let Some(unit_path) = unit_path {
+        units::load_unit_file(&unit_path, find_name, unit_dirs).map_err(|e| format!("{}", e))
     } else {
         Err(format!("Cannot find unit file for unit: {}", find_name))
     }
@@ -111,38 +89,101 @@ fn check_all_names_exist(
     Ok(())
 }
 
+/// Load the slices the new units are placed in if they are not loaded yet. Slices without a unit file get the default
+/// settings.
+fn load_missing_slices(new_units: &mut UnitTable, run_info: &RuntimeInfo) -> Result<(), String> {
+    loop {
+        let missing = units::missing_slices(new_units, &run_info.unit_table);
+        if missing.is_empty() {
+            return Ok(());
+        }
+        for id in missing {
+            let slice = if find_new_unit_path(&run_info.config.unit_dirs, &id.name)?.is_some() {
+                load_new_unit(&run_info.config.unit_dirs, &id.name)?
+            } else {
+                trace!("Create slice {} without a unit file", id.name);
+                units::make_implicit_slice(&id)?
+            };
+            new_units.insert(id, slice);
+        }
+    }
+}
+
+/// Setup relations of before <-> after / requires <-> requiredby and so on in the unit that the new unit refers to
+fn add_reverse_relations(
+    new_id: &units::UnitId,
+    new_deps: &units::Dependencies,
+    unit_id: &units::UnitId,
+    unit_deps: &mut units::Dependencies,
+) {
+    if new_deps.after.contains(unit_id) {
+        unit_deps.before.push(new_id.clone());
+    }
+    if new_deps.before.contains(unit_id) {
+        unit_deps.after.push(new_id.clone());
+    }
+    if new_deps.requires.contains(unit_id) {
+        unit_deps.required_by.push(new_id.clone());
+    }
+    if new_deps.wants.contains(unit_id) {
+        unit_deps.wanted_by.push(new_id.clone());
+    }
+    if new_deps.required_by.contains(unit_id) {
+        unit_deps.requires.push(new_id.clone());
+    }
+    if new_deps.wanted_by.contains(unit_id) {
+        unit_deps.wants.push(new_id.clone());
+    }
+    if new_deps.conflicts.contains(unit_id) {
+        unit_deps.conflicted_by.push(new_id.clone());
+    }
+    if new_deps.binds_to.contains(unit_id) {
+        unit_deps.bound_by.push(new_id.clone());
+    }
+    if new_deps.part_of.contains(unit_id) {
+        unit_deps.consists_of.push(new_id.clone());
+    }
+    if new_deps.requisite.contains(unit_id) {
+        unit_deps.requisite_of.push(new_id.clone());
+    }
+}
+
 /// Inserts new units but first checks that the units referenced by the new units do exist
-pub fn insert_new_units(new_units: UnitTable, run_info: &mut RuntimeInfo) -> Result<(), String> {
+pub fn insert_new_units(
+    mut new_units: UnitTable,
+    run_info: &mut RuntimeInfo,
+) -> Result<(), String> {
     // TODO check if new unit only refs existing units
     // TODO check if all ref'd units are not failed
+    load_missing_slices(&mut new_units, run_info)?;
     {
         let unit_table = &mut run_info.unit_table;
         trace!("Check all names exist");
         check_all_names_exist(&new_units, unit_table)?;
 
-        for (new_id, new_unit) in new_units.into_iter() {
+        let mut inserted_ids = Vec::new();
+        for (new_id, mut new_unit) in new_units.into_iter() {
             trace!("Add new unit: {}", new_unit.id.name);
-            // Setup relations of before <-> after / requires <-> requiredby
             for unit in unit_table.values_mut() {
-                if new_unit.common.dependencies.after.contains(&unit.id) {
-                    unit.common.dependencies.before.push(new_id.clone());
-                }
-                if new_unit.common.dependencies.before.contains(&unit.id) {
-                    unit.common.dependencies.after.push(new_id.clone());
-                }
-                if new_unit.common.dependencies.requires.contains(&unit.id) {
-                    unit.common.dependencies.required_by.push(new_id.clone());
-                }
-                if new_unit.common.dependencies.wants.contains(&unit.id) {
-                    unit.common.dependencies.wanted_by.push(new_id.clone());
-                }
-                if new_unit.common.dependencies.required_by.contains(&unit.id) {
-                    unit.common.dependencies.requires.push(new_id.clone());
-                }
-                if new_unit.common.dependencies.wanted_by.contains(&unit.id) {
-                    unit.common.dependencies.wants.push(new_id.clone());
-                }
+                add_reverse_relations(
+                    &new_id,
+                    &new_unit.common.dependencies,
+                    &unit.id,
+                    &mut unit.common.dependencies,
+                );
+            }
+            // the units that were added before this one could refer to it too
+            for inserted_id in &inserted_ids {
+                let inserted = unit_table.get(inserted_id).unwrap();
+                add_reverse_relations(
+                    inserted_id,
+                    &inserted.common.dependencies,
+                    &new_id,
+                    &mut new_unit.common.dependencies,
+                );
             }
+            new_unit.dedup_dependencies();
+            inserted_ids.push(new_id.clone());
             unit_table.insert(new_id, new_unit);
         }
     }
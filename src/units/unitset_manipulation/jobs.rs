@@ -0,0 +1,766 @@
+//! Requests to change the state of units are turned into jobs. All jobs needed for one request form a transaction that
+//! is checked before anything runs: jobs for the same unit are merged, contradicting jobs and ordering cycles make the
+//! whole transaction fail.
+//!
+//! Installed jobs live in the job queue until they finished. There is at most one job per unit, a new transaction
+//! merges its jobs into the installed ones or replaces them if they did not start running yet. Jobs run as concurrently
+//! as the before/after ordering allows. If a job fails, the pending jobs of the transaction that require the failed
+//! unit are cancelled.
+
+use crate::runtime_info::*;
+use crate::units::*;
+
+use log::{error, trace};
+use std::collections::HashMap;
+use std::sync::{Condvar, Mutex};
+use threadpool::ThreadPool;
+
+pub type JobId = u64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobKind {
+    Start,
+    Stop,
+    Restart,
+    Reload,
+    /// Fails if the unit is not started. Does not change the unit.
+    VerifyActive,
+}
+
+impl JobKind {
+    /// The kind of job that does what both jobs do. Returns None if the jobs contradict each other.
+    pub fn merge(self, other: JobKind) -> Option<JobKind> {
+        use JobKind::*;
+        match (self, other) {
+            (Stop, Stop) => Some(Stop),
+            (Stop, _) | (_, Stop) => None,
+            (Restart, _) | (_, Restart) => Some(Restart),
+            (Start, Start) | (Start, VerifyActive) | (VerifyActive, Start) => Some(Start),
+            // a stopped unit can not be reloaded. Restarting it covers both.
+            (Start, Reload) | (Reload, Start) => Some(Restart),
+            (Reload, Reload) | (Reload, VerifyActive) | (VerifyActive, Reload) => Some(Reload),
+            (VerifyActive, VerifyActive) => Some(VerifyActive),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobKind::Start => "start",
+            JobKind::Stop => "stop",
+            JobKind::Restart => "restart",
+            JobKind::Reload => "reload",
+            JobKind::VerifyActive => "verify-active",
+        }
+    }
+
+    fn is_stop(&self) -> bool {
+        *self == JobKind::Stop
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JobState {
+    Waiting,
+    Running,
+}
+
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub id: JobId,
+    pub unit: UnitId,
+    pub kind: JobKind,
+    pub state: JobState,
+    /// The ordering of the unit when the job was installed
+    after: Vec<UnitId>,
+    before: Vec<UnitId>,
+    /// The units that have to be active for this unit
+    requires: Vec<UnitId>,
+}
+
+impl Job {
+    /// Whether this job has to wait until the other job finished. Stop jobs run before all other jobs of units they
+    /// are ordered with. Stop jobs among themselves run in the reverse order of the start jobs.
+    ///
+    /// Verifying that a unit is active only looks at its current state and never waits. Jobs wait for the verification
+    /// of the units they require, so they do not run if it fails.
+    fn waits_for(&self, other: &Job) -> bool {
+        if self.kind == JobKind::VerifyActive {
+            return false;
+        }
+        if other.kind == JobKind::VerifyActive {
+            return self.requires.contains(&other.unit);
+        }
+        let ordered_after = self.after.contains(&other.unit);
+        let ordered_before = self.before.contains(&other.unit);
+        match (self.kind.is_stop(), other.kind.is_stop()) {
+            (false, false) => ordered_after,
+            (true, true) => ordered_before,
+            (false, true) => ordered_after || ordered_before,
+            (true, false) => false,
+        }
+    }
+}
+
+enum JobResult {
+    Done,
+    Failed(UnitOperationError),
+    Cancelled,
+}
+
+#[derive(Default)]
+struct InstalledJobs {
+    next_id: JobId,
+    jobs: HashMap<UnitId, Job>,
+    /// Results of finished or cancelled jobs until the transaction that owns them collects them
+    results: HashMap<JobId, JobResult>,
+}
+
+impl InstalledJobs {
+    fn contains_job(&self, id: JobId) -> bool {
+        self.jobs.values().any(|job| job.id == id)
+    }
+
+    fn remove_job(&mut self, id: JobId) -> Option<Job> {
+        let unit = self
+            .jobs
+            .values()
+            .find(|job| job.id == id)
+            .map(|job| job.unit.clone())?;
+        self.jobs.remove(&unit)
+    }
+}
+
+/// The jobs that are currently installed
+#[derive(Default)]
+pub struct JobQueue {
+    installed: Mutex<InstalledJobs>,
+    changed: Condvar,
+}
+
+impl JobQueue {
+    /// All installed jobs, ordered by their id
+    pub fn list(&self) -> Vec<Job> {
+        let mut jobs: Vec<Job> = self
+            .installed
+            .lock()
+            .unwrap()
+            .jobs
+            .values()
+            .cloned()
+            .collect();
+        jobs.sort_by_key(|job| job.id);
+        jobs
+    }
+
+    /// Cancel a job that did not start running yet
+    pub fn cancel(&self, id: JobId) -> Result<Job, String> {
+        let installed = &mut *self.installed.lock().unwrap();
+        match installed.jobs.values().find(|job| job.id == id) {
+            None => return Err(format!("No job with id: {}", id)),
+            Some(job) if job.state == JobState::Running => {
+                return Err(format!(
+                    "Job {} ({} {}) is already running and can not be cancelled",
+                    id,
+                    job.kind.as_str(),
+                    job.unit.name
+                ))
+            }
+            Some(_) => {}
+        }
+        let job = installed.remove_job(id).unwrap();
+        installed.results.insert(id, JobResult::Cancelled);
+        self.changed.notify_all();
+        Ok(job)
+    }
+
+    fn finish(&self, id: JobId, result: JobResult) {
+        let installed = &mut *self.installed.lock().unwrap();
+        installed.remove_job(id);
+        installed.results.insert(id, result);
+        self.changed.notify_all();
+    }
+}
+
+struct PlannedJob {
+    kind: JobKind,
+    after: Vec<UnitId>,
+    before: Vec<UnitId>,
+    requires: Vec<UnitId>,
+}
+
+/// All jobs that are needed for one request
+pub struct Transaction {
+    anchor: UnitId,
+    mode: JobMode,
+    jobs: HashMap<UnitId, PlannedJob>,
+}
+
+impl Transaction {
+    /// Plan the jobs for a request of this kind for the anchor unit. With pull_in_dependencies set, start and restart
+    /// requests also start all units the anchor needs and stop requests also stop all units that require the anchor.
+    /// Otherwise only the units required by the anchor are verified to be active.
+    ///
+    /// Units bound to or part of the anchor always stop with it and running ones restart with it. Started units stop
+    /// the units they conflict with and their requisites are only verified to be active, never started.
+    pub fn new(
+        anchor: UnitId,
+        kind: JobKind,
+        pull_in_dependencies: bool,
+        unit_table: &UnitTable,
+    ) -> Result<Transaction, String> {
+        let mut transaction = Transaction {
+            anchor: anchor.clone(),
+            mode: JobMode::Replace,
+            jobs: HashMap::new(),
+        };
+        transaction.add_job(&anchor, kind, unit_table)?;
+
+        let anchor_unit = unit_table
+            .get(&anchor)
+            .ok_or_else(|| format!("No unit found with id: {}", anchor))?;
+        let mut started_ids = vec![anchor.clone()];
+        match kind {
+            JobKind::Start | JobKind::Restart if pull_in_dependencies => {
+                let mut next = 0;
+                while next < started_ids.len() {
+                    if let Some(unit) = unit_table.get(&started_ids[next]) {
+                        for id in Self::started_with(unit) {
+                            if !started_ids.contains(&id) {
+                                started_ids.push(id);
+                            }
+                        }
+                    }
+                    next += 1;
+                }
+                for id in &started_ids[1..] {
+                    transaction.add_job(id, JobKind::Start, unit_table)?;
+                }
+            }
+            JobKind::Start | JobKind::Restart => {
+                // like in systemd the slice of a unit is always started with it, it only groups units
+                let mut slice_ids: Vec<UnitId> = Vec::new();
+                let mut next_ids = anchor_unit.common.dependencies.requires.clone();
+                while let Some(id) = next_ids.pop() {
+                    if id.kind == UnitIdKind::Slice && !slice_ids.contains(&id) {
+                        if let Some(slice) = unit_table.get(&id) {
+                            next_ids.extend(slice.common.dependencies.requires.iter().cloned());
+                        }
+                        slice_ids.push(id);
+                    }
+                }
+                for id in &slice_ids {
+                    transaction.add_job(id, JobKind::Start, unit_table)?;
+                }
+                for id in &anchor_unit.common.dependencies.requires {
+                    if !slice_ids.contains(id) {
+                        transaction.add_job(id, JobKind::VerifyActive, unit_table)?;
+                    }
+                }
+                for id in &anchor_unit.common.dependencies.binds_to {
+                    transaction.add_job(id, JobKind::VerifyActive, unit_table)?;
+                }
+            }
+            JobKind::Stop => {
+                transaction.add_stop_jobs(
+                    Self::stopped_with(anchor_unit, pull_in_dependencies),
+                    pull_in_dependencies,
+                    unit_table,
+                )?;
+            }
+            JobKind::Reload | JobKind::VerifyActive => {}
+        }
+
+        if kind == JobKind::Restart {
+            // units bound to or part of the anchor are restarted with it, if they are running
+            let mut next_ids = anchor_unit.common.dependencies.stop_with_this();
+            while let Some(id) = next_ids.pop() {
+                if transaction.jobs.contains_key(&id) {
+                    continue;
+                }
+                let unit = match unit_table.get(&id) {
+                    Some(unit) => unit,
+                    None => continue,
+                };
+                if !unit.common.status.read().unwrap().is_started() {
+                    continue;
+                }
+                transaction.add_job(&id, JobKind::Restart, unit_table)?;
+                next_ids.extend(unit.common.dependencies.stop_with_this());
+            }
+        }
+
+        if kind == JobKind::Start || kind == JobKind::Restart {
+            for id in &started_ids {
+                let unit = match unit_table.get(id) {
+                    Some(unit) => unit,
+                    None => continue,
+                };
+                // requisites are never started by the transaction, they only have to be active already
+                for requisite_id in &unit.common.dependencies.requisite {
+                    transaction.add_job(requisite_id, JobKind::VerifyActive, unit_table)?;
+                }
+                // a conflicting unit that gets started by this transaction too makes it contradictory. Units that never
+                // ran do not need to be stopped.
+                let conflicting = unit
+                    .common
+                    .dependencies
+                    .stop_when_starting_this()
+                    .into_iter()
+                    .filter(|id| {
+                        transaction.jobs.contains_key(id)
+                            || matches!(unit_table.get(id), Some(unit)
+                                if *unit.common.status.read().unwrap() != UnitStatus::NeverStarted)
+                    })
+                    .collect();
+                transaction.add_stop_jobs(conflicting, true, unit_table)?;
+            }
+        }
+
+        transaction.check_ordering_cycles()?;
+        Ok(transaction)
+    }
+
+    /// The units that are started with this unit. Requisites are left out even if this unit is ordered after them,
+    /// unless it also wants, requires or is bound to them.
+    fn started_with(unit: &Unit) -> Vec<UnitId> {
+        let deps = &unit.common.dependencies;
+        let mut ids = deps.start_before_this();
+        ids.extend(deps.start_concurrently_with_this());
+        ids.retain(|id| {
+            !deps.requisite.contains(id)
+                || deps.wants.contains(id)
+                || deps.requires.contains(id)
+                || deps.binds_to.contains(id)
+        });
+        ids
+    }
+
+    /// Plan the jobs for a request like systemd does for this job mode. Replace and fail pull in the dependencies,
+    /// isolate additionally stops all running units that are not part of the transaction.
+    pub fn with_mode(
+        anchor: UnitId,
+        kind: JobKind,
+        mode: JobMode,
+        unit_table: &UnitTable,
+    ) -> Result<Transaction, String> {
+        let mut transaction = if mode == JobMode::IgnoreDependencies {
+            let mut transaction = Transaction {
+                anchor: anchor.clone(),
+                mode,
+                jobs: HashMap::new(),
+            };
+            transaction.add_job(&anchor, kind, unit_table)?;
+            transaction
+        } else {
+            Transaction::new(anchor, kind, true, unit_table)?
+        };
+        transaction.mode = mode;
+
+        if mode == JobMode::Isolate {
+            let mut running: Vec<&UnitId> = unit_table
+                .iter()
+                .filter(|(id, unit)| {
+                    let status = unit.common.status.read().unwrap();
+                    !transaction.jobs.contains_key(id)
+                        && !status.is_stopped()
+                        && *status != UnitStatus::NeverStarted
+                })
+                .map(|(id, _)| id)
+                .collect();
+            running.sort();
+            for id in running {
+                transaction.add_job(id, JobKind::Stop, unit_table)?;
+            }
+            transaction.check_ordering_cycles()?;
+        }
+        Ok(transaction)
+    }
+
+    /// The units that have to stop when this unit stops. Units bound to it or part of it always stop with it, units
+    /// that require it only if dependencies are pulled in.
+    fn stopped_with(unit: &Unit, pull_in_dependencies: bool) -> Vec<UnitId> {
+        let mut ids = unit.common.dependencies.stop_with_this();
+        if pull_in_dependencies {
+            ids.extend(unit.common.dependencies.kill_before_this());
+        }
+        ids
+    }
+
+    /// Add stop jobs for these units and all units that have to stop with them
+    fn add_stop_jobs(
+        &mut self,
+        mut next_ids: Vec<UnitId>,
+        pull_in_dependencies: bool,
+        unit_table: &UnitTable,
+    ) -> Result<(), String> {
+        while let Some(id) = next_ids.pop() {
+            if let Some(planned) = self.jobs.get(&id) {
+                if planned.kind.is_stop() {
+                    continue;
+                }
+            }
+            self.add_job(&id, JobKind::Stop, unit_table)?;
+            if let Some(unit) = unit_table.get(&id) {
+                next_ids.extend(Self::stopped_with(unit, pull_in_dependencies));
+            }
+        }
+        Ok(())
+    }
+
+    /// The kind of job planned for each unit
+    pub fn jobs(&self) -> HashMap<UnitId, JobKind> {
+        self.jobs
+            .iter()
+            .map(|(id, job)| (id.clone(), job.kind))
+            .collect()
+    }
+
+    /// Add a job to the transaction. A job for a unit that already has a job is merged into the existing one.
+    fn add_job(
+        &mut self,
+        id: &UnitId,
+        kind: JobKind,
+        unit_table: &UnitTable,
+    ) -> Result<(), String> {
+        if let Some(planned) = self.jobs.get_mut(id) {
+            planned.kind = planned.kind.merge(kind).ok_or_else(|| {
+                format!(
+                    "Transaction is contradictory: unit {} would get a {} and a {} job",
+                    id.name,
+                    planned.kind.as_str(),
+                    kind.as_str()
+                )
+            })?;
+            return Ok(());
+        }
+        let unit = unit_table
+            .get(id)
+            .ok_or_else(|| format!("No unit found with id: {}", id))?;
+        self.jobs.insert(
+            id.clone(),
+            PlannedJob {
+                kind,
+                after: unit.common.dependencies.after.clone(),
+                before: unit.common.dependencies.before.clone(),
+                requires: unit
+                    .common
+                    .dependencies
+                    .requires
+                    .iter()
+                    .chain(unit.common.dependencies.binds_to.iter())
+                    .chain(unit.common.dependencies.requisite.iter())
+                    .cloned()
+                    .collect(),
+            },
+        );
+        Ok(())
+    }
+
+    fn planned_as_job(&self, id: &UnitId) -> Job {
+        let planned = &self.jobs[id];
+        Job {
+            id: 0,
+            unit: id.clone(),
+            kind: planned.kind,
+            state: JobState::Waiting,
+            after: planned.after.clone(),
+            before: planned.before.clone(),
+            requires: planned.requires.clone(),
+        }
+    }
+
+    /// The jobs of a transaction must be able to run in some order
+    fn check_ordering_cycles(&self) -> Result<(), String> {
+        let jobs: HashMap<UnitId, Job> = self
+            .jobs
+            .keys()
+            .map(|id| (id.clone(), self.planned_as_job(id)))
+            .collect();
+        // 0 = unvisited, 1 = on the current path, 2 = done
+        let mut marks: HashMap<&UnitId, u8> = HashMap::new();
+        let mut path = Vec::new();
+        let mut ids: Vec<&UnitId> = jobs.keys().collect();
+        ids.sort();
+        for id in ids {
+            Self::visit(id, &jobs, &mut marks, &mut path)?;
+        }
+        Ok(())
+    }
+
+    fn visit<'a>(
+        id: &'a UnitId,
+        jobs: &'a HashMap<UnitId, Job>,
+        marks: &mut HashMap<&'a UnitId, u8>,
+        path: &mut Vec<&'a UnitId>,
+    ) -> Result<(), String> {
+        match marks.get(id) {
+            Some(2) => return Ok(()),
+            Some(1) => {
+                let start = path.iter().position(|elem| *elem == id).unwrap();
+                let names: Vec<_> = path[start..].iter().map(|id| id.name.clone()).collect();
+                return Err(format!(
+                    "Transaction contains an ordering cycle: {:?}",
+                    names
+                ));
+            }
+            _ => {}
+        }
+        marks.insert(id, 1);
+        path.push(id);
+        let job = &jobs[id];
+        for (other_id, other) in jobs {
+            if other_id != id && job.waits_for(other) {
+                Self::visit(other_id, jobs, marks, path)?;
+            }
+        }
+        path.pop();
+        marks.insert(id, 2);
+        Ok(())
+    }
+
+    /// The units in the transaction that (transitively) require the failed unit
+    fn requiring(&self, failed: &UnitId) -> Vec<UnitId> {
+        let mut failed_ids = vec![failed.clone()];
+        loop {
+            let new_ids: Vec<UnitId> = self
+                .jobs
+                .iter()
+                .filter(|(id, planned)| {
+                    !failed_ids.contains(id)
+                        && planned.requires.iter().any(|req| failed_ids.contains(req))
+                })
+                .map(|(id, _)| id.clone())
+                .collect();
+            if new_ids.is_empty() {
+                break;
+            }
+            failed_ids.extend(new_ids);
+        }
+        failed_ids.remove(0);
+        failed_ids
+    }
+
+    /// Merge the jobs into the queue. Returns the ids of the jobs that were installed for this transaction and the ids
+    /// of already installed jobs this transaction waits for. Nothing is changed if a job can not be installed.
+    fn install(
+        &self,
+        installed: &mut InstalledJobs,
+    ) -> Result<(HashMap<JobId, UnitId>, Vec<JobId>), String> {
+        for (id, planned) in &self.jobs {
+            if let Some(job) = installed.jobs.get(id) {
+                let merged = job.kind.merge(planned.kind);
+                let conflicting = match job.state {
+                    JobState::Running => merged != Some(job.kind),
+                    // with the fail mode queued jobs are not replaced
+                    JobState::Waiting => merged.is_none() && self.mode == JobMode::Fail,
+                };
+                if conflicting {
+                    return Err(format!(
+                        "The {} job for unit {} conflicts with the {} {} job {}",
+                        planned.kind.as_str(),
+                        id.name,
+                        if job.state == JobState::Running {
+                            "running"
+                        } else {
+                            "queued"
+                        },
+                        job.kind.as_str(),
+                        job.id
+                    ));
+                }
+            }
+        }
+
+        let mut own = HashMap::new();
+        let mut followed = Vec::new();
+        let mut ids: Vec<&UnitId> = self.jobs.keys().collect();
+        ids.sort();
+        for id in ids {
+            let planned = &self.jobs[id];
+            if let Some(job) = installed.jobs.get_mut(id) {
+                if let Some(merged) = job.kind.merge(planned.kind) {
+                    trace!(
+                        "Merge {} job for unit {} into job {}",
+                        planned.kind.as_str(),
+                        id.name,
+                        job.id
+                    );
+                    job.kind = merged;
+                    followed.push(job.id);
+                    continue;
+                }
+                trace!(
+                    "Replace waiting {} job {} for unit {}",
+                    job.kind.as_str(),
+                    job.id,
+                    id.name
+                );
+                let replaced = job.id;
+                installed.remove_job(replaced);
+                installed.results.insert(replaced, JobResult::Cancelled);
+            }
+            installed.next_id += 1;
+            let mut job = self.planned_as_job(id);
+            job.id = installed.next_id;
+            own.insert(job.id, id.clone());
+            installed.jobs.insert(id.clone(), job);
+        }
+        Ok((own, followed))
+    }
+}
+
+fn run_job(job: &Job, run_info: &RuntimeInfo) -> JobResult {
+    trace!(
+        "Run {} job {} for {}",
+        job.kind.as_str(),
+        job.id,
+        job.unit.name
+    );
+    let result = match job.kind {
+        JobKind::Start => {
+            activate_unit(job.unit.clone(), run_info, ActivationSource::Regular).map(|_| ())
+        }
+        JobKind::Stop => deactivate_unit(&job.unit, run_info),
+        JobKind::Restart => reactivate_unit(job.unit.clone(), run_info),
+        JobKind::Reload => reload_unit(job.unit.clone(), run_info),
+        JobKind::VerifyActive => {
+            let status = match run_info.unit_table.get(&job.unit) {
+                Some(unit) => unit.common.status.read().unwrap().clone(),
+                None => UnitStatus::NeverStarted,
+            };
+            if status.is_started() {
+                Ok(())
+            } else {
+                Err(UnitOperationError {
+                    reason: UnitOperationErrorReason::GenericStartError(format!(
+                        "Unit is not active. Status is: {:?}",
+                        status
+                    )),
+                    unit_name: job.unit.name.clone(),
+                    unit_id: job.unit.clone(),
+                })
+            }
+        }
+    };
+    match result {
+        Ok(()) => JobResult::Done,
+        Err(e) => {
+            error!("Error while running {} job: {}", job.kind.as_str(), e);
+            JobResult::Failed(e)
+        }
+    }
+}
+
+/// Check and install the transaction and run its jobs. Returns the errors of all jobs that failed or were cancelled.
+pub fn run_transaction(
+    transaction: Transaction,
+    run_info: ArcMutRuntimeInfo,
+) -> Vec<UnitOperationError> {
+    let queue = run_info.read().unwrap().jobs.clone();
+    let mut installed = queue.installed.lock().unwrap();
+    let (mut own, mut followed) = match transaction.install(&mut installed) {
+        Ok(jobs) => jobs,
+        Err(e) => {
+            return vec![UnitOperationError {
+                reason: UnitOperationErrorReason::TransactionRejected(e),
+                unit_name: transaction.anchor.name.clone(),
+                unit_id: transaction.anchor.clone(),
+            }]
+        }
+    };
+    queue.changed.notify_all();
+    trace!(
+        "Installed jobs for {}: {:?}",
+        transaction.anchor.name,
+        own.values().map(|id| &id.name).collect::<Vec<_>>()
+    );
+
+    // TODO make configurable or at least make guess about amount of threads
+    let tpool = ThreadPool::new(6);
+    let mut errors = Vec::new();
+    loop {
+        let mut failed = Vec::new();
+        for (job_id, unit_id) in own.clone() {
+            let result = match installed.results.remove(&job_id) {
+                Some(result) => result,
+                None => continue,
+            };
+            own.remove(&job_id);
+            match result {
+                JobResult::Done => {}
+                JobResult::Failed(e) => {
+                    errors.push(e);
+                    failed.push(unit_id);
+                }
+                JobResult::Cancelled => errors.push(UnitOperationError {
+                    reason: UnitOperationErrorReason::JobCancelled,
+                    unit_name: unit_id.name.clone(),
+                    unit_id,
+                }),
+            }
+        }
+
+        // roll back the pending jobs that can not succeed anymore
+        for failed_id in failed {
+            for id in transaction.requiring(&failed_id) {
+                let job_id = match installed.jobs.get(&id) {
+                    Some(job) if job.state == JobState::Waiting && own.contains_key(&job.id) => {
+                        job.id
+                    }
+                    _ => continue,
+                };
+                trace!(
+                    "Cancel job {} for {}. {} failed",
+                    job_id,
+                    id.name,
+                    failed_id.name
+                );
+                installed.remove_job(job_id);
+                own.remove(&job_id);
+                errors.push(UnitOperationError {
+                    reason: UnitOperationErrorReason::DependencyError(vec![failed_id.clone()]),
+                    unit_name: id.name.clone(),
+                    unit_id: id.clone(),
+                });
+            }
+        }
+
+        followed.retain(|job_id| installed.contains_job(*job_id));
+        if own.is_empty() && followed.is_empty() {
+            break;
+        }
+
+        let runnable: Vec<JobId> = own
+            .iter()
+            .filter(|(_, unit_id)| {
+                let job = &installed.jobs[*unit_id];
+                job.state == JobState::Waiting
+                    && !installed
+                        .jobs
+                        .values()
+                        .any(|other| other.id != job.id && job.waits_for(other))
+            })
+            .map(|(job_id, _)| *job_id)
+            .collect();
+        for job_id in runnable {
+            let unit_id = &own[&job_id];
+            let job = installed.jobs.get_mut(unit_id).unwrap();
+            job.state = JobState::Running;
+            let job = job.clone();
+            let run_info = run_info.clone();
+            let queue = queue.clone();
+            tpool.execute(move || {
+                let result = {
+                    let run_info = &*run_info.read().unwrap();
+                    run_job(&job, run_info)
+                };
+                queue.finish(job.id, result);
+            });
+        }
+
+        installed = queue.changed.wait(installed).unwrap();
+    }
+    drop(installed);
+    tpool.join();
+    errors
+}
@@ -3,6 +3,7 @@
 mod activate;
 mod deactivate;
 mod insert_new;
+mod jobs;
 mod locking;
 mod remove;
 mod sanity_check;
@@ -10,6 +11,7 @@ mod sanity_check;
 pub use activate::*;
 pub use deactivate::*;
 pub use insert_new::*;
+pub use jobs::*;
 pub use locking::*;
 pub use remove::*;
 pub use sanity_check::*;
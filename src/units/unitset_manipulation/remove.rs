@@ -1,6 +1,8 @@
 use log::trace;
 
+use crate::reactor::Reactor;
 use crate::runtime_info::*;
+use crate::services::StdIo;
 use crate::units::*;
 
 /// Remove this unit from the run_info and cleanup all references to it
@@ -22,7 +24,11 @@ pub fn remove_unit_with_dependencies(
         check_deactivated_recursive(id, run_info)?;
     }
 
-    remove_with_depending_units(remove_id.clone(), &mut run_info.unit_table);
+    remove_with_depending_units(
+        remove_id.clone(),
+        &mut run_info.unit_table,
+        &run_info.reactor,
+    );
 
     Ok(())
 }
@@ -53,12 +59,24 @@ fn check_deactivated_recursive(remove_id: UnitId, run_info: &RuntimeInfo) -> Res
 /// Remove all occurences of this ID in other units.
 /// This requires that this unit is removed at the same time
 /// as all units that mention this unit by name!
-fn remove_single_unit(rm_id: UnitId, unit_table: &mut UnitTable) {
+fn remove_single_unit(rm_id: UnitId, unit_table: &mut UnitTable, reactor: &Reactor) {
     for unit in unit_table.values_mut() {
         unit.common.dependencies.remove_id(&rm_id);
     }
     // actuallyy remove the unit from the unit table
-    unit_table.remove(&rm_id);
+    if let Some(unit) = unit_table.remove(&rm_id) {
+        reactor.deregister_unit(&rm_id);
+        if let Specific::Service(srvc) = &unit.specific {
+            // the notification socket is closed when it is dropped, the pipes have to be closed here
+            let state = &*srvc.state.read().unwrap();
+            for stdio in [&state.srvc.stdout, &state.srvc.stderr].iter() {
+                if let Some(StdIo::Piped(r, w)) = stdio {
+                    crate::sockets::close_raw_fd(*r);
+                    crate::sockets::close_raw_fd(*w);
+                }
+            }
+        }
+    }
 }
 
 fn find_all_depending(rm_id: UnitId, unit_table: &UnitTable, ids: &mut Vec<UnitId>) {
@@ -83,12 +101,12 @@ fn find_all_depending(rm_id: UnitId, unit_table: &UnitTable, ids: &mut Vec<UnitI
 }
 
 /// Remove all occurences in other units and all units that explicitly mention this unit in their config
-fn remove_with_depending_units(rm_id: UnitId, unit_table: &mut UnitTable) {
+fn remove_with_depending_units(rm_id: UnitId, unit_table: &mut UnitTable, reactor: &Reactor) {
     trace!("Remove unit: {:?}", rm_id);
     // follow the units install section and check if the units have this unit in their Install-/Unit-config.
     // If so, remove them too
 
-    remove_single_unit(rm_id.clone(), unit_table);
+    remove_single_unit(rm_id.clone(), unit_table, reactor);
     // first remove all depending units
     let mut next_ids = Vec::new();
     for (id, unit) in unit_table.iter() {
@@ -100,6 +118,6 @@ fn remove_with_depending_units(rm_id: UnitId, unit_table: &mut UnitTable) {
     }
 
     for id in next_ids {
-        remove_with_depending_units(id, unit_table);
+        remove_with_depending_units(id, unit_table, reactor);
     }
 }
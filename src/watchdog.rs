@@ -0,0 +1,142 @@
+//! Supervise services that use the sd_notify watchdog (WatchdogSec=). Services that do not send WATCHDOG=1 in time are
+//! killed with the WatchdogSignal= and then handled by the exit handler like any other exit.
+use log::{error, trace};
+
+use crate::runtime_info::*;
+use crate::units::*;
+
+use std::time::{Duration, Instant};
+
+/// Check the watchdogs at least this often, deadlines can move when services change WATCHDOG_USEC=
+const MAX_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Clone, Debug, Default)]
+pub struct Watchdog {
+    /// Set from WatchdogSec= when the service starts and changed with WATCHDOG_USEC=. None means no supervision.
+    pub timeout: Option<Duration>,
+    pub last_ping: Option<Instant>,
+    /// The service sent WATCHDOG=trigger
+    pub triggered: bool,
+    /// When the watchdog signal was sent to the service
+    pub fired: Option<Instant>,
+}
+
+impl Watchdog {
+    /// Start supervising with the configured timeout
+    pub fn arm(&mut self, timeout: Option<Duration>) {
+        *self = Watchdog {
+            timeout,
+            last_ping: Some(Instant::now()),
+            triggered: false,
+            fired: None,
+        };
+    }
+
+    pub fn disarm(&mut self) {
+        self.timeout = None;
+        self.last_ping = None;
+        self.triggered = false;
+    }
+
+    /// WATCHDOG=1
+    pub fn ping(&mut self) {
+        self.last_ping = Some(Instant::now());
+    }
+
+    /// WATCHDOG=trigger
+    pub fn trigger(&mut self) {
+        self.triggered = true;
+    }
+
+    /// WATCHDOG_USEC=. Zero disables the watchdog.
+    pub fn set_timeout(&mut self, usec: u64) {
+        self.timeout = if usec == 0 {
+            None
+        } else {
+            Some(Duration::from_micros(usec))
+        };
+        self.last_ping = Some(Instant::now());
+    }
+
+    /// How long until the watchdog expires. Zero if it already expired, None if it is not armed.
+    pub fn time_left(&self) -> Option<Duration> {
+        if self.fired.is_some() {
+            return None;
+        }
+        if self.triggered {
+            return Some(Duration::from_secs(0));
+        }
+        let timeout = self.timeout?;
+        let last_ping = self.last_ping?;
+        Some(timeout.saturating_sub(last_ping.elapsed()))
+    }
+}
+
+pub fn start_watchdog_thread(run_info: ArcMutRuntimeInfo) {
+    std::thread::spawn(move || loop {
+        let wait_time = check_watchdogs(&run_info.read().unwrap());
+        std::thread::sleep(wait_time);
+    });
+}
+
+/// Send the watchdog signal to all services whose watchdog expired. Processes that ignore it get killed after the
+/// stop timeout. Returns how long to wait until the next check.
+fn check_watchdogs(run_info: &RuntimeInfo) -> Duration {
+    let mut wait_time = MAX_CHECK_INTERVAL;
+    for unit in run_info.unit_table.values() {
+        let srvc = if let Specific::Service(srvc) = &unit.specific {
+            srvc
+        } else {
+            continue;
+        };
+        // Units that change their state right now are not supervised
+        match unit.common.status.try_read() {
+            Ok(status) if *status == UnitStatus::Started(StatusStarted::Running) => {}
+            _ => continue,
+        }
+        let state = &mut *match srvc.state.try_write() {
+            Ok(state) => state,
+            Err(_) => continue,
+        };
+        let pid = match state.srvc.pid {
+            Some(pid) => pid,
+            None => continue,
+        };
+
+        if let Some(fired) = state.srvc.watchdog.fired {
+            let kill_timeout = state.srvc.get_stop_timeout(&srvc.conf);
+            if kill_timeout.map(|t| fired.elapsed() >= t).unwrap_or(false) {
+                error!(
+                    "Service {} did not exit after the watchdog signal. Killing it.",
+                    unit.id.name
+                );
+                state
+                    .srvc
+                    .kill_all_remaining_processes(&srvc.conf, &unit.id.name);
+            }
+            continue;
+        }
+
+        match state.srvc.watchdog.time_left() {
+            Some(left) if left.as_nanos() == 0 => {
+                error!(
+                    "Watchdog timeout for service {}. Sending {}",
+                    unit.id.name, srvc.conf.watchdog_signal
+                );
+                state.srvc.watchdog.fired = Some(Instant::now());
+                if let Err(e) = nix::sys::signal::kill(pid, srvc.conf.watchdog_signal) {
+                    error!(
+                        "Could not send the watchdog signal to service {}: {}",
+                        unit.id.name, e
+                    );
+                }
+            }
+            Some(left) => {
+                trace!("Watchdog of service {} expires in {:?}", unit.id.name, left);
+                wait_time = wait_time.min(left);
+            }
+            None => {}
+        }
+    }
+    wait_time
+}
@@ -31,23 +31,24 @@ USED_FEATURES = {}
 
 SUPPORTED_FEATURES = {
     "READY": {"icon": ICON_TICK, "text": "Waiting for ready notification for service-type notify is supported"},
+    "RELOADING": {"icon": ICON_TICK, "text": "Services of type notify can bracket a reload with RELOADING=1 and READY=1"},
     "STATUS": {"icon": ICON_TICK, "text": "Sending free-text status updates to be displayed for the user is supported"},
-    "NOTIFY_SOCKET": {"icon": ICON_TICK, "text": "Listening to a notification socket is supported (see section fd_notifiy for details on which messages are understood). NotifyAccess= is not fully supported though."},
+    "NOTIFY_SOCKET": {"icon": ICON_TICK, "text": "Listening to a notification socket is supported (see section fd_notifiy for details on which messages are understood)."},
     "LISTEN_FDS": {"icon": ICON_TICK, "text": "Providing number of filedescriptors is supported"},
     "LISTEN_FDNAMES": {"icon": ICON_TICK, "text": "Providing names for filedescriptors is supported"},
     "LISTEN_PID": {"icon": ICON_TICK, "text": "Provifing the listen_pid to the child is supported"},
     "After": {"icon": ICON_TICK, "text":  "Ordering of units according to before/after relation is supported fully"},
     "Before": {"icon": ICON_TICK, "text": "Ordering of units according to before/after relation is supported fully"},
-    "Type": {"icon": ICON_QMARK, "text": "Types are partly supported. Simple, dbus, notify, oneshot are supported. Forking, idle are not."},
-    "Restart": {"icon": ICON_QMARK, "text": "Restart is partially supported. The settings 'always' and 'no' are supported"},
+    "Type": {"icon": ICON_TICK, "text": "Simple, exec, notify, oneshot, forking, idle and dbus (with the dbus_support feature) are supported."},
+    "Restart": {"icon": ICON_TICK, "text": "Restart is supported with all settings: no, always, on-success, on-failure, on-abnormal, on-abort and on-watchdog"},
     "BusName": {"icon": ICON_TICK, "text": "Setting a bus name to wait for services of type dbus is supported."},
-    "NotifyAccess": {"icon": ICON_QMARK, "text": "Not fully supported. All settings are accepted but are not being enforced right now. Acts as if 'all' was set."},
+    "NotifyAccess": {"icon": ICON_TICK, "text": "All settings are supported. The sender of each notification is checked with SCM_CREDENTIALS (on Linux). all accepts notifications from any process."},
     "Sockets": {"icon": ICON_QMARK, "text": "Adding more socket files to servcies is supported. But only so that one socket belongs to only one service (sytsemd allows for sockets to belong to multiple services)."},
     "ListenStream": {"icon": ICON_TICK, "text": "Opening streaming sockets is supported. The whole IPv4 and IPv6 stuff needs some attention though"},
     "ListenDatagram": {"icon": ICON_TICK, "text": "Opening datagram sockets is supported. The whole IPv4 and IPv6 stuff needs some attention though"},
     "ListenSequentialPacket": {"icon": ICON_TICK, "text": "Opening sequential packet sockets is supported."},
     "ListenFIFO": {"icon": ICON_TICK, "text": "Opening FIFOs is supported. Filemode setting is not supported as of yet though."},
-    "Accept": {"icon": ICON_QMARK, "text": "Only the setting 'no' is supported. Inted-style activation is not yet supported."},
+    "Accept": {"icon": ICON_TICK, "text": "Each connection is served by a new instance of the template service (like foo@3-127.0.0.1:4711.service). The connection is passed as LISTEN_FDS=1 or as stdin/stdout with StandardInput=socket."},
     "ExecStart": {"icon": ICON_TICK, "text": "Exec'ing the command given is supported. The return value is checked for oneshot services. Ignoring the return value with the '-' prefix is supported, other prefixes are not."},
     "ExecStartPre": {"icon": ICON_QMARK,  "text": "Allowing commands to be run is supported. The return value is checked. Ignoring the return value with the '-' prefix is supported, other prefixes are not."},
     "ExecStartPost": {"icon": ICON_QMARK, "text": "Allowing commands to be run is supported. The return value is checked. Ignoring the return value with the '-' prefix is supported, other prefixes are not."},
@@ -66,8 +67,92 @@ SUPPORTED_FEATURES = {
     "User": {"icon": ICON_QMARK, "text": "The user id can be set for starting services. Currently only done for the main executable"},
     "Group": {"icon": ICON_QMARK, "text": "The group id can be set for starting services. Currently only done for the main executable"},
     "SupplementaryGroups": {"icon": ICON_QMARK, "text": "The supplementary group ids can be set for starting services. Currently only done for the main executable"},
-    "StandardOutput": {"icon": ICON_QMARK, "text": "Standardoutput can be either handled by rustysds normal logging or be redirected to a file, either write or append. Other modes are not yet supported."},
-    "StandardError": {"icon": ICON_QMARK, "text": "Standarderror can be either handled by rustysds normal logging or be redirected to a file, either write or append. Other modes are not yet supported."},
+    "StandardOutput": {"icon": ICON_TICK, "text": "Supports file:, append:, truncate:, journal (the log of rustysd), null, inherit, tty, socket and fd:name. Other modes are not supported."},
+    "StandardError": {"icon": ICON_TICK, "text": "Same as StandardOutput=. With inherit stderr goes wherever stdout goes."},
+    "OnBootSec": {"icon": ICON_TICK, "text": "Supported. The time is relative to the start of rustysd"},
+    "OnUnitActiveSec": {"icon": ICON_TICK, "text": "Supported"},
+    "OnUnitInactiveSec": {"icon": ICON_TICK, "text": "Supported"},
+    "OnCalendar": {"icon": ICON_QMARK, "text": "Calendar expressions with weekdays, dates and times (including lists, ranges and repetitions) and the usual shorthands like daily are supported. Timezones and fractional seconds are not"},
+    "AccuracySec": {"icon": ICON_TICK, "text": "Timers elapsing within the accuracy window share one wakeup"},
+    "RandomizedDelaySec": {"icon": ICON_TICK, "text": "Supported"},
+    "Unit": {"icon": ICON_TICK, "text": "Setting the unit that gets activated by a timer or path unit is supported. Defaults to the service with the same name"},
+    "Persistent": {"icon": ICON_TICK, "text": "The last trigger is stored in the state_dir from the rustysd config, so elapses missed while rustysd was not running are caught up on"},
+    "PathExists": {"icon": ICON_TICK, "text": "Supported"},
+    "PathExistsGlob": {"icon": ICON_TICK, "text": "Supported. Only the directory before the first wildcard is watched for changes"},
+    "PathChanged": {"icon": ICON_TICK, "text": "Supported with inotify (Linux only)"},
+    "PathModified": {"icon": ICON_TICK, "text": "Supported with inotify (Linux only)"},
+    "DirectoryNotEmpty": {"icon": ICON_TICK, "text": "Supported"},
+    "MakeDirectory": {"icon": ICON_TICK, "text": "Supported. The directories are always created with mode 0755"},
+    "RestartSec": {"icon": ICON_TICK, "text": "RestartSec is supported. Defaults to 100ms"},
+    "StartLimitIntervalSec": {"icon": ICON_TICK, "text": "StartLimitIntervalSec is supported for services. Defaults to 10s"},
+    "StartLimitBurst": {"icon": ICON_TICK, "text": "StartLimitBurst is supported for services. Defaults to 5"},
+    "PIDFile": {"icon": ICON_TICK, "text": ""},
+    "ExecReload": {"icon": ICON_TICK, "text": ""},
+    "WatchdogSec": {"icon": ICON_TICK, "text": "Supervising services with the sd_notify watchdog is supported"},
+    "WatchdogSignal": {"icon": ICON_TICK, "text": "The signal sent to services whose watchdog expired is configurable"},
+    "WATCHDOG_PID": {"icon": ICON_TICK, "text": "Set for services that have a watchdog configured"},
+    "WATCHDOG": {"icon": ICON_TICK, "text": "Services can keep their watchdog alive with WATCHDOG=1 or trigger it with WATCHDOG=trigger"},
+    "WATCHDOG_USEC": {"icon": ICON_TICK, "text": "Services can change their watchdog timeout. It is also provided to services in $WATCHDOG_USEC"},
+    "STOPPING": {"icon": ICON_TICK, "text": "Services can announce that they are shutting down, the unit is shown as stopping until they exited"},
+    "ERRNO": {"icon": ICON_TICK, "text": "The reported error is stored and shown in the status of the service"},
+    "MAINPID": {"icon": ICON_TICK, "text": "Services can change their main process with MAINPID=. The main pid is passed to the ExecReload=/ExecStop= commands in $MAINPID"},
+    "EXTEND_TIMEOUT_USEC": {"icon": ICON_TICK, "text": "Services can extend the start, reload and stop timeouts"},
+    "FDSTORE": {"icon": ICON_TICK, "text": "Services can store fds (sent with SCM_RIGHTS) up to FileDescriptorStoreMax=. They are passed back in $LISTEN_FDS when the service is restarted"},
+    "FDSTOREREMOVE": {"icon": ICON_TICK, "text": "Removing stored fds by their FDNAME= is supported"},
+    "FDNAME": {"icon": ICON_TICK, "text": "Stored fds are passed back with this name in $LISTEN_FDNAMES"},
+    "FileDescriptorStoreMax": {"icon": ICON_TICK, "text": "Limits how many fds a service may store. Defaults to 0, which disables the fd store"},
+    "MaxConnections": {"icon": ICON_TICK, "text": ""},
+    "MaxConnectionsPerSource": {"icon": ICON_TICK, "text": "The source is the peer address for network sockets and the uid of the peer for unix sockets."},
+    "StandardInput": {"icon": ICON_QMARK, "text": "Only null and socket (for instances of sockets with Accept=yes) are supported."},
+    "Requisite": {"icon": ICON_TICK, "text": "Fails the start if the unit is not active, it is never pulled in"},
+    "BindsTo": {"icon": ICON_TICK, "text": "Pulled in like Requires=, stops the unit when the bound unit stops or exits"},
+    "PartOf": {"icon": ICON_TICK, "text": "Stopping and restarting the unit is propagated, starting is not"},
+    "Conflicts": {"icon": ICON_TICK, "text": "Starting the unit stops the conflicting units. Starting both in one transaction fails"},
+    "OnFailure": {"icon": ICON_TICK, "text": "Started when the unit fails, gets $MONITOR_UNIT, $MONITOR_SERVICE_RESULT, $MONITOR_EXIT_CODE and $MONITOR_EXIT_STATUS"},
+    "OnSuccess": {"icon": ICON_TICK, "text": "Started when the unit finishes successfully, gets the same $MONITOR_* variables as OnFailure="},
+    "OnFailureJobMode": {"icon": ICON_TICK, "text": "replace, fail, isolate and ignore-dependencies"},
+    "OnSuccessJobMode": {"icon": ICON_TICK, "text": "replace, fail, isolate and ignore-dependencies"},
+    "ConditionPathExists": {"icon": ICON_TICK, "text": "Negation with ! and triggering conditions with | are supported"},
+    "AssertPathExists": {"icon": ICON_TICK, "text": "Negation with ! and triggering conditions with | are supported"},
+    "ConditionPathIsDirectory": {"icon": ICON_TICK, "text": ""},
+    "AssertPathIsDirectory": {"icon": ICON_TICK, "text": ""},
+    "ConditionDirectoryNotEmpty": {"icon": ICON_TICK, "text": ""},
+    "AssertDirectoryNotEmpty": {"icon": ICON_TICK, "text": ""},
+    "ConditionFileNotEmpty": {"icon": ICON_TICK, "text": ""},
+    "AssertFileNotEmpty": {"icon": ICON_TICK, "text": ""},
+    "ConditionFileIsExecutable": {"icon": ICON_TICK, "text": ""},
+    "AssertFileIsExecutable": {"icon": ICON_TICK, "text": ""},
+    "ConditionKernelCommandLine": {"icon": ICON_TICK, "text": "Single words or key=value pairs of /proc/cmdline"},
+    "AssertKernelCommandLine": {"icon": ICON_TICK, "text": "Single words or key=value pairs of /proc/cmdline"},
+    "ConditionVirtualization": {"icon": ICON_TICK, "text": "Booleans, vm, container and the names systemd-detect-virt uses for the common hypervisors and container managers"},
+    "AssertVirtualization": {"icon": ICON_TICK, "text": "Booleans, vm, container and the names systemd-detect-virt uses for the common hypervisors and container managers"},
+    "ConditionUser": {"icon": ICON_TICK, "text": "User names, uids and @system"},
+    "AssertUser": {"icon": ICON_TICK, "text": "User names, uids and @system"},
+    "ConditionGroup": {"icon": ICON_TICK, "text": "Group names and gids, supplementary groups count too"},
+    "AssertGroup": {"icon": ICON_TICK, "text": "Group names and gids, supplementary groups count too"},
+    "ConditionHost": {"icon": ICON_TICK, "text": "Hostnames and machine ids, no globs"},
+    "AssertHost": {"icon": ICON_TICK, "text": "Hostnames and machine ids, no globs"},
+    "ConditionEnvironment": {"icon": ICON_TICK, "text": "Checks the environment of rustysd"},
+    "AssertEnvironment": {"icon": ICON_TICK, "text": "Checks the environment of rustysd"},
+    "ConditionFirstBoot": {"icon": ICON_TICK, "text": "First boot means /etc/machine-id is missing or not yet initialized"},
+    "AssertFirstBoot": {"icon": ICON_TICK, "text": "First boot means /etc/machine-id is missing or not yet initialized"},
+    "TTYPath": {"icon": ICON_TICK, "text": "Used by StandardOutput=tty and StandardError=tty"},
+    "SyslogIdentifier": {"icon": ICON_TICK, "text": "The output of the service is logged with this name"},
+    "MemoryMax": {"icon": ICON_TICK, "text": "Needs the cgroups feature and cgroup v2"},
+    "MemoryHigh": {"icon": ICON_TICK, "text": "Needs the cgroups feature and cgroup v2"},
+    "MemoryLow": {"icon": ICON_TICK, "text": "Needs the cgroups feature and cgroup v2"},
+    "CPUWeight": {"icon": ICON_TICK, "text": "Needs the cgroups feature and cgroup v2"},
+    "CPUQuota": {"icon": ICON_TICK, "text": "Needs the cgroups feature and cgroup v2"},
+    "TasksMax": {"icon": ICON_TICK, "text": "Needs the cgroups feature and cgroup v2"},
+    "IOWeight": {"icon": ICON_TICK, "text": "Needs the cgroups feature and cgroup v2"},
+    "AllowedCPUs": {"icon": ICON_TICK, "text": "Needs the cgroups feature and cgroup v2"},
+    "Slice": {"icon": ICON_TICK, "text": "Slices nest by their names, needs the cgroups feature for the cgroups"},
+    "KillMode": {"icon": ICON_TICK, "text": "control-group, mixed, process and none are supported"},
+    "KillSignal": {"icon": ICON_TICK, "text": "Sent to the processes selected by KillMode= when the service is stopped"},
+    "RestartKillSignal": {"icon": ICON_TICK, "text": "Used instead of KillSignal= when the service is restarted"},
+    "SendSIGHUP": {"icon": ICON_TICK, "text": "SIGHUP is sent right after the KillSignal="},
+    "SendSIGKILL": {"icon": ICON_TICK, "text": "Processes still running after TimeoutStopSec= get the FinalKillSignal= unless this is set to no"},
+    "FinalKillSignal": {"icon": ICON_TICK, "text": "Sent to the remaining processes after TimeoutStopSec= (SIGKILL by default)"},
 }
 
 def main():